@@ -0,0 +1,103 @@
+use crate::models::Version;
+use crate::utils::is_cjk;
+use std::collections::HashMap;
+
+const ENGLISH: &[&str] = &[
+	"a", "about", "after", "all", "am", "an", "and", "any", "are", "as", "at", "be", "been", "but", "by",
+	"can", "could", "did", "do", "does", "for", "from", "had", "has", "have", "he", "her", "him", "his",
+	"how", "i", "if", "in", "into", "is", "it", "its", "me", "more", "my", "no", "nor", "not", "now", "o",
+	"of", "on", "one", "or", "our", "out", "shall", "she", "so", "some", "such", "than", "that", "the",
+	"their", "them", "then", "there", "these", "they", "this", "those", "thou", "thee", "thy", "to", "up",
+	"upon", "us", "was", "we", "were", "what", "when", "where", "which", "while", "who", "whom", "why",
+	"will", "with", "would", "ye", "yet", "you", "your",
+];
+const GERMAN: &[&str] = &[
+	"als", "am", "an", "auch", "auf", "aus", "bei", "bin", "bis", "da", "das", "dass", "daß", "dem", "den",
+	"der", "des", "die", "dich", "dir", "du", "ein", "eine", "einem", "einen", "einer", "er", "es", "für",
+	"hat", "ich", "ihm", "ihn", "ihr", "im", "in", "ist", "mich", "mir", "mit", "nach", "nicht", "noch",
+	"nur", "ob", "oder", "sich", "sie", "sind", "so", "um", "und", "uns", "von", "vor", "war", "was", "wie",
+	"wir", "wo", "zu", "zum", "zur",
+];
+const FRENCH: &[&str] = &[
+	"au", "aux", "avec", "ce", "ces", "dans", "de", "des", "du", "elle", "en", "est", "et", "il", "je",
+	"la", "le", "les", "leur", "lui", "ma", "mais", "me", "mes", "mon", "ne", "ni", "nous", "on", "ou",
+	"par", "pas", "pour", "qu", "que", "qui", "sa", "se", "ses", "son", "sur", "ta", "te", "tes", "toi",
+	"ton", "tu", "un", "une", "vous",
+];
+const RUSSIAN: &[&str] = &[
+	"а", "без", "бы", "в", "во", "вот", "все", "всё", "вы", "да", "для", "до", "его", "ее", "её", "если",
+	"же", "за", "и", "из", "или", "им", "их", "к", "как", "ко", "ли", "мне", "мы", "на", "над", "не",
+	"нет", "ни", "но", "о", "об", "он", "она", "они", "от", "по", "под", "при", "с", "со", "так", "там",
+	"то", "ты", "у", "уж", "что", "это", "я",
+];
+const BULGARIAN: &[&str] = &[
+	"а", "в", "във", "да", "за", "и", "из", "или", "как", "ме", "ми", "на", "не", "но", "от", "по", "се",
+	"си", "с", "със", "та", "те", "то", "ти", "ще", "че", "я", "аз", "ти", "той", "тя", "ние", "вие", "те",
+];
+const PERSIAN: &[&str] = &[
+	"و", "در", "به", "از", "که", "را", "با", "این", "آن", "تا", "بر", "هم", "یا", "چو", "چون", "ما", "من",
+	"تو", "او", "هر", "است", "بود", "شد",
+];
+const CHINESE: &[&str] = &[
+	"之", "乎", "也", "而", "其", "以", "於", "于", "為", "为", "不", "者", "與", "与", "則", "则", "矣",
+	"焉", "哉", "兮", "的", "了", "是", "在", "有",
+];
+const JAPANESE: &[&str] = &[
+	"の", "に", "は", "を", "が", "と", "も", "で", "て", "や", "な", "し", "か", "り", "る", "た", "ぞ",
+];
+
+fn stopwords(language: Option<&str>) -> &'static [&'static str] {
+	match language.map(|l| l.split('-').next().unwrap_or(l)) {
+		Some("eng") | Some("en") => ENGLISH,
+		Some("deu") | Some("de") => GERMAN,
+		Some("fra") | Some("fr") => FRENCH,
+		Some("rus") | Some("ru") => RUSSIAN,
+		Some("bul") | Some("bg") => BULGARIAN,
+		Some("fas") | Some("fa") => PERSIAN,
+		Some("lzh") | Some("zho") | Some("yue") | Some("zh") => CHINESE,
+		Some("jpn") | Some("ojp") | Some("ja") => JAPANESE,
+		_ => &[],
+	}
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+	let mut tokens = Vec::new();
+	let mut word = String::new();
+	for c in text.chars() {
+		if is_cjk(c) {
+			if !word.is_empty() {
+				tokens.push(std::mem::take(&mut word));
+			}
+			tokens.push(c.to_string());
+		} else if c.is_alphanumeric() || (c == '\'' && !word.is_empty()) {
+			word.extend(c.to_lowercase());
+		} else if !word.is_empty() {
+			tokens.push(std::mem::take(&mut word));
+		}
+	}
+	if !word.is_empty() {
+		tokens.push(word);
+	}
+	tokens
+}
+
+// Most frequent content words across the given versions, skipping each version's stopwords
+pub fn word_frequencies<'a>(versions: impl Iterator<Item = &'a Version>) -> Vec<(String, usize)> {
+	let mut counts: HashMap<String, usize> = HashMap::new();
+	for version in versions {
+		let stop = stopwords(version.language.as_deref());
+		for token in tokenize(&version.text) {
+			let token = token.trim_end_matches('\'').to_string();
+			if token.chars().all(|c| c.is_numeric()) || stop.contains(&token.as_str()) {
+				continue;
+			}
+			if token.chars().count() < 2 && !token.chars().all(is_cjk) {
+				continue;
+			}
+			*counts.entry(token).or_insert(0) += 1;
+		}
+	}
+	let mut frequencies: Vec<(String, usize)> = counts.into_iter().collect();
+	frequencies.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+	frequencies
+}
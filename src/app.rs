@@ -1,6 +1,51 @@
-use crate::models::{Poem, Version};
-use std::collections::HashMap;
-use ratatui::widgets::ListState;
+use crate::models::{poems_dir, read_versions, sample_poem, write_poem, year_label, LoadError, Poem, Version};
+use crate::analysis::word_frequencies;
+use crate::audio::Recording;
+use crate::dictionary::lookup;
+use crate::editor::Editor;
+use crate::ui::{interlinear_lines, parse_markdown, render_poem_text, word_spans, wrap_poem_lines};
+#[cfg(feature = "tts")]
+use crate::speech::Speech;
+#[cfg(feature = "search")]
+use crate::search::SearchIndex;
+use crate::collate::Collation;
+use crate::config::Config;
+use crate::git;
+use crate::keymap::Action;
+use crate::history::History;
+use crate::library;
+use crate::facets::{self, Facet};
+use crate::sync;
+use crate::tasks::Task;
+use crate::commands::{self, command_line};
+use crate::utils::{get_language_name, language_label, script_language_label, surname_sort_key, title_group, Interner, LanguageTag};
+use std::{borrow::Cow, collections::{BTreeMap, HashMap, HashSet}, fs, io, path::PathBuf, rc::Rc, time::SystemTime};
+use ratatui::{text::Line, widgets::{ListState, TableState}};
+use crossterm::event::KeyEvent;
+use rand::Rng;
+
+// Where the reader opens instead of the menu, from the command line
+#[derive(Debug)]
+pub enum Start {
+	// A poem id, as picked by `leaves pick`
+	Poem(String),
+	// The first poem loaded, for a poem file read on its own
+	First,
+	Random,
+	// The poem read most recently
+	Last,
+	// The poems of an author, matched by name
+	Author(String),
+}
+
+// What the reader loop hands on: a key, a new terminal size, or a tick when neither came
+// in time, for what goes on while nobody types
+#[derive(Debug)]
+pub enum Message {
+	Key(KeyEvent),
+	Resize,
+	Tick,
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum AppMode {
@@ -12,6 +57,258 @@ pub enum AppMode {
 	FilteredList,
 	Search,
 	VersionSelect,
+	Statistics,
+	WordFrequency,
+	Definition,
+	LoadErrors,
+	FolderList,
+	// Groups of poems under a facet such as their translators, by its place in `App::facets`
+	FacetList(usize),
+	// Popup listing the fields of the version being viewed
+	Metadata,
+	// Popup showing what merging the marked poem into the current one would do
+	ConfirmMerge,
+	// Popup editing the path of the current poem's file inside the library
+	Rename,
+	// Popup asking before the current poem's file is moved to the trash
+	ConfirmDelete,
+	// Files in the trash, to be restored
+	Trash,
+	// Popup form editing the title, author, language, flags and tags of the current version
+	EditMetadata,
+	// The text of the current version in the built-in editor
+	Editing,
+	// Popup with the uncommitted changes to the current poem's file
+	GitDiff,
+	// The message for committing the current poem's file being typed
+	GitCommit,
+	// Shown instead of the menu while the library has no poems
+	Onboarding,
+	// Popup with what a command of `keybind` printed
+	CommandOutput,
+}
+
+// The name an author is listed under, for each of their other names in `author_names`: the
+// one most poems give as their author, so 松尾芭蕉 and Matsuo Bashō are listed once
+fn author_aliases(poems: &[Poem]) -> HashMap<String, String> {
+	let canonicals: Vec<&Version> = poems.iter().filter_map(Poem::canonical).collect();
+	// Names given together are one author, however many poems it takes to link them
+	let mut groups: Vec<HashSet<String>> = Vec::new();
+	for version in &canonicals {
+		let Some(author) = &version.author else { continue };
+		let mut group: HashSet<String> = std::iter::once(author).chain(version.author_names.iter().flat_map(|names| names.values())).cloned().collect();
+		if group.len() == 1 {
+			continue;
+		}
+		let (linked, rest): (Vec<_>, Vec<_>) = groups.into_iter().partition(|g| !g.is_disjoint(&group));
+		group.extend(linked.into_iter().flatten());
+		groups = rest;
+		groups.push(group);
+	}
+	let mut counts: HashMap<&String, usize> = HashMap::new();
+	for author in canonicals.iter().filter_map(|v| v.author.as_ref()) {
+		*counts.entry(author).or_insert(0) += 1;
+	}
+	let mut aliases = HashMap::new();
+	for group in groups {
+		let Some(listed) = group.iter().max_by_key(|name| (counts.get(name).copied().unwrap_or(0), std::cmp::Reverse(*name))).cloned() else { continue };
+		for name in group {
+			if name != listed {
+				aliases.insert(name, listed.clone());
+			}
+		}
+	}
+	aliases
+}
+
+// Sort key putting poems in order of their year, those without one last
+fn chronological_key(year: Option<i32>) -> (bool, i32) {
+	(year.is_none(), year.unwrap_or(0))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+	Alphabetical,
+	Count,
+	Recent,
+	Length,
+	// By `year`, earliest first; poems without one come last
+	Chronological,
+}
+
+impl SortOrder {
+	pub fn next(self) -> SortOrder {
+		match self {
+			SortOrder::Alphabetical => SortOrder::Count,
+			SortOrder::Count => SortOrder::Recent,
+			SortOrder::Recent => SortOrder::Length,
+			SortOrder::Length => SortOrder::Chronological,
+			SortOrder::Chronological => SortOrder::Alphabetical,
+		}
+	}
+	pub fn label(self) -> &'static str {
+		match self {
+			SortOrder::Alphabetical => "alphabetical",
+			SortOrder::Count => "by count",
+			SortOrder::Recent => "recently added",
+			SortOrder::Length => "by length",
+			SortOrder::Chronological => "chronological",
+		}
+	}
+}
+
+// How the viewer shows a version that has a transliteration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScriptView {
+	Original,
+	Transliteration,
+	// Each line followed by its transliteration
+	Interlinear,
+}
+
+// Everything the viewer's wrapped lines depend on besides the text itself, which only changes
+// when the library is loaded again and so a new App is made
+#[derive(Clone, PartialEq)]
+struct WrapKey {
+	poem: usize,
+	version: String,
+	text_variant: Option<String>,
+	script: ScriptView,
+	width: usize,
+	initials: bool,
+	word_cursor: Option<(usize, usize)>,
+}
+
+impl ScriptView {
+	pub fn next(self) -> ScriptView {
+		match self {
+			ScriptView::Original => ScriptView::Transliteration,
+			ScriptView::Transliteration => ScriptView::Interlinear,
+			ScriptView::Interlinear => ScriptView::Original,
+		}
+	}
+	pub fn label(self) -> &'static str {
+		match self {
+			ScriptView::Original => "original",
+			ScriptView::Transliteration => "transliteration",
+			ScriptView::Interlinear => "interlinear",
+		}
+	}
+}
+
+// A row of the grouped title list: a section header or a position in `sorted_titles`
+#[derive(Debug, Clone, PartialEq)]
+pub enum TitleRow {
+	Header(String),
+	Title(usize),
+}
+
+// What the reader does once a task running in the background ends
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaskEnd {
+	// Loads the library again, as the task may have changed it
+	ReloadLibrary,
+	// Shows what the task wrote
+	ShowOutput,
+}
+
+// Menu entries, in the order they are listed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MenuEntry {
+	Authors,
+	Languages,
+	Titles,
+	// Only when the library has subdirectories
+	Folders,
+	Search,
+	Random,
+	Unread,
+	Statistics,
+	// Only when some poem has a value for the facet
+	Facet(usize),
+	// Only when files were skipped
+	LoadErrors,
+	// Only when there are files in the trash
+	Trash,
+	// Only when a sync left files to reconcile
+	SyncConflicts,
+	// Only when there is something to sync with
+	Sync,
+}
+
+// The fields of the quick metadata editor as typed. The flags read "yes" or "no" and are
+// toggled rather than typed; tags are separated by commas.
+#[derive(Debug, Default)]
+pub struct MetadataForm {
+	pub fields: Vec<(&'static str, String)>,
+	pub selected: usize,
+}
+
+impl MetadataForm {
+	const FLAGS: [&'static str; 2] = ["Right to left", "Vertical"];
+
+	fn new(version: &Version) -> MetadataForm {
+		let flag = |value: Option<bool>| if value.unwrap_or(false) { "yes" } else { "no" }.to_string();
+		let fields = vec![
+			("Title", version.title.clone().unwrap_or_default()),
+			("Author", version.author.clone().unwrap_or_default()),
+			("Language", version.language.clone().unwrap_or_default()),
+			(Self::FLAGS[0], flag(version.rtl)),
+			(Self::FLAGS[1], flag(version.vertical)),
+			("Tags", version.tags.clone().unwrap_or_default().join(", ")),
+		];
+		MetadataForm { fields, selected: 0 }
+	}
+
+	pub fn is_flag(&self, i: usize) -> bool {
+		Self::FLAGS.contains(&self.fields[i].0)
+	}
+
+	pub fn next(&mut self) {
+		self.selected = (self.selected + 1) % self.fields.len();
+	}
+
+	pub fn previous(&mut self) {
+		self.selected = self.selected.checked_sub(1).unwrap_or(self.fields.len() - 1);
+	}
+
+	// A typed character goes into a text field; on a flag any key toggles it
+	pub fn type_char(&mut self, c: char) {
+		let flag = self.is_flag(self.selected);
+		let value = &mut self.fields[self.selected].1;
+		if flag {
+			*value = if value == "yes" { "no" } else { "yes" }.to_string();
+		} else {
+			value.push(c);
+		}
+	}
+
+	pub fn delete_char(&mut self) {
+		if !self.is_flag(self.selected) {
+			self.fields[self.selected].1.pop();
+		}
+	}
+
+	pub fn to_edit(&self) -> library::MetadataEdit {
+		let text = |i: usize| Some(self.fields[i].1.trim().to_string()).filter(|value| !value.is_empty());
+		let flag = |i: usize| (self.fields[i].1 == "yes").then_some(true);
+		let tags: Vec<String> = self.fields[5].1.split(',').map(str::trim).filter(|tag| !tag.is_empty()).map(str::to_string).collect();
+		library::MetadataEdit {
+			title: text(0),
+			author: text(1),
+			language: text(2),
+			rtl: flag(3),
+			vertical: flag(4),
+			tags: Some(tags).filter(|tags| !tags.is_empty()),
+		}
+	}
+}
+
+// A row of the folder browser: a subdirectory with the number of poems below it, or a poem
+#[derive(Debug, Clone, PartialEq)]
+pub enum FolderEntry {
+	Folder(String, usize),
+	Poem(usize),
 }
 
 pub struct App {
@@ -20,57 +317,185 @@ pub struct App {
 	pub current_version: String,
 	pub mode: AppMode,
 	pub previous_mode: Option<AppMode>,
+	// Poem and version left by each `see_also` link followed, newest last, for going back
+	pub link_trail: Vec<(usize, String)>,
+	// Lines scrolled down, or for a vertical poem columns scrolled across
 	pub scroll_position: u16,
 	pub viewport_height: Option<u16>,
-	pub author_counts: HashMap<String, usize>,
+	// How far the poem being read could be scrolled when it was last drawn: its wrapped lines
+	// beyond the screen, or for a vertical poem the columns that did not fit
+	pub max_scroll: u16,
+	// Author and language names are interned: each is stored once, however many poems and
+	// lists name it
+	pub author_counts: HashMap<Rc<str>, usize>,
 	pub author_list_state: ListState,
-	pub language_counts: HashMap<String, usize>,
+	pub language_counts: HashMap<Rc<str>, usize>,
+	// The entry of the language browser each language code as written is under
+	language_groups: HashMap<String, Rc<str>>,
 	pub language_list_state: ListState,
-	pub title_list_state: ListState,
+	pub title_list_state: TableState,
 	pub filtered_list_state: ListState,
 	pub menu_state: ListState,
+	// Every facet (see `facets`), with how many groups each has, counted once
+	pub facets: Vec<Rc<dyn Facet>>,
+	facet_counts: Vec<usize>,
+	// Groups of the open facet, in order, each with its poems and the version that put them there
+	pub facet_groups: Vec<(String, Vec<(usize, String)>)>,
+	pub facet_list_state: ListState,
 	pub filtered_poems: Option<Vec<usize>>,
 	pub search_query: String,
 	pub search_list_state: ListState,
 	pub search_results: Vec<usize>,
 	pub version_list_state: ListState,
+	pub low_memory: bool,
+	pub loaded_poem: Option<usize>,
+	pub author_sort_names: HashMap<Rc<str>, String>,
+	// Other names of authors, to the one each is listed under
+	author_aliases: HashMap<String, Rc<str>>,
+	pub sort_authors_by_surname: bool,
+	// Language codes whose versions open before the canonical one, most wanted first
+	pub preferred_languages: Vec<String>,
+	pub author_sort: SortOrder,
+	pub language_sort: SortOrder,
+	pub title_sort: SortOrder,
+	pub filtered_sort: SortOrder,
+	// Sorted orderings are cached here and rebuilt only when a sort order or the library changes
+	sorted_titles: Vec<(usize, String)>,
+	pub title_rows: Vec<TitleRow>,
+	sorted_authors: Vec<Rc<str>>,
+	sorted_languages: Vec<Rc<str>>,
+	collation: Collation,
+	pub history: History,
+	last_marked: Option<usize>,
+	// Poems not in the reading history, counted when the library changes rather than each frame
+	unread: usize,
+	pub word_frequencies: Vec<(String, usize)>,
+	pub word_frequency_state: ListState,
+	pub list_reading_time: bool,
+	pub acrostic: bool,
+	// Kept from poem to poem, for those with a transliteration
+	pub script_view: ScriptView,
+	// Name of the text variant shown instead of `text`, likewise kept from poem to poem
+	pub text_variant: Option<String>,
+	// Selected (line, word) in the displayed poem text while the word cursor is active
+	pub word_cursor: Option<(usize, usize)>,
+	// The poem being read as last wrapped for the viewer, so a long poem is not wrapped again
+	// on every frame and key press
+	wrapped: Option<(WrapKey, Vec<Line<'static>>)>,
+	pub definition: Option<(String, String)>,
+	pub definition_scroll: u16,
+	pub dictionary_command: Option<String>,
+	// One-off message shown in place of the status bar until the next key press
+	pub status_message: Option<String>,
+	// Reading the built-in poems of `leaves --demo`, which cannot be edited
+	pub demo: bool,
+	// Off for the demo and for a file read on its own, which leave the reading history alone
+	pub save_history: bool,
+	// Poem files cannot be changed from the reader: the demo, a file read on its own, or an archive
+	pub read_only: bool,
+	// Poem marked with `M` to be merged into the next poem `M` is pressed on
+	pub merge_mark: Option<usize>,
+	// The new path being typed in the rename popup
+	pub rename_input: String,
+	pub metadata_form: MetadataForm,
+	pub editor: Option<Editor>,
+	// Uncommitted poem files and their state (see `git::status`) when the library is in git
+	pub git_status: Option<HashMap<String, char>>,
+	pub git_diff: String,
+	pub git_diff_scroll: u16,
+	pub commit_input: String,
+	// Files in the library's trash, read at start and after every change to it
+	pub trashed: Vec<String>,
+	pub trash_list_state: ListState,
+	// Poem files changed on both sides of a `leaves sync`, with the other side's version beside them
+	pub sync_conflicts: Vec<String>,
+	// Whether `leaves sync` would do anything, which puts syncing on the menu
+	pub syncable: bool,
+	// Work running in the background (see `tasks`), shown in a popup until it is done
+	pub task: Option<(Task, TaskEnd)>,
+	// Commands of the `keybind` setting by their key in the viewer
+	pub commands: HashMap<char, String>,
+	// The command last run with what it printed, and how far that is scrolled
+	pub command_output: Option<(String, String)>,
+	pub command_output_scroll: u16,
+	pub recording: Option<Recording>,
+	pub audio_player: Option<String>,
+	#[cfg(feature = "tts")]
+	pub speech: Option<Speech>,
+	#[cfg(feature = "tts")]
+	pub tts_command: Option<String>,
+	// Full-text index of the poems, opened at the first search, with the position of each poem by id
+	#[cfg(feature = "search")]
+	search_index: Option<(SearchIndex, HashMap<String, usize>)>,
+	// Poem files skipped at startup, with the reason
+	pub load_errors: Vec<LoadError>,
+	pub load_errors_scroll: u16,
+	// Directory shown in the folder browser, relative to the library ("" at the top)
+	pub folder: String,
+	pub folder_entries: Vec<FolderEntry>,
+	pub folder_list_state: ListState,
 }
 
 impl App {
-	pub fn new(poems: Vec<Poem>) -> Self {
-		let author_counts = poems.iter().filter_map(|p| p.canonical()).filter_map(|v| v.author.as_ref()).fold(HashMap::new(), |mut map, author| {
-			*map.entry(author.clone()).or_insert(0) += 1;
-			map
-		});
-		let language_counts = poems.iter().flat_map(|p| {
-			p.versions.values().filter_map(|v| v.language.as_ref())
-		}).fold(HashMap::new(), |mut map, lang| {
-			*map.entry(lang.clone()).or_insert(0) += 1;
-			map
-		});
+	pub fn new(poems: Vec<Poem>, config: &Config, history: History) -> Self {
+		let mut names = Interner::default();
+		let author_aliases: HashMap<String, Rc<str>> = author_aliases(&poems).into_iter()
+			.map(|(alias, listed)| (alias, names.intern(&listed)))
+			.collect();
+		let mut listed = |author: &str| author_aliases.get(author).cloned().unwrap_or_else(|| names.intern(author));
+		let mut author_counts = HashMap::new();
+		let mut author_sort_names = HashMap::new();
+		for version in poems.iter().filter_map(|p| p.canonical()) {
+			let Some(author) = &version.author else { continue };
+			let author = listed(author);
+			if let Some(sort_name) = &version.sort_name {
+				author_sort_names.insert(author.clone(), sort_name.clone());
+			}
+			*author_counts.entry(author).or_insert(0) += 1;
+		}
+		let mut language_groups: HashMap<String, Rc<str>> = HashMap::new();
+		let mut language_counts = HashMap::new();
+		for code in poems.iter().flat_map(|p| p.versions.values().filter_map(|v| v.language.as_deref())) {
+			if !language_groups.contains_key(code) {
+				let group = match LanguageTag::parse(code) {
+					Some(tag) if config.merge_language_scripts => names.intern(&tag.without_script().to_string()),
+					Some(tag) => names.intern(&tag.to_string()),
+					None => names.intern(code),
+				};
+				language_groups.insert(code.to_string(), group);
+			}
+			*language_counts.entry(language_groups[code].clone()).or_insert(0) += 1;
+		}
 		let mut list_state = ListState::default();
 		list_state.select(Some(0));
 		let mut menu_state = ListState::default();
 		menu_state.select(Some(0));
 		let mut language_list_state = ListState::default();
 		language_list_state.select(Some(0));
-		let mut title_list_state = ListState::default();
+		let mut title_list_state = TableState::default();
 		title_list_state.select(Some(0));
 		let mut filtered_list_state = ListState::default();
 		filtered_list_state.select(Some(0));
-		Self {
+		let mut app = Self {
 			poems,
 			current_poem: 0,
 			current_version: "canonical".to_string(),
 			mode: AppMode::Menu,
 			previous_mode: None,
+			link_trail: Vec::new(),
 			scroll_position: 0,
 			viewport_height: None,
+			max_scroll: 0,
 			author_counts,
 			author_list_state: list_state,
 			language_counts,
+			language_groups,
 			language_list_state,
 			menu_state,
+			facets: facets::all(config),
+			facet_counts: Vec::new(),
+			facet_groups: Vec::new(),
+			facet_list_state: ListState::default(),
 			title_list_state,
 			filtered_list_state,
 			filtered_poems: None,
@@ -86,6 +511,981 @@ impl App {
 				state.select(Some(0));
 				state
 			},
+			low_memory: config.low_memory,
+			loaded_poem: None,
+			author_sort_names,
+			author_aliases,
+			collation: Collation::new(config),
+			sort_authors_by_surname: config.sort_authors_by_surname,
+			preferred_languages: config.languages.clone(),
+			author_sort: SortOrder::Alphabetical,
+			language_sort: SortOrder::Count,
+			title_sort: SortOrder::Alphabetical,
+			filtered_sort: SortOrder::Alphabetical,
+			sorted_titles: Vec::new(),
+			title_rows: Vec::new(),
+			sorted_authors: Vec::new(),
+			sorted_languages: Vec::new(),
+			history,
+			last_marked: None,
+			unread: 0,
+			word_frequencies: Vec::new(),
+			word_frequency_state: ListState::default(),
+			list_reading_time: config.list_reading_time,
+			acrostic: false,
+			script_view: ScriptView::Original,
+			text_variant: None,
+			word_cursor: None,
+			wrapped: None,
+			definition: None,
+			definition_scroll: 0,
+			dictionary_command: config.dictionary_command.clone(),
+			status_message: None,
+			demo: false,
+			save_history: true,
+			read_only: false,
+			merge_mark: None,
+			rename_input: String::new(),
+			metadata_form: MetadataForm::default(),
+			editor: None,
+			git_status: None,
+			git_diff: String::new(),
+			git_diff_scroll: 0,
+			commit_input: String::new(),
+			trashed: Vec::new(),
+			sync_conflicts: Vec::new(),
+			syncable: false,
+			task: None,
+			commands: config.keybind.iter().filter_map(|(key, command)| {
+				let mut chars = key.chars();
+				match (chars.next(), chars.next()) {
+					(Some(c), None) => Some((c, command.clone())),
+					_ => {
+						tracing::warn!(key, "keybind keys are single characters; ignored");
+						None
+					},
+				}
+			}).collect(),
+			command_output: None,
+			command_output_scroll: 0,
+			trash_list_state: ListState::default().with_selected(Some(0)),
+			recording: None,
+			audio_player: config.audio_player.clone(),
+			#[cfg(feature = "tts")]
+			speech: None,
+			#[cfg(feature = "tts")]
+			tts_command: config.tts_command.clone(),
+			#[cfg(feature = "search")]
+			search_index: None,
+			load_errors: Vec::new(),
+			load_errors_scroll: 0,
+			folder: String::new(),
+			folder_entries: Vec::new(),
+			folder_list_state: ListState::default(),
+		};
+		if app.poems.is_empty() {
+			app.mode = AppMode::Onboarding;
+		}
+		app.facet_counts = app.facets.iter().map(|facet| app.group_by(facet.as_ref()).len()).collect();
+		app.refresh_sorted();
+		app
+	}
+	// Writes the sample poem shown on the onboarding screen into the library
+	pub fn create_sample_poem(&self) -> io::Result<PathBuf> {
+		fs::create_dir_all(poems_dir())?;
+		write_poem("hope-is-the-thing-with-feathers.poem", &sample_poem())
+	}
+	// Rebuilds the cached orderings; call after changing a sort order or reloading poems
+	pub fn refresh_sorted(&mut self) {
+		self.sorted_titles = self.compute_sorted_titles();
+		self.title_rows.clear();
+		let mut current_group = None;
+		for (i, (_, title)) in self.sorted_titles.iter().enumerate() {
+			if self.title_sort == SortOrder::Alphabetical {
+				let group = self.collation.title_group(title);
+				if current_group.as_ref() != Some(&group) {
+					self.title_rows.push(TitleRow::Header(group.clone()));
+					current_group = Some(group);
+				}
+			}
+			self.title_rows.push(TitleRow::Title(i));
+		}
+		self.sorted_authors = self.compute_sorted_authors();
+		self.sorted_languages = self.compute_sorted_languages();
+		self.unread = (0..self.poems.len()).filter(|&i| !self.is_read(i)).count();
+	}
+	// In low-memory mode only the poem being viewed keeps its text;
+	// the previous one is dropped as soon as another is opened.
+	pub fn ensure_current_loaded(&mut self) -> io::Result<()> {
+		if !self.low_memory || self.loaded_poem == Some(self.current_poem) || self.poems.is_empty() {
+			return Ok(());
+		}
+		if let Some(previous) = self.loaded_poem.take() {
+			self.poems[previous].unload_text();
+		}
+		self.poems[self.current_poem].reload_text()?;
+		self.loaded_poem = Some(self.current_poem);
+		Ok(())
+	}
+	// Records the poem being viewed in the reading history, once per visit
+	pub fn mark_current_read(&mut self) -> io::Result<()> {
+		if self.last_marked == Some(self.current_poem) || self.poems.is_empty() {
+			return Ok(());
+		}
+		if !self.is_read(self.current_poem) {
+			self.unread -= 1;
+		}
+		self.history.mark_read(&self.poems[self.current_poem].history_key());
+		self.last_marked = Some(self.current_poem);
+		if !self.save_history {
+			return Ok(());
+		}
+		self.history.save()
+	}
+	pub fn is_read(&self, poem_idx: usize) -> bool {
+		self.history.is_read(&self.poems[poem_idx].history_key())
+	}
+	pub fn unread_count(&self) -> usize {
+		self.unread
+	}
+	// Opens the next unread poem after the current one, within the active list if there is one
+	pub fn next_unread(&mut self) {
+		let candidates: Vec<usize> = match &self.filtered_poems {
+			Some(indices) => indices.clone(),
+			None => (0..self.poems.len()).collect(),
+		};
+		let start = candidates.iter().position(|&i| i == self.current_poem).map_or(0, |p| p + 1);
+		let next = candidates.iter().cycle().skip(start).take(candidates.len()).find(|&&i| !self.is_read(i));
+		if let Some(&poem_index) = next {
+			self.current_poem = poem_index;
+			self.current_version = self.preferred_version(poem_index);
+			self.scroll_position = 0;
+		}
+	}
+	pub fn show_unread(&mut self) {
+		let mut indices: Vec<usize> = (0..self.poems.len()).filter(|&i| !self.is_read(i)).collect();
+		self.sort_poem_indices(&mut indices, self.filtered_sort);
+		if let Some(&first) = indices.first() {
+			self.current_poem = first;
+		}
+		self.filtered_poems = Some(indices);
+		self.filtered_list_state.select(Some(0));
+		self.previous_mode = Some(AppMode::Menu);
+		self.mode = AppMode::FilteredList;
+	}
+	// Syncs the library in the background; the reader loop loads it again once that is done
+	pub fn start_sync(&mut self) {
+		if self.task.is_none() {
+			self.task = Some((Task::spawn("Syncing the library", sync::sync_configured), TaskEnd::ReloadLibrary));
+		}
+	}
+	// Runs the command `key` is bound to in `keybind` on the version being read, in the background
+	pub fn run_command(&mut self, key: char) {
+		let Some(template) = self.commands.get(&key) else { return };
+		if self.task.is_some() {
+			return;
+		}
+		let poem = &self.poems[self.current_poem];
+		let version = self.get_current_version();
+		let file = poems_dir().join(&poem.filename).to_string_lossy().into_owned();
+		let selection = self.selected_word().unwrap_or_default();
+		let line = command_line(template, &[
+			("file", &file),
+			("title", version.title.as_deref().unwrap_or_default()),
+			("author", poem.author_for(version).unwrap_or_default()),
+			("language", version.language.as_deref().unwrap_or_default()),
+			("id", poem.id()),
+			("version", &self.current_version),
+			("selection", &selection),
+		]);
+		tracing::info!(line, "running command");
+		self.task = Some((Task::spawn(template, move |out| commands::run(out, &line)), TaskEnd::ShowOutput));
+	}
+	// Shows what a command printed once it ended, in a popup when it printed anything
+	pub fn show_command_output(&mut self, task: Task) {
+		let mut lines = task.lines;
+		match task.finished {
+			Some(Err(e)) if e.kind() == io::ErrorKind::Interrupted => {
+				self.status_message = Some(format!("{}: cancelled", task.title));
+				return;
+			},
+			Some(Err(e)) => lines.push(e.to_string()),
+			_ => {},
+		}
+		if lines.is_empty() {
+			self.status_message = Some(format!("{}: done", task.title));
+			return;
+		}
+		self.command_output = Some((task.title, lines.join("\n")));
+		self.command_output_scroll = 0;
+		self.mode = AppMode::CommandOutput;
+	}
+	pub fn show_sync_conflicts(&mut self) {
+		let mut indices: Vec<usize> = (0..self.poems.len()).filter(|&i| self.sync_conflicts.contains(&self.poems[i].filename)).collect();
+		self.sort_poem_indices(&mut indices, self.filtered_sort);
+		if let Some(&first) = indices.first() {
+			self.current_poem = first;
+		}
+		self.filtered_poems = Some(indices);
+		self.filtered_list_state.select(Some(0));
+		self.previous_mode = Some(AppMode::Menu);
+		self.mode = AppMode::FilteredList;
+	}
+	pub fn menu_entries(&self) -> Vec<MenuEntry> {
+		let mut entries = vec![MenuEntry::Authors, MenuEntry::Languages, MenuEntry::Titles];
+		if self.poems.iter().any(|p| !p.folder().is_empty()) {
+			entries.push(MenuEntry::Folders);
+		}
+		entries.extend((0..self.facets.len()).filter(|&i| self.facet_counts[i] > 0).map(MenuEntry::Facet));
+		entries.extend([MenuEntry::Search, MenuEntry::Random, MenuEntry::Unread, MenuEntry::Statistics]);
+		if !self.load_errors.is_empty() {
+			entries.push(MenuEntry::LoadErrors);
+		}
+		if !self.trashed.is_empty() {
+			entries.push(MenuEntry::Trash);
+		}
+		if !self.sync_conflicts.is_empty() {
+			entries.push(MenuEntry::SyncConflicts);
+		}
+		if self.syncable {
+			entries.push(MenuEntry::Sync);
+		}
+		entries
+	}
+	pub fn menu_len(&self) -> usize {
+		self.menu_entries().len()
+	}
+	// The groups of `facet` in order; a poem is listed once per group, at its first version there
+	fn group_by(&self, facet: &dyn Facet) -> Vec<(String, Vec<(usize, String)>)> {
+		let mut groups: BTreeMap<(i32, String), Vec<(usize, String)>> = BTreeMap::new();
+		for (i, poem) in self.poems.iter().enumerate() {
+			// Texts dropped in low-memory mode are read back just for the grouping
+			let reloaded = if poem.unloaded && facet.needs_text() {
+				read_versions(&poem.filename, poem.document)
+					.inspect_err(|e| tracing::warn!(file = poem.filename, error = %e, "could not read the poem back for a facet"))
+					.ok()
+			} else {
+				None
+			};
+			let versions = reloaded.as_ref().unwrap_or(&poem.versions);
+			let mut keys: Vec<&String> = versions.keys().collect();
+			keys.sort_by_key(|key| (*key != "canonical", *key));
+			for key in keys {
+				for value in facet.values(poem, key, &versions[key]) {
+					let group = groups.entry(value).or_default();
+					if group.last().map(|(last, _)| *last) != Some(i) {
+						group.push((i, key.clone()));
+					}
+				}
+			}
+		}
+		groups.into_iter().map(|((_, name), poems)| (name, poems)).collect()
+	}
+	// How many groups the menu entry of `facet` leads to
+	pub fn facet_group_count(&self, facet: usize) -> usize {
+		self.facet_counts[facet]
+	}
+	pub fn open_facet(&mut self, facet: usize) {
+		self.facet_groups = self.group_by(self.facets[facet].as_ref());
+		self.facet_list_state.select(Some(0));
+		self.mode = AppMode::FacetList(facet);
+	}
+	pub fn next_facet_group(&mut self) {
+		let len = self.facet_groups.len().max(1);
+		let i = self.facet_list_state.selected().map_or(0, |i| (i + 1) % len);
+		self.facet_list_state.select(Some(i));
+	}
+	pub fn previous_facet_group(&mut self) {
+		let len = self.facet_groups.len().max(1);
+		let i = self.facet_list_state.selected().map_or(0, |i| if i == 0 { len - 1 } else { i - 1 });
+		self.facet_list_state.select(Some(i));
+	}
+	// The version a poem is listed under in the selected facet group
+	pub fn facet_version(&self, poem_idx: usize) -> Option<&String> {
+		let (_, poems) = self.facet_groups.get(self.facet_list_state.selected()?)?;
+		poems.iter().find(|(i, _)| *i == poem_idx).map(|(_, key)| key)
+	}
+	pub fn select_current_facet_group(&mut self) {
+		let AppMode::FacetList(facet) = self.mode else { return };
+		let Some((_, poems)) = self.facet_list_state.selected().and_then(|i| self.facet_groups.get(i)) else { return };
+		let mut indices: Vec<usize> = poems.iter().map(|(i, _)| *i).collect();
+		if let Some(order) = self.facets[facet].sort() {
+			self.filtered_sort = order;
+		}
+		self.sort_poem_indices(&mut indices, self.filtered_sort);
+		if let Some(&first) = indices.first() {
+			self.current_poem = first;
+		}
+		self.filtered_poems = Some(indices);
+		self.filtered_list_state.select(Some(0));
+		self.previous_mode = Some(AppMode::FacetList(facet));
+		self.mode = AppMode::FilteredList;
+	}
+	// Label and value of every field set in the version being viewed, for the metadata popup
+	pub fn metadata_rows(&self) -> Vec<(&'static str, String)> {
+		let poem = &self.poems[self.current_poem];
+		let version = self.get_current_version();
+		let mut rows = vec![("Version", self.current_version.clone())];
+		let mut push = |label, value: Option<String>| {
+			if let Some(value) = value {
+				rows.push((label, value));
+			}
+		};
+		push("Title", version.title.clone());
+		push("Author", version.author.clone());
+		push("Filed as", version.sort_name.clone());
+		push("Translator", version.translator.clone());
+		push("Written", version.date_label());
+		push("Form", version.form.clone());
+		push("Language", version.language.as_ref().map(|code| match get_language_name(code) {
+			Some(name) => format!("{} ({})", name, code),
+			None => code.clone(),
+		}));
+		push("Tags", version.tags.as_ref().filter(|tags| !tags.is_empty()).map(|tags| tags.join(", ")));
+		push("Source", version.source.clone());
+		push("License", version.license.clone());
+		push("Recording", version.audio.clone());
+		push("Image", version.image.clone());
+		push("Length", Some(version.counts.summary()));
+		push("File", Some(poem.filename.clone()));
+		for (i, (id, idx)) in self.see_also().into_iter().enumerate() {
+			let link = match idx.and_then(|idx| self.poems[idx].canonical()) {
+				Some(linked) => format!("{}  {} - {}", i + 1, linked.author.as_deref().unwrap_or("Unknown"), linked.title.as_deref().unwrap_or("Untitled")),
+				None => format!("   {} (not in the library)", id),
+			};
+			rows.push((if i == 0 { "See also" } else { "" }, link));
+		}
+		rows
+	}
+	// Poems linked from the version being read with `see_also`, or else from the canonical
+	// version, with their index; ids missing from the library have none
+	pub fn see_also(&self) -> Vec<(String, Option<usize>)> {
+		let poem = &self.poems[self.current_poem];
+		let links = self.get_current_version().see_also.as_ref()
+			.or_else(|| poem.canonical().and_then(|v| v.see_also.as_ref()));
+		links.into_iter().flatten()
+			.map(|id| (id.clone(), self.poems.iter().position(|p| p.id() == id.as_str())))
+			.collect()
+	}
+	// Opens the `n`th linked poem (from 0), remembering the poem left so backspace returns to it
+	pub fn follow_link(&mut self, n: usize) {
+		let Some(Some(idx)) = self.see_also().get(n).map(|(_, idx)| *idx) else {
+			return;
+		};
+		self.link_trail.push((self.current_poem, self.current_version.clone()));
+		self.view_poem(idx);
+		self.scroll_position = 0;
+	}
+	// Returns to the poem the last link was followed from; false when no link was followed
+	pub fn back_from_link(&mut self) -> bool {
+		let Some((idx, version)) = self.link_trail.pop() else {
+			return false;
+		};
+		self.current_poem = idx;
+		self.current_version = version;
+		self.scroll_position = 0;
+		true
+	}
+	// Marks the current poem for merging, or, with another poem marked, asks to merge that one into this
+	pub fn mark_for_merge(&mut self) {
+		if self.read_only {
+			self.status_message = Some("These poems are read-only".to_string());
+			return;
+		}
+		let title = |poem: &Poem| poem.canonical().and_then(|v| v.title.clone()).unwrap_or_else(|| poem.id().to_string());
+		match self.merge_mark {
+			Some(marked) if marked != self.current_poem => {
+				let checked = library::check_rewritable(&self.poems[self.current_poem])
+					.and_then(|()| library::check_rewritable(&self.poems[marked]));
+				match checked {
+					Ok(()) => self.mode = AppMode::ConfirmMerge,
+					Err(e) => self.status_message = Some(format!("Cannot merge: {}", e)),
+				}
+			}
+			_ => {
+				self.merge_mark = Some(self.current_poem);
+				self.status_message = Some(format!("Marked \"{}\"; press M on the poem to merge it into", title(&self.poems[self.current_poem])));
+			}
+		}
+	}
+	// What the merge popup shows: where each version of the marked poem goes, and what happens to its file
+	pub fn merge_preview(&self) -> Vec<String> {
+		let Some(marked) = self.merge_mark else {
+			return Vec::new();
+		};
+		let (into, from) = (&self.poems[self.current_poem], &self.poems[marked]);
+		let mut lines = vec![format!("Versions of {} added to {}:", from.filename, into.filename), String::new()];
+		for (key, new_key) in library::merge_keys(into, from) {
+			let version = &from.versions[&key];
+			lines.push(format!("  {} → {}  ({})", key, new_key, version.title.as_deref().unwrap_or("Untitled")));
+		}
+		lines.push(String::new());
+		lines.push(format!("{} is then moved to {}/.", from.filename, library::TRASH_DIR));
+		lines
+	}
+	// Opens the rename popup on a name made from the author and title
+	pub fn start_rename(&mut self) {
+		if self.read_only {
+			self.status_message = Some("These poems are read-only".to_string());
+			return;
+		}
+		self.rename_input = library::suggested_filename(&self.poems[self.current_poem]);
+		self.mode = AppMode::Rename;
+	}
+	// Switches the input between the suggested name and the current one
+	pub fn toggle_rename_suggestion(&mut self) {
+		let poem = &self.poems[self.current_poem];
+		self.rename_input = if self.rename_input == poem.filename { library::suggested_filename(poem) } else { poem.filename.clone() };
+	}
+	// Renames the file to the typed path; every poem it holds follows, with its reading history
+	pub fn finish_rename(&mut self) {
+		let from = self.poems[self.current_poem].filename.clone();
+		match library::rename(&from, &self.rename_input) {
+			Ok(to) if to == from => self.mode = AppMode::Viewing,
+			Ok(to) => {
+				for poem in self.poems.iter_mut().filter(|p| p.filename == from) {
+					poem.set_filename(&to);
+				}
+				self.history.rename_file(&from, &to);
+				if self.save_history {
+					if let Err(e) = self.history.save() {
+						tracing::warn!(error = %e, "could not save the history after a rename");
+					}
+				}
+				self.refresh_sorted();
+				self.status_message = Some(format!("Renamed {} to {}", from, to));
+				self.mode = AppMode::Viewing;
+			}
+			// The popup stays open to correct the name
+			Err(e) => self.status_message = Some(format!("Cannot rename: {}", e)),
+		}
+	}
+	// Opens the metadata editor on the version being read
+	pub fn start_metadata_edit(&mut self) {
+		if self.read_only {
+			self.status_message = Some("These poems are read-only".to_string());
+			return;
+		}
+		self.metadata_form = MetadataForm::new(self.get_current_version());
+		self.mode = AppMode::EditMetadata;
+	}
+	// Opens the built-in editor on the text of the version being read, as it is in the file now
+	pub fn start_editing(&mut self) {
+		if self.read_only {
+			self.status_message = Some("These poems are read-only".to_string());
+			return;
+		}
+		let poem = &self.poems[self.current_poem];
+		let text = library::check_rewritable(poem)
+			.and_then(|()| read_versions(&poem.filename, None))
+			.and_then(|mut versions| versions.remove(&self.current_version)
+				.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} no longer has a version {}", poem.filename, self.current_version))));
+		match text {
+			Ok(version) => {
+				self.editor = Some(Editor::new(&version.text));
+				self.mode = AppMode::Editing;
+			}
+			Err(e) => self.status_message = Some(format!("Cannot edit: {}", e)),
+		}
+	}
+	// Whether the poem's file has uncommitted changes, as `M`, `A` or `?`
+	pub fn git_state(&self, poem_idx: usize) -> Option<char> {
+		self.git_status.as_ref()?.get(&self.poems[poem_idx].filename).copied()
+	}
+	// Shows what changed in the current poem's file since the last commit
+	pub fn show_git_diff(&mut self) {
+		if self.read_only {
+			self.status_message = Some("These poems are read-only".to_string());
+			return;
+		}
+		self.git_status = git::status();
+		if self.git_status.is_none() {
+			self.status_message = Some("The library is not in a git repository".to_string());
+			return;
+		}
+		if self.git_state(self.current_poem).is_none() {
+			self.status_message = Some(format!("{} has no uncommitted changes", self.poems[self.current_poem].filename));
+			return;
+		}
+		match git::diff(&self.poems[self.current_poem].filename) {
+			Ok(diff) => {
+				self.git_diff = diff;
+				self.git_diff_scroll = 0;
+				self.mode = AppMode::GitDiff;
+			}
+			Err(e) => self.status_message = Some(e.to_string()),
+		}
+	}
+	// Commits the current poem's file with the typed message
+	pub fn commit_current(&mut self) {
+		let filename = self.poems[self.current_poem].filename.clone();
+		let message = self.commit_input.trim();
+		if message.is_empty() {
+			self.status_message = Some("A commit needs a message".to_string());
+			return;
+		}
+		match git::commit(&filename, message) {
+			Ok(()) => {
+				self.git_status = git::status();
+				self.status_message = Some(format!("Committed {}", filename));
+				self.mode = AppMode::Viewing;
+			}
+			Err(e) => self.status_message = Some(e.to_string()),
+		}
+	}
+	// Asks before moving the current poem's file to the trash
+	pub fn start_delete(&mut self) {
+		if self.read_only {
+			self.status_message = Some("These poems are read-only".to_string());
+			return;
+		}
+		self.mode = AppMode::ConfirmDelete;
+	}
+	// What the delete popup shows: the file, and the other poems it takes along
+	pub fn delete_preview(&self) -> Vec<String> {
+		let filename = &self.poems[self.current_poem].filename;
+		let mut lines = vec![format!("Move {} to {}/?", filename, library::TRASH_DIR), String::new()];
+		let poems: Vec<&Poem> = self.poems.iter().filter(|p| p.filename == *filename).collect();
+		if poems.len() > 1 {
+			lines.push(format!("The file holds {} poems, which all go with it.", poems.len()));
+			lines.push(String::new());
+		}
+		lines.push("It can be restored from \"Trash\" in the menu.".to_string());
+		lines
+	}
+	pub fn next_trashed(&mut self) {
+		let i = self.trash_list_state.selected().map_or(0, |i| (i + 1) % self.trashed.len().max(1));
+		self.trash_list_state.select(Some(i));
+	}
+	pub fn previous_trashed(&mut self) {
+		let i = match self.trash_list_state.selected() {
+			Some(0) | None => self.trashed.len().saturating_sub(1),
+			Some(i) => i - 1,
+		};
+		self.trash_list_state.select(Some(i));
+	}
+	// Lists the subdirectories and poems of `folder`, as they are on disk
+	pub fn open_folder(&mut self, folder: String) {
+		let prefix = if folder.is_empty() { String::new() } else { format!("{}/", folder) };
+		let mut subfolders: BTreeMap<String, usize> = BTreeMap::new();
+		let mut poems = Vec::new();
+		for (i, poem) in self.poems.iter().enumerate() {
+			if poem.folder() == folder {
+				poems.push(FolderEntry::Poem(i));
+			} else if let Some(rest) = format!("{}/", poem.folder()).strip_prefix(&prefix) {
+				let name = rest.split('/').next().unwrap_or(rest);
+				*subfolders.entry(name.to_string()).or_default() += 1;
+			}
+		}
+		self.folder_entries = subfolders.into_iter().map(|(name, count)| FolderEntry::Folder(name, count)).collect();
+		self.folder_entries.extend(poems);
+		self.folder = folder;
+		self.folder_list_state.select(Some(0));
+		self.mode = AppMode::FolderList;
+	}
+	// Goes back to the parent directory, keeping the one just left selected, or to the menu from the top
+	pub fn leave_folder(&mut self) {
+		if self.folder.is_empty() {
+			self.set_mode(AppMode::Menu);
+			return;
+		}
+		let (parent, name) = match self.folder.rsplit_once('/') {
+			Some((parent, name)) => (parent.to_string(), name.to_string()),
+			None => (String::new(), self.folder.clone()),
+		};
+		self.open_folder(parent);
+		let position = self.folder_entries.iter().position(|e| matches!(e, FolderEntry::Folder(n, _) if *n == name));
+		self.folder_list_state.select(Some(position.unwrap_or(0)));
+	}
+	pub fn next_folder_entry(&mut self) {
+		let i = match self.folder_list_state.selected() {
+			Some(i) => (i + 1) % self.folder_entries.len().max(1),
+			None => 0,
+		};
+		self.folder_list_state.select(Some(i));
+	}
+	pub fn previous_folder_entry(&mut self) {
+		let i = match self.folder_list_state.selected() {
+			Some(i) => if i == 0 { self.folder_entries.len().saturating_sub(1) } else { i - 1 },
+			None => 0,
+		};
+		self.folder_list_state.select(Some(i));
+	}
+	// Opens the selected subdirectory, or reads the selected poem with ←/→ moving within the directory
+	pub fn select_current_folder_entry(&mut self) {
+		let Some(entry) = self.folder_list_state.selected().and_then(|i| self.folder_entries.get(i)).cloned() else { return };
+		match entry {
+			FolderEntry::Folder(name, _) => {
+				let folder = if self.folder.is_empty() { name } else { format!("{}/{}", self.folder, name) };
+				self.open_folder(folder);
+			}
+			FolderEntry::Poem(poem_index) => {
+				let indices = self.folder_entries.iter().filter_map(|e| match e {
+					FolderEntry::Poem(i) => Some(*i),
+					FolderEntry::Folder(..) => None,
+				}).collect();
+				self.current_poem = poem_index;
+				self.current_version = self.preferred_version(poem_index);
+				self.filtered_poems = Some(indices);
+				self.previous_mode = Some(AppMode::FolderList);
+				self.mode = AppMode::Viewing;
+			}
+		}
+	}
+	// The keys of a poem's versions, canonical first and the others in order
+	pub fn version_keys(&self, poem_idx: usize) -> Vec<&String> {
+		let mut keys: Vec<&String> = self.poems[poem_idx].versions.keys().collect();
+		keys.sort_by_key(|key| (*key != "canonical", *key));
+		keys
+	}
+	// The version a poem opens at: the first in a preferred language, or else the canonical one
+	pub fn preferred_version(&self, poem_idx: usize) -> String {
+		let versions = &self.poems[poem_idx].versions;
+		let keys = self.version_keys(poem_idx);
+		self.preferred_languages.iter()
+			.find_map(|language| keys.iter().find(|key| versions[**key].language.as_deref() == Some(language)))
+			.map_or_else(|| "canonical".to_string(), |key| key.to_string())
+	}
+	// How a version is offered in the version selector: "Language — title (translator)", with
+	// the key standing in for a missing language
+	pub fn version_choice(&self, poem_idx: usize, key: &str) -> String {
+		let version = &self.poems[poem_idx].versions[key];
+		let language = match version.language.as_deref() {
+			Some(code) => language_label(code),
+			None => key,
+		};
+		let mut label = format!("{} — {}", language, version.title.as_deref().unwrap_or("Untitled"));
+		if let Some(translator) = &version.translator {
+			label.push_str(&format!(" ({})", translator));
+		}
+		label
+	}
+	// Moves to the next or previous version of the poem being read, wrapping around
+	pub fn cycle_version(&mut self, forward: bool) {
+		let keys = self.version_keys(self.current_poem);
+		let position = keys.iter().position(|key| **key == self.current_version).unwrap_or(0);
+		let next = if forward { (position + 1) % keys.len() } else { (position + keys.len() - 1) % keys.len() };
+		self.current_version = keys[next].clone();
+	}
+	// Which of the poem's versions is being read, as "key 2/3", when it has several
+	pub fn version_label(&self) -> Option<String> {
+		let keys = self.version_keys(self.current_poem);
+		let position = keys.iter().position(|key| **key == self.current_version)?;
+		(keys.len() > 1).then(|| format!("{} {}/{}", self.current_version, position + 1, keys.len()))
+	}
+	// Whether `languages` picked the version being read over the canonical one
+	pub fn is_preferred_pick(&self) -> bool {
+		self.current_version != "canonical" && self.current_version == self.preferred_version(self.current_poem)
+	}
+	fn view_poem(&mut self, idx: usize) {
+		self.current_poem = idx;
+		self.current_version = self.preferred_version(idx);
+		self.mode = AppMode::Viewing;
+	}
+	// Starts the reader somewhere other than the menu; fails when there is nothing to show there
+	pub fn start_at(&mut self, start: Start) -> io::Result<()> {
+		let not_found = |message: String| io::Error::new(io::ErrorKind::NotFound, message);
+		match start {
+			Start::Poem(id) => {
+				let idx = self.poems.iter().position(|p| p.id() == id).ok_or_else(|| not_found(format!("no poem with id {}", id)))?;
+				self.view_poem(idx);
+			}
+			Start::First => {
+				if self.poems.is_empty() {
+					return Err(not_found("there is no poem to show".to_string()));
+				}
+				self.view_poem(0);
+			}
+			Start::Random => {
+				if self.poems.is_empty() {
+					return Err(not_found("the library is empty".to_string()));
+				}
+				self.view_poem(rand::thread_rng().gen_range(0..self.poems.len()));
+			}
+			Start::Last => {
+				let last = self.poems.iter().enumerate()
+					.filter_map(|(i, poem)| self.history.last_read(&poem.history_key()).map(|time| (time, i)))
+					.max()
+					.ok_or_else(|| not_found("no poem has been read yet".to_string()))?;
+				self.view_poem(last.1);
+			}
+			Start::Author(name) => {
+				// An exact name first, otherwise the one author whose name contains `name`;
+				// the `sort_name` and other names count too, so "Pushkin" finds Александр Пушкин
+				let wanted = name.to_lowercase();
+				let names = |author: &Rc<str>| [Some(&**author), self.author_sort_names.get(author).map(String::as_str)].into_iter().flatten()
+					.chain(self.author_aliases.iter().filter(|(_, listed)| *listed == author).map(|(alias, _)| alias.as_str()))
+					.map(|n| n.to_lowercase()).collect::<Vec<_>>();
+				let exact = self.get_sorted_authors().iter().position(|a| names(a).contains(&wanted));
+				let matching: Vec<usize> = self.get_sorted_authors().iter().enumerate()
+					.filter(|(_, a)| names(a).iter().any(|n| n.contains(&wanted)))
+					.map(|(i, _)| i)
+					.collect();
+				let index = match (exact, matching.as_slice()) {
+					(Some(i), _) => i,
+					(None, [i]) => *i,
+					(None, []) => return Err(not_found(format!("no author matches \"{}\"", name))),
+					(None, several) => {
+						let names: Vec<&str> = several.iter().map(|&i| &*self.get_sorted_authors()[i]).collect();
+						return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("\"{}\" matches several authors: {}", name, names.join(", "))));
+					}
+				};
+				self.author_list_state.select(Some(index));
+				self.select_current_author();
+			}
+		}
+		Ok(())
+	}
+	// Extra text shown after a poem in lists: its year, and the reading time when enabled
+	pub fn list_suffix(&self, poem_idx: usize) -> String {
+		let Some(canonical) = self.poems[poem_idx].canonical() else { return String::new() };
+		let mut suffix = String::new();
+		if let Some(date) = canonical.year().map(year_label) {
+			suffix.push_str(&format!(" {}", date));
+		}
+		if self.list_reading_time {
+			suffix.push_str(&format!(" {}", canonical.counts.reading_time_label()));
+		}
+		if self.sync_conflicts.contains(&self.poems[poem_idx].filename) {
+			suffix.push_str(" [conflict]");
+		}
+		if let Some(state) = self.git_state(poem_idx) {
+			suffix.push_str(&format!(" [{}]", state));
+		}
+		suffix
+	}
+	// The current version with the text variant chosen, when it has one of that name; the
+	// variant is romanized afresh, as `transliteration` goes with `text`
+	pub fn shown_version(&self) -> Cow<'_, Version> {
+		let version = self.get_current_version();
+		match self.text_variant.as_ref().and_then(|name| version.variants.as_ref()?.get(name)) {
+			Some(text) => Cow::Owned(Version { text: text.clone(), transliteration: None, ..version.clone() }),
+			None => Cow::Borrowed(version),
+		}
+	}
+	// The variant `x` switches to next, None for `text`
+	pub fn next_text_variant(&self) -> Option<&String> {
+		let variants = self.get_current_version().variants.as_ref()?;
+		match self.text_variant.as_ref().filter(|name| variants.contains_key(*name)) {
+			Some(name) => variants.keys().skip_while(|key| *key != name).nth(1),
+			None => variants.keys().next(),
+		}
+	}
+	pub fn cycle_text_variant(&mut self) {
+		if self.get_current_version().variants.as_ref().is_none_or(BTreeMap::is_empty) {
+			self.status_message = Some("No other text for this version".to_string());
+			return;
+		}
+		self.text_variant = self.next_text_variant().cloned();
+		self.word_cursor = None;
+		self.scroll_position = 0;
+	}
+	// The name of the text variant shown, when it is not `text`
+	pub fn shown_text_variant(&self) -> Option<&String> {
+		let variants = self.get_current_version().variants.as_ref()?;
+		self.text_variant.as_ref().filter(|name| variants.contains_key(*name))
+	}
+	// The script view the current version is shown in: the one chosen, if it has a transliteration
+	pub fn shown_script(&self) -> ScriptView {
+		if self.script_view != ScriptView::Original && self.shown_version().transliteration().is_some() {
+			self.script_view
+		} else {
+			ScriptView::Original
+		}
+	}
+	// A transliteration is laid out in lines, left to right, whatever the original is
+	pub fn shows_vertical(&self) -> bool {
+		self.shown_version().is_vertical() && self.shown_script() == ScriptView::Original
+	}
+	pub fn shows_rtl(&self) -> bool {
+		self.shown_version().is_rtl() && self.shown_script() != ScriptView::Transliteration
+	}
+	pub fn cycle_script_view(&mut self) {
+		if self.shown_version().transliteration().is_none() {
+			self.status_message = Some("No transliteration for this version".to_string());
+			return;
+		}
+		self.script_view = self.shown_script().next();
+		self.word_cursor = None;
+		self.scroll_position = 0;
+	}
+	// The text as shown in the viewer: epigraph followed by the rendered poem, or its
+	// transliteration; interlinear text is the original
+	pub fn current_display_text(&self) -> String {
+		let version = self.shown_version();
+		let mut text = String::new();
+		if let Some(epigraph) = &version.epigraph {
+			text.push_str(epigraph);
+			text.push('\n');
+		}
+		match version.transliteration().filter(|_| self.shown_script() == ScriptView::Transliteration) {
+			Some(transliteration) => text.push_str(&parse_markdown(&transliteration)),
+			None => text.push_str(&render_poem_text(&version)),
+		}
+		text
+	}
+	// The poem being read wrapped to `width` columns, as the viewer shows it; wrapped again on
+	// every frame in low-memory mode, which keeps no cache
+	pub fn wrapped_lines(&mut self, width: usize) -> Cow<'_, [Line<'static>]> {
+		let key = WrapKey {
+			poem: self.current_poem,
+			version: self.current_version.clone(),
+			text_variant: self.text_variant.clone(),
+			script: self.shown_script(),
+			width,
+			initials: self.acrostic && !self.shows_vertical(),
+			word_cursor: self.word_cursor,
+		};
+		if self.wrapped.as_ref().is_none_or(|(cached, _)| *cached != key) {
+			let version = self.shown_version();
+			let text = self.current_display_text();
+			let epigraph_lines = version.epigraph.as_deref().map_or(0, |epigraph| epigraph.lines().count());
+			let rtl = self.shows_rtl();
+			let lines = match version.transliteration().filter(|_| key.script == ScriptView::Interlinear) {
+				Some(transliteration) => interlinear_lines(&text, &parse_markdown(&transliteration), epigraph_lines, width, rtl),
+				None => wrap_poem_lines(&text, width, epigraph_lines, key.initials, rtl, key.word_cursor),
+			};
+			if self.low_memory {
+				return Cow::Owned(lines);
+			}
+			self.wrapped = Some((key, lines));
+		}
+		Cow::Borrowed(self.wrapped.as_ref().map_or(&[], |(_, lines)| lines))
+	}
+	pub fn toggle_word_cursor(&mut self) {
+		if self.word_cursor.is_some() || self.shows_vertical() || self.shown_script() == ScriptView::Interlinear {
+			self.word_cursor = None;
+			return;
+		}
+		let text = self.current_display_text();
+		let first_line = text.lines().enumerate().skip(self.scroll_position as usize).find(|(_, l)| !l.trim().is_empty());
+		self.word_cursor = first_line.map(|(i, _)| (i, 0));
+	}
+	// Moves the word cursor by `lines` lines or `words` words, skipping blank lines
+	pub fn move_word_cursor(&mut self, lines: isize, words: isize) {
+		let Some((line, word)) = self.word_cursor else { return };
+		let text = self.current_display_text();
+		let word_counts: Vec<usize> = text.lines().map(|l| word_spans(l).len()).collect();
+		let (mut line, mut word) = (line as isize, word as isize + words);
+		if word < 0 && line > 0 {
+			line -= 1;
+			while line > 0 && word_counts[line as usize] == 0 {
+				line -= 1;
+			}
+			word = word_counts[line as usize] as isize - 1;
+		} else if word >= word_counts[line as usize] as isize && (line as usize) + 1 < word_counts.len() {
+			line += 1;
+			while (line as usize) + 1 < word_counts.len() && word_counts[line as usize] == 0 {
+				line += 1;
+			}
+			word = 0;
+		}
+		let step = lines.signum();
+		for _ in 0..lines.abs() {
+			let mut next = line + step;
+			while next >= 0 && (next as usize) < word_counts.len() && word_counts[next as usize] == 0 {
+				next += step;
+			}
+			if next >= 0 && (next as usize) < word_counts.len() {
+				line = next;
+			}
+		}
+		let line = line.max(0) as usize;
+		let word = word.clamp(0, word_counts[line].saturating_sub(1) as isize) as usize;
+		self.word_cursor = Some((line, word));
+		// Keep the cursor on screen
+		if let Some(viewport_height) = self.viewport_height {
+			let line = line as u16;
+			if line < self.scroll_position {
+				self.scroll_position = line;
+			} else if line >= self.scroll_position + viewport_height {
+				self.scroll_position = line + 1 - viewport_height;
+			}
+		}
+	}
+	pub fn selected_word(&self) -> Option<String> {
+		let (line, word) = self.word_cursor?;
+		let text = self.current_display_text();
+		let spans = word_spans(text.lines().nth(line)?);
+		let (_, word) = spans.get(word)?;
+		let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+		(!word.is_empty()).then(|| word.to_string())
+	}
+	pub fn lookup_selected_word(&mut self) {
+		if let Some(word) = self.selected_word() {
+			let definition = lookup(&word, self.dictionary_command.as_deref());
+			self.definition = Some((word, definition));
+			self.definition_scroll = 0;
+			self.mode = AppMode::Definition;
+		}
+	}
+	// Illustration for the current version, falling back to the one on the canonical version
+	#[cfg(feature = "images")]
+	pub fn current_image(&self) -> Option<String> {
+		let poem = &self.poems[self.current_poem];
+		self.get_current_version().image.clone().or_else(|| poem.canonical().and_then(|v| v.image.clone()))
+	}
+	// Plays the recording attached to the current version, or stops it if already playing
+	pub fn toggle_recording(&mut self) {
+		if self.recording.take().is_some() {
+			return;
+		}
+		let Some(audio) = self.get_current_version().audio.clone() else { return };
+		match Recording::start(&audio, self.audio_player.as_deref(), self.current_poem, &self.current_version) {
+			Ok(recording) => self.recording = Some(recording),
+			Err(e) => self.status_message = Some(format!("Could not play {}: {} (set `audio_player` in the config)", audio, e)),
+		}
+	}
+	// Drops a recording that has finished or belongs to a poem no longer on screen
+	// On every tick of the reader loop: drops a recording or reading that came to an end by
+	// itself. True when that changed what is on screen.
+	pub fn tick(&mut self) -> bool {
+		let playing = self.recording.is_some();
+		self.stop_stale_recording();
+		#[cfg(feature = "tts")]
+		{
+			let speaking = self.speech.is_some();
+			self.stop_stale_speech();
+			if speaking != self.speech.is_some() {
+				return true;
+			}
+		}
+		playing != self.recording.is_some()
+	}
+	pub fn stop_stale_recording(&mut self) {
+		let stale = self.recording.as_mut().is_some_and(|r| {
+			!r.is_running() || r.poem != self.current_poem || r.version != self.current_version
+		});
+		if stale {
+			self.recording = None;
+		}
+	}
+	// Starts reading the current version aloud, or stops if it is already being read
+	#[cfg(feature = "tts")]
+	pub fn toggle_speech(&mut self) {
+		if self.speech.take().is_some() {
+			return;
+		}
+		let version = self.get_current_version();
+		let mut text = String::new();
+		if let Some(title) = &version.title {
+			text.push_str(title);
+			text.push_str(".\n\n");
+		}
+		text.push_str(&parse_markdown(&version.text));
+		match Speech::start(&text, version.language.as_deref(), self.tts_command.as_deref(), self.current_poem, &self.current_version) {
+			Ok(speech) => self.speech = Some(speech),
+			Err(e) => self.status_message = Some(format!("Could not start text-to-speech: {} (set `tts_command` in the config)", e)),
+		}
+	}
+	// Drops speech that has finished or belongs to a poem no longer on screen
+	#[cfg(feature = "tts")]
+	pub fn stop_stale_speech(&mut self) {
+		let stale = self.speech.as_mut().is_some_and(|s| {
+			!s.is_running() || s.poem != self.current_poem || s.version != self.current_version
+		});
+		if stale {
+			self.speech = None;
 		}
 	}
 	pub fn get_current_version(&self) -> &Version {
@@ -94,12 +1494,102 @@ impl App {
 			.or_else(|| poem.canonical())
 			.expect("Poem should have at least a canonical version")
 	}
-	pub fn get_sorted_titles(&self) -> Vec<(usize, String)> {
-		let mut titles: Vec<_> = self.poems.iter().enumerate().filter_map(|(i, p)| {
-			p.canonical().and_then(|v| v.title.as_ref()).map(|title| (i, title.clone()))
-		}).collect();
-		titles.sort_by(|a, b| a.1.to_lowercase().cmp(&b.1.to_lowercase()));
-		titles
+	pub fn get_sorted_titles(&self) -> &[(usize, String)] {
+		&self.sorted_titles
+	}
+	fn compute_sorted_titles(&self) -> Vec<(usize, String)> {
+		let mut indices: Vec<usize> = (0..self.poems.len())
+			.filter(|&i| self.poems[i].canonical().is_some_and(|v| v.title.is_some()))
+			.collect();
+		self.sort_poem_indices(&mut indices, self.title_sort);
+		indices.into_iter()
+			.filter_map(|i| self.poems[i].canonical().and_then(|v| v.title.clone()).map(|title| (i, title)))
+			.collect()
+	}
+	fn sort_poem_indices(&self, indices: &mut [usize], order: SortOrder) {
+		let title_key = |i: &usize| self.poems[*i].canonical().and_then(|v| v.title.clone()).unwrap_or_default();
+		let collation = &self.collation;
+		match order {
+			SortOrder::Alphabetical => collation.sort_by_key(indices, |i| ((), title_key(i))),
+			SortOrder::Count => collation.sort_by_key(indices, |i| (std::cmp::Reverse(self.poems[*i].versions.len()), title_key(i))),
+			SortOrder::Recent => collation.sort_by_key(indices, |i| (std::cmp::Reverse(self.poems[*i].modified), title_key(i))),
+			SortOrder::Length => collation.sort_by_key(indices, |i| (self.poems[*i].line_count, title_key(i))),
+			SortOrder::Chronological => collation.sort_by_key(indices, |i| (chronological_key(self.poems[*i].year()), title_key(i))),
+		}
+	}
+	// Latest modification time, total line count and earliest year of the poems in each group
+	fn group_stats<'a>(&'a self, keys_of: impl Fn(&'a Poem) -> Vec<&'a str>) -> HashMap<&'a str, (Option<SystemTime>, usize, Option<i32>)> {
+		let mut stats: HashMap<&str, (Option<SystemTime>, usize, Option<i32>)> = HashMap::new();
+		for poem in &self.poems {
+			for key in keys_of(poem) {
+				let entry = stats.entry(key).or_insert((None, 0, None));
+				entry.0 = entry.0.max(poem.modified);
+				entry.1 += poem.line_count;
+				entry.2 = match (entry.2, poem.year()) {
+					(Some(a), Some(b)) => Some(a.min(b)),
+					(a, b) => a.or(b),
+				};
+			}
+		}
+		stats
+	}
+	fn sort_groups<'a>(&'a self, keys: &mut [Rc<str>], order: SortOrder, counts: &HashMap<Rc<str>, usize>, alphabetical_key: impl Fn(&str) -> String, keys_of: impl Fn(&'a Poem) -> Vec<&'a str>) {
+		match order {
+			SortOrder::Alphabetical => self.collation.sort_by_key(keys, |k| ((), alphabetical_key(k))),
+			SortOrder::Count => self.collation.sort_by_key(keys, |k| (std::cmp::Reverse(counts[k]), alphabetical_key(k))),
+			SortOrder::Recent | SortOrder::Length | SortOrder::Chronological => {
+				let stats = self.group_stats(keys_of);
+				match order {
+					SortOrder::Recent => self.collation.sort_by_key(keys, |k| (std::cmp::Reverse(stats.get(&**k).and_then(|s| s.0)), alphabetical_key(k))),
+					SortOrder::Length => self.collation.sort_by_key(keys, |k| (stats.get(&**k).map_or(0, |s| s.1), alphabetical_key(k))),
+					_ => self.collation.sort_by_key(keys, |k| (chronological_key(stats.get(&**k).and_then(|s| s.2)), alphabetical_key(k))),
+				}
+			}
+		}
+	}
+	pub fn cycle_sort(&mut self) {
+		match self.mode {
+			AppMode::AuthorList => {
+				let selected = self.author_list_state.selected().and_then(|i| self.get_sorted_authors().get(i).cloned());
+				self.author_sort = self.author_sort.next();
+				self.refresh_sorted();
+				let position = selected.and_then(|a| self.get_sorted_authors().iter().position(|x| *x == a));
+				self.author_list_state.select(Some(position.unwrap_or(0)));
+			},
+			AppMode::LanguageList => {
+				let selected = self.language_list_state.selected().and_then(|i| self.get_sorted_languages().get(i).cloned());
+				self.language_sort = self.language_sort.next();
+				self.refresh_sorted();
+				let position = selected.and_then(|l| self.get_sorted_languages().iter().position(|x| *x == l));
+				self.language_list_state.select(Some(position.unwrap_or(0)));
+			},
+			AppMode::TitleList => {
+				let selected = self.title_list_state.selected().and_then(|i| self.get_sorted_titles().get(i).map(|t| t.0));
+				self.title_sort = self.title_sort.next();
+				self.refresh_sorted();
+				let position = selected.and_then(|p| self.get_sorted_titles().iter().position(|t| t.0 == p));
+				self.title_list_state.select(Some(position.unwrap_or(0)));
+			},
+			AppMode::FilteredList => {
+				self.filtered_sort = self.filtered_sort.next();
+				if let Some(mut indices) = self.filtered_poems.take() {
+					let selected = self.filtered_list_state.selected().and_then(|i| indices.get(i).copied());
+					self.sort_poem_indices(&mut indices, self.filtered_sort);
+					let position = selected.and_then(|p| indices.iter().position(|&i| i == p));
+					self.filtered_poems = Some(indices);
+					self.filtered_list_state.select(Some(position.unwrap_or(0)));
+				}
+			},
+			_ => {}
+		}
+	}
+	pub fn current_sort(&self) -> SortOrder {
+		match self.mode {
+			AppMode::AuthorList => self.author_sort,
+			AppMode::LanguageList => self.language_sort,
+			AppMode::TitleList => self.title_sort,
+			_ => self.filtered_sort,
+		}
 	}
 	pub fn next_title(&mut self) {
 		let titles = self.get_sorted_titles();
@@ -117,13 +1607,19 @@ impl App {
 		};
 		self.title_list_state.select(Some(i));
 	}
+	// Moves the title selection to the first title filed under the same section as `letter`
+	pub fn jump_to_title_group(&mut self, letter: char) {
+		let group = title_group(&letter.to_string());
+		if let Some(position) = self.get_sorted_titles().iter().position(|(_, title)| self.collation.title_group(title) == group) {
+			self.title_list_state.select(Some(position));
+		}
+	}
 	pub fn select_current_title(&mut self) {
 		if let Some(index) = self.title_list_state.selected() {
-			let titles = self.get_sorted_titles();
-			if let Some((poem_index, _)) = titles.get(index) {
-				self.current_poem = *poem_index;
-				self.current_version = "canonical".to_string();
-				self.filtered_poems = Some(vec![*poem_index]);
+			if let Some(&(poem_index, _)) = self.get_sorted_titles().get(index) {
+				self.current_poem = poem_index;
+				self.current_version = self.preferred_version(poem_index);
+				self.filtered_poems = Some(vec![poem_index]);
 				self.previous_mode = Some(AppMode::TitleList);
 				self.mode = AppMode::Viewing;
 			}
@@ -145,22 +1641,41 @@ impl App {
 		};
 		self.author_list_state.select(Some(i));
 	}
-	pub fn get_sorted_authors(&self) -> Vec<String> {
+	// The author a poem is listed under, by its canonical version
+	pub fn listed_author<'a>(&'a self, poem: &'a Poem) -> Option<&'a str> {
+		let author = poem.canonical()?.author.as_deref()?;
+		Some(self.author_aliases.get(author).map_or(author, |listed| listed))
+	}
+	pub fn get_sorted_authors(&self) -> &[Rc<str>] {
+		&self.sorted_authors
+	}
+	fn compute_sorted_authors(&self) -> Vec<Rc<str>> {
 		let mut authors: Vec<_> = self.author_counts.keys().cloned().collect();
-		authors.sort();
+		let alphabetical_key = |author: &str| if self.sort_authors_by_surname {
+			surname_sort_key(author, self.author_sort_names.get(author).map(String::as_str))
+		} else {
+			author.to_string()
+		};
+		self.sort_groups(&mut authors, self.author_sort, &self.author_counts, alphabetical_key, |p| {
+			self.listed_author(p).into_iter().collect()
+		});
 		authors
 	}
+	pub fn toggle_author_sort(&mut self) {
+		self.sort_authors_by_surname = !self.sort_authors_by_surname;
+		self.refresh_sorted();
+		self.author_list_state.select(Some(0));
+	}
 	pub fn select_current_author(&mut self) {
 		if let Some(index) = self.author_list_state.selected() {
-			let authors = self.get_sorted_authors();
-			if let Some(author) = authors.get(index) {
-				self.filtered_poems = Some(
-					self.poems.iter()
-						.enumerate()
-						.filter(|(_, poem)| poem.canonical().and_then(|v| v.author.as_ref()) == Some(author))
-						.map(|(i, _)| i)
-						.collect()
-				);
+			if let Some(author) = self.get_sorted_authors().get(index).cloned() {
+				let mut indices: Vec<usize> = self.poems.iter()
+					.enumerate()
+					.filter(|(_, poem)| self.listed_author(poem) == Some(&*author))
+					.map(|(i, _)| i)
+					.collect();
+				self.sort_poem_indices(&mut indices, self.filtered_sort);
+				self.filtered_poems = Some(indices);
 				if let Some(ref indices) = self.filtered_poems {
 					if !indices.is_empty() {
 						self.current_poem = indices[0];
@@ -172,9 +1687,53 @@ impl App {
 			}
 		}
 	}
-	pub fn get_sorted_languages(&self) -> Vec<String> {
+	// Counts the recurring vocabulary of the selected author's original texts
+	pub fn analyse_current_author(&mut self) -> io::Result<()> {
+		let Some(author) = self.author_list_state.selected().and_then(|i| self.get_sorted_authors().get(i).cloned()) else {
+			return Ok(());
+		};
+		let mut versions = Vec::new();
+		for poem in &self.poems {
+			let Some(canonical) = poem.canonical() else { continue };
+			if self.listed_author(poem) != Some(&*author) {
+				continue;
+			}
+			// Texts dropped in low-memory mode are read back just for the count
+			if poem.unloaded {
+				if let Some(version) = read_versions(&poem.filename, poem.document)?.remove("canonical") {
+					versions.push(version);
+				}
+			} else {
+				versions.push(canonical.clone());
+			}
+		}
+		self.word_frequencies = word_frequencies(versions.iter());
+		self.word_frequency_state.select(Some(0));
+		self.mode = AppMode::WordFrequency;
+		Ok(())
+	}
+	pub fn next_word(&mut self) {
+		let i = match self.word_frequency_state.selected() {
+			Some(i) => (i + 1) % self.word_frequencies.len().max(1),
+			None => 0,
+		};
+		self.word_frequency_state.select(Some(i));
+	}
+	pub fn previous_word(&mut self) {
+		let i = match self.word_frequency_state.selected() {
+			Some(i) => if i == 0 { self.word_frequencies.len().saturating_sub(1) } else { i - 1 },
+			None => 0,
+		};
+		self.word_frequency_state.select(Some(i));
+	}
+	pub fn get_sorted_languages(&self) -> &[Rc<str>] {
+		&self.sorted_languages
+	}
+	fn compute_sorted_languages(&self) -> Vec<Rc<str>> {
 		let mut languages: Vec<_> = self.language_counts.keys().cloned().collect();
-		languages.sort_by_key(|lang| std::cmp::Reverse(self.language_counts[lang]));
+		self.sort_groups(&mut languages, self.language_sort, &self.language_counts, script_language_label, |p| {
+			p.versions.values().filter_map(|v| v.language.as_deref()).map(|lang| &*self.language_groups[lang]).collect()
+		});
 		languages
 	}
 	pub fn next_language(&mut self) {
@@ -200,26 +1759,34 @@ impl App {
 				let mut filtered_with_versions = Vec::new();
 				for (i, poem) in self.poems.iter().enumerate() {
 					for (version_key, version) in &poem.versions {
-						if version.language.as_deref() == Some(language) {
+						if self.in_language_group(version, language) {
 							filtered_with_versions.push((i, version_key.clone()));
 						}
 					}
 				}
-				if !filtered_with_versions.is_empty() {
-					self.current_poem = filtered_with_versions[0].0;
-					self.current_version = filtered_with_versions[0].1.clone();
+				let mut indices: Vec<usize> = filtered_with_versions.iter().map(|(i, _)| *i).collect();
+				self.sort_poem_indices(&mut indices, self.filtered_sort);
+				if let Some(&first) = indices.first() {
+					self.current_poem = first;
+					if let Some((_, version_key)) = filtered_with_versions.iter().find(|(i, _)| *i == first) {
+						self.current_version = version_key.clone();
+					}
 				}
-				self.filtered_poems = Some(filtered_with_versions.iter().map(|(i, _)| *i).collect());
+				self.filtered_poems = Some(indices);
 				self.filtered_list_state.select(Some(0));
 				self.previous_mode = Some(AppMode::LanguageList);
 				self.mode = AppMode::FilteredList;
 			}
 		}
 	}
+	// Whether a version is in the language browser's entry `group`
+	pub fn in_language_group(&self, version: &Version, group: &str) -> bool {
+		version.language.as_ref().and_then(|lang| self.language_groups.get(lang)).is_some_and(|g| **g == *group)
+	}
 	pub fn get_version_in_language(&self, poem_idx: usize, language: &str) -> (&Version, bool) {
 		let poem = &self.poems[poem_idx];
 		for version in poem.versions.values() {
-			if version.language.as_deref() == Some(language) {
+			if self.in_language_group(version, language) {
 				return (version, true);
 			}
 		}
@@ -243,11 +1810,20 @@ impl App {
 				if let Some(index) = self.language_list_state.selected() {
 					let languages = self.get_sorted_languages();
 					if let Some(language) = languages.get(index) {
-						return format!("Poems in {}", language);
+						return format!("Poems in {}", script_language_label(language));
 					}
 				}
 			},
+			Some(AppMode::FacetList(facet)) => {
+				if let Some((group, _)) = self.facet_list_state.selected().and_then(|i| self.facet_groups.get(i)) {
+					return self.facets[facet].group_title(group);
+				}
+			},
 			Some(AppMode::TitleList) => return "Search Results".to_string(),
+			Some(AppMode::Menu) => return match self.menu_state.selected().and_then(|i| self.menu_entries().get(i).copied()) {
+				Some(MenuEntry::SyncConflicts) => "Sync conflicts".to_string(),
+				_ => "Unread poems".to_string(),
+			},
 			_ => {}
 		}
 		"Filtered Poems".to_string()
@@ -259,6 +1835,7 @@ impl App {
 		self.scroll_position = (self.scroll_position.saturating_add(delta)).min(max_scroll);
 	}
 	pub fn next_poem(&mut self) {
+		self.word_cursor = None;
 		match &self.filtered_poems {
 			Some(indices) => {
 				let current_pos = indices.iter().position(|&i| i == self.current_poem).unwrap_or(0);
@@ -270,6 +1847,7 @@ impl App {
 		}
 	}
 	pub fn previous_poem(&mut self) {
+		self.word_cursor = None;
 		match &self.filtered_poems {
 			Some(indices) => {
 				let current_pos = indices.iter().position(|&i| i == self.current_poem).unwrap_or(0);
@@ -309,44 +1887,331 @@ impl App {
 							if let Some(language) = languages.get(selected_lang_idx) {
 								let poem = &self.poems[poem_index];
 								for (version_key, version) in &poem.versions {
-									if version.language.as_deref() == Some(language) {
+									if self.in_language_group(version, language) {
 										self.current_version = version_key.clone();
 										break;
 									}
 								}
 							}
 						}
+					} else if let Some(key) = self.facet_version(poem_index).filter(|_| matches!(self.previous_mode, Some(AppMode::FacetList(_)))) {
+						self.current_version = key.clone();
 					} else {
-						self.current_version = "canonical".to_string();
+						self.current_version = self.preferred_version(poem_index);
 					}
 					self.mode = AppMode::Viewing;
 				}
 			}
 		}
 	}
+	// Carries out an action of the keymap. Those that rewrite the library, and quitting, are
+	// left to the reader loop.
+	pub fn handle_action(&mut self, action: Action) -> io::Result<()> {
+		match action {
+			Action::Type(c) => match self.mode {
+				AppMode::Rename => self.rename_input.push(c),
+				AppMode::GitCommit => self.commit_input.push(c),
+				AppMode::EditMetadata => self.metadata_form.type_char(c),
+				AppMode::Search => {
+					self.search_query.push(c);
+					self.update_search_results();
+				},
+				_ => {}
+			},
+			Action::DeleteChar => match self.mode {
+				AppMode::Rename => {
+					self.rename_input.pop();
+				},
+				AppMode::GitCommit => {
+					self.commit_input.pop();
+				},
+				AppMode::EditMetadata => self.metadata_form.delete_char(),
+				AppMode::Search => {
+					self.search_query.pop();
+					self.update_search_results();
+				},
+				_ => {}
+			},
+			Action::Down => self.step(true),
+			Action::Up => self.step(false),
+			Action::Select => self.select(),
+			Action::Back => self.back(),
+			Action::Close => match self.mode {
+				AppMode::GitCommit => self.mode = AppMode::GitDiff,
+				AppMode::Search => self.mode = AppMode::Menu,
+				AppMode::Viewing => self.word_cursor = None,
+				_ => self.mode = AppMode::Viewing,
+			},
+			Action::Menu => {
+				self.link_trail.clear();
+				self.mode = AppMode::Menu;
+			},
+			Action::Search => {
+				self.link_trail.clear();
+				self.start_search();
+			},
+			Action::CycleSort => self.cycle_sort(),
+			Action::NextPoem => self.next_poem(),
+			Action::PreviousPoem => self.previous_poem(),
+			Action::NextUnread => self.next_unread(),
+			// The position may be left over from a longer poem
+			Action::ScrollLeft => self.scroll_position = self.scroll_position.min(self.max_scroll).saturating_sub(1),
+			Action::ScrollRight => self.scroll_down(1, self.max_scroll),
+			Action::ShowInfo => self.mode = AppMode::Metadata,
+			Action::FollowLink(n) => self.follow_link(n),
+			Action::ChooseVersion => {
+				let current = self.version_keys(self.current_poem).iter().position(|key| **key == self.current_version);
+				self.version_list_state.select(Some(current.unwrap_or(0)));
+				self.mode = AppMode::VersionSelect;
+			},
+			Action::NextVersion => self.cycle_version(true),
+			Action::PreviousVersion => self.cycle_version(false),
+			Action::ToggleAcrostic => self.acrostic = !self.acrostic,
+			Action::CycleScriptView => self.cycle_script_view(),
+			Action::CycleTextVariant => self.cycle_text_variant(),
+			Action::ToggleWordCursor => self.toggle_word_cursor(),
+			Action::MoveWordCursor(lines, words) => self.move_word_cursor(lines, words),
+			Action::LookUpWord => {
+				if self.word_cursor.is_some() {
+					self.lookup_selected_word();
+				} else {
+					self.toggle_word_cursor();
+				}
+			},
+			Action::ToggleRecording => self.toggle_recording(),
+			#[cfg(feature = "tts")]
+			Action::ToggleSpeech => self.toggle_speech(),
+			#[cfg(feature = "tts")]
+			Action::PauseSpeech => {
+				if let Some(speech) = self.speech.as_mut() {
+					speech.toggle_pause();
+				}
+			},
+			Action::OpenFile => self.open_file(),
+			Action::StartEditing => self.start_editing(),
+			Action::StartMetadataEdit => self.start_metadata_edit(),
+			Action::StartRename => self.start_rename(),
+			Action::ToggleRenameSuggestion => self.toggle_rename_suggestion(),
+			Action::FinishRename => self.finish_rename(),
+			Action::StartDelete => self.start_delete(),
+			Action::MarkForMerge => self.mark_for_merge(),
+			Action::ShowGitDiff => self.show_git_diff(),
+			Action::RunCommand(key) => self.run_command(key),
+			Action::CancelTask => if let Some((task, _)) = &self.task {
+				task.cancel();
+			},
+			Action::StartCommit => {
+				let verb = if self.git_state(self.current_poem) == Some('M') { "Edit" } else { "Add" };
+				self.commit_input = format!("{} {}", verb, self.poems[self.current_poem].filename);
+				self.mode = AppMode::GitCommit;
+			},
+			Action::Commit => self.commit_current(),
+			Action::ToggleAuthorSort => self.toggle_author_sort(),
+			Action::AnalyseAuthor => self.analyse_current_author()?,
+			Action::JumpToTitleGroup(letter) => self.jump_to_title_group(letter),
+			Action::Quit | Action::CreateSamplePoem | Action::AddTranslation | Action::Delete | Action::Merge
+				| Action::Restore | Action::SaveMetadata | Action::Editor(_) => {}
+		}
+		Ok(())
+	}
+	fn start_search(&mut self) {
+		self.mode = AppMode::Search;
+		self.search_query.clear();
+		self.search_results.clear();
+		self.update_search_results();
+		self.search_list_state.select(Some(0));
+	}
+	// One step down or up whatever is on screen
+	fn step(&mut self, down: bool) {
+		match self.mode {
+			AppMode::Viewing => if !down {
+				self.scroll_up(1);
+			} else if !self.shows_vertical() {
+				self.scroll_down(1, self.max_scroll);
+			},
+			AppMode::AuthorList => if down { self.next_author() } else { self.previous_author() },
+			AppMode::LanguageList => if down { self.next_language() } else { self.previous_language() },
+			AppMode::FacetList(_) => if down { self.next_facet_group() } else { self.previous_facet_group() },
+			AppMode::TitleList => if down { self.next_title() } else { self.previous_title() },
+			AppMode::FilteredList => if down { self.next_filtered() } else { self.previous_filtered() },
+			AppMode::FolderList => if down { self.next_folder_entry() } else { self.previous_folder_entry() },
+			AppMode::Trash => if down { self.next_trashed() } else { self.previous_trashed() },
+			AppMode::WordFrequency => if down { self.next_word() } else { self.previous_word() },
+			AppMode::Menu => if let Some(i) = self.menu_state.selected() {
+				let total = self.menu_len();
+				self.menu_state.select(Some(if down { (i + 1) % total } else if i == 0 { total - 1 } else { i - 1 }));
+			},
+			AppMode::Search => if let Some(i) = self.search_list_state.selected() {
+				let total = self.search_results.len().max(1);
+				self.search_list_state.select(Some(if down { (i + 1) % total } else if i == 0 { self.search_results.len().saturating_sub(1) } else { i - 1 }));
+			},
+			AppMode::VersionSelect => {
+				let total = self.poems[self.current_poem].versions.len();
+				let i = match self.version_list_state.selected() {
+					Some(i) if down => (i + 1) % total,
+					Some(i) => if i == 0 { total - 1 } else { i - 1 },
+					None => 0,
+				};
+				self.version_list_state.select(Some(i));
+			},
+			AppMode::EditMetadata => if down { self.metadata_form.next() } else { self.metadata_form.previous() },
+			AppMode::Definition => self.definition_scroll = if down { self.definition_scroll.saturating_add(1) } else { self.definition_scroll.saturating_sub(1) },
+			AppMode::GitDiff => self.git_diff_scroll = if down { self.git_diff_scroll.saturating_add(1) } else { self.git_diff_scroll.saturating_sub(1) },
+			AppMode::CommandOutput => self.command_output_scroll = if down { self.command_output_scroll.saturating_add(1) } else { self.command_output_scroll.saturating_sub(1) },
+			AppMode::LoadErrors => self.load_errors_scroll = if down { self.load_errors_scroll.saturating_add(1) } else { self.load_errors_scroll.saturating_sub(1) },
+			AppMode::Statistics | AppMode::Metadata | AppMode::ConfirmMerge | AppMode::Rename | AppMode::ConfirmDelete
+				| AppMode::Editing | AppMode::GitCommit | AppMode::Onboarding => {},
+		}
+	}
+	// Opens what is selected: a poem, an entry of a list or the menu, a version
+	fn select(&mut self) {
+		match self.mode {
+			AppMode::Search => {
+				if let Some(&poem_index) = self.search_list_state.selected().and_then(|i| self.search_results.get(i)) {
+					self.current_poem = poem_index;
+					self.current_version = self.preferred_version(poem_index);
+					self.mode = AppMode::Viewing;
+				}
+			},
+			AppMode::AuthorList => self.select_current_author(),
+			AppMode::LanguageList => self.select_current_language(),
+			AppMode::FacetList(_) => self.select_current_facet_group(),
+			AppMode::TitleList => self.select_current_title(),
+			AppMode::FilteredList => self.select_current_filtered(),
+			AppMode::FolderList => self.select_current_folder_entry(),
+			AppMode::VersionSelect => {
+				let selected = self.version_list_state.selected().and_then(|i| self.version_keys(self.current_poem).get(i).map(|key| key.to_string()));
+				if let Some(selected_version) = selected {
+					self.current_version = selected_version;
+					self.mode = AppMode::Viewing;
+				}
+			},
+			AppMode::Menu => match self.menu_state.selected().and_then(|i| self.menu_entries().get(i).copied()) {
+				Some(MenuEntry::Authors) => self.mode = AppMode::AuthorList,
+				Some(MenuEntry::Languages) => self.mode = AppMode::LanguageList,
+				Some(MenuEntry::Titles) => self.mode = AppMode::TitleList,
+				Some(MenuEntry::Folders) => self.open_folder(String::new()),
+				Some(MenuEntry::Facet(facet)) => self.open_facet(facet),
+				Some(MenuEntry::Search) => self.start_search(),
+				Some(MenuEntry::Random) if !self.poems.is_empty() => {
+					self.current_poem = rand::thread_rng().gen_range(0..self.poems.len());
+					self.current_version = self.preferred_version(self.current_poem);
+					self.filtered_poems = None;
+					self.mode = AppMode::Viewing;
+				},
+				Some(MenuEntry::Unread) => self.show_unread(),
+				Some(MenuEntry::Statistics) => self.mode = AppMode::Statistics,
+				Some(MenuEntry::LoadErrors) => self.mode = AppMode::LoadErrors,
+				Some(MenuEntry::Trash) => self.mode = AppMode::Trash,
+				Some(MenuEntry::SyncConflicts) => self.show_sync_conflicts(),
+				Some(MenuEntry::Sync) => self.start_sync(),
+				Some(MenuEntry::Random) | None => {}
+			},
+			_ => {}
+		}
+	}
+	// One level up: back along the links followed, to the list a poem was opened from, to the menu
+	fn back(&mut self) {
+		match self.mode {
+			AppMode::Viewing => {
+				if self.back_from_link() {
+					// Back along the `see_also` links followed
+				} else if self.filtered_poems.is_some() && self.previous_mode == Some(AppMode::FolderList) {
+					self.mode = AppMode::FolderList;
+				} else if self.filtered_poems.is_some() {
+					self.mode = AppMode::FilteredList;
+				} else {
+					self.mode = AppMode::Menu;
+				}
+			},
+			AppMode::FilteredList => self.mode = self.previous_mode.clone().unwrap_or(AppMode::Menu),
+			AppMode::WordFrequency => self.mode = AppMode::AuthorList,
+			AppMode::FolderList => self.leave_folder(),
+			AppMode::Definition | AppMode::Metadata => self.mode = AppMode::Viewing,
+			AppMode::AuthorList | AppMode::LanguageList | AppMode::FacetList(_) | AppMode::TitleList | AppMode::Statistics | AppMode::LoadErrors | AppMode::Trash => {
+				self.set_mode(AppMode::Menu)
+			},
+			_ => {}
+		}
+	}
+	// Opens the poem file in the program the system has for it
+	fn open_file(&mut self) {
+		if self.demo {
+			self.status_message = Some("The demo poems are built in and cannot be edited".to_string());
+			return;
+		}
+		if self.read_only {
+			self.status_message = Some("These poems are read-only".to_string());
+			return;
+		}
+		let poem_path = poems_dir().join(&self.poems[self.current_poem].filename);
+		#[cfg(target_os = "macos")]
+		let opened = std::process::Command::new("open").arg(&poem_path).spawn();
+		#[cfg(target_os = "linux")]
+		let opened = std::process::Command::new("xdg-open").arg(&poem_path).spawn();
+		#[cfg(target_os = "windows")]
+		// `start` takes a quoted first argument for the window title
+		let opened = std::process::Command::new("cmd").args(["/C", "start", ""]).arg(&poem_path).spawn();
+		#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+		let opened: io::Result<std::process::Child> = Err(io::Error::new(io::ErrorKind::Unsupported, "no program to open files with"));
+		match opened {
+			// The reader goes on while the program is open; it is waited for on the side
+			Ok(mut child) => {
+				std::thread::spawn(move || child.wait());
+			},
+			Err(e) => self.status_message = Some(format!("Could not open the poem file: {}", e)),
+		}
+	}
 	pub fn set_mode(&mut self, new_mode: AppMode) {
 		self.mode = new_mode;
 		self.scroll_position = 0;
 	}
-	pub fn update_search_results(&mut self) {
+	// Poems whose title or author has the search query in it
+	fn title_or_author_matches(&self) -> Vec<usize> {
 		let query = self.search_query.to_lowercase();
-		if query.is_empty() {
+		self.poems.iter().enumerate()
+			.filter(|(_, poem)| poem.matches(&query))
+			.map(|(i, _)| i)
+			.collect()
+	}
+	#[cfg(not(feature = "search"))]
+	fn search_matches(&mut self) -> Vec<usize> {
+		self.title_or_author_matches()
+	}
+	// Poems found by the full-text index, best first, then any others whose title or author
+	// has the query in it
+	#[cfg(feature = "search")]
+	fn search_matches(&mut self) -> Vec<usize> {
+		if self.search_index.is_none() {
+			// The demo and a file read on its own are indexed in memory, leaving the library's alone
+			match SearchIndex::open(&self.poems, !self.demo && self.save_history) {
+				Ok(index) => {
+					let positions = self.poems.iter().enumerate().map(|(i, poem)| (poem.id().to_string(), i)).collect();
+					self.search_index = Some((index, positions));
+				}
+				Err(e) => self.status_message = Some(format!("Could not open the search index: {}", e)),
+			}
+		}
+		let mut found = match &self.search_index {
+			Some((index, positions)) => match index.search(&self.search_query, 500) {
+				Ok(ids) => ids.iter().filter_map(|id| positions.get(id).copied()).collect(),
+				Err(e) => {
+					self.status_message = Some(format!("Could not search: {}", e));
+					Vec::new()
+				}
+			},
+			None => Vec::new(),
+		};
+		let ranked: HashSet<usize> = found.iter().copied().collect();
+		found.extend(self.title_or_author_matches().into_iter().filter(|i| !ranked.contains(i)));
+		found
+	}
+	pub fn update_search_results(&mut self) {
+		if self.search_query.is_empty() {
 			self.search_results.clear();
 			self.search_list_state.select(None);
 		} else {
-			self.search_results = self.poems.iter().enumerate().filter_map(|(i, poem)| {
-				if let Some(canonical) = poem.canonical() {
-					let title_match = canonical.title.as_ref().map_or(false, |t| t.to_lowercase().contains(&query));
-					let author_match = canonical.author.as_ref().map_or(false, |a| a.to_lowercase().contains(&query));
-					if title_match || author_match {
-						Some(i)
-					} else {
-						None
-					}
-				} else {
-					None
-				}
-			}).collect();
+			self.search_results = self.search_matches();
 			if self.search_results.is_empty() {
 				self.search_list_state.select(None);
 			} else if self.search_list_state.selected().is_none() {
@@ -355,3 +2220,351 @@ impl App {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// The demo poems, read-only and without a reading history, open at `id`
+	fn app_at(id: &str) -> App {
+		let mut app = App::new(crate::models::demo_poems(), &Config::default(), History::default());
+		app.demo = true;
+		app.read_only = true;
+		app.save_history = false;
+		app.start_at(Start::Poem(id.to_string())).unwrap();
+		app
+	}
+
+	fn app() -> App {
+		app_at("dickinson-hope")
+	}
+
+	fn id(app: &App) -> &str {
+		app.poems[app.current_poem].id()
+	}
+
+	#[test]
+	fn typing_goes_to_the_prompt_of_the_mode() {
+		let mut app = app();
+		app.handle_action(Action::Search).unwrap();
+		assert_eq!(app.mode, AppMode::Search);
+		for c in "hope".chars() {
+			app.handle_action(Action::Type(c)).unwrap();
+		}
+		assert_eq!(app.search_query, "hope");
+		assert!(app.search_results.iter().any(|&i| app.poems[i].id() == "dickinson-hope"));
+		app.handle_action(Action::DeleteChar).unwrap();
+		assert_eq!(app.search_query, "hop");
+		app.mode = AppMode::GitCommit;
+		app.handle_action(Action::Type('x')).unwrap();
+		app.handle_action(Action::Type('y')).unwrap();
+		app.handle_action(Action::DeleteChar).unwrap();
+		assert_eq!(app.commit_input, "x");
+	}
+
+	#[test]
+	fn select_opens_the_search_result() {
+		let mut app = app_at("goethe-wandrers-nachtlied");
+		app.handle_action(Action::Search).unwrap();
+		for c in "dickinson".chars() {
+			app.handle_action(Action::Type(c)).unwrap();
+		}
+		app.handle_action(Action::Select).unwrap();
+		assert_eq!(app.mode, AppMode::Viewing);
+		assert_eq!(id(&app), "dickinson-hope");
+	}
+
+	#[test]
+	fn down_and_up_step_through_the_menu_around_the_ends() {
+		let mut app = app();
+		app.handle_action(Action::Menu).unwrap();
+		app.menu_state.select(Some(0));
+		app.handle_action(Action::Up).unwrap();
+		assert_eq!(app.menu_state.selected(), Some(app.menu_len() - 1));
+		app.handle_action(Action::Down).unwrap();
+		assert_eq!(app.menu_state.selected(), Some(0));
+	}
+
+	#[test]
+	fn down_scrolls_the_poem_no_further_than_it_goes() {
+		let mut app = app();
+		app.max_scroll = 1;
+		app.handle_action(Action::Down).unwrap();
+		app.handle_action(Action::Down).unwrap();
+		assert_eq!(app.scroll_position, 1);
+		app.handle_action(Action::Up).unwrap();
+		assert_eq!(app.scroll_position, 0);
+	}
+
+	#[test]
+	fn select_in_the_menu_opens_the_entry() {
+		let mut app = app();
+		app.handle_action(Action::Menu).unwrap();
+		let authors = app.menu_entries().iter().position(|e| *e == MenuEntry::Authors);
+		app.menu_state.select(authors);
+		app.handle_action(Action::Select).unwrap();
+		assert_eq!(app.mode, AppMode::AuthorList);
+	}
+
+	#[test]
+	fn back_goes_from_a_list_to_the_menu_and_from_a_poem_to_its_list() {
+		let mut app = app();
+		app.mode = AppMode::AuthorList;
+		app.handle_action(Action::Back).unwrap();
+		assert_eq!(app.mode, AppMode::Menu);
+		app.mode = AppMode::AuthorList;
+		app.author_list_state.select(Some(0));
+		app.handle_action(Action::Select).unwrap();
+		assert_eq!(app.mode, AppMode::FilteredList);
+		app.handle_action(Action::Select).unwrap();
+		assert_eq!(app.mode, AppMode::Viewing);
+		app.handle_action(Action::Back).unwrap();
+		assert_eq!(app.mode, AppMode::FilteredList);
+		app.handle_action(Action::Back).unwrap();
+		assert_eq!(app.mode, AppMode::AuthorList);
+	}
+
+	#[test]
+	fn close_leaves_popups_and_prompts() {
+		let mut app = app();
+		for mode in [AppMode::Metadata, AppMode::VersionSelect, AppMode::Definition, AppMode::ConfirmDelete, AppMode::GitDiff, AppMode::CommandOutput] {
+			app.mode = mode;
+			app.handle_action(Action::Close).unwrap();
+			assert_eq!(app.mode, AppMode::Viewing);
+		}
+		app.mode = AppMode::GitCommit;
+		app.handle_action(Action::Close).unwrap();
+		assert_eq!(app.mode, AppMode::GitDiff);
+		app.mode = AppMode::Search;
+		app.handle_action(Action::Close).unwrap();
+		assert_eq!(app.mode, AppMode::Menu);
+		app.mode = AppMode::Viewing;
+		app.word_cursor = Some((0, 0));
+		app.handle_action(Action::Close).unwrap();
+		assert_eq!(app.word_cursor, None);
+	}
+
+	#[test]
+	fn menu_and_search_forget_the_links_followed() {
+		let mut app = app();
+		app.link_trail.push((0, "canonical".to_string()));
+		app.handle_action(Action::Menu).unwrap();
+		assert_eq!(app.mode, AppMode::Menu);
+		assert!(app.link_trail.is_empty());
+		app.link_trail.push((0, "canonical".to_string()));
+		app.handle_action(Action::Search).unwrap();
+		assert!(app.link_trail.is_empty());
+	}
+
+	#[test]
+	fn next_and_previous_poem_come_back_to_the_same() {
+		let mut app = app();
+		let start = app.current_poem;
+		app.handle_action(Action::NextPoem).unwrap();
+		assert_ne!(app.current_poem, start);
+		app.handle_action(Action::PreviousPoem).unwrap();
+		assert_eq!(app.current_poem, start);
+	}
+
+	#[test]
+	fn next_unread_skips_poems_read() {
+		let mut app = app();
+		app.history.mark_read(&app.poems[app.current_poem].history_key());
+		app.handle_action(Action::NextUnread).unwrap();
+		assert!(!app.is_read(app.current_poem));
+	}
+
+	#[test]
+	fn reading_a_poem_counts_it_once() {
+		let mut app = app();
+		let unread = app.unread_count();
+		app.mark_current_read().unwrap();
+		assert_eq!(app.unread_count(), unread - 1);
+		app.last_marked = None;
+		app.mark_current_read().unwrap();
+		assert_eq!(app.unread_count(), unread - 1);
+	}
+
+	#[test]
+	fn wrapped_lines_follow_width_and_cursor() {
+		let mut app = app_at("dickinson-hope");
+		let wide = app.wrapped_lines(200).to_vec();
+		assert!(app.wrapped_lines(12).len() > wide.len());
+		assert_eq!(app.wrapped_lines(200), wide.as_slice());
+		app.handle_action(Action::ToggleWordCursor).unwrap();
+		assert_ne!(app.wrapped_lines(200), wide.as_slice());
+	}
+
+	#[test]
+	fn low_memory_keeps_no_wrapped_lines() {
+		let mut app = app_at("dickinson-hope");
+		app.low_memory = true;
+		assert!(!app.wrapped_lines(200).is_empty());
+		assert!(app.wrapped.is_none());
+	}
+
+	#[test]
+	fn scrolling_columns_stays_within_the_poem() {
+		let mut app = app_at("li-bai-quiet-night");
+		app.max_scroll = 2;
+		for _ in 0..3 {
+			app.handle_action(Action::ScrollRight).unwrap();
+		}
+		assert_eq!(app.scroll_position, 2);
+		// Left over from a wider poem
+		app.scroll_position = 9;
+		app.handle_action(Action::ScrollLeft).unwrap();
+		assert_eq!(app.scroll_position, 1);
+	}
+
+	#[test]
+	fn show_info_opens_the_metadata() {
+		let mut app = app();
+		app.handle_action(Action::ShowInfo).unwrap();
+		assert_eq!(app.mode, AppMode::Metadata);
+	}
+
+	#[test]
+	fn following_a_missing_link_stays() {
+		let mut app = app();
+		app.handle_action(Action::FollowLink(0)).unwrap();
+		assert_eq!(id(&app), "dickinson-hope");
+		assert!(app.link_trail.is_empty());
+	}
+
+	#[test]
+	fn versions_are_chosen_and_cycled() {
+		let mut app = app_at("goethe-wandrers-nachtlied");
+		app.handle_action(Action::ChooseVersion).unwrap();
+		assert_eq!(app.mode, AppMode::VersionSelect);
+		app.handle_action(Action::Down).unwrap();
+		app.handle_action(Action::Select).unwrap();
+		assert_eq!(app.mode, AppMode::Viewing);
+		assert_eq!(app.current_version, "longfellow");
+		app.handle_action(Action::NextVersion).unwrap();
+		assert_eq!(app.current_version, "canonical");
+		app.handle_action(Action::PreviousVersion).unwrap();
+		assert_eq!(app.current_version, "longfellow");
+	}
+
+	#[test]
+	fn toggles_of_the_viewer() {
+		let mut app = app_at("li-bai-quiet-night");
+		app.handle_action(Action::ToggleAcrostic).unwrap();
+		assert!(app.acrostic);
+		app.handle_action(Action::CycleScriptView).unwrap();
+		assert_eq!(app.shown_script(), ScriptView::Transliteration);
+		app.handle_action(Action::CycleTextVariant).unwrap();
+		assert!(app.status_message.is_some());
+	}
+
+	#[test]
+	fn the_word_cursor_moves_by_words_and_lines() {
+		let mut app = app();
+		app.handle_action(Action::ToggleWordCursor).unwrap();
+		assert_eq!(app.word_cursor, Some((0, 0)));
+		app.handle_action(Action::MoveWordCursor(0, 1)).unwrap();
+		assert_eq!(app.word_cursor, Some((0, 1)));
+		app.handle_action(Action::MoveWordCursor(1, 0)).unwrap();
+		assert_eq!(app.word_cursor.map(|(line, _)| line), Some(1));
+		app.handle_action(Action::ToggleWordCursor).unwrap();
+		assert_eq!(app.word_cursor, None);
+		// Looking up a word first needs one selected
+		app.handle_action(Action::LookUpWord).unwrap();
+		assert!(app.word_cursor.is_some());
+		assert_eq!(app.mode, AppMode::Viewing);
+	}
+
+	#[test]
+	fn a_version_without_audio_has_nothing_to_play() {
+		let mut app = app();
+		app.handle_action(Action::ToggleRecording).unwrap();
+		assert!(app.recording.is_none());
+	}
+
+	#[test]
+	fn read_only_poems_are_not_changed() {
+		let mut app = app();
+		// Read-only as an archive or a file read on its own is, not only as the demo
+		app.demo = false;
+		for action in [Action::OpenFile, Action::StartEditing, Action::StartMetadataEdit, Action::StartRename, Action::StartDelete, Action::MarkForMerge, Action::ShowGitDiff] {
+			app.status_message = None;
+			app.handle_action(action).unwrap();
+			assert_eq!(app.mode, AppMode::Viewing, "{:?}", action);
+			assert_eq!(app.status_message.as_deref(), Some("These poems are read-only"), "{:?}", action);
+		}
+	}
+
+	#[test]
+	fn rename_switches_between_the_suggestion_and_the_file_name() {
+		let mut app = app();
+		app.rename_input = app.poems[app.current_poem].filename.clone();
+		app.handle_action(Action::ToggleRenameSuggestion).unwrap();
+		assert_eq!(app.rename_input, "emily-dickinson-hope-is-the-thing-with-feathers.poem");
+		app.handle_action(Action::ToggleRenameSuggestion).unwrap();
+		assert_eq!(app.rename_input, "dickinson-hope.poem");
+	}
+
+	#[test]
+	fn a_commit_starts_from_a_message_and_needs_one() {
+		let mut app = app();
+		app.mode = AppMode::GitDiff;
+		app.handle_action(Action::StartCommit).unwrap();
+		assert_eq!(app.mode, AppMode::GitCommit);
+		assert_eq!(app.commit_input, "Add dickinson-hope.poem");
+		app.commit_input.clear();
+		app.handle_action(Action::Commit).unwrap();
+		assert_eq!(app.status_message.as_deref(), Some("A commit needs a message"));
+	}
+
+	#[test]
+	fn the_author_list_sorts_and_counts_words() {
+		let mut app = app();
+		app.mode = AppMode::AuthorList;
+		let by_surname = app.sort_authors_by_surname;
+		app.handle_action(Action::ToggleAuthorSort).unwrap();
+		assert_ne!(app.sort_authors_by_surname, by_surname);
+		app.handle_action(Action::CycleSort).unwrap();
+		assert_eq!(app.author_sort, SortOrder::Count);
+		app.handle_action(Action::AnalyseAuthor).unwrap();
+		assert_eq!(app.mode, AppMode::WordFrequency);
+		app.handle_action(Action::Back).unwrap();
+		assert_eq!(app.mode, AppMode::AuthorList);
+	}
+
+	#[test]
+	fn titles_jump_to_their_letter() {
+		let mut app = app();
+		app.mode = AppMode::TitleList;
+		app.handle_action(Action::JumpToTitleGroup('W')).unwrap();
+		let (_, title) = &app.get_sorted_titles()[app.title_list_state.selected().unwrap()];
+		assert!(title.starts_with('W'));
+	}
+
+	#[test]
+	fn actions_of_the_reader_loop_change_nothing_here() {
+		let mut app = app();
+		for action in [Action::Quit, Action::CreateSamplePoem, Action::AddTranslation, Action::Delete, Action::Merge, Action::Restore, Action::SaveMetadata] {
+			app.handle_action(action).unwrap();
+			assert_eq!(app.mode, AppMode::Viewing);
+			assert_eq!(id(&app), "dickinson-hope");
+		}
+	}
+
+	#[test]
+	fn facets_of_the_config_are_browsed_from_the_menu() {
+		let century = crate::config::FacetConfig { name: "century".to_string(), field: "year".to_string(), step: Some(100), canonical_only: true, sort: Some("alphabetical".to_string()) };
+		let config = Config { facets: vec![century], ..Config::default() };
+		let mut app = App::new(crate::models::demo_poems(), &config, History::default());
+		let facet = app.facets.iter().position(|facet| facet.name() == "century").unwrap();
+		assert!(app.menu_entries().contains(&MenuEntry::Facet(facet)));
+		app.open_facet(facet);
+		let groups: Vec<&str> = app.facet_groups.iter().map(|(name, _)| name.as_str()).collect();
+		assert_eq!(groups, ["700–799", "1300–1399", "1600–1699", "1700–1799", "1800–1899"]);
+		app.facet_list_state.select(Some(4));
+		app.handle_action(Action::Select).unwrap();
+		assert_eq!(app.mode, AppMode::FilteredList);
+		assert_eq!(app.filtered_sort, SortOrder::Alphabetical);
+		assert_eq!(app.filtered_poems.as_ref().map(Vec::len), Some(2));
+	}
+}
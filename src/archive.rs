@@ -0,0 +1,45 @@
+use flate2::read::GzDecoder;
+use std::{fs::File, io::{self, Read}, path::Path};
+
+fn is_zip(path: &Path) -> bool {
+	path.to_string_lossy().ends_with(".zip")
+}
+
+// Hidden files, and anything under a hidden directory such as `.git`
+fn hidden(name: &str) -> bool {
+	name.split('/').any(|part| part.starts_with('.'))
+}
+
+// Every regular file in a `.zip`, `.tar.gz` or `.tgz` archive that `wanted` accepts, as its
+// path inside the archive (with `/` between folders) and its content
+pub fn read_entries(path: &Path, wanted: impl Fn(&str) -> bool) -> io::Result<Vec<(String, Vec<u8>)>> {
+	let mut entries = Vec::new();
+	if is_zip(path) {
+		let mut archive = zip::ZipArchive::new(File::open(path)?).map_err(io::Error::other)?;
+		for i in 0..archive.len() {
+			let mut file = archive.by_index(i).map_err(io::Error::other)?;
+			let name = file.name().to_string();
+			if file.is_dir() || hidden(&name) || !wanted(&name) {
+				continue;
+			}
+			let mut content = Vec::new();
+			file.read_to_end(&mut content)?;
+			entries.push((name, content));
+		}
+	} else {
+		let mut archive = tar::Archive::new(GzDecoder::new(File::open(path)?));
+		for entry in archive.entries()? {
+			let mut entry = entry?;
+			let name = entry.path()?.to_string_lossy().trim_start_matches("./").to_string();
+			if !entry.header().entry_type().is_file() || hidden(&name) || !wanted(&name) {
+				continue;
+			}
+			let mut content = Vec::new();
+			entry.read_to_end(&mut content)?;
+			entries.push((name, content));
+		}
+	}
+	entries.sort_by(|a, b| a.0.cmp(&b.0));
+	Ok(entries)
+}
+
@@ -0,0 +1,54 @@
+use crate::models::poems_dir;
+use std::{io, path::PathBuf, process::{Child, Command, Stdio}};
+
+#[cfg(target_os = "macos")]
+const DEFAULT_PLAYER: &str = "afplay {file}";
+#[cfg(not(target_os = "macos"))]
+const DEFAULT_PLAYER: &str = "mpv --no-video --really-quiet {file}";
+
+// Recordings are given relative to the poetry directory unless absolute
+pub fn audio_path(audio: &str) -> PathBuf {
+	poems_dir().join(audio)
+}
+
+// A running audio player playing the recording attached to one version of a poem
+pub struct Recording {
+	child: Child,
+	pub poem: usize,
+	pub version: String,
+}
+
+impl Recording {
+	// Starts `command` (or the platform default) with `{file}` filled in, or the file appended
+	pub fn start(audio: &str, command: Option<&str>, poem: usize, version: &str) -> io::Result<Recording> {
+		let path = audio_path(audio);
+		if !path.exists() {
+			return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} not found", path.display())));
+		}
+		let file = path.to_string_lossy();
+		let template = command.unwrap_or(DEFAULT_PLAYER);
+		let mut args: Vec<String> = template.split_whitespace().map(|part| part.replace("{file}", &file)).collect();
+		if !template.contains("{file}") {
+			args.push(file.to_string());
+		}
+		let program = args.remove(0);
+		let child = Command::new(program)
+			.args(args)
+			.stdin(Stdio::null())
+			.stdout(Stdio::null())
+			.stderr(Stdio::null())
+			.spawn()?;
+		Ok(Recording { child, poem, version: version.to_string() })
+	}
+
+	pub fn is_running(&mut self) -> bool {
+		matches!(self.child.try_wait(), Ok(None))
+	}
+}
+
+impl Drop for Recording {
+	fn drop(&mut self) {
+		let _ = self.child.kill();
+		let _ = self.child.wait();
+	}
+}
@@ -0,0 +1,65 @@
+use crate::config::Config;
+use crate::history::now;
+use crate::models::poems_dir;
+use crate::paths;
+use crate::utils::date_from_days;
+use std::{fs, io, path::{Path, PathBuf}, sync::Once, time::{Duration, SystemTime}};
+
+// How long backups are kept when `backup_days` is unset
+const DEFAULT_DAYS: u64 = 30;
+
+static PRUNE: Once = Once::new();
+
+fn backup_dir() -> PathBuf {
+	paths::state_dir().join("backups")
+}
+
+// One directory per second a file was changed in, named after that time (UTC) so they sort
+fn run_dir(seconds: u64) -> PathBuf {
+	let (year, month, day) = date_from_days(seconds / 86400);
+	let time = seconds % 86400;
+	backup_dir().join(format!("{:04}-{:02}-{:02}-{:02}{:02}{:02}", year, month, day, time / 3600, time % 3600 / 60, time % 60))
+}
+
+// Copies a poem file about to be rewritten into the backups, at its path inside the library,
+// unless it is a new file or `backup_days` is 0. The first backup of a run also removes
+// those older than `backup_days`.
+pub fn backup(path: &Path) -> io::Result<()> {
+	let days = Config::load()?.backup_days.unwrap_or(DEFAULT_DAYS);
+	if days == 0 || !path.is_file() {
+		return Ok(());
+	}
+	PRUNE.call_once(|| {
+		if let Err(e) = prune(Duration::from_secs(days * 86400)) {
+			tracing::warn!(error = %e, "could not remove old backups");
+		}
+	});
+	let root = poems_dir();
+	let relative = path.strip_prefix(&root).unwrap_or(Path::new(path.file_name().unwrap_or_default()));
+	let target = run_dir(now()).join(relative);
+	if let Some(parent) = target.parent() {
+		fs::create_dir_all(parent)?;
+	}
+	fs::copy(path, &target)?;
+	Ok(())
+}
+
+fn prune(age: Duration) -> io::Result<()> {
+	let Ok(entries) = fs::read_dir(backup_dir()) else {
+		return Ok(());
+	};
+	let cutoff = SystemTime::now() - age;
+	for entry in entries {
+		let entry = entry?;
+		let metadata = entry.metadata()?;
+		if metadata.modified()? >= cutoff {
+			continue;
+		}
+		if metadata.is_dir() {
+			fs::remove_dir_all(entry.path())?;
+		} else {
+			fs::remove_file(entry.path())?;
+		}
+	}
+	Ok(())
+}
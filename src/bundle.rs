@@ -0,0 +1,81 @@
+use crate::models::{poem_document, poems_dir, write_poem, Poem, Version};
+use serde::Deserialize;
+use std::{collections::HashMap, fs, io::{self, Write}, path::Path};
+
+// An anthology is a multi-document YAML file: a manifest, then one document per poem
+// holding its id and its versions exactly as they appear in a `.poem` file.
+const FORMAT: &str = "leaves-anthology";
+const FORMAT_VERSION: u64 = 1;
+
+#[derive(Deserialize)]
+struct Manifest {
+	format: String,
+	version: u64,
+}
+
+#[derive(Deserialize)]
+struct Bundled {
+	id: String,
+	versions: HashMap<String, Version>,
+}
+
+fn invalid(message: String) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub fn bundle(poems: &[&Poem]) -> io::Result<String> {
+	let mut manifest = serde_yaml::Mapping::new();
+	manifest.insert("format".into(), FORMAT.into());
+	manifest.insert("version".into(), FORMAT_VERSION.into());
+	manifest.insert("poems".into(), poems.iter().map(|p| serde_yaml::Value::from(p.id())).collect());
+	let mut documents = vec![serde_yaml::Value::Mapping(manifest)];
+	for poem in poems {
+		let mut document = serde_yaml::Mapping::new();
+		document.insert("id".into(), poem.id().into());
+		document.insert("versions".into(), serde_yaml::Value::Mapping(poem_document(&poem.versions)?));
+		documents.push(serde_yaml::Value::Mapping(document));
+	}
+	let mut yaml = String::new();
+	for document in documents {
+		yaml.push_str("---\n");
+		yaml.push_str(&serde_yaml::to_string(&document).map_err(|e| invalid(e.to_string()))?);
+	}
+	Ok(yaml)
+}
+
+// Unpacks an anthology into `.poem` files in the library and returns how many were written.
+// Poems whose id is already taken are skipped.
+pub fn unbundle(out: &mut impl Write, file: &Path) -> io::Result<usize> {
+	let content = fs::read_to_string(file)?;
+	let mut documents = serde_yaml::Deserializer::from_str(&content);
+	let manifest = documents.next().ok_or_else(|| invalid(format!("{} is empty", file.display())))?;
+	match Manifest::deserialize(manifest) {
+		Ok(manifest) if manifest.format == FORMAT && manifest.version <= FORMAT_VERSION => {}
+		Ok(manifest) if manifest.format == FORMAT => {
+			return Err(invalid(format!("{} needs a newer leaves (anthology version {})", file.display(), manifest.version)));
+		}
+		_ => return Err(invalid(format!("{} is not a leaves anthology", file.display()))),
+	}
+	let mut written = 0;
+	for (i, document) in documents.enumerate() {
+		let poem = Bundled::deserialize(document).map_err(|e| invalid(format!("poem {} in {}: {}", i + 1, file.display(), e)))?;
+		// Ids become file names, so they must not reach outside the library
+		if poem.id.is_empty() || poem.id.contains(['/', '\\']) || poem.id.starts_with('.') {
+			eprintln!("leaves: skipping poem with unusable id {:?}", poem.id);
+			continue;
+		}
+		if !poem.versions.contains_key("canonical") {
+			eprintln!("leaves: skipping {}: no canonical version", poem.id);
+			continue;
+		}
+		let filename = format!("{}.poem", poem.id);
+		if poems_dir().join(&filename).exists() {
+			eprintln!("leaves: skipping {}: {} already exists", poem.id, filename);
+			continue;
+		}
+		let path = write_poem(&filename, &poem.versions)?;
+		writeln!(out, "{}", path.display())?;
+		written += 1;
+	}
+	Ok(written)
+}
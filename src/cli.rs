@@ -0,0 +1,645 @@
+use crate::config::Config;
+use crate::export::{self, convert, stanzas, CardStyle, ExportFormat, PLAIN};
+use crate::bundle;
+use crate::doctor;
+use crate::feed;
+use crate::formatter::format_files;
+use crate::fetch::{self, POETRYDB};
+use crate::import::{self, ImportFormat};
+use crate::history::History;
+use crate::migrate::migrate;
+use crate::sync;
+use crate::app::Start;
+use crate::models::{check_writable, is_encrypted, library_names, load_poems, parse_file, read_library_file, Poem};
+use crate::publish::publish;
+use crate::stats::Stats;
+use crate::ui;
+use clap::{error::ErrorKind, Args, CommandFactory, Parser, Subcommand};
+use rand::seq::SliceRandom;
+use serde::Serialize;
+use crossterm::terminal;
+use std::{collections::{BTreeMap, HashSet}, fs, io::{self, IsTerminal, Write}, path::{Path, PathBuf}};
+use textwrap::core::display_width;
+
+#[derive(Debug, Parser)]
+#[command(name = "leaves", version, about = "A terminal poetry reader")]
+pub struct Cli {
+	#[command(subcommand)]
+	pub command: Option<Command>,
+	/// Fail on any malformed poem file instead of skipping it (`strict` in the config)
+	#[arg(long, global = true)]
+	pub strict: bool,
+	/// Never write into the library: editing, deleting, renaming, fmt, migrate, import and the
+	/// like are refused (`read_only` in the config)
+	#[arg(long, global = true)]
+	pub read_only: bool,
+	/// Log load timings and key presses to leaves.log in the state directory (`LEAVES_LOG` takes a filter instead)
+	#[arg(long, short, global = true)]
+	pub verbose: bool,
+	/// Start the reader on a few built-in poems instead of the library
+	#[arg(long)]
+	pub demo: bool,
+	/// Poem file to read on its own, without the library; it is read again whenever it changes
+	#[arg(group = "start", conflicts_with = "demo")]
+	pub file: Option<PathBuf>,
+	/// Open the reader at a random poem
+	#[arg(long, group = "start")]
+	pub random: bool,
+	/// Open the reader at the poem read most recently
+	#[arg(long, group = "start")]
+	pub last: bool,
+	/// Open the reader at the poems of an author (any unique part of the name will do)
+	#[arg(long, group = "start", value_name = "NAME")]
+	pub author: Option<String>,
+}
+
+impl Cli {
+	// Where the reader opens, when one of the flags above says it should skip the menu.
+	// A poem file is loaded in place of the library, so the reader opens at its first poem.
+	pub fn start(&self) -> Option<Start> {
+		if self.command.is_some() && (self.file.is_some() || self.random || self.last || self.author.is_some()) {
+			Cli::command().error(ErrorKind::ArgumentConflict, "a poem file, --random, --last and --author open the reader and cannot be combined with a subcommand").exit();
+		}
+		if self.file.is_some() {
+			Some(Start::First)
+		} else if self.random {
+			Some(Start::Random)
+		} else if self.last {
+			Some(Start::Last)
+		} else {
+			self.author.clone().map(Start::Author)
+		}
+	}
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+	#[command(flatten)]
+	Library(LibraryCommand),
+	/// Print a short random excerpt with attribution, e.g. from a shell rc file
+	Quote {
+		/// Longest excerpt in lines (`quote_max_lines` in the config, 4 by default)
+		#[arg(long)]
+		lines: Option<usize>,
+	},
+	/// Convert poems from other formats into `.poem` files in the library
+	Import {
+		#[arg(long, value_enum, default_value_t = ImportFormat::Text)]
+		from: ImportFormat,
+		/// Files or directories to import
+		#[arg(required = true)]
+		paths: Vec<PathBuf>,
+		/// Author of every imported text file (the name of its directory by default)
+		#[arg(long)]
+		author: Option<String>,
+		/// Language code of every imported text file, instead of asking per poem
+		#[arg(long)]
+		language: Option<String>,
+	},
+	/// Search PoetryDB for public-domain poems and save the chosen ones into the library
+	Fetch {
+		#[arg(long)]
+		author: Option<String>,
+		/// Title or part of one
+		#[arg(long)]
+		title: Option<String>,
+		/// Address of a PoetryDB instance
+		#[arg(long, default_value = POETRYDB)]
+		api: String,
+	},
+	/// Unpack an anthology made with `bundle` into the library
+	Unbundle {
+		file: PathBuf,
+	},
+	/// Rewrite `.poem` files still in the legacy schema into the current one
+	Migrate {
+		/// Only report which files would change
+		#[arg(long)]
+		dry_run: bool,
+	},
+	/// Check every poem file for problems; exits with an error status when there are errors
+	Doctor {
+		#[arg(long)]
+		json: bool,
+	},
+	/// Rewrite `.poem` files in a consistent layout so diffs stay clean
+	Fmt {
+		/// Poem ids; every `.poem` file when omitted
+		ids: Vec<String>,
+		/// Only list files that are not formatted, exiting with an error status if there are any
+		#[arg(long)]
+		check: bool,
+	},
+	/// Sync the library with its other copies (see `sync_backend`), or download the library
+	/// configured as a URL and update the local copy
+	Sync,
+}
+
+// Commands that need every poem loaded
+#[derive(Debug, Subcommand)]
+pub enum LibraryCommand {
+	/// Export poems for use in other programs
+	Export {
+		#[arg(long, value_enum)]
+		format: ExportFormat,
+		/// Kind of flashcards to generate (anki only)
+		#[arg(long, value_enum, default_value_t = CardStyle::FirstLine)]
+		cards: CardStyle,
+		/// Write to this file instead of stdout
+		#[arg(long, short)]
+		out: Option<PathBuf>,
+		#[command(flatten)]
+		selection: Selection,
+	},
+	/// List poems
+	List {
+		#[command(flatten)]
+		selection: Selection,
+		#[arg(long)]
+		json: bool,
+	},
+	/// Search poems by title or author, and with `index` in the configuration by their text too
+	Search {
+		query: String,
+		#[arg(long)]
+		json: bool,
+	},
+	/// Print a poem
+	Show {
+		id: String,
+		/// Version to print instead of the canonical one
+		#[arg(long)]
+		version: Option<String>,
+		#[arg(long)]
+		json: bool,
+	},
+	/// Print a poem rendered the way the reader shows it
+	Cat {
+		id: String,
+		/// Version to print instead of the canonical one
+		#[arg(long)]
+		version: Option<String>,
+		/// Wrap lines at this many columns (the terminal width, or 80 when piped)
+		#[arg(long)]
+		width: Option<usize>,
+		/// Never use colours
+		#[arg(long, conflicts_with = "ansi")]
+		plain: bool,
+		/// Use colours even when not writing to a terminal
+		#[arg(long)]
+		ansi: bool,
+	},
+	/// Print library and reading statistics
+	Stats {
+		#[arg(long)]
+		json: bool,
+	},
+	/// Print a random poem
+	Random {
+		#[command(flatten)]
+		selection: Selection,
+		#[arg(long)]
+		json: bool,
+	},
+	/// Write an Atom feed with one poem per day
+	Feed {
+		/// Write to this file instead of stdout
+		#[arg(long, short)]
+		out: Option<PathBuf>,
+		/// How many days back the feed goes
+		#[arg(long, default_value_t = 30)]
+		days: u64,
+		/// Base URL of a `leaves publish` site to link entries to
+		#[arg(long)]
+		link: Option<String>,
+		/// Only poems whose every version has a public-domain `license`
+		#[arg(long)]
+		public_domain: bool,
+	},
+	/// Serve the library as a read-only JSON API
+	#[cfg(feature = "server")]
+	Serve {
+		#[arg(long, default_value_t = 8080)]
+		port: u16,
+		/// Address to listen on; use 0.0.0.0 to reach it from other machines
+		#[arg(long, default_value = "127.0.0.1")]
+		host: String,
+	},
+	/// Print `id<TAB>author<TAB>title<TAB>language` per poem for fzf or rofi, or open the reader at a poem
+	Pick {
+		/// Poem to open; read from stdin when it is piped in, e.g. `leaves pick | fzf | leaves pick`
+		id: Option<String>,
+	},
+	/// Pack poems into a single anthology file for sharing
+	Bundle {
+		/// Write to this file instead of stdout
+		#[arg(long, short)]
+		out: Option<PathBuf>,
+		#[command(flatten)]
+		selection: Selection,
+	},
+	/// Render the library into a static HTML site
+	Publish {
+		/// Directory to write the site into
+		#[arg(long, short)]
+		out: PathBuf,
+		/// Only poems whose every version has a public-domain `license`
+		#[arg(long)]
+		public_domain: bool,
+	},
+}
+
+// Which poems a command applies to
+#[derive(Debug, Args)]
+pub struct Selection {
+	/// Poem ids (file names without extension); every poem when omitted
+	pub ids: Vec<String>,
+	/// Only poems by this author
+	#[arg(long)]
+	pub author: Option<String>,
+	/// Only poems with a version in this language code
+	#[arg(long)]
+	pub language: Option<String>,
+	/// Only poems whose every version has a public-domain `license`
+	#[arg(long)]
+	pub public_domain: bool,
+}
+
+impl Selection {
+	// The selected poems in author, then title order
+	pub fn apply<'a>(&self, poems: &'a [Poem]) -> io::Result<Vec<&'a Poem>> {
+		if let Some(missing) = self.ids.iter().find(|id| !poems.iter().any(|p| p.id() == id.as_str())) {
+			return Err(io::Error::new(io::ErrorKind::NotFound, format!("no poem with id {}", missing)));
+		}
+		let mut selected: Vec<&Poem> = poems.iter()
+			.filter(|p| self.ids.is_empty() || self.ids.iter().any(|id| id == p.id()))
+			.filter(|p| match &self.author {
+				Some(author) => p.canonical().and_then(|v| v.author.as_deref()) == Some(author.as_str()),
+				None => true,
+			})
+			.filter(|p| match &self.language {
+				Some(language) => p.versions.values().any(|v| v.language.as_deref() == Some(language.as_str())),
+				None => true,
+			})
+			.filter(|p| !self.public_domain || p.is_public_domain())
+			.collect();
+		selected.sort_by_key(|p| {
+			let canonical = p.canonical();
+			(canonical.and_then(|v| v.author.clone()), canonical.and_then(|v| v.title.clone()))
+		});
+		Ok(selected)
+	}
+}
+
+// Poem as emitted by `--json` and the HTTP API; listings leave out the text
+#[derive(Serialize)]
+pub struct PoemJson<'a> {
+	id: &'a str,
+	filename: &'a str,
+	versions: BTreeMap<&'a str, serde_json::Value>,
+}
+
+impl<'a> PoemJson<'a> {
+	pub fn new(poem: &'a Poem, with_text: bool, only_version: Option<&str>) -> PoemJson<'a> {
+		let versions = poem.versions.iter()
+			.filter(|(key, _)| only_version.is_none_or(|only| only == key.as_str()))
+			.map(|(key, version)| {
+				let mut value = serde_json::to_value(version).unwrap_or_default();
+				if let Some(fields) = value.as_object_mut() {
+					if !with_text {
+						fields.remove("text");
+					}
+					fields.insert("lines".into(), version.counts.lines.into());
+					fields.insert("words".into(), (version.counts.words + version.counts.cjk_chars).into());
+				}
+				(key.as_str(), value)
+			})
+			.collect();
+		PoemJson { id: poem.id(), filename: &poem.filename, versions }
+	}
+}
+
+fn print_json(out: &mut impl Write, value: &impl Serialize) -> io::Result<()> {
+	let json = serde_json::to_string_pretty(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+	writeln!(out, "{}", json)?;
+	Ok(())
+}
+
+fn print_list(out: &mut impl Write, poems: &[&Poem], json: bool) -> io::Result<()> {
+	if json {
+		let entries: Vec<PoemJson> = poems.iter().map(|p| PoemJson::new(p, false, None)).collect();
+		return print_json(out, &entries);
+	}
+	for poem in poems {
+		if let Some(canonical) = poem.canonical() {
+			writeln!(out, "{} - {} [{}]", canonical.author.as_deref().unwrap_or("Unknown"), canonical.title.as_deref().unwrap_or("Untitled"), poem.id())?;
+		}
+	}
+	Ok(())
+}
+
+fn print_poem(out: &mut impl Write, poem: &Poem, version: Option<&str>, json: bool) -> io::Result<()> {
+	let key = version.unwrap_or("canonical");
+	let Some(selected) = poem.versions.get(key) else {
+		return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} has no version {}", poem.id(), key)));
+	};
+	if json {
+		return print_json(out, &PoemJson::new(poem, true, version));
+	}
+	writeln!(out, "{}", selected.title.as_deref().unwrap_or("Untitled"))?;
+	writeln!(out, "{}", selected.author.as_deref().unwrap_or("Unknown"))?;
+	writeln!(out)?;
+	if let Some(epigraph) = &selected.epigraph {
+		writeln!(out, "{}\n", epigraph.trim_end())?;
+	}
+	writeln!(out, "{}", selected.text.trim_end())?;
+	Ok(())
+}
+
+// Runs the viewer's pipeline (markdown, vertical layout, wrapping, RTL reordering) into plain lines
+fn print_rendered(out: &mut impl Write, poem: &Poem, version: Option<&str>, width: Option<usize>, colour: Option<bool>) -> io::Result<()> {
+	let key = version.unwrap_or("canonical");
+	let Some(selected) = poem.versions.get(key) else {
+		return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} has no version {}", poem.id(), key)));
+	};
+	let tty = io::stdout().is_terminal();
+	let (columns, rows) = if tty { terminal::size().unwrap_or((80, 24)) } else { (80, u16::MAX) };
+	let width = width.unwrap_or(columns as usize).max(1);
+	let ansi = colour.unwrap_or(tty);
+	let rtl = selected.is_rtl() && !selected.is_vertical();
+
+	let mut text = String::new();
+	let mut epigraph_lines = 0;
+	if let Some(epigraph) = &selected.epigraph {
+		text.push_str(epigraph);
+		text.push('\n');
+		epigraph_lines = epigraph.lines().count();
+	}
+	text.push_str(&ui::render_poem_text_for_height(selected, rows.saturating_sub(3) as usize));
+
+	let author = selected.author.as_deref().unwrap_or("Unknown");
+	let title = selected.title.as_deref().unwrap_or("Untitled");
+	if ansi {
+		writeln!(out, "\x1b[33m{}\x1b[0m - \x1b[33m{}\x1b[0m\n", author, title)?;
+	} else {
+		writeln!(out, "{} - {}\n", author, title)?;
+	}
+	for line in ui::wrap_poem_lines(&text, width, epigraph_lines, false, rtl, None) {
+		let line: String = line.spans.iter().map(|span| span.content.as_ref()).collect();
+		if rtl {
+			let padding = width.saturating_sub(display_width(&line));
+			writeln!(out, "{}{}", " ".repeat(padding), line)?;
+		} else {
+			writeln!(out, "{}", line.trim_end())?;
+		}
+	}
+	Ok(())
+}
+
+fn print_stats(out: &mut impl Write, stats: &Stats, json: bool) -> io::Result<()> {
+	if json {
+		return print_json(out, stats);
+	}
+	writeln!(out, "Poems:           {}", stats.poems)?;
+	writeln!(out, "Authors:         {}", stats.authors)?;
+	writeln!(out, "Languages:       {}", stats.languages)?;
+	writeln!(out, "Versions:        {}", stats.versions)?;
+	writeln!(out, "Lines:           {}", stats.lines)?;
+	writeln!(out, "Words:           {}", stats.words)?;
+	writeln!(out, "Poems read:      {} of {}", stats.poems_read, stats.poems)?;
+	writeln!(out, "Read this week:  {}", stats.read_this_week)?;
+	writeln!(out, "Current streak:  {} day(s)", stats.streak_days)?;
+	for (author, reads) in &stats.top_authors {
+		writeln!(out, "Most read:       {} ({})", author, reads)?;
+	}
+	Ok(())
+}
+
+// One poem per line in a fixed column order; tabs and newlines in fields would break the columns
+fn print_pick_lines(out: &mut impl Write, poems: &[Poem]) -> io::Result<()> {
+	let field = |text: &str| text.replace(['\t', '\n'], " ");
+	let mut sorted: Vec<&Poem> = poems.iter().collect();
+	sorted.sort_by_key(|p| p.id());
+	for poem in sorted {
+		if let Some(canonical) = poem.canonical() {
+			writeln!(out, "{}\t{}\t{}\t{}",
+				poem.id(),
+				field(canonical.author.as_deref().unwrap_or("Unknown")),
+				field(canonical.title.as_deref().unwrap_or("Untitled")),
+				canonical.language.as_deref().unwrap_or(""))?;
+		}
+	}
+	Ok(())
+}
+
+// The id from the argument, or from the first line piped in (a whole picker line works too)
+fn picked_id(id: Option<String>) -> io::Result<Option<String>> {
+	let id = match id {
+		Some(id) => id,
+		None if !io::stdin().is_terminal() => {
+			let mut line = String::new();
+			io::stdin().read_line(&mut line)?;
+			line
+		}
+		None => return Ok(None),
+	};
+	let id = id.split('\t').next().unwrap_or_default().trim();
+	Ok((!id.is_empty()).then(|| id.to_string()))
+}
+
+fn find<'a>(poems: &'a [Poem], id: &str) -> io::Result<&'a Poem> {
+	poems.iter().find(|p| p.id() == id)
+		.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no poem with id {}", id)))
+}
+
+// Writes command output to `out`, or to stdout when no file is given
+fn write_output(stdout: &mut impl Write, out: Option<&PathBuf>, content: &str) -> io::Result<()> {
+	match out {
+		Some(path) => fs::write(path, content),
+		None => stdout.write_all(content.as_bytes()),
+	}
+}
+
+// Whole stanzas that fit in `max_lines`, or else couplets taken from the longer stanzas
+fn excerpts(text: &str, max_lines: usize) -> Vec<Vec<&str>> {
+	let stanzas = stanzas(text);
+	let short: Vec<Vec<&str>> = stanzas.iter().filter(|s| s.len() <= max_lines).cloned().collect();
+	if !short.is_empty() {
+		return short;
+	}
+	stanzas.iter().flat_map(|s| s.chunks(max_lines.clamp(1, 2)).map(|c| c.to_vec())).collect()
+}
+
+// Reads random files one at a time instead of the whole library, so it stays fast enough for a shell rc file
+fn print_quote(out: &mut impl Write, max_lines: usize) -> io::Result<()> {
+	let mut rng = rand::thread_rng();
+	let mut names = library_names()?;
+	names.shuffle(&mut rng);
+	for name in names {
+		let Ok(content) = read_library_file(&name) else { continue };
+		let poems: Vec<_> = parse_file(Path::new(&name), &content).into_iter().flatten().collect();
+		let Some(canonical) = poems.choose(&mut rng).and_then(|versions| versions.get("canonical")) else { continue };
+		let text = convert(&canonical.text, &PLAIN);
+		let Some(excerpt) = excerpts(&text, max_lines).choose(&mut rng).cloned() else { continue };
+		for line in excerpt {
+			writeln!(out, "{}", line.trim())?;
+		}
+		writeln!(out, "    — {}, {}", canonical.author.as_deref().unwrap_or("Unknown"), canonical.title.as_deref().unwrap_or("Untitled"))?;
+		return Ok(());
+	}
+	Err(io::Error::new(io::ErrorKind::NotFound, "no poems to quote from"))
+}
+
+// Ids of the poems whose text has words starting with those of `query`, when the library
+// is indexed; none otherwise
+#[cfg(feature = "sqlite")]
+fn text_matches(query: &str) -> io::Result<HashSet<String>> {
+	if !crate::index::in_use()? {
+		return Ok(HashSet::new());
+	}
+	crate::index::search(query)
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn text_matches(_query: &str) -> io::Result<HashSet<String>> {
+	Ok(HashSet::new())
+}
+
+// Poems found by the full-text index, best first; the others matching are listed after them
+#[cfg(feature = "search")]
+fn ranked_matches<'a>(poems: &'a [Poem], query: &str) -> io::Result<Vec<&'a Poem>> {
+	let index = crate::search::SearchIndex::open(poems, true)?;
+	let by_id: std::collections::HashMap<&str, &Poem> = poems.iter().map(|p| (p.id(), p)).collect();
+	Ok(index.search(query, poems.len())?.iter().filter_map(|id| by_id.get(id.as_str()).copied()).collect())
+}
+
+#[cfg(not(feature = "search"))]
+fn ranked_matches<'a>(_poems: &'a [Poem], _query: &str) -> io::Result<Vec<&'a Poem>> {
+	Ok(Vec::new())
+}
+
+// Returns the id of a poem to open in the reader when the command asks for one
+pub fn run(command: Command, strict: bool) -> io::Result<Option<String>> {
+	let mut stdout = io::stdout().lock();
+	let writes = match &command {
+		Command::Fmt { check, .. } => !check,
+		Command::Migrate { dry_run } => !dry_run,
+		Command::Import { .. } | Command::Fetch { .. } | Command::Unbundle { .. } => true,
+		_ => false,
+	};
+	if writes {
+		check_writable()?;
+	}
+	// These work on files directly and skip loading the library, so they stay quick
+	// and keep working while some poem file is broken
+	let result = match command {
+		Command::Quote { lines } => {
+			let max_lines = lines.or(Config::load()?.quote_max_lines).unwrap_or(4);
+			print_quote(&mut stdout, max_lines)
+		}
+		Command::Import { from, paths, author, language } => {
+			let count = match from {
+				ImportFormat::Text => import::text(&mut stdout, &paths, author.as_deref(), language.as_deref())?,
+				ImportFormat::Markdown => import::markdown(&mut stdout, &paths)?,
+			};
+			writeln!(stdout, "Imported {} poems", count)
+		}
+		Command::Fetch { author, title, api } => {
+			let count = fetch::fetch(&mut stdout, &api, author.as_deref(), title.as_deref())?;
+			writeln!(stdout, "Saved {} poems", count)
+		}
+		Command::Unbundle { file } => {
+			let count = bundle::unbundle(&mut stdout, &file)?;
+			writeln!(stdout, "Unpacked {} poems", count)
+		}
+		Command::Migrate { dry_run } => migrate(&mut stdout, dry_run).map(|_| ()),
+		Command::Sync => sync::sync_configured(&mut stdout),
+		Command::Doctor { json } => {
+			let report = doctor::check()?;
+			if json {
+				print_json(&mut stdout, &report)?;
+			} else {
+				doctor::print_report(&mut stdout, &report)?;
+			}
+			match report.errors() {
+				0 => Ok(()),
+				errors => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} errors in the library", errors))),
+			}
+		}
+		Command::Fmt { ids, check } => match format_files(&mut stdout, &ids, check)? {
+			changed if check && changed > 0 => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} files need formatting", changed))),
+			_ => Ok(()),
+		},
+		Command::Library(command) => return run_with_library(&mut stdout, command, strict),
+	};
+	result.map(|()| None)
+}
+
+// Commands that put poems where others can read them
+fn publishes(command: &LibraryCommand) -> bool {
+	match command {
+		LibraryCommand::Feed { .. } | LibraryCommand::Publish { .. } => true,
+		#[cfg(feature = "server")]
+		LibraryCommand::Serve { .. } => true,
+		_ => false,
+	}
+}
+
+fn run_with_library(stdout: &mut io::StdoutLock, command: LibraryCommand, strict: bool) -> io::Result<Option<String>> {
+	let (mut poems, _) = load_poems(strict)?;
+	if let LibraryCommand::Feed { public_domain: true, .. } | LibraryCommand::Publish { public_domain: true, .. } = command {
+		poems.retain(Poem::is_public_domain);
+	}
+	// Encrypted poems are private, so they never go online or out on the network
+	if publishes(&command) {
+		poems.retain(|p| !is_encrypted(&p.filename));
+	}
+	let result = match command {
+		LibraryCommand::Export { format, cards, out, selection } => {
+			let selected = selection.apply(&poems)?;
+			let content = match format {
+				ExportFormat::Anki => export::anki(&selected, cards),
+				ExportFormat::Latex => export::latex(&selected),
+				ExportFormat::Typst => export::typst(&selected),
+			};
+			write_output(stdout, out.as_ref(), &content)
+		}
+		LibraryCommand::List { selection, json } => print_list(stdout, &selection.apply(&poems)?, json),
+		LibraryCommand::Search { query, json } => {
+			let mut matches = ranked_matches(&poems, &query)?;
+			let lowercase = query.to_lowercase();
+			let in_text = text_matches(&lowercase)?;
+			let mut rest: Vec<&Poem> = poems.iter()
+				.filter(|p| (p.matches(&lowercase) || in_text.contains(p.id())) && !matches.iter().any(|m| m.id() == p.id()))
+				.collect();
+			rest.sort_by_key(|p| p.canonical().map(|v| (v.author.clone(), v.title.clone())));
+			matches.extend(rest);
+			print_list(stdout, &matches, json)
+		}
+		LibraryCommand::Show { id, version, json } => print_poem(stdout, find(&poems, &id)?, version.as_deref(), json),
+		LibraryCommand::Cat { id, version, width, plain, ansi } => {
+			let colour = if plain { Some(false) } else if ansi { Some(true) } else { None };
+			print_rendered(stdout, find(&poems, &id)?, version.as_deref(), width, colour)
+		}
+		LibraryCommand::Stats { json } => print_stats(stdout, &Stats::compute(&poems, &History::load()?), json),
+		LibraryCommand::Random { selection, json } => {
+			let selected = selection.apply(&poems)?;
+			let poem = selected.choose(&mut rand::thread_rng())
+				.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no poems to choose from"))?;
+			print_poem(stdout, poem, None, json)
+		}
+		LibraryCommand::Feed { out, days, link, .. } => write_output(stdout, out.as_ref(), &feed::atom(&poems, days, link.as_deref())),
+		#[cfg(feature = "server")]
+		LibraryCommand::Serve { port, host } => crate::server::serve(&poems, &host, port),
+		LibraryCommand::Pick { id } => match picked_id(id)? {
+			Some(id) => return find(&poems, &id).map(|poem| Some(poem.id().to_string())),
+			None => print_pick_lines(stdout, &poems),
+		},
+		LibraryCommand::Bundle { out, selection } => write_output(stdout, out.as_ref(), &bundle::bundle(&selection.apply(&poems)?)?),
+		LibraryCommand::Publish { out, .. } => {
+			let count = publish(&poems, &out)?;
+			writeln!(stdout, "Published {} poems to {}", count, out.display())
+		}
+	};
+	result.map(|()| None)
+}
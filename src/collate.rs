@@ -0,0 +1,120 @@
+use crate::config::Config;
+use crate::utils::{is_han, title_group, LanguageTag};
+use any_ascii::any_ascii_char;
+use icu_collator::{Collator, CollatorOptions, Strength};
+use icu_provider::DataLocale;
+use isolang::Language;
+use std::{borrow::Cow, cmp::Ordering};
+
+// How CJK text is spelled out before it is compared (`cjk_collation`)
+#[derive(Clone, Copy)]
+enum Reading {
+	// Han characters by their Mandarin reading
+	Pinyin,
+	// Hiragana and katakana in Latin letters; kanji keep their place after them
+	Romaji,
+}
+
+// Orders titles, authors and languages by the rules of a locale: `collation` in the config,
+// else the first of `languages`, else the Unicode root order, which already files accented
+// letters with their base letter and keeps each script together
+pub struct Collation {
+	collator: Collator,
+	// The same order telling apart only base letters, for grouping
+	letters: Collator,
+	reading: Option<Reading>,
+}
+
+// ICU knows most languages by their shortest code: `de`, not `deu`
+fn locale_of(code: &str) -> String {
+	let Some(mut tag) = LanguageTag::parse(code) else {
+		return code.to_string();
+	};
+	if let Some(short) = Language::from_639_3(&tag.language).and_then(|l| l.to_639_1()) {
+		tag.language = short.to_string();
+	}
+	tag.to_string()
+}
+
+impl Collation {
+	pub fn new(config: &Config) -> Collation {
+		// A locale with extensions (`zh-u-co-stroke`) is passed on whole
+		let locale = match (&config.collation, config.languages.first()) {
+			(Some(locale), _) if locale.contains("-u-") => locale.clone(),
+			(Some(code), _) | (None, Some(code)) => locale_of(code),
+			(None, None) => String::new(),
+		};
+		let mut letter_options = CollatorOptions::new();
+		letter_options.strength = Some(Strength::Primary);
+		let collators = locale.parse::<DataLocale>().ok().and_then(|locale| {
+			Some((Collator::try_new(&locale, CollatorOptions::new()).ok()?, Collator::try_new(&locale, letter_options).ok()?))
+		});
+		let (collator, letters) = collators.unwrap_or_else(|| {
+			tracing::warn!(locale = %locale, "no collation for this locale, sorting in the root order");
+			let root = |options| Collator::try_new(&DataLocale::default(), options).expect("the root collation is built in");
+			(root(CollatorOptions::new()), root(letter_options))
+		});
+		let reading = match config.cjk_collation.as_deref() {
+			Some("pinyin") => Some(Reading::Pinyin),
+			Some("romaji") => Some(Reading::Romaji),
+			Some(other) => {
+				tracing::warn!(cjk_collation = other, "expected \"pinyin\" or \"romaji\"");
+				None
+			},
+			None => None,
+		};
+		Collation { collator, letters, reading }
+	}
+
+	// The text as it is compared: with `cjk_collation` set, its CJK characters spelled out
+	pub fn spelled<'a>(&self, text: &'a str) -> Cow<'a, str> {
+		let Some(reading) = self.reading else {
+			return Cow::Borrowed(text);
+		};
+		let spells = |c: char| match reading {
+			Reading::Pinyin => is_han(c),
+			Reading::Romaji => matches!(c as u32, 0x3040..=0x30FF),
+		};
+		if !text.chars().any(spells) {
+			return Cow::Borrowed(text);
+		}
+		let mut spelled = String::with_capacity(text.len());
+		for c in text.chars() {
+			if spells(c) {
+				spelled.push_str(&any_ascii_char(c).to_lowercase());
+			} else {
+				spelled.push(c);
+			}
+		}
+		Cow::Owned(spelled)
+	}
+
+	// The section of the title list a title is filed under: `title_group` of it as spelled
+	// out, with an accented letter under its base letter where the locale counts them as one
+	// (`É` under `E`, but `Å` after `Z` in Swedish)
+	pub fn title_group(&self, title: &str) -> String {
+		let group = title_group(&self.spelled(title));
+		let mut letters = group.chars();
+		if let (Some(letter), None) = (letters.next(), letters.next()) {
+			let base = any_ascii_char(letter).to_uppercase();
+			if !base.is_empty() && self.letters.compare(&group, &base) == Ordering::Equal {
+				return base;
+			}
+		}
+		group
+	}
+
+	// Sorts `items` by the first part of `key` and then by its text, in this collation; keys
+	// are worked out once per item, as `sort_by_cached_key` does
+	pub fn sort_by_key<T: Clone, K: Ord>(&self, items: &mut [T], key: impl Fn(&T) -> (K, String)) {
+		let mut keyed: Vec<(K, String, T)> = items.iter().map(|item| {
+			let (first, text) = key(item);
+			let text = self.spelled(&text).into_owned();
+			(first, text, item.clone())
+		}).collect();
+		keyed.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| self.collator.compare(&a.1, &b.1)));
+		for (slot, (_, _, item)) in items.iter_mut().zip(keyed) {
+			*slot = item;
+		}
+	}
+}
@@ -0,0 +1,83 @@
+use crate::models::poems_dir;
+use crate::tasks;
+use std::{io::{self, Write}, process::Command};
+
+// Commands of the `keybind` setting, run by the shell from the library directory on the poem
+// being read, e.g. `pandoc {file} -o {title}.pdf`. Placeholders are filled in quoted, so a
+// title with spaces or quotes stays one word.
+
+// Quoted for `sh`, or for `cmd` on Windows
+fn quote(value: &str) -> String {
+	if cfg!(windows) {
+		format!("\"{}\"", value.replace('"', "\"\""))
+	} else {
+		format!("'{}'", value.replace('\'', "'\\''"))
+	}
+}
+
+// `template` with each `{name}` of `fields` replaced by its value. The template is read once,
+// so a value that looks like a placeholder, such as a title of `{author}`, is never filled in.
+pub fn command_line(template: &str, fields: &[(&str, &str)]) -> String {
+	let mut line = String::new();
+	let mut rest = template;
+	while let Some(start) = rest.find('{') {
+		line.push_str(&rest[..start]);
+		rest = &rest[start..];
+		let field = rest.find('}').and_then(|end| fields.iter().find(|(name, _)| *name == &rest[1..end]).map(|(_, value)| (end, value)));
+		match field {
+			Some((end, value)) => {
+				line.push_str(&quote(value));
+				rest = &rest[end + 1..];
+			},
+			None => {
+				line.push('{');
+				rest = &rest[1..];
+			},
+		}
+	}
+	line.push_str(rest);
+	line
+}
+
+// Runs `line` and writes what it printed, its errors after its output; fails when it does
+pub fn run(out: &mut impl Write, line: &str) -> io::Result<()> {
+	let mut command = if cfg!(windows) { Command::new("cmd") } else { Command::new("sh") };
+	command.arg(if cfg!(windows) { "/C" } else { "-c" }).arg(line).current_dir(poems_dir());
+	let output = tasks::output(&mut command).map_err(|e| io::Error::new(e.kind(), format!("could not run the command: {}", e)))?;
+	out.write_all(&output.stdout)?;
+	out.write_all(&output.stderr)?;
+	if !output.status.success() {
+		return Err(io::Error::other(format!("the command failed ({})", output.status)));
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn placeholders_are_filled_in_quoted() {
+		let line = command_line("pandoc {file} -o {title}.pdf {unknown}", &[("file", "a b.poem"), ("title", "Hope")]);
+		if cfg!(windows) {
+			assert_eq!(line, "pandoc \"a b.poem\" -o \"Hope\".pdf {unknown}");
+		} else {
+			assert_eq!(line, "pandoc 'a b.poem' -o 'Hope'.pdf {unknown}");
+		}
+	}
+
+	#[test]
+	#[cfg(not(windows))]
+	fn values_are_not_read_as_placeholders() {
+		let line = command_line("echo {title} {author}", &[("title", "{author}"), ("author", "$(rm -rf ~)")]);
+		assert_eq!(line, "echo '{author}' '$(rm -rf ~)'");
+	}
+
+	#[test]
+	#[cfg(not(windows))]
+	fn quotes_in_values_stay_in_them() {
+		assert_eq!(command_line("echo {title}", &[("title", "It's; rm x")]), "echo 'It'\\''s; rm x'");
+		let output = std::process::Command::new("sh").arg("-c").arg(command_line("printf %s {title}", &[("title", "It's $(a) `b`")])).output().unwrap();
+		assert_eq!(String::from_utf8_lossy(&output.stdout), "It's $(a) `b`");
+	}
+}
@@ -0,0 +1,124 @@
+use crate::paths;
+use serde::Deserialize;
+use std::{collections::HashMap, io, fs, path::PathBuf};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+	// Keep only the poem being viewed in memory and skip every cache,
+	// trading CPU for memory on very small machines.
+	pub low_memory: bool,
+	// Start the author list in surname order instead of given-name order
+	pub sort_authors_by_surname: bool,
+	// Show the estimated reading time next to entries in the title and filtered lists
+	pub list_reading_time: bool,
+	// Command used to look up words, with `{word}` as placeholder, e.g. "sdcv -n {word}"
+	pub dictionary_command: Option<String>,
+	// Text-to-speech command reading the poem from stdin, with `{voice}` as placeholder
+	pub tts_command: Option<String>,
+	// Player for recordings, with `{file}` as placeholder (appended when missing)
+	pub audio_player: Option<String>,
+	// "kitty", "sixel" or "none"; detected from the terminal when unset
+	pub image_protocol: Option<String>,
+	// Longest excerpt `leaves quote` prints, in lines (4 when unset)
+	pub quote_max_lines: Option<usize>,
+	// Refuse to start when any poem file is malformed instead of skipping it
+	pub strict: bool,
+	// Never write into the library, as with `--read-only`: for shared or system-wide collections
+	pub read_only: bool,
+	// Keep an SQLite index of the library in the cache directory (with the `sqlite` feature):
+	// files unchanged since are loaded from it rather than read and parsed again, and `leaves
+	// search` looks through the text of the poems as well. The files stay what counts.
+	pub index: bool,
+	// File extensions read from the library directory ("poem" and "md" when unset);
+	// everything but `md` is read as YAML
+	pub extensions: Option<Vec<String>>,
+	// Where poems are read from instead of ~/literature/poetry: a directory, (with the
+	// `archives` feature) a read-only `.zip`/`.tar.gz` file, or the `https://` URL of either
+	// an archive or a git repository, copied locally by `leaves sync`; `~/` is expanded
+	pub library: Option<PathBuf>,
+	// Colours of the reader: "dark" (light text, the default) or "light" (dark text)
+	pub theme: Option<String>,
+	// Borders, scrollbars and symbols in ASCII, for terminals whose fonts lack box drawing;
+	// detected when unset, so `false` keeps them on a terminal taken for one of those
+	pub ascii_only: Option<bool>,
+	// Language codes, most wanted first; poems open at a version in the first of them they
+	// have instead of the canonical one
+	pub languages: Vec<String>,
+	// Display names by language code, added to the built-in ones or replacing them: regional
+	// codes, constructed languages, historical stages
+	pub language_names: HashMap<String, String>,
+	// Browse one language written in several scripts (`sr-Cyrl`, `sr-Latn`) as one entry
+	pub merge_language_scripts: bool,
+	// Locale whose alphabetical order lists follow, e.g. "sv" or "zh-u-co-stroke"; the first
+	// of `languages` when unset
+	pub collation: Option<String>,
+	// "pinyin" or "romaji": compare Chinese characters, or Japanese kana, by their reading in
+	// Latin letters, so they sort in among Latin titles
+	pub cjk_collation: Option<String>,
+	// Draw Arabic and Persian letters joined, as presentation forms, for terminals that show
+	// each letter on its own
+	pub arabic_shaping: bool,
+	// Language of menus, list titles and status bar hints, e.g. "fr" or "zh-Hant"; English
+	// when unset or not translated
+	pub interface_language: Option<String>,
+	// Days to keep the copies made of poem files before leaves rewrites them (30 when
+	// unset); 0 turns the copies off
+	pub backup_days: Option<u64>,
+	// How `leaves sync` keeps the library directory the same across machines: "git" (commit,
+	// pull and push the repository it is in), "rsync" or "rclone" (copying both ways with
+	// `sync_remote`)
+	pub sync_backend: Option<String>,
+	// The other copy for rsync ("host:poetry") or rclone ("drive:poetry")
+	pub sync_remote: Option<String>,
+	// File of age keys (from `age-keygen`) for poems encrypted to a key, as `.poem.age` files;
+	// those encrypted with a passphrase ask for it instead. `~/` is expanded.
+	pub age_identity: Option<PathBuf>,
+	// Commands run on the poem being read by a key of the viewer the reader does not use, e.g.
+	// `x: pandoc {file} -o {title}.pdf`, with `{file}`, `{title}`, `{author}`, `{language}`,
+	// `{id}`, `{version}` and `{selection}` (the word under the word cursor) filled in
+	pub keybind: HashMap<String, String>,
+	// Further ways of browsing the library, each with its menu entry (see `FacetConfig`)
+	pub facets: Vec<FacetConfig>,
+}
+
+// A facet grouping poems by a field of their versions, e.g. `{name: decade, field: year,
+// step: 10}`
+#[derive(Debug, Clone, Deserialize)]
+pub struct FacetConfig {
+	// The menu entry reads "Browse by <name>"
+	pub name: String,
+	// `year`, `first_line`, `tags`, another field of the poem format, or one leaves does not
+	// use, such as `meter`; a list gives a poem a group for each of its items
+	pub field: String,
+	// Numbers are grouped into ranges this wide, e.g. 10 for decades
+	pub step: Option<i64>,
+	// Only the canonical version of each poem is grouped
+	#[serde(default)]
+	pub canonical_only: bool,
+	// How the poems of a group are listed at first: alphabetical, recent, length or chronological
+	pub sort: Option<String>,
+}
+
+pub fn config_path() -> PathBuf {
+	paths::or_legacy(paths::config_dir().join("config.yaml"), &[".config", "leaves", "config.yaml"])
+}
+
+impl Config {
+	pub fn extensions(&self) -> Vec<String> {
+		match &self.extensions {
+			Some(extensions) => extensions.iter().map(|e| e.trim_start_matches('.').to_string()).collect(),
+			None => vec!["poem".to_string(), "md".to_string()],
+		}
+	}
+
+	pub fn load() -> io::Result<Config> {
+		let path = config_path();
+		if !path.exists() {
+			return Ok(Config::default());
+		}
+		let content = fs::read_to_string(&path)?;
+		serde_yaml::from_str(&content)
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+	}
+}
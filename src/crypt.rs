@@ -0,0 +1,76 @@
+use crate::config::Config;
+use crate::utils::home_dir;
+use age::secrecy::SecretString;
+use age::x25519;
+use std::{fs, io::{self, Read, Write}, str::FromStr, sync::Mutex};
+
+// Asked for the first time a file encrypted with a passphrase is opened, and kept for the
+// rest of the run once it opened one, so rewriting the file needs no second prompt
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+
+fn passphrase() -> io::Result<String> {
+	if let Some(passphrase) = PASSPHRASE.lock().unwrap_or_else(|e| e.into_inner()).clone() {
+		return Ok(passphrase);
+	}
+	rpassword::prompt_password("Passphrase for encrypted poems: ")
+		.map_err(|e| io::Error::new(e.kind(), format!("could not ask for the passphrase: {}", e)))
+}
+
+// The keys in the `age_identity` file, as written by `age-keygen`
+fn identities() -> io::Result<Vec<x25519::Identity>> {
+	let Some(path) = Config::load()?.age_identity else {
+		return Ok(Vec::new());
+	};
+	let path = match path.strip_prefix("~") {
+		Ok(rest) => home_dir().join(rest),
+		Err(_) => path,
+	};
+	let content = fs::read_to_string(&path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+	content.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'))
+		.map(|line| x25519::Identity::from_str(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))))
+		.collect()
+}
+
+fn is_passphrase_file(bytes: &[u8]) -> bool {
+	age::Decryptor::new(bytes).is_ok_and(|decryptor| decryptor.is_scrypt())
+}
+
+// The content of an encrypted file, with the passphrase or the `age_identity` keys depending
+// on how it was encrypted
+pub fn decrypt(bytes: &[u8]) -> io::Result<Vec<u8>> {
+	let failed = |e: age::DecryptError| io::Error::new(io::ErrorKind::InvalidData, format!("could not decrypt: {}", e));
+	let decryptor = age::Decryptor::new(bytes).map_err(failed)?;
+	let mut plain = Vec::new();
+	if decryptor.is_scrypt() {
+		let passphrase = passphrase()?;
+		let identity = age::scrypt::Identity::new(SecretString::from(passphrase.clone()));
+		decryptor.decrypt(std::iter::once(&identity as &dyn age::Identity)).map_err(failed)?.read_to_end(&mut plain)?;
+		*PASSPHRASE.lock().unwrap_or_else(|e| e.into_inner()) = Some(passphrase);
+	} else {
+		let identities = identities()?;
+		if identities.is_empty() {
+			return Err(io::Error::new(io::ErrorKind::NotFound, "encrypted to a key; set `age_identity` to the file holding it"));
+		}
+		decryptor.decrypt(identities.iter().map(|i| i as &dyn age::Identity)).map_err(failed)?.read_to_end(&mut plain)?;
+	}
+	Ok(plain)
+}
+
+// Encrypts the new content of a file the way `previous` (what the file held) was: with the
+// passphrase, or to the keys of `age_identity`
+pub fn encrypt(previous: &[u8], plain: &[u8]) -> io::Result<Vec<u8>> {
+	let encryptor = if is_passphrase_file(previous) {
+		age::Encryptor::with_user_passphrase(SecretString::from(passphrase()?))
+	} else {
+		let recipients: Vec<x25519::Recipient> = identities()?.iter().map(x25519::Identity::to_public).collect();
+		if recipients.is_empty() {
+			return Err(io::Error::new(io::ErrorKind::NotFound, "encrypting to a key needs `age_identity` set to the file holding it"));
+		}
+		age::Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn age::Recipient)).map_err(|e| io::Error::other(e.to_string()))?
+	};
+	let mut encrypted = Vec::new();
+	let mut writer = encryptor.wrap_output(&mut encrypted)?;
+	writer.write_all(plain)?;
+	writer.finish()?;
+	Ok(encrypted)
+}
@@ -0,0 +1,29 @@
+use std::process::Command;
+
+// Tried in order when no `dictionary_command` is configured
+const DEFAULT_COMMANDS: &[&str] = &["sdcv -n --utf8-output {word}", "dict {word}"];
+
+// Runs a command template such as "sdcv -n {word}" and returns its output if it produced any
+fn run(template: &str, word: &str) -> Option<String> {
+	let mut parts = template.split_whitespace().map(|part| part.replace("{word}", word));
+	let program = parts.next()?;
+	let output = Command::new(program).args(parts).output().ok()?;
+	let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+	if output.status.success() && !text.is_empty() {
+		Some(text)
+	} else {
+		None
+	}
+}
+
+// Looks `word` up with the configured command, or with sdcv/dictd when none is set
+pub fn lookup(word: &str, command: Option<&str>) -> String {
+	let definition = match command {
+		Some(template) => run(template, word),
+		None => DEFAULT_COMMANDS.iter().find_map(|template| run(template, word)),
+	};
+	definition.unwrap_or_else(|| match command {
+		Some(template) => format!("No definition found for \"{}\" using `{}`.", word, template),
+		None => format!("No definition found for \"{}\".\n\nInstall sdcv (StarDict) or dictd, or set `dictionary_command` in the config.", word),
+	})
+}
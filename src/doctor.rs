@@ -0,0 +1,126 @@
+use crate::models::{parse_file, poem_id, read_library};
+use crate::utils::get_language_name;
+use serde::Serialize;
+use std::{collections::BTreeMap, io::{self, Write}, path::Path};
+
+#[derive(Debug, Serialize)]
+pub struct Problem {
+	pub file: String,
+	// Short machine-friendly name, e.g. `missing-canonical`
+	pub kind: &'static str,
+	// Warnings point at likely mistakes; errors keep a poem out of the library
+	pub error: bool,
+	pub message: String,
+	// Position of a parse error
+	pub line: Option<usize>,
+	pub column: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+	pub files: usize,
+	pub problems: Vec<Problem>,
+}
+
+impl Report {
+	pub fn errors(&self) -> usize {
+		self.problems.iter().filter(|p| p.error).count()
+	}
+}
+
+fn problem(file: &str, kind: &'static str, error: bool, message: String) -> Problem {
+	Problem { file: file.to_string(), kind, error, message, line: None, column: None }
+}
+
+// A `see_also` id to look up once every id is known, with where it was written
+struct Link {
+	file: String,
+	place: String,
+	id: String,
+}
+
+// Problems in one file's content; returns how many poems it holds
+fn check_content(file: &str, path: &Path, content: &str, problems: &mut Vec<Problem>, links: &mut Vec<Link>) -> usize {
+	let parsed = parse_file(path, content);
+	let count = parsed.len();
+	for (i, result) in parsed.into_iter().enumerate() {
+		let versions = match result {
+			Ok(versions) => versions,
+			Err(e) => {
+				let kind = if e.message.contains("no `canonical` version") { "missing-canonical" } else { "invalid" };
+				problems.push(Problem {
+					line: e.location.map(|(line, _)| line),
+					column: e.location.map(|(_, column)| column),
+					..problem(file, kind, true, e.to_string())
+				});
+				continue;
+			}
+		};
+		let poem = if count > 1 { format!("poem {}: ", i + 1) } else { String::new() };
+		let mut keys: Vec<&String> = versions.keys().collect();
+		keys.sort();
+		for key in keys {
+			let version = &versions[key];
+			if version.text.trim().is_empty() {
+				problems.push(problem(file, "empty-text", true, format!("{}version `{}` has no text", poem, key)));
+			}
+			if let Some(language) = &version.language {
+				if get_language_name(language).is_none() {
+					problems.push(problem(file, "unknown-language", false, format!("{}version `{}` has language `{}`, which has no display name", poem, key, language)));
+				}
+			}
+			for id in version.see_also.iter().flatten() {
+				links.push(Link { file: file.to_string(), place: format!("{}version `{}`", poem, key), id: id.clone() });
+			}
+		}
+	}
+	count
+}
+
+// Checks every poem file in the library
+pub fn check() -> io::Result<Report> {
+	let mut files = 0;
+	let mut problems = Vec::new();
+	let mut ids: BTreeMap<String, Vec<String>> = BTreeMap::new();
+	let mut links = Vec::new();
+	read_library(|library_file| {
+		files += 1;
+		let file = library_file.name;
+		let bytes = match library_file.content {
+			Ok(bytes) => bytes,
+			Err(e) => {
+				problems.push(problem(&file, "unreadable", true, e.to_string()));
+				return Ok(());
+			}
+		};
+		match String::from_utf8(bytes) {
+			Ok(content) => {
+				let count = check_content(&file, Path::new(&file), &content, &mut problems, &mut links);
+				for document in (1..=count).map(|i| (count > 1).then_some(i)) {
+					ids.entry(poem_id(&file, document)).or_default().push(file.clone());
+				}
+			}
+			Err(e) => problems.push(problem(&file, "not-utf8", true, format!("invalid UTF-8 at byte {}", e.utf8_error().valid_up_to()))),
+		}
+		Ok(())
+	})?;
+	for (id, files) in ids.iter().filter(|(_, files)| files.len() > 1) {
+		for file in files {
+			problems.push(problem(file, "duplicate-id", true, format!("id `{}` is shared by {}", id, files.join(", "))));
+		}
+	}
+	for link in links.iter().filter(|link| !ids.contains_key(&link.id)) {
+		problems.push(problem(&link.file, "broken-link", false, format!("{} has `see_also` id `{}`, which is no poem in the library", link.place, link.id)));
+	}
+	Ok(Report { files, problems })
+}
+
+pub fn print_report(out: &mut impl Write, report: &Report) -> io::Result<()> {
+	for p in &report.problems {
+		writeln!(out, "{}: {}: {}", p.file, if p.error { "error" } else { "warning" }, p.message)?;
+	}
+	let errors = report.errors();
+	let warnings = report.problems.len() - errors;
+	writeln!(out, "Checked {} files: {} errors, {} warnings", report.files, errors, warnings)?;
+	Ok(())
+}
@@ -0,0 +1,221 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+// A small modal editor for the text of a poem: normal mode moves around and deletes, insert
+// mode types. Columns count characters, not bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditorMode {
+	Normal,
+	Insert,
+}
+
+// What the reader should do after a key
+#[derive(Debug, PartialEq)]
+pub enum EditorAction {
+	None,
+	Save,
+	// Leave without saving; only offered once the changes were confirmed to be dropped
+	Quit,
+}
+
+#[derive(Debug)]
+pub struct Editor {
+	pub lines: Vec<String>,
+	pub row: usize,
+	pub col: usize,
+	pub mode: EditorMode,
+	// First line shown, kept so the cursor stays on screen
+	pub scroll: usize,
+	pub modified: bool,
+	// Lines and cursor before each change; a whole stay in insert mode is one change
+	undo: Vec<(Vec<String>, usize, usize)>,
+	// `d` was pressed and waits for a second `d`
+	pending_delete: bool,
+	// `q` was refused once because of unsaved changes; a second `q` drops them
+	quit_warned: bool,
+}
+
+impl Editor {
+	pub fn new(text: &str) -> Editor {
+		let mut lines: Vec<String> = text.trim_end_matches('\n').split('\n').map(str::to_string).collect();
+		if lines.is_empty() {
+			lines.push(String::new());
+		}
+		Editor { lines, row: 0, col: 0, mode: EditorMode::Normal, scroll: 0, modified: false, undo: Vec::new(), pending_delete: false, quit_warned: false }
+	}
+
+	pub fn text(&self) -> String {
+		format!("{}\n", self.lines.join("\n"))
+	}
+
+	fn line_len(&self) -> usize {
+		self.lines[self.row].chars().count()
+	}
+
+	// Byte offset of the cursor column in the current line
+	fn offset(&self, col: usize) -> usize {
+		self.lines[self.row].char_indices().nth(col).map_or(self.lines[self.row].len(), |(i, _)| i)
+	}
+
+	fn snapshot(&mut self) {
+		self.undo.push((self.lines.clone(), self.row, self.col));
+		self.modified = true;
+	}
+
+	fn clamp_col(&mut self) {
+		let max = match self.mode {
+			EditorMode::Insert => self.line_len(),
+			EditorMode::Normal => self.line_len().saturating_sub(1),
+		};
+		self.col = self.col.min(max);
+	}
+
+	fn move_to(&mut self, row: usize) {
+		self.row = row.min(self.lines.len() - 1);
+		self.clamp_col();
+	}
+
+	// Keeps the cursor row within `height` lines from `scroll`
+	pub fn scroll_to_cursor(&mut self, height: usize) {
+		if self.row < self.scroll {
+			self.scroll = self.row;
+		} else if height > 0 && self.row >= self.scroll + height {
+			self.scroll = self.row + 1 - height;
+		}
+	}
+
+	pub fn handle_key(&mut self, key: KeyEvent) -> EditorAction {
+		if key.code == KeyCode::Char('s') && key.modifiers.contains(KeyModifiers::CONTROL) {
+			return EditorAction::Save;
+		}
+		match self.mode {
+			EditorMode::Insert => self.insert_key(key.code),
+			EditorMode::Normal => return self.normal_key(key.code),
+		}
+		EditorAction::None
+	}
+
+	fn insert_key(&mut self, code: KeyCode) {
+		match code {
+			KeyCode::Esc => {
+				self.mode = EditorMode::Normal;
+				self.col = self.col.saturating_sub(1);
+				self.clamp_col();
+			},
+			KeyCode::Char(c) => {
+				let at = self.offset(self.col);
+				self.lines[self.row].insert(at, c);
+				self.col += 1;
+			},
+			KeyCode::Enter => {
+				let at = self.offset(self.col);
+				let rest = self.lines[self.row].split_off(at);
+				self.lines.insert(self.row + 1, rest);
+				self.row += 1;
+				self.col = 0;
+			},
+			KeyCode::Backspace if self.col > 0 => {
+				let at = self.offset(self.col - 1);
+				self.lines[self.row].remove(at);
+				self.col -= 1;
+			},
+			KeyCode::Backspace if self.row > 0 => {
+				let line = self.lines.remove(self.row);
+				self.row -= 1;
+				self.col = self.line_len();
+				self.lines[self.row].push_str(&line);
+			},
+			code => self.move_cursor(code),
+		}
+	}
+
+	fn normal_key(&mut self, code: KeyCode) -> EditorAction {
+		if code != KeyCode::Char('q') {
+			self.quit_warned = false;
+		}
+		if std::mem::take(&mut self.pending_delete) && code == KeyCode::Char('d') {
+			self.snapshot();
+			self.lines.remove(self.row);
+			if self.lines.is_empty() {
+				self.lines.push(String::new());
+			}
+			self.move_to(self.row);
+			return EditorAction::None;
+		}
+		match code {
+			KeyCode::Char('i') => self.start_insert(),
+			KeyCode::Char('a') => {
+				self.start_insert();
+				self.col = (self.col + 1).min(self.line_len());
+			},
+			KeyCode::Char('A') => {
+				self.start_insert();
+				self.col = self.line_len();
+			},
+			KeyCode::Char('I') => {
+				self.start_insert();
+				self.col = 0;
+			},
+			KeyCode::Char('o') | KeyCode::Char('O') => {
+				self.start_insert();
+				if code == KeyCode::Char('o') {
+					self.row += 1;
+				}
+				self.lines.insert(self.row, String::new());
+				self.col = 0;
+			},
+			KeyCode::Char('x') if self.line_len() > 0 => {
+				self.snapshot();
+				let at = self.offset(self.col);
+				self.lines[self.row].remove(at);
+				self.clamp_col();
+			},
+			KeyCode::Char('d') => self.pending_delete = true,
+			KeyCode::Char('u') => {
+				if let Some((lines, row, col)) = self.undo.pop() {
+					self.lines = lines;
+					self.row = row;
+					self.col = col;
+					self.modified = true;
+				}
+			},
+			KeyCode::Char('q') if self.modified && !self.quit_warned => self.quit_warned = true,
+			KeyCode::Char('q') => return EditorAction::Quit,
+			KeyCode::Char('h') => self.move_cursor(KeyCode::Left),
+			KeyCode::Char('j') => self.move_cursor(KeyCode::Down),
+			KeyCode::Char('k') => self.move_cursor(KeyCode::Up),
+			KeyCode::Char('l') => self.move_cursor(KeyCode::Right),
+			KeyCode::Char('0') => self.move_cursor(KeyCode::Home),
+			KeyCode::Char('$') => self.move_cursor(KeyCode::End),
+			code => self.move_cursor(code),
+		}
+		EditorAction::None
+	}
+
+	fn start_insert(&mut self) {
+		self.snapshot();
+		self.mode = EditorMode::Insert;
+	}
+
+	fn move_cursor(&mut self, code: KeyCode) {
+		match code {
+			KeyCode::Left => self.col = self.col.saturating_sub(1),
+			KeyCode::Right => {
+				self.col += 1;
+				self.clamp_col();
+			},
+			KeyCode::Up => self.move_to(self.row.saturating_sub(1)),
+			KeyCode::Down => self.move_to(self.row + 1),
+			KeyCode::Home => self.col = 0,
+			KeyCode::End => {
+				self.col = self.line_len();
+				self.clamp_col();
+			},
+			_ => {},
+		}
+	}
+
+	// Whether `q` was just refused, for the status bar to say why
+	pub fn quit_warned(&self) -> bool {
+		self.quit_warned
+	}
+}
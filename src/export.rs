@@ -0,0 +1,352 @@
+use crate::models::{Poem, Version};
+use crate::utils::is_cjk;
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ExportFormat {
+	/// Tab-separated flashcards for Anki's File → Import
+	Anki,
+	/// A XeLaTeX document typesetting each poem in a `verse` environment
+	Latex,
+	/// A Typst anthology with a section per author and a table of contents
+	Typst,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum CardStyle {
+	/// First line on the front, the whole poem on the back
+	FirstLine,
+	/// Each line on the front, the line that follows on the back
+	LinePair,
+	/// The previous stanza on the front, the next stanza on the back
+	Stanza,
+	/// One cloze note per stanza with every line hidden in turn
+	Cloze,
+}
+
+// How the poem schema's inline markdown is written in an output format
+pub struct Markup {
+	pub bold: (&'static str, &'static str),
+	pub italic: (&'static str, &'static str),
+	pub heading: (&'static str, &'static str),
+	pub escape: fn(char, &mut String),
+}
+
+pub const HTML: Markup = Markup {
+	bold: ("<b>", "</b>"),
+	italic: ("<i>", "</i>"),
+	heading: ("<b>", "</b>"),
+	escape: escape_html,
+};
+
+pub fn escape_html(c: char, out: &mut String) {
+	match c {
+		'&' => out.push_str("&amp;"),
+		'<' => out.push_str("&lt;"),
+		'>' => out.push_str("&gt;"),
+		'"' => out.push_str("&quot;"),
+		_ => out.push(c),
+	}
+}
+
+// Markup dropped altogether
+pub const PLAIN: Markup = Markup {
+	bold: ("", ""),
+	italic: ("", ""),
+	heading: ("", ""),
+	escape: keep,
+};
+
+fn keep(c: char, out: &mut String) {
+	out.push(c);
+}
+
+pub const LATEX: Markup = Markup {
+	bold: ("\\textbf{", "}"),
+	italic: ("\\emph{", "}"),
+	heading: ("\\textbf{", "}"),
+	escape: escape_latex,
+};
+
+pub fn escape_latex(c: char, out: &mut String) {
+	match c {
+		'\\' => out.push_str("\\textbackslash{}"),
+		'~' => out.push_str("\\textasciitilde{}"),
+		'^' => out.push_str("\\textasciicircum{}"),
+		'&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+			out.push('\\');
+			out.push(c);
+		},
+		// Keeps a line starting with `[` from being read as an optional argument of `\\`
+		'[' => out.push_str("{[}"),
+		_ => out.push(c),
+	}
+}
+
+pub const TYPST: Markup = Markup {
+	bold: ("*", "*"),
+	italic: ("_", "_"),
+	heading: ("#strong[", "]"),
+	escape: escape_typst,
+};
+
+pub fn escape_typst(c: char, out: &mut String) {
+	if matches!(c, '\\' | '*' | '_' | '`' | '$' | '#' | '@' | '<' | '>' | '[' | ']' | '/' | '~' | '=' | '-' | '+') {
+		out.push('\\');
+	}
+	out.push(c);
+}
+
+// Rewrites `**bold**`, `*italic*` and `## section` lines of a poem text in the given markup
+pub fn convert(text: &str, markup: &Markup) -> String {
+	let mut result = String::new();
+	let mut in_bold = false;
+	let mut in_italic = false;
+	let mut in_heading = false;
+	let mut chars = text.chars().peekable();
+	while let Some(c) = chars.next() {
+		match c {
+			'#' if chars.peek() == Some(&'#') => {
+				chars.next();
+				while chars.peek() == Some(&' ') {
+					chars.next();
+				}
+				result.push_str(markup.heading.0);
+				in_heading = true;
+			},
+			'\n' if in_heading => {
+				result.push_str(markup.heading.1);
+				result.push('\n');
+				in_heading = false;
+			},
+			'*' if chars.peek() == Some(&'*') => {
+				chars.next();
+				result.push_str(if in_bold { markup.bold.1 } else { markup.bold.0 });
+				in_bold = !in_bold;
+			},
+			'*' => {
+				result.push_str(if in_italic { markup.italic.1 } else { markup.italic.0 });
+				in_italic = !in_italic;
+			},
+			_ => (markup.escape)(c, &mut result),
+		}
+	}
+	if in_heading {
+		result.push_str(markup.heading.1);
+	}
+	result
+}
+
+// Stanzas are separated by blank lines
+pub fn stanzas(text: &str) -> Vec<Vec<&str>> {
+	let mut stanzas = vec![Vec::new()];
+	for line in text.lines() {
+		if line.trim().is_empty() {
+			if !stanzas.last().is_some_and(|s| s.is_empty()) {
+				stanzas.push(Vec::new());
+			}
+		} else if let Some(stanza) = stanzas.last_mut() {
+			stanza.push(line);
+		}
+	}
+	stanzas.retain(|s| !s.is_empty());
+	stanzas
+}
+
+// "Title — Author" as shown under each card
+fn attribution(version: &Version) -> String {
+	let mut text = String::new();
+	escape_into(version.title.as_deref().unwrap_or("Untitled"), &mut text);
+	if let Some(author) = &version.author {
+		text.push_str(" — ");
+		escape_into(author, &mut text);
+	}
+	text
+}
+
+fn escape_into(text: &str, out: &mut String) {
+	for c in text.chars() {
+		escape_html(c, out);
+	}
+}
+
+// Anki tags cannot contain spaces
+fn author_tag(version: &Version) -> String {
+	version.author.as_deref().unwrap_or("Unknown").split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+// Tab-separated notes with Anki's import headers; tags go in the last column
+pub fn anki(poems: &[&Poem], style: CardStyle) -> String {
+	let notetype = if style == CardStyle::Cloze { "Cloze" } else { "Basic" };
+	let mut out = format!("#separator:tab\n#html:true\n#notetype:{}\n#tags column:3\n", notetype);
+	for version in poems.iter().filter_map(|p| p.canonical()) {
+		let html = convert(&version.text, &HTML).replace('\t', " ");
+		let stanzas = stanzas(&html);
+		let lines: Vec<&str> = stanzas.iter().flatten().copied().collect();
+		let source = format!("<br><br><i>{}</i>", attribution(version));
+		let tag = author_tag(version);
+		let mut card = |front: String, back: String| {
+			out.push_str(&format!("{}\t{}\t{}\n", front, back, tag));
+		};
+		match style {
+			CardStyle::FirstLine => {
+				if let Some(first) = lines.first() {
+					let poem = stanzas.iter().map(|s| s.join("<br>")).collect::<Vec<_>>().join("<br><br>");
+					card(format!("{}{}", first, source), poem);
+				}
+			},
+			CardStyle::LinePair => {
+				for pair in lines.windows(2) {
+					card(format!("{}{}", pair[0], source), pair[1].to_string());
+				}
+			},
+			CardStyle::Stanza => {
+				for (i, stanza) in stanzas.iter().enumerate() {
+					let front = match i {
+						0 => "(opening stanza)".to_string(),
+						_ => stanzas[i - 1].join("<br>"),
+					};
+					card(format!("{}{}", front, source), stanza.join("<br>"));
+				}
+			},
+			CardStyle::Cloze => {
+				for stanza in &stanzas {
+					let cloze: Vec<String> = stanza.iter().enumerate().map(|(i, line)| format!("{{{{c{}::{}}}}}", i + 1, line)).collect();
+					card(format!("{}{}", cloze.join("<br>"), source), String::new());
+				}
+			},
+		}
+	}
+	out
+}
+
+// Polyglossia language for a version's language code; CJK is handled by xeCJK instead
+fn polyglossia_language(language: Option<&str>) -> Option<&'static str> {
+	match language.map(|l| l.split('-').next().unwrap_or(l)) {
+		Some("eng") | Some("en") => Some("english"),
+		Some("deu") | Some("de") => Some("german"),
+		Some("fra") | Some("fr") => Some("french"),
+		Some("rus") | Some("ru") => Some("russian"),
+		Some("bul") | Some("bg") => Some("bulgarian"),
+		Some("fas") | Some("fa") => Some("persian"),
+		_ => None,
+	}
+}
+
+fn latex_escaped(text: &str) -> String {
+	let mut out = String::new();
+	for c in text.chars() {
+		escape_latex(c, &mut out);
+	}
+	out
+}
+
+// A standalone XeLaTeX document; every poem switches to its own language so hyphenation
+// and right-to-left scripts come out right.
+pub fn latex(poems: &[&Poem]) -> String {
+	let versions: Vec<&Version> = poems.iter().filter_map(|p| p.canonical()).collect();
+	let mut languages: Vec<&str> = versions.iter().filter_map(|v| polyglossia_language(v.language.as_deref())).collect();
+	languages.sort();
+	languages.dedup();
+	let cjk = versions.iter().any(|v| v.text.chars().any(is_cjk));
+
+	let mut out = String::from("\\documentclass{article}\n\\usepackage{fontspec}\n\\usepackage{polyglossia}\n");
+	out.push_str("\\setmainlanguage{english}\n");
+	let others: Vec<&str> = languages.iter().copied().filter(|l| *l != "english").collect();
+	if !others.is_empty() {
+		out.push_str(&format!("\\setotherlanguages{{{}}}\n", others.join(",")));
+	}
+	if others.contains(&"persian") {
+		out.push_str("\\newfontfamily\\persianfont[Script=Arabic]{Amiri}\n");
+	}
+	if others.iter().any(|l| matches!(*l, "russian" | "bulgarian")) {
+		out.push_str("\\newfontfamily\\cyrillicfont{CMU Serif}\n");
+	}
+	if cjk {
+		out.push_str("\\usepackage{xeCJK}\n\\setCJKmainfont{Noto Serif CJK TC}\n");
+	}
+	out.push_str("\\usepackage{verse}\n\\begin{document}\n");
+
+	for (i, version) in versions.iter().enumerate() {
+		if i > 0 {
+			out.push_str("\n\\clearpage\n");
+		}
+		let language = polyglossia_language(version.language.as_deref()).filter(|l| *l != "english");
+		if let Some(language) = language {
+			out.push_str(&format!("\\begin{{{}}}\n", language));
+		}
+		out.push_str(&format!("\\poemtitle{{{}}}\n", latex_escaped(version.title.as_deref().unwrap_or("Untitled"))));
+		if let Some(author) = &version.author {
+			out.push_str(&format!("\\begin{{center}}\\emph{{{}}}\\end{{center}}\n", latex_escaped(author)));
+		}
+		if let Some(epigraph) = &version.epigraph {
+			let epigraph = convert(epigraph.trim_end(), &LATEX).replace('\n', "\\\\\n");
+			out.push_str(&format!("\\begin{{flushright}}\\small\n{}\n\\end{{flushright}}\n", epigraph));
+		}
+		out.push_str("\\begin{verse}\n");
+		let text = convert(&version.text, &LATEX);
+		let stanza_text: Vec<String> = stanzas(&text).iter().map(|stanza| stanza.join(" \\\\\n")).collect();
+		out.push_str(&stanza_text.join("\n\n"));
+		out.push_str("\n\\end{verse}\n");
+		if let Some(language) = language {
+			out.push_str(&format!("\\end{{{}}}\n", language));
+		}
+	}
+	out.push_str("\\end{document}\n");
+	out
+}
+
+// Typst `lang` for a version's language code; the text direction follows from it
+fn typst_language(language: Option<&str>) -> Option<&'static str> {
+	match language.map(|l| l.split('-').next().unwrap_or(l)) {
+		Some("eng") | Some("en") => Some("en"),
+		Some("deu") | Some("de") => Some("de"),
+		Some("fra") | Some("fr") => Some("fr"),
+		Some("rus") | Some("ru") => Some("ru"),
+		Some("bul") | Some("bg") => Some("bg"),
+		Some("fas") | Some("fa") => Some("fa"),
+		Some("lzh") | Some("zho") | Some("yue") | Some("zh") => Some("zh"),
+		Some("jpn") | Some("ojp") | Some("ja") => Some("ja"),
+		Some("mn") => Some("mn"),
+		_ => None,
+	}
+}
+
+fn typst_escaped(text: &str) -> String {
+	let mut out = String::new();
+	for c in text.chars() {
+		escape_typst(c, &mut out);
+	}
+	out
+}
+
+// A Typst anthology: a contents page, then one section per author with a heading per poem
+pub fn typst(poems: &[&Poem]) -> String {
+	let mut out = String::from("#set page(paper: \"a5\", margin: 2cm, numbering: \"1\")\n");
+	out.push_str("#set text(font: (\"Linux Libertine\", \"Amiri\", \"Noto Serif CJK TC\"), size: 11pt)\n");
+	out.push_str("#show heading.where(level: 1): it => { pagebreak(weak: true); it }\n");
+	out.push_str("#outline(indent: auto)\n");
+
+	let mut current_author: Option<&str> = None;
+	for version in poems.iter().filter_map(|p| p.canonical()) {
+		let author = version.author.as_deref().unwrap_or("Unknown");
+		if current_author != Some(author) {
+			out.push_str(&format!("\n= {}\n", typst_escaped(author)));
+			current_author = Some(author);
+		}
+		out.push_str(&format!("\n== {}\n\n", typst_escaped(version.title.as_deref().unwrap_or("Untitled"))));
+		out.push_str("#[\n");
+		if let Some(language) = typst_language(version.language.as_deref()) {
+			out.push_str(&format!("#set text(lang: \"{}\")\n", language));
+		}
+		if let Some(epigraph) = &version.epigraph {
+			let epigraph = convert(epigraph.trim_end(), &TYPST).replace('\n', " \\\n");
+			out.push_str(&format!("#align(end, text(size: 0.9em)[{}])\n\n", epigraph));
+		}
+		let text = convert(&version.text, &TYPST);
+		let stanza_text: Vec<String> = stanzas(&text).iter().map(|stanza| stanza.join(" \\\n")).collect();
+		out.push_str(&stanza_text.join("\n\n"));
+		out.push_str("\n]\n");
+	}
+	out
+}
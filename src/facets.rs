@@ -0,0 +1,188 @@
+use crate::app::SortOrder;
+use crate::config::{Config, FacetConfig};
+use crate::models::{Poem, Version};
+use std::rc::Rc;
+
+// Further ways of grouping poems, each browsed from its own menu entry when any poem has a
+// value for it. Unlike authors and languages they are read from every version, not only
+// the canonical one. Besides the built-in ones they come from `facets` in the config and,
+// with the `plugins` feature, from scripts.
+pub trait Facet {
+	// What is grouped by, in lower case, e.g. "translator"
+	fn name(&self) -> String;
+	// The groups the version `key` of a poem belongs to, each with a rank that orders the
+	// groups before their names do
+	fn values(&self, poem: &Poem, key: &str, version: &Version) -> Vec<(i32, String)>;
+	// Whether the groups depend on the text, which low-memory mode has to read back for them
+	fn needs_text(&self) -> bool {
+		false
+	}
+	// How the poems of a group are listed at first
+	fn sort(&self) -> Option<SortOrder> {
+		None
+	}
+	fn menu_label(&self) -> String {
+		format!("Browse by {}", self.name())
+	}
+	fn list_title(&self) -> String {
+		format!("{}s", capitalized(&self.name()))
+	}
+	fn group_title(&self, group: &str) -> String {
+		format!("{}: {}", capitalized(&self.name()), group)
+	}
+}
+
+fn capitalized(name: &str) -> String {
+	let mut chars = name.chars();
+	chars.next().map_or(String::new(), |first| first.to_uppercase().chain(chars).collect())
+}
+
+#[cfg(feature = "plugins")]
+thread_local! {
+	// Facets added by plugins, offered by every app made after
+	static REGISTERED: std::cell::RefCell<Vec<Rc<dyn Facet>>> = std::cell::RefCell::new(Vec::new());
+}
+
+#[cfg(feature = "plugins")]
+pub fn register(facet: Rc<dyn Facet>) {
+	REGISTERED.with(|registered| registered.borrow_mut().push(facet));
+}
+
+// Every facet in the order of the menu: the built-in ones, those of the config, then those
+// of plugins
+pub fn all(config: &Config) -> Vec<Rc<dyn Facet>> {
+	let mut facets: Vec<Rc<dyn Facet>> = vec![Rc::new(Translator), Rc::new(Period), Rc::new(Form)];
+	facets.extend(config.facets.iter().map(|facet| Rc::new(facet.clone()) as Rc<dyn Facet>));
+	#[cfg(feature = "plugins")]
+	REGISTERED.with(|registered| facets.extend(registered.borrow().iter().cloned()));
+	facets
+}
+
+struct Translator;
+
+impl Facet for Translator {
+	fn name(&self) -> String {
+		"translator".to_string()
+	}
+	fn values(&self, _: &Poem, _: &str, version: &Version) -> Vec<(i32, String)> {
+		version.translator.iter().map(|translator| (0, translator.clone())).collect()
+	}
+	fn group_title(&self, group: &str) -> String {
+		format!("Translated by {}", group)
+	}
+}
+
+// Decades from 1800 on, centuries before, by the year of the canonical version
+struct Period;
+
+impl Facet for Period {
+	fn name(&self) -> String {
+		"period".to_string()
+	}
+	fn values(&self, _: &Poem, key: &str, version: &Version) -> Vec<(i32, String)> {
+		if key != "canonical" {
+			return Vec::new();
+		}
+		version.year().map(period).into_iter().collect()
+	}
+	fn sort(&self) -> Option<SortOrder> {
+		Some(SortOrder::Chronological)
+	}
+	fn group_title(&self, group: &str) -> String {
+		format!("Poems from the {}", group)
+	}
+}
+
+// Compared without case, so "Sonnet" and "sonnet" are one group
+struct Form;
+
+impl Facet for Form {
+	fn name(&self) -> String {
+		"form".to_string()
+	}
+	fn values(&self, _: &Poem, _: &str, version: &Version) -> Vec<(i32, String)> {
+		version.form.iter().map(|form| (0, form.trim().to_lowercase())).collect()
+	}
+}
+
+// The decade, or before 1800 the century, a year falls in, ranked by its first year
+fn period(year: i32) -> (i32, String) {
+	if year >= 1800 {
+		let decade = year / 10 * 10;
+		return (decade, format!("{}s", decade));
+	}
+	let century = (year.abs() - 1).max(0) / 100 + 1;
+	let suffix = match (century % 10, century % 100) {
+		(1, n) if n != 11 => "st",
+		(2, n) if n != 12 => "nd",
+		(3, n) if n != 13 => "rd",
+		_ => "th",
+	};
+	if year > 0 {
+		((century - 1) * 100 + 1, format!("{}{} century", century, suffix))
+	} else {
+		(-century * 100, format!("{}{} century BC", century, suffix))
+	}
+}
+
+// Numbers ranked by their value, in groups of `step` when given
+fn number(value: i64, step: Option<i64>) -> (i32, String) {
+	match step.filter(|&step| step > 1) {
+		Some(step) => {
+			let start = value.div_euclid(step) * step;
+			(start as i32, format!("{}–{}", start, start + step - 1))
+		},
+		None => (value as i32, value.to_string()),
+	}
+}
+
+impl Facet for FacetConfig {
+	fn name(&self) -> String {
+		self.name.clone()
+	}
+	fn needs_text(&self) -> bool {
+		self.field == "first_line"
+	}
+	fn values(&self, _: &Poem, key: &str, version: &Version) -> Vec<(i32, String)> {
+		if self.canonical_only && key != "canonical" {
+			return Vec::new();
+		}
+		let text = |value: &Option<String>| value.iter().map(|value| (0, value.trim().to_string())).collect();
+		match self.field.as_str() {
+			"year" => version.year().map(|year| number(year as i64, self.step)).into_iter().collect(),
+			"first_line" => version.text.lines().map(str::trim).find(|line| !line.is_empty()).map(|line| (0, line.to_string())).into_iter().collect(),
+			"tags" => version.tags.iter().flatten().map(|tag| (0, tag.clone())).collect(),
+			"title" => text(&version.title),
+			"author" => text(&version.author),
+			"translator" => text(&version.translator),
+			"date" => text(&version.date),
+			"form" => text(&version.form),
+			"language" => text(&version.language),
+			"license" => text(&version.license),
+			"source" => text(&version.source),
+			// Fields leaves does not know, such as `meter`: text, numbers or lists of them
+			field => {
+				let value = |value: &serde_yaml::Value| match value {
+					serde_yaml::Value::String(text) => Some((0, text.trim().to_string())),
+					serde_yaml::Value::Number(n) => n.as_i64().map(|n| number(n, self.step)).or_else(|| Some((0, n.to_string()))),
+					serde_yaml::Value::Bool(b) => Some((0, b.to_string())),
+					_ => None,
+				};
+				match version.other.get(field) {
+					Some(serde_yaml::Value::Sequence(values)) => values.iter().filter_map(value).collect(),
+					Some(other) => value(other).into_iter().collect(),
+					None => Vec::new(),
+				}
+			},
+		}.into_iter().filter(|(_, name)| !name.is_empty()).collect()
+	}
+	fn sort(&self) -> Option<SortOrder> {
+		match self.sort.as_deref()? {
+			"alphabetical" => Some(SortOrder::Alphabetical),
+			"recent" => Some(SortOrder::Recent),
+			"length" => Some(SortOrder::Length),
+			"chronological" => Some(SortOrder::Chronological),
+			_ => None,
+		}
+	}
+}
@@ -0,0 +1,56 @@
+use crate::export::{convert, escape_html, stanzas, HTML};
+use crate::history::{now, DAY};
+use crate::models::{daily_poem, Poem};
+use crate::utils::date_from_days;
+
+fn escaped(text: &str) -> String {
+	let mut out = String::new();
+	for c in text.chars() {
+		escape_html(c, &mut out);
+	}
+	out
+}
+
+fn timestamp(day: u64) -> String {
+	let (year, month, date) = date_from_days(day);
+	format!("{:04}-{:02}-{:02}T00:00:00Z", year, month, date)
+}
+
+// An Atom feed with the daily poem of each of the last `days` days, newest first.
+// With a `link` (where `leaves publish` output is hosted) entries point at the poem pages.
+pub fn atom(poems: &[Poem], days: u64, link: Option<&str>) -> String {
+	let today = now() / DAY;
+	let link = link.map(|l| l.trim_end_matches('/'));
+	let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+	out.push_str("<title>leaves: poem of the day</title>\n<id>urn:leaves:daily</id>\n");
+	out.push_str(&format!("<updated>{}</updated>\n<author><name>leaves</name></author>\n", timestamp(today)));
+	if let Some(link) = link {
+		out.push_str(&format!("<link href=\"{}/\"/>\n", escaped(link)));
+	}
+	for day in (today.saturating_sub(days.saturating_sub(1))..=today).rev() {
+		let Some(poem) = daily_poem(poems, day) else { break };
+		let Some(version) = poem.canonical() else { continue };
+		let (year, month, date) = date_from_days(day);
+		let title = version.title.as_deref().unwrap_or("Untitled");
+		let author = version.author.as_deref().unwrap_or("Unknown");
+		let mut content = String::new();
+		if let Some(epigraph) = &version.epigraph {
+			content.push_str(&format!("<p><i>{}</i></p>", convert(epigraph.trim_end(), &HTML).replace('\n', "<br>")));
+		}
+		for stanza in stanzas(&convert(&version.text, &HTML)) {
+			content.push_str(&format!("<p>{}</p>", stanza.join("<br>")));
+		}
+		out.push_str("<entry>\n");
+		out.push_str(&format!("<title>{} — {}</title>\n", escaped(title), escaped(author)));
+		out.push_str(&format!("<id>tag:leaves,{:04}-{:02}-{:02}:{}</id>\n", year, month, date, escaped(poem.id())));
+		out.push_str(&format!("<updated>{}</updated>\n", timestamp(day)));
+		out.push_str(&format!("<author><name>{}</name></author>\n", escaped(author)));
+		if let Some(link) = link {
+			out.push_str(&format!("<link href=\"{}/poems/{}.html\"/>\n", escaped(link), escaped(poem.id())));
+		}
+		out.push_str(&format!("<content type=\"html\">{}</content>\n", escaped(&content)));
+		out.push_str("</entry>\n");
+	}
+	out.push_str("</feed>\n");
+	out
+}
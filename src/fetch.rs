@@ -0,0 +1,134 @@
+use crate::models::{poems_dir, write_poem, Version};
+use crate::utils::slug;
+use serde::Deserialize;
+use std::{collections::HashMap, io::{self, IsTerminal, Write}, process::Command};
+
+pub const POETRYDB: &str = "https://poetrydb.org";
+
+#[derive(Debug, Deserialize)]
+struct Found {
+	title: String,
+	author: String,
+	lines: Vec<String>,
+}
+
+fn percent_encode(text: &str) -> String {
+	let mut encoded = String::new();
+	for byte in text.bytes() {
+		if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+			encoded.push(byte as char);
+		} else {
+			encoded.push_str(&format!("%{:02X}", byte));
+		}
+	}
+	encoded
+}
+
+// PoetryDB searches by field: `/author/<a>`, `/title/<t>` or both as `/author,title/<a>;<t>`
+fn search_url(api: &str, author: Option<&str>, title: Option<&str>) -> io::Result<String> {
+	let (fields, terms) = match (author, title) {
+		(Some(author), Some(title)) => ("author,title", format!("{};{}", percent_encode(author), percent_encode(title))),
+		(Some(author), None) => ("author", percent_encode(author)),
+		(None, Some(title)) => ("title", percent_encode(title)),
+		(None, None) => return Err(io::Error::new(io::ErrorKind::InvalidInput, "give an --author, a --title or both")),
+	};
+	Ok(format!("{}/{}/{}", api.trim_end_matches('/'), fields, terms))
+}
+
+// Downloads with curl rather than linking an HTTP client into the reader
+fn search(url: &str) -> io::Result<Vec<Found>> {
+	let output = Command::new("curl").args(["-fsSL", url]).output()
+		.map_err(|e| io::Error::new(e.kind(), format!("could not run curl: {}", e)))?;
+	if !output.status.success() {
+		return Err(io::Error::other(format!("{}: {}", url, String::from_utf8_lossy(&output.stderr).trim())));
+	}
+	let value: serde_json::Value = serde_json::from_slice(&output.stdout).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+	// No matches come back as `{"status": 404, ...}` instead of an empty list
+	if !value.is_array() {
+		return Ok(Vec::new());
+	}
+	serde_json::from_value(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+// Parses answers like `all`, `3` or `1,4-6` into indices below `count`
+fn parse_choice(answer: &str, count: usize) -> Option<Vec<usize>> {
+	let answer = answer.trim();
+	if answer.is_empty() || answer == "all" {
+		return Some((0..count).collect());
+	}
+	let mut chosen = Vec::new();
+	for part in answer.split(',').map(str::trim) {
+		let (start, end) = part.split_once('-').unwrap_or((part, part));
+		let (start, end): (usize, usize) = (start.trim().parse().ok()?, end.trim().parse().ok()?);
+		if start == 0 || end < start || end > count {
+			return None;
+		}
+		chosen.extend(start - 1..end);
+	}
+	chosen.sort();
+	chosen.dedup();
+	Some(chosen)
+}
+
+// Which results to keep; asked for on a terminal, everything otherwise
+fn choose(count: usize) -> io::Result<Vec<usize>> {
+	if !io::stdin().is_terminal() {
+		return Ok((0..count).collect());
+	}
+	loop {
+		eprint!("Save which poems? (e.g. 1,3-5, all or none) [all]: ");
+		let mut answer = String::new();
+		io::stdin().read_line(&mut answer)?;
+		if answer.trim() == "none" {
+			return Ok(Vec::new());
+		}
+		match parse_choice(&answer, count) {
+			Some(chosen) => return Ok(chosen),
+			None => eprintln!("Numbers between 1 and {}, please", count),
+		}
+	}
+}
+
+// Searches PoetryDB, lists what it found and saves the chosen poems as `.poem` files.
+// Returns how many were written; poems already in the library are skipped.
+pub fn fetch(out: &mut impl Write, api: &str, author: Option<&str>, title: Option<&str>) -> io::Result<usize> {
+	let url = search_url(api, author, title)?;
+	let found = search(&url)?;
+	if found.is_empty() {
+		writeln!(out, "Nothing found")?;
+		return Ok(0);
+	}
+	for (i, poem) in found.iter().enumerate() {
+		let first_line = poem.lines.iter().find(|l| !l.trim().is_empty()).map_or("", |l| l.trim());
+		writeln!(out, "{:>3}. {} - {} ({} lines)", i + 1, poem.author, poem.title, poem.lines.len())?;
+		if first_line != poem.title.trim() {
+			writeln!(out, "     {}", first_line)?;
+		}
+	}
+	out.flush()?;
+	let mut saved = 0;
+	for i in choose(found.len())? {
+		let poem = &found[i];
+		let filename: String = format!("{}-{}", slug(&poem.author), slug(&poem.title)).chars().take(80).collect();
+		let filename = format!("{}.poem", filename.trim_end_matches('-'));
+		if poems_dir().join(&filename).exists() {
+			eprintln!("leaves: skipping {}: {} already exists", poem.title, filename);
+			continue;
+		}
+		let text: Vec<&str> = poem.lines.iter().map(|l| l.trim_end()).collect();
+		let version = Version {
+			title: Some(poem.title.clone()),
+			author: Some(poem.author.clone()),
+			// PoetryDB only holds English poetry
+			language: Some("eng".to_string()),
+			text: format!("{}\n", text.join("\n").trim_matches('\n')),
+			license: Some("public domain".to_string()),
+			source: Some(search_url(api, Some(&poem.author), Some(&poem.title))?),
+			..Default::default()
+		};
+		let written = write_poem(&filename, &HashMap::from([("canonical".to_string(), version)]))?;
+		writeln!(out, "Saved {}", written.display())?;
+		saved += 1;
+	}
+	Ok(saved)
+}
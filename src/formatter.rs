@@ -0,0 +1,79 @@
+use crate::models::{is_encrypted, is_markdown, parse_documents, poem_files, poem_id, poem_yaml, read_poem_file, rewrite_poem, Version};
+use std::{collections::HashMap, io::{self, Write}, path::PathBuf};
+
+// Unix line endings, no trailing spaces and no blank lines around the poem
+fn normalize(text: &str) -> String {
+	let text = text.replace("\r\n", "\n").replace('\r', "\n");
+	let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+	let start = lines.iter().take_while(|l| l.is_empty()).count();
+	let end = lines.len() - lines.iter().rev().take_while(|l| l.is_empty()).count();
+	lines[start..end.max(start)].join("\n")
+}
+
+// The poems of a `.poem` file with their text tidied, or None when any of them does not parse
+fn normalized_poems(content: &str) -> Option<Vec<HashMap<String, Version>>> {
+	let mut poems = Vec::new();
+	for parsed in parse_documents(content) {
+		let (mut versions, _) = parsed.ok()?;
+		for version in versions.values_mut() {
+			// A trailing newline keeps even one-line poems in `|` block style
+			version.text = format!("{}\n", normalize(&version.text));
+			if let Some(variants) = &mut version.variants {
+				for text in variants.values_mut() {
+					*text = format!("{}\n", normalize(text));
+				}
+			}
+			version.transliteration = version.transliteration.as_deref().map(normalize).filter(|t| !t.is_empty());
+			version.epigraph = version.epigraph.as_deref().map(normalize).filter(|e| !e.is_empty());
+		}
+		poems.push(versions);
+	}
+	Some(poems)
+}
+
+// Full-line comments at the top levels of the file would be lost when it is rewritten.
+// Poem text is indented deeper, so `#` headings inside it do not count.
+pub fn has_comments(content: &str) -> bool {
+	content.lines().any(|l| l.trim_start().starts_with('#') && l.len() - l.trim_start().len() <= 2)
+}
+
+// Rewrites `.poem` files (all of them, or the given ids) into the canonical layout and
+// returns how many changed. With `check` nothing is written.
+pub fn format_files(out: &mut impl Write, ids: &[String], check: bool) -> io::Result<usize> {
+	let mut files: Vec<PathBuf> = poem_files()?.into_iter()
+		.filter(|path| !is_markdown(path))
+		.filter(|path| ids.is_empty() || ids.contains(&poem_id(&path.to_string_lossy(), None)))
+		.collect();
+	files.sort();
+	if let Some(missing) = ids.iter().find(|id| !files.iter().any(|path| poem_id(&path.to_string_lossy(), None) == **id)) {
+		return Err(io::Error::new(io::ErrorKind::NotFound, format!("no poem file with id {}", missing)));
+	}
+	let mut changed = 0;
+	for path in files {
+		let content = match read_poem_file(&path) {
+			Ok(content) => content,
+			Err(e) if is_encrypted(&path.to_string_lossy()) => {
+				eprintln!("leaves: {} cannot be read, left alone: {}", path.display(), e);
+				continue;
+			},
+			Err(e) => return Err(e),
+		};
+		let Some(poems) = normalized_poems(&content) else {
+			eprintln!("leaves: {} does not parse, left alone (see `leaves doctor`)", path.display());
+			continue;
+		};
+		if poem_yaml(&poems)? == content {
+			continue;
+		}
+		if has_comments(&content) {
+			eprintln!("leaves: {} has comments that formatting would drop, left alone", path.display());
+			continue;
+		}
+		changed += 1;
+		writeln!(out, "{}", path.display())?;
+		if !check {
+			rewrite_poem(&path, &poems)?;
+		}
+	}
+	Ok(changed)
+}
@@ -0,0 +1,63 @@
+use crate::models::{check_writable, poems_dir};
+use crate::tasks;
+use std::{collections::HashMap, io, process::Command};
+
+// Runs git in the library directory and returns what it printed
+pub fn git(args: &[&str]) -> io::Result<String> {
+	let output = tasks::output(Command::new("git").arg("-C").arg(poems_dir()).args(args))
+		.map_err(|e| io::Error::new(e.kind(), format!("could not run git: {}", e)))?;
+	if !output.status.success() {
+		return Err(io::Error::other(format!("git {}: {}", args[0], String::from_utf8_lossy(&output.stderr).trim())));
+	}
+	Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Changed poem files by their name inside the library, as `M` (modified), `A` (added to the
+// index) or `?` (untracked); None when the library is not in a git repository
+pub fn status() -> Option<HashMap<String, char>> {
+	// Porcelain paths start at the top of the repository, which may be above the library
+	let prefix = git(&["rev-parse", "--show-prefix"]).ok()?;
+	let prefix = prefix.trim();
+	let output = git(&["status", "--porcelain", "-z", "--untracked-files=all", "--", "."]).ok()?;
+	let mut changed = HashMap::new();
+	let mut entries = output.split('\0');
+	while let Some(entry) = entries.next() {
+		let (Some(code), Some(path)) = (entry.get(..2), entry.get(3..)) else { continue };
+		// A rename is followed by the path it was renamed from
+		if code.starts_with('R') || code.starts_with('C') {
+			entries.next();
+		}
+		let state = match code {
+			"??" => '?',
+			code if code.contains('A') => 'A',
+			code if code.contains('D') => continue,
+			_ => 'M',
+		};
+		if let Some(name) = path.strip_prefix(prefix) {
+			changed.insert(name.to_string(), state);
+		}
+	}
+	Some(changed)
+}
+
+// Changes to a poem file since the last commit; an untracked file is shown whole
+pub fn diff(filename: &str) -> io::Result<String> {
+	let tracked = git(&["ls-files", "--error-unmatch", "--", filename]).is_ok();
+	if tracked {
+		git(&["diff", "--no-color", "HEAD", "--", filename])
+	} else {
+		// Exits with 1 whenever there are differences, which for a new file is always
+		let output = Command::new("git").arg("-C").arg(poems_dir())
+			.args(["diff", "--no-color", "--no-index", "--", "/dev/null", filename]).output()
+			.map_err(|e| io::Error::new(e.kind(), format!("could not run git: {}", e)))?;
+		Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+	}
+}
+
+// Commits a poem file as it is now, with only its changes
+pub fn commit(filename: &str, message: &str) -> io::Result<()> {
+	check_writable()?;
+	git(&["add", "--", filename])?;
+	git(&["commit", "--quiet", "-m", message, "--", filename])?;
+	Ok(())
+}
@@ -0,0 +1,69 @@
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, io, fs, path::PathBuf, time::{SystemTime, UNIX_EPOCH}};
+
+// Which poems have been opened and when, persisted between sessions
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct History {
+	// Poem filename -> unix timestamps of every time it was opened
+	#[serde(default)]
+	pub read: HashMap<String, Vec<u64>>,
+}
+
+pub fn history_path() -> PathBuf {
+	paths::or_legacy(paths::state_dir().join("history.yaml"), &[".local", "state", "leaves", "history.yaml"])
+}
+
+pub const DAY: u64 = 24 * 60 * 60;
+
+pub fn now() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+impl History {
+	pub fn load() -> io::Result<History> {
+		let path = history_path();
+		if !path.exists() {
+			return Ok(History::default());
+		}
+		let content = fs::read_to_string(&path)?;
+		serde_yaml::from_str(&content)
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+	}
+
+	pub fn save(&self) -> io::Result<()> {
+		let path = history_path();
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		let content = serde_yaml::to_string(self)
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+		fs::write(path, content)
+	}
+
+	pub fn mark_read(&mut self, filename: &str) {
+		self.read.entry(filename.to_string()).or_default().push(now());
+	}
+
+	// When the poem was last opened, as a unix timestamp
+	pub fn last_read(&self, filename: &str) -> Option<u64> {
+		self.read.get(filename).and_then(|times| times.iter().max().copied())
+	}
+
+	pub fn is_read(&self, filename: &str) -> bool {
+		self.read.contains_key(filename)
+	}
+
+	// Carries the history of a poem file, and of each poem in it, over to its new name
+	pub fn rename_file(&mut self, from: &str, to: &str) {
+		let keys: Vec<String> = self.read.keys()
+			.filter(|key| key.as_str() == from || key.strip_prefix(from).is_some_and(|rest| rest.starts_with('#')))
+			.cloned()
+			.collect();
+		for key in keys {
+			if let Some(times) = self.read.remove(&key) {
+				self.read.entry(format!("{}{}", to, &key[from.len()..])).or_default().extend(times);
+			}
+		}
+	}
+}
@@ -0,0 +1,143 @@
+use crate::config::Config;
+use crate::utils::LanguageTag;
+use std::sync::OnceLock;
+
+// Interface languages besides English, in the order of the translations in `STRINGS`
+const LANGUAGES: [&str; 8] = ["bul", "deu", "fas", "fra", "jpn", "rus", "zho-Hans", "zho-Hant"];
+
+// Interface strings by their English text: menu entries, list titles and status bar hints.
+// A string missing here stays in English.
+const STRINGS: [(&str, [&str; 8]); 89] = [
+	// Menu and list titles
+	("Menu", ["Меню", "Menü", "منو", "Menu", "メニュー", "Меню", "菜单", "選單"]),
+	("Browse by author", ["По автор", "Nach Autor", "بر اساس شاعر", "Par auteur", "作者別", "По автору", "按作者浏览", "依作者瀏覽"]),
+	("Browse by language", ["По език", "Nach Sprache", "بر اساس زبان", "Par langue", "言語別", "По языку", "按语言浏览", "依語言瀏覽"]),
+	("Browse by title", ["По заглавие", "Nach Titel", "بر اساس عنوان", "Par titre", "題名別", "По названию", "按标题浏览", "依標題瀏覽"]),
+	("Browse by folder", ["По папка", "Nach Ordner", "بر اساس پوشه", "Par dossier", "フォルダ別", "По папке", "按文件夹浏览", "依資料夾瀏覽"]),
+	("Browse by translator", ["По преводач", "Nach Übersetzer", "بر اساس مترجم", "Par traducteur", "翻訳者別", "По переводчику", "按译者浏览", "依譯者瀏覽"]),
+	("Browse by period", ["По период", "Nach Epoche", "بر اساس دوره", "Par époque", "時代別", "По эпохе", "按时期浏览", "依時期瀏覽"]),
+	("Browse by form", ["По форма", "Nach Form", "بر اساس قالب", "Par forme", "形式別", "По форме", "按体裁浏览", "依體裁瀏覽"]),
+	("Search", ["Търсене", "Suche", "جستجو", "Recherche", "検索", "Поиск", "搜索", "搜尋"]),
+	("Random poem", ["Случайно стихотворение", "Zufälliges Gedicht", "شعر تصادفی", "Poème au hasard", "ランダムな詩", "Случайное стихотворение", "随机一首", "隨機一首"]),
+	("Unread poems", ["Непрочетени", "Ungelesene Gedichte", "شعرهای نخوانده", "Poèmes non lus", "未読の詩", "Непрочитанные", "未读的诗", "未讀的詩"]),
+	("Statistics", ["Статистика", "Statistik", "آمار", "Statistiques", "統計", "Статистика", "统计", "統計"]),
+	("Load errors", ["Грешки при зареждане", "Ladefehler", "خطاهای بارگذاری", "Erreurs de chargement", "読み込みエラー", "Ошибки загрузки", "加载错误", "載入錯誤"]),
+	("Trash", ["Кошче", "Papierkorb", "سطل زباله", "Corbeille", "ゴミ箱", "Корзина", "回收站", "資源回收筒"]),
+	("Sync conflicts", ["Конфликти при синхронизация", "Synchronisationskonflikte", "تعارض‌های همگام‌سازی", "Conflits de synchronisation", "同期の競合", "Конфликты синхронизации", "同步冲突", "同步衝突"]),
+	("Sync library", ["Синхронизиране на библиотеката", "Bibliothek synchronisieren", "همگام‌سازی کتابخانه", "Synchroniser la bibliothèque", "ライブラリを同期", "Синхронизировать библиотеку", "同步诗库", "同步詩庫"]),
+	("Titles", ["Заглавия", "Titel", "عنوان‌ها", "Titres", "題名", "Названия", "标题", "標題"]),
+	("Authors", ["Автори", "Autoren", "شاعران", "Auteurs", "作者", "Авторы", "作者", "作者"]),
+	("Languages", ["Езици", "Sprachen", "زبان‌ها", "Langues", "言語", "Языки", "语言", "語言"]),
+	("Translators", ["Преводачи", "Übersetzer", "مترجمان", "Traducteurs", "翻訳者", "Переводчики", "译者", "譯者"]),
+	("Periods", ["Периоди", "Epochen", "دوره‌ها", "Époques", "時代", "Эпохи", "时期", "時期"]),
+	("Forms", ["Форми", "Formen", "قالب‌ها", "Formes", "形式", "Формы", "体裁", "體裁"]),
+	("Select Version", ["Избор на версия", "Version wählen", "انتخاب نسخه", "Choisir une version", "版を選ぶ", "Выбор версии", "选择版本", "選擇版本"]),
+	(" About this poem ", [" За стихотворението ", " Über dieses Gedicht ", " دربارهٔ این شعر ", " À propos de ce poème ", " この詩について ", " О стихотворении ", " 关于这首诗 ", " 關於這首詩 "]),
+	// Status bar hints
+	("acrostic", ["акростих", "Akrostichon", "توشیح", "acrostiche", "折句", "акростих", "藏头", "藏頭"]),
+	("back", ["назад", "zurück", "بازگشت", "retour", "戻る", "назад", "返回", "返回"]),
+	("back along link", ["назад по връзката", "Verweis zurück", "بازگشت از پیوند", "revenir du lien", "リンクを戻る", "назад по ссылке", "沿链接返回", "沿連結返回"]),
+	("back to authors", ["към авторите", "zu den Autoren", "بازگشت به شاعران", "retour aux auteurs", "作者一覧へ", "к авторам", "返回作者", "返回作者"]),
+	("back to list", ["към списъка", "zur Liste", "بازگشت به فهرست", "retour à la liste", "一覧へ", "к списку", "返回列表", "返回列表"]),
+	("back to the diff", ["към разликите", "zu den Änderungen", "بازگشت به تغییرات", "retour aux différences", "差分へ戻る", "к изменениям", "返回差异", "返回差異"]),
+	("cancel", ["отказ", "abbrechen", "لغو", "annuler", "キャンセル", "отмена", "取消", "取消"]),
+	("choose", ["избор", "wählen", "انتخاب", "choisir", "選ぶ", "выбрать", "选择", "選擇"]),
+	("close", ["затваряне", "schließen", "بستن", "fermer", "閉じる", "закрыть", "关闭", "關閉"]),
+	("commit", ["къмит", "committen", "ثبت", "valider", "コミット", "коммит", "提交", "提交"]),
+	("commit this file", ["къмит на файла", "diese Datei committen", "ثبت این فایل", "valider ce fichier", "このファイルをコミット", "закоммитить файл", "提交此文件", "提交此檔案"]),
+	("create the sample poem", ["създаване на примерно стихотворение", "Beispielgedicht anlegen", "ساختن شعر نمونه", "créer le poème d'exemple", "サンプルの詩を作る", "создать пример", "创建示例诗", "建立範例詩"]),
+	("define", ["значение", "nachschlagen", "معنی", "définir", "辞書を引く", "значение", "释义", "釋義"]),
+	("delete", ["изтриване", "löschen", "حذف", "supprimer", "削除", "удалить", "删除", "刪除"]),
+	("delete character", ["изтриване на знак", "Zeichen löschen", "حذف نویسه", "supprimer le caractère", "文字を削除", "удалить символ", "删除字符", "刪除字元"]),
+	("delete line", ["изтриване на ред", "Zeile löschen", "حذف سطر", "supprimer la ligne", "行を削除", "удалить строку", "删除行", "刪除行"]),
+	("drop the changes", ["отхвърляне на промените", "Änderungen verwerfen", "دور ریختن تغییرات", "abandonner les modifications", "変更を捨てる", "отменить изменения", "放弃修改", "放棄修改"]),
+	("edit", ["редактиране", "bearbeiten", "ویرایش", "modifier", "編集", "изменить", "编辑", "編輯"]),
+	("exit", ["изход", "verlassen", "خروج", "sortir", "終了", "выход", "退出", "退出"]),
+	("exit word cursor", ["без курсор за думи", "Wortcursor verlassen", "خروج از مکان‌نمای واژه", "quitter le curseur de mots", "単語カーソルを終える", "выйти из курсора слов", "退出词光标", "退出詞游標"]),
+	("field", ["поле", "Feld", "فیلد", "champ", "項目", "поле", "字段", "欄位"]),
+	("info", ["информация", "Info", "اطلاعات", "infos", "情報", "сведения", "信息", "資訊"]),
+	("info & links", ["информация и връзки", "Info & Verweise", "اطلاعات و پیوندها", "infos et liens", "情報とリンク", "сведения и ссылки", "信息与链接", "資訊與連結"]),
+	("insert", ["вмъкване", "einfügen", "درج", "insérer", "挿入", "вставка", "插入", "插入"]),
+	("jump to letter", ["към буква", "zum Buchstaben", "پرش به حرف", "aller à la lettre", "文字へ移動", "к букве", "跳到字母", "跳到字母"]),
+	("main menu", ["главно меню", "Hauptmenü", "منوی اصلی", "menu principal", "メインメニュー", "главное меню", "主菜单", "主選單"]),
+	("menu", ["меню", "Menü", "منو", "menu", "メニュー", "меню", "菜单", "選單"]),
+	("merge", ["сливане", "zusammenführen", "ادغام", "fusionner", "統合", "объединить", "合并", "合併"]),
+	("move", ["преместване", "bewegen", "جابه‌جایی", "déplacer", "移動", "перемещение", "移动", "移動"]),
+	("navigate poems", ["стихотворения", "Gedichte blättern", "پیمایش شعرها", "parcourir les poèmes", "詩を移動", "листать стихи", "切换诗", "切換詩"]),
+	("next unread", ["следващо непрочетено", "nächstes ungelesenes", "نخواندهٔ بعدی", "prochain non lu", "次の未読", "следующее непрочитанное", "下一首未读", "下一首未讀"]),
+	("normal mode", ["нормален режим", "Normalmodus", "حالت عادی", "mode normal", "ノーマルモード", "обычный режим", "普通模式", "一般模式"]),
+	("open", ["отваряне", "öffnen", "باز کردن", "ouvrir", "開く", "открыть", "打开", "開啟"]),
+	("open a linked poem", ["свързано стихотворение", "verknüpftes Gedicht öffnen", "باز کردن شعر پیوندشده", "ouvrir un poème lié", "リンクした詩を開く", "открыть связанное", "打开相关的诗", "開啟相關的詩"]),
+	("parent folder", ["горна папка", "übergeordneter Ordner", "پوشهٔ بالاتر", "dossier parent", "親フォルダ", "папка выше", "上级文件夹", "上層資料夾"]),
+	("pause", ["пауза", "Pause", "مکث", "pause", "一時停止", "пауза", "暂停", "暫停"]),
+	("play recording", ["пускане на записа", "Aufnahme abspielen", "پخش صدا", "écouter l'enregistrement", "録音を再生", "слушать запись", "播放录音", "播放錄音"]),
+	("quit", ["изход", "beenden", "خروج", "quitter", "終了", "выход", "退出", "退出"]),
+	("read aloud", ["четене на глас", "vorlesen", "خواندن با صدا", "lire à voix haute", "読み上げ", "читать вслух", "朗读", "朗讀"]),
+	("rename", ["преименуване", "umbenennen", "تغییر نام", "renommer", "名前を変更", "переименовать", "重命名", "重新命名"]),
+	("restore", ["възстановяване", "wiederherstellen", "بازگردانی", "restaurer", "元に戻す", "восстановить", "恢复", "還原"]),
+	("resume", ["продължаване", "fortsetzen", "ادامه", "reprendre", "再開", "продолжить", "继续", "繼續"]),
+	("save", ["запис", "speichern", "ذخیره", "enregistrer", "保存", "сохранить", "保存", "儲存"]),
+	("scroll", ["превъртане", "blättern", "پیمایش", "défiler", "スクロール", "прокрутка", "滚动", "捲動"]),
+	("scroll columns", ["превъртане на колоните", "Spalten blättern", "پیمایش ستون‌ها", "faire défiler les colonnes", "列をスクロール", "прокрутка столбцов", "滚动各列", "捲動各行"]),
+	("select", ["избор", "auswählen", "انتخاب", "sélectionner", "選択", "выбрать", "选择", "選擇"]),
+	("sort by given name", ["по собствено име", "nach Vornamen", "بر اساس نام", "trier par prénom", "名で並べる", "по имени", "按名排序", "依名排序"]),
+	("sort by surname", ["по фамилия", "nach Nachnamen", "بر اساس نام خانوادگی", "trier par nom", "姓で並べる", "по фамилии", "按姓排序", "依姓排序"]),
+	("stop", ["стоп", "anhalten", "توقف", "arrêter", "停止", "стоп", "停止", "停止"]),
+	("stop recording", ["спиране на записа", "Aufnahme anhalten", "توقف صدا", "arrêter l'enregistrement", "録音を停止", "остановить запись", "停止录音", "停止錄音"]),
+	("suggested/current name", ["предложено/текущо име", "vorgeschlagener/jetziger Name", "نام پیشنهادی/کنونی", "nom suggéré/actuel", "提案名/現在名", "предложенное/текущее имя", "建议/当前名称", "建議/目前名稱"]),
+	("switch version", ["смяна на версията", "Version wechseln", "تغییر نسخه", "changer de version", "版を切り替え", "сменить версию", "切换版本", "切換版本"]),
+	("toggle", ["превключване", "umschalten", "تغییر وضعیت", "basculer", "切り替え", "переключить", "切换", "切換"]),
+	("uncommitted changes", ["непотвърдени промени", "ungesicherte Änderungen", "تغییرات ثبت‌نشده", "modifications non validées", "未コミットの変更", "незакоммиченные изменения", "未提交的修改", "未提交的修改"]),
+	("undo", ["отмяна", "rückgängig", "واگرد", "annuler", "元に戻す", "отменить", "撤销", "復原"]),
+	("word frequency", ["честота на думите", "Worthäufigkeit", "بسامد واژه‌ها", "fréquence des mots", "語の頻度", "частота слов", "词频", "詞頻"]),
+	// Sort orders and script views, named in the status bar
+	("alphabetical", ["азбучно", "alphabetisch", "الفبایی", "alphabétique", "五十音順", "по алфавиту", "按字母", "依字母"]),
+	("by count", ["по брой", "nach Anzahl", "بر اساس شمار", "par nombre", "数順", "по количеству", "按数量", "依數量"]),
+	("recently added", ["последно добавени", "zuletzt hinzugefügt", "تازه‌افزوده", "ajoutés récemment", "最近追加", "недавно добавленные", "最近添加", "最近新增"]),
+	("by length", ["по дължина", "nach Länge", "بر اساس طول", "par longueur", "長さ順", "по длине", "按长度", "依長度"]),
+	("chronological", ["хронологично", "chronologisch", "زمانی", "chronologique", "年代順", "по времени", "按时间", "依時間"]),
+	("original", ["оригинал", "Original", "اصل", "original", "原文", "оригинал", "原文", "原文"]),
+	("original text", ["оригинален текст", "Originaltext", "متن اصلی", "texte original", "原文", "исходный текст", "原文", "原文"]),
+	("transliteration", ["транслитерация", "Umschrift", "آوانویسی", "translittération", "翻字", "транслитерация", "转写", "轉寫"]),
+	("interlinear", ["междуредово", "interlinear", "بین‌سطری", "interlinéaire", "対訳行", "подстрочно", "逐行对照", "逐行對照"]),
+];
+
+// The position in `LANGUAGES` of `interface_language` from the config, read once; None for English
+fn language_index() -> Option<usize> {
+	static INDEX: OnceLock<Option<usize>> = OnceLock::new();
+	*INDEX.get_or_init(|| {
+		let code = Config::load().ok()?.interface_language?;
+		let index = interface_language(&code);
+		if index.is_none() && !matches!(code.as_str(), "en" | "eng") {
+			tracing::warn!(interface_language = code, "no translation of the interface; it stays in English");
+		}
+		index
+	})
+}
+
+// Chinese is shown in traditional characters for `zh-Hant` and the regions that use them
+fn interface_language(code: &str) -> Option<usize> {
+	let tag = LanguageTag::parse(code)?;
+	let language = match tag.language.as_str() {
+		"bg" => "bul",
+		"de" | "ger" => "deu",
+		"fa" | "per" | "pes" => "fas",
+		"fr" | "fre" => "fra",
+		"ja" => "jpn",
+		"ru" => "rus",
+		"zh" | "chi" | "zho" | "cmn" => {
+			let traditional = tag.script.as_deref() == Some("Hant") || matches!(tag.region.as_deref(), Some("TW" | "HK" | "MO"));
+			if traditional { "zho-Hant" } else { "zho-Hans" }
+		},
+		other => other,
+	};
+	LANGUAGES.iter().position(|known| *known == language)
+}
+
+// An interface string in the interface language, or as it is when there is no translation
+pub fn tr(english: &str) -> &str {
+	let Some(index) = language_index() else {
+		return english;
+	};
+	STRINGS.iter().find(|(key, _)| *key == english).map_or(english, |(_, translations)| translations[index])
+}
@@ -0,0 +1,193 @@
+use std::{fs, path::Path};
+
+// Name of the file in the library directory listing paths to skip, in gitignore syntax
+pub const IGNORE_FILE: &str = ".leavesignore";
+
+#[derive(Debug)]
+struct Pattern {
+	glob: Vec<char>,
+	// `!pattern` brings back what an earlier pattern left out
+	negate: bool,
+	// `pattern/` only matches directories
+	dir_only: bool,
+	// A slash anywhere but at the end ties the pattern to the library directory;
+	// otherwise it matches the name at any depth
+	anchored: bool,
+}
+
+// The patterns of a `.leavesignore` file; the last one matching a path decides
+#[derive(Debug, Default)]
+pub struct Ignore {
+	patterns: Vec<Pattern>,
+}
+
+impl Ignore {
+	// Reads `.leavesignore` from `dir`; a missing file ignores nothing
+	pub fn load(dir: &Path) -> Ignore {
+		fs::read_to_string(dir.join(IGNORE_FILE)).map(|content| Ignore::parse(&content)).unwrap_or_default()
+	}
+
+	pub fn parse(content: &str) -> Ignore {
+		let mut patterns = Vec::new();
+		for line in content.lines() {
+			let line = line.trim_end();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			let (negate, line) = match line.strip_prefix('!') {
+				Some(rest) => (true, rest),
+				None => (false, line.strip_prefix('\\').unwrap_or(line)),
+			};
+			let (dir_only, line) = match line.strip_suffix('/') {
+				Some(rest) => (true, rest),
+				None => (false, line),
+			};
+			let anchored = line.contains('/');
+			let glob = line.strip_prefix('/').unwrap_or(line).chars().collect();
+			patterns.push(Pattern { glob, negate, dir_only, anchored });
+		}
+		Ignore { patterns }
+	}
+
+	// Whether `relative`, a path inside the library directory, is left out. Directories are
+	// checked before descending into them, so nothing below an ignored one is looked at.
+	pub fn ignores(&self, relative: &Path, is_dir: bool) -> bool {
+		let path: Vec<char> = relative.to_string_lossy().replace('\\', "/").chars().collect();
+		let name: Vec<char> = relative.file_name().unwrap_or_default().to_string_lossy().chars().collect();
+		let mut ignored = false;
+		for pattern in &self.patterns {
+			if pattern.dir_only && !is_dir {
+				continue;
+			}
+			let subject = if pattern.anchored { &path } else { &name };
+			if glob_match(&pattern.glob, subject) {
+				ignored = !pattern.negate;
+			}
+		}
+		ignored
+	}
+}
+
+// `*` and `?` stop at slashes, `**` crosses them and `[a-z]` is a character class
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+	match pattern.first() {
+		None => text.is_empty(),
+		Some('*') if pattern.get(1) == Some(&'*') => {
+			let rest = &pattern[2..];
+			// `a/**/b` also matches `a/b`
+			if rest.first() == Some(&'/') && glob_match(&rest[1..], text) {
+				return true;
+			}
+			(0..=text.len()).any(|i| glob_match(rest, &text[i..]))
+		}
+		Some('*') => (0..=text.len())
+			.take_while(|&i| i == 0 || text[i - 1] != '/')
+			.any(|i| glob_match(&pattern[1..], &text[i..])),
+		Some('?') => text.first().is_some_and(|&c| c != '/') && glob_match(&pattern[1..], &text[1..]),
+		Some('[') => match pattern.iter().skip(2).position(|&c| c == ']').map(|end| end + 2) {
+			Some(end) => text.first().is_some_and(|&c| c != '/' && in_class(&pattern[1..end], c)) && glob_match(&pattern[end + 1..], &text[1..]),
+			None => text.first() == Some(&'[') && glob_match(&pattern[1..], &text[1..]),
+		},
+		Some('\\') if pattern.len() > 1 => text.first() == Some(&pattern[1]) && glob_match(&pattern[2..], &text[1..]),
+		Some(&c) => text.first() == Some(&c) && glob_match(&pattern[1..], &text[1..]),
+	}
+}
+
+fn in_class(class: &[char], c: char) -> bool {
+	let (negate, class) = match class.first() {
+		Some('!') | Some('^') => (true, &class[1..]),
+		_ => (false, class),
+	};
+	let mut found = false;
+	let mut i = 0;
+	while i < class.len() {
+		if class.get(i + 1) == Some(&'-') && i + 2 < class.len() {
+			found |= (class[i]..=class[i + 2]).contains(&c);
+			i += 3;
+		} else {
+			found |= class[i] == c;
+			i += 1;
+		}
+	}
+	found != negate
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn file(ignore: &Ignore, path: &str) -> bool {
+		ignore.ignores(Path::new(path), false)
+	}
+
+	fn dir(ignore: &Ignore, path: &str) -> bool {
+		ignore.ignores(Path::new(path), true)
+	}
+
+	#[test]
+	fn double_stars_cross_directories() {
+		let ignore = Ignore::parse("drafts/**/*.poem\n**/old\n");
+		assert!(file(&ignore, "drafts/a.poem"));
+		assert!(file(&ignore, "drafts/2024/may/a.poem"));
+		assert!(!file(&ignore, "final/a.poem"));
+		assert!(dir(&ignore, "old"));
+		assert!(dir(&ignore, "rilke/elegies/old"));
+		assert!(!dir(&ignore, "rilke/older"));
+		// A single star stays inside one directory
+		let ignore = Ignore::parse("drafts/*.poem\n");
+		assert!(file(&ignore, "drafts/a.poem"));
+		assert!(!file(&ignore, "drafts/2024/a.poem"));
+	}
+
+	#[test]
+	fn the_last_matching_pattern_decides() {
+		let ignore = Ignore::parse("*.poem\n!keep.poem\n");
+		assert!(file(&ignore, "a.poem"));
+		assert!(!file(&ignore, "keep.poem"));
+		let ignore = Ignore::parse("!keep.poem\n*.poem\n");
+		assert!(file(&ignore, "keep.poem"));
+		let ignore = Ignore::parse("*.poem\n!*.poem\nsecret.poem\n");
+		assert!(!file(&ignore, "a.poem"));
+		assert!(file(&ignore, "secret.poem"));
+	}
+
+	#[test]
+	fn a_trailing_slash_only_matches_directories() {
+		let ignore = Ignore::parse("drafts/\n");
+		assert!(dir(&ignore, "drafts"));
+		assert!(dir(&ignore, "rilke/drafts"));
+		assert!(!file(&ignore, "drafts"));
+	}
+
+	#[test]
+	fn a_slash_anchors_the_pattern_to_the_library() {
+		let ignore = Ignore::parse("/notes.poem\nprivate/diary.poem\n");
+		assert!(file(&ignore, "notes.poem"));
+		assert!(!file(&ignore, "rilke/notes.poem"));
+		assert!(file(&ignore, "private/diary.poem"));
+		assert!(!file(&ignore, "old/private/diary.poem"));
+		// Without one a name matches at any depth
+		let ignore = Ignore::parse("notes.poem\n");
+		assert!(file(&ignore, "rilke/notes.poem"));
+	}
+
+	#[test]
+	fn classes_match_one_character() {
+		let ignore = Ignore::parse("draft[0-9].poem\nv[!a-c].poem\n");
+		assert!(file(&ignore, "draft1.poem"));
+		assert!(!file(&ignore, "draftx.poem"));
+		assert!(!file(&ignore, "draft10.poem"));
+		assert!(file(&ignore, "vd.poem"));
+		assert!(!file(&ignore, "vb.poem"));
+	}
+
+	#[test]
+	fn escaped_characters_are_taken_literally() {
+		let ignore = Ignore::parse("\\#notes.poem\n\\!bang.poem\nstar\\*.poem\n# a comment\n");
+		assert!(file(&ignore, "#notes.poem"));
+		assert!(file(&ignore, "!bang.poem"));
+		assert!(file(&ignore, "star*.poem"));
+		assert!(!file(&ignore, "stars.poem"));
+		assert!(!file(&ignore, "# a comment"));
+	}
+}
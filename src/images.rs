@@ -0,0 +1,193 @@
+use crate::models::poems_dir;
+use crossterm::{cursor::MoveTo, queue};
+use image::{imageops::FilterType, RgbaImage};
+use ratatui::layout::Rect;
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+	Kitty,
+	Sixel,
+}
+
+// Uses the `image_protocol` setting if given, otherwise guesses from the environment
+pub fn detect(setting: Option<&str>) -> Option<Protocol> {
+	match setting {
+		Some("kitty") => return Some(Protocol::Kitty),
+		Some("sixel") => return Some(Protocol::Sixel),
+		Some("none") => return None,
+		_ => {}
+	}
+	let term = std::env::var("TERM").unwrap_or_default();
+	let term_program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+	if std::env::var_os("KITTY_WINDOW_ID").is_some() || term == "xterm-kitty" || term == "xterm-ghostty"
+		|| matches!(term_program.as_str(), "WezTerm" | "ghostty") {
+		Some(Protocol::Kitty)
+	} else if term.contains("sixel") || term.starts_with("foot") || term == "mlterm" {
+		Some(Protocol::Sixel)
+	} else {
+		None
+	}
+}
+
+// Pixel size of a terminal cell, falling back to a common 8x16
+fn cell_size() -> (u32, u32) {
+	match crossterm::terminal::window_size() {
+		Ok(size) if size.width > 0 && size.columns > 0 && size.rows > 0 => {
+			((size.width / size.columns) as u32, (size.height / size.rows) as u32)
+		}
+		_ => (8, 16),
+	}
+}
+
+// Draws poem illustrations, keeping the last encoded image so redraws are cheap
+pub struct Renderer {
+	pub protocol: Option<Protocol>,
+	// Image path and area of the last encoded image, with its escape sequence
+	cache: Option<(String, Rect, Vec<u8>)>,
+	shown: bool,
+}
+
+impl Renderer {
+	pub fn new(protocol: Option<Protocol>) -> Renderer {
+		Renderer { protocol, cache: None, shown: false }
+	}
+
+	// Draws `image` fitted into `area`, or removes the previous one when there is nothing to show.
+	// Returns true when the screen has to be repainted because an image went away.
+	pub fn update(&mut self, out: &mut impl Write, target: Option<(&str, Rect)>) -> io::Result<bool> {
+		let Some(protocol) = self.protocol else { return Ok(false) };
+		let Some((image, area)) = target else {
+			if !self.shown {
+				return Ok(false);
+			}
+			self.shown = false;
+			if protocol == Protocol::Kitty {
+				out.write_all(b"\x1b_Ga=d,q=2\x1b\\")?;
+				out.flush()?;
+			}
+			return Ok(true);
+		};
+		let cached = matches!(&self.cache, Some((path, rect, _)) if path == image && *rect == area);
+		if !cached {
+			// Remember failures too, so a broken file is not decoded on every frame
+			self.cache = Some((image.to_string(), area, Vec::new()));
+			let pixels = load(image, area)?;
+			let encoded = match protocol {
+				Protocol::Kitty => kitty(&pixels),
+				Protocol::Sixel => sixel(&pixels),
+			};
+			self.cache = Some((image.to_string(), area, encoded));
+		}
+		if let Some((_, _, encoded)) = &self.cache {
+			if !encoded.is_empty() {
+				queue!(out, MoveTo(area.x, area.y))?;
+				out.write_all(encoded)?;
+				out.flush()?;
+				self.shown = true;
+			}
+		}
+		Ok(false)
+	}
+}
+
+// Images are given relative to the poetry directory unless absolute
+fn load(image: &str, area: Rect) -> io::Result<RgbaImage> {
+	let path = poems_dir().join(image);
+	let decoded = image::open(&path)
+		.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?;
+	let (cell_width, cell_height) = cell_size();
+	Ok(decoded.resize(area.width as u32 * cell_width, area.height as u32 * cell_height, FilterType::Triangle).to_rgba8())
+}
+
+fn base64(data: &[u8]) -> String {
+	const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+	let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+	for chunk in data.chunks(3) {
+		let n = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+		for i in 0..4 {
+			if i <= chunk.len() {
+				encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+			} else {
+				encoded.push('=');
+			}
+		}
+	}
+	encoded
+}
+
+// Kitty graphics protocol: raw RGBA sent in 4096-byte chunks, drawn beneath the text layer
+fn kitty(pixels: &RgbaImage) -> Vec<u8> {
+	let payload = base64(pixels.as_raw());
+	let chunks: Vec<&[u8]> = payload.as_bytes().chunks(4096).collect();
+	let mut out = Vec::with_capacity(payload.len() + chunks.len() * 32);
+	for (i, chunk) in chunks.iter().enumerate() {
+		let more = (i + 1 < chunks.len()) as u8;
+		if i == 0 {
+			let _ = write!(out, "\x1b_Ga=T,i=1,f=32,s={},v={},z=-1,C=1,q=2,m={};", pixels.width(), pixels.height(), more);
+		} else {
+			let _ = write!(out, "\x1b_Gm={};", more);
+		}
+		out.extend_from_slice(chunk);
+		out.extend_from_slice(b"\x1b\\");
+	}
+	out
+}
+
+// Sixel: pixels quantized to a 6x6x6 colour cube, transparent pixels left unset
+fn sixel(pixels: &RgbaImage) -> Vec<u8> {
+	let (width, height) = pixels.dimensions();
+	let level = |v: u8| (v as u32 * 5 + 127) / 255;
+	let indices: Vec<Option<usize>> = pixels.pixels()
+		.map(|p| (p[3] >= 128).then(|| (level(p[0]) * 36 + level(p[1]) * 6 + level(p[2])) as usize))
+		.collect();
+	let colour = |x: u32, y: u32| indices[(y * width + x) as usize];
+	let mut out = Vec::new();
+	let _ = write!(out, "\x1bP0;1q\"1;1;{};{}", width, height);
+	for i in 0..216 {
+		let _ = write!(out, "#{};2;{};{};{}", i, i / 36 * 20, i / 6 % 6 * 20, i % 6 * 20);
+	}
+	for band in (0..height).step_by(6) {
+		let rows = 6.min(height - band);
+		let mut used = [false; 216];
+		for y in band..band + rows {
+			for x in 0..width {
+				if let Some(c) = colour(x, y) {
+					used[c] = true;
+				}
+			}
+		}
+		for c in (0..216).filter(|&c| used[c]) {
+			let _ = write!(out, "#{}", c);
+			let mut run: Option<(u8, usize)> = None;
+			for x in 0..width {
+				let bits = (0..rows).filter(|&dy| colour(x, band + dy) == Some(c)).fold(0u8, |acc, dy| acc | 1 << dy);
+				let ch = b'?' + bits;
+				run = match run {
+					Some((prev, n)) if prev == ch => Some((prev, n + 1)),
+					Some((prev, n)) => {
+						push_run(&mut out, prev, n);
+						Some((ch, 1))
+					}
+					None => Some((ch, 1)),
+				};
+			}
+			if let Some((prev, n)) = run {
+				push_run(&mut out, prev, n);
+			}
+			out.push(b'$');
+		}
+		out.push(b'-');
+	}
+	out.extend_from_slice(b"\x1b\\");
+	out
+}
+
+fn push_run(out: &mut Vec<u8>, ch: u8, n: usize) {
+	if n > 3 {
+		let _ = write!(out, "!{}", n);
+		out.push(ch);
+	} else {
+		out.extend(std::iter::repeat_n(ch, n));
+	}
+}
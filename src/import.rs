@@ -0,0 +1,126 @@
+use crate::models::{parse_markdown, poems_dir, write_poem, Version};
+use clap::ValueEnum;
+use std::{collections::HashMap, fs, io::{self, IsTerminal, Write}, path::{Path, PathBuf}};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ImportFormat {
+	/// Plain text with the title on the first line
+	Text,
+	/// Markdown with YAML frontmatter
+	Markdown,
+}
+
+// Files with the given extension among `paths`, looking through directories recursively
+fn collect_files(paths: &[PathBuf], extension: &str) -> io::Result<Vec<PathBuf>> {
+	let mut files = Vec::new();
+	for path in paths {
+		if path.is_dir() {
+			let mut children = Vec::new();
+			for entry in fs::read_dir(path)? {
+				children.push(entry?.path());
+			}
+			files.extend(collect_files(&children, extension)?);
+		} else if path.extension().and_then(|s| s.to_str()) == Some(extension) {
+			files.push(path.clone());
+		}
+	}
+	files.sort();
+	Ok(files)
+}
+
+// Imported poems are named after their source file
+fn target(source: &Path) -> String {
+	format!("{}.poem", source.file_stem().unwrap_or_default().to_string_lossy())
+}
+
+fn skip_existing(source: &Path) {
+	eprintln!("leaves: skipping {}: {} already exists", source.display(), target(source));
+}
+
+// Saves an imported poem, skipping ones already in the library
+fn save(out: &mut impl Write, source: &Path, versions: &HashMap<String, Version>) -> io::Result<bool> {
+	match write_poem(&target(source), versions) {
+		Ok(written) => {
+			writeln!(out, "{} -> {}", source.display(), written.display())?;
+			Ok(true)
+		}
+		Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+			skip_existing(source);
+			Ok(false)
+		}
+		Err(e) => Err(e),
+	}
+}
+
+// Converts markdown poems into `.poem` files and returns how many were written
+pub fn markdown(out: &mut impl Write, paths: &[PathBuf]) -> io::Result<usize> {
+	let mut imported = 0;
+	for path in collect_files(paths, "md")? {
+		let Some(versions) = parse_markdown(&fs::read_to_string(&path)?) else {
+			eprintln!("leaves: skipping {}: no poem text or unreadable frontmatter", path.display());
+			continue;
+		};
+		if save(out, &path, &versions)? {
+			imported += 1;
+		}
+	}
+	Ok(imported)
+}
+
+// The first non-blank line as the title and everything after it as the text
+fn parse_text(content: &str) -> Option<(String, String)> {
+	let mut lines = content.lines().map(str::trim_end).skip_while(|l| l.is_empty());
+	let title = lines.next()?.trim().to_string();
+	let mut body: Vec<&str> = lines.skip_while(|l| l.is_empty()).collect();
+	while body.last().is_some_and(|l| l.is_empty()) {
+		body.pop();
+	}
+	if body.is_empty() {
+		return None;
+	}
+	Some((title, format!("{}\n", body.join("\n"))))
+}
+
+// Asks for the language code of a poem, offering `default`; without a terminal the default is taken
+fn confirm_language(source: &Path, title: &str, default: &str) -> io::Result<String> {
+	if !io::stdin().is_terminal() {
+		return Ok(default.to_string());
+	}
+	eprint!("Language of \"{}\" ({}) [{}]: ", title, source.display(), default);
+	let mut answer = String::new();
+	io::stdin().read_line(&mut answer)?;
+	let answer = answer.trim();
+	Ok(if answer.is_empty() { default } else { answer }.to_string())
+}
+
+// Converts plain-text poems into `.poem` files and returns how many were written. The author
+// is `author` or else the name of the directory holding the file. Unless `language` is given,
+// each poem's language is asked for, offering the previous answer.
+pub fn text(out: &mut impl Write, paths: &[PathBuf], author: Option<&str>, language: Option<&str>) -> io::Result<usize> {
+	let mut imported = 0;
+	let mut last_language = language.unwrap_or("eng").to_string();
+	for path in collect_files(paths, "txt")? {
+		if poems_dir().join(target(&path)).exists() {
+			skip_existing(&path);
+			continue;
+		}
+		let Some((title, text)) = parse_text(&fs::read_to_string(&path)?) else {
+			eprintln!("leaves: skipping {}: needs a title line followed by the poem", path.display());
+			continue;
+		};
+		let author = author.map(str::to_string).or_else(|| {
+			let directory = path.canonicalize().ok()?.parent()?.file_name()?.to_string_lossy().replace('_', " ");
+			Some(directory)
+		});
+		let language = match language {
+			Some(language) => language.to_string(),
+			None => confirm_language(&path, &title, &last_language)?,
+		};
+		last_language = language.clone();
+		let version = Version { title: Some(title), author, language: Some(language), text, ..Default::default() };
+		if save(out, &path, &HashMap::from([("canonical".to_string(), version)]))? {
+			imported += 1;
+		}
+	}
+	Ok(imported)
+}
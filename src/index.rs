@@ -0,0 +1,155 @@
+use crate::config::Config;
+use crate::models::{drop_texts, is_archive, is_encrypted, library_name, plain_content, poem_files, poem_from, poems_dir, push_library_file, with_counts, LibraryFile, LoadError, Poem, Version};
+use crate::paths;
+use rusqlite::{Connection, OptionalExtension, Transaction};
+use std::{collections::{HashMap, HashSet}, fs, io, path::PathBuf, time::{SystemTime, UNIX_EPOCH}};
+
+// An SQLite copy of the library for large collections (`index` in the config): the parsed
+// poems of each file as of its modification time, so unchanged files are neither read nor
+// parsed again, and their titles, authors and text in a full-text table for `leaves search`.
+// The files stay what counts; the index is brought up to date with them on every load.
+
+// Raised whenever what is stored changes, which has the index built again
+const SCHEMA: i32 = 1;
+
+pub fn index_path() -> PathBuf {
+	paths::cache_dir().join("index.sqlite")
+}
+
+// Whether poems are loaded and searched through the index; an archive is read whole anyway
+pub fn in_use() -> io::Result<bool> {
+	Ok(Config::load()?.index && !is_archive(&poems_dir()))
+}
+
+fn sql_error(e: rusqlite::Error) -> io::Error {
+	io::Error::other(format!("library index: {}", e))
+}
+
+// Opens the index, starting it afresh when it was made by another version of leaves or for
+// another library
+fn open() -> io::Result<Connection> {
+	let path = index_path();
+	if let Some(parent) = path.parent() {
+		fs::create_dir_all(parent)?;
+	}
+	let conn = Connection::open(&path).map_err(sql_error)?;
+	let library = poems_dir().to_string_lossy().into_owned();
+	let schema: i32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).map_err(sql_error)?;
+	let indexed: Option<String> = if schema == SCHEMA {
+		conn.query_row("SELECT value FROM meta WHERE key = 'library'", [], |row| row.get(0)).optional().map_err(sql_error)?
+	} else {
+		None
+	};
+	if indexed.as_deref() != Some(library.as_str()) {
+		conn.execute_batch(&format!("
+			DROP TABLE IF EXISTS meta;
+			DROP TABLE IF EXISTS files;
+			DROP TABLE IF EXISTS texts;
+			CREATE TABLE meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+			CREATE TABLE files (name TEXT PRIMARY KEY, modified INTEGER NOT NULL, poems TEXT NOT NULL);
+			CREATE VIRTUAL TABLE texts USING fts5(file UNINDEXED, id UNINDEXED, title, author, text, tokenize = 'unicode61 remove_diacritics 2');
+			PRAGMA user_version = {};
+		", SCHEMA)).map_err(sql_error)?;
+		conn.execute("INSERT INTO meta VALUES ('library', ?1)", [&library]).map_err(sql_error)?;
+	}
+	Ok(conn)
+}
+
+// Modification times are compared to the nanosecond, so a file saved twice in a second is
+// still read again
+fn stamp(modified: SystemTime) -> i64 {
+	modified.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as i64)
+}
+
+// The poems stored for a file, or None when they no longer read back, e.g. after a change to
+// `Version`, and the file has to be parsed again
+fn stored_poems(tx: &Transaction, name: &str, modified: Option<SystemTime>) -> io::Result<Option<Vec<Poem>>> {
+	let json: String = tx.prepare_cached("SELECT poems FROM files WHERE name = ?1").map_err(sql_error)?
+		.query_row([name], |row| row.get(0)).map_err(sql_error)?;
+	let Ok(documents) = serde_json::from_str::<Vec<HashMap<String, Version>>>(&json) else { return Ok(None) };
+	let several = documents.len() > 1;
+	Ok(Some(documents.into_iter().enumerate().map(|(i, versions)| {
+		poem_from(name, several.then_some(i + 1), with_counts(versions), modified)
+	}).collect()))
+}
+
+// Replaces what is stored for a file with its poems; a file that did not parse whole is left
+// out, so its errors are found again next time
+fn store(tx: &Transaction, name: &str, modified: i64, poems: Option<&[Poem]>) -> io::Result<()> {
+	tx.prepare_cached("DELETE FROM files WHERE name = ?1").map_err(sql_error)?.execute([name]).map_err(sql_error)?;
+	tx.prepare_cached("DELETE FROM texts WHERE file = ?1").map_err(sql_error)?.execute([name]).map_err(sql_error)?;
+	let Some(poems) = poems else { return Ok(()) };
+	let documents: Vec<&HashMap<String, Version>> = poems.iter().map(|p| &p.versions).collect();
+	// Fields kept from the YAML that JSON cannot hold, such as keys that are not strings
+	let Ok(json) = serde_json::to_string(&documents) else { return Ok(()) };
+	tx.prepare_cached("INSERT INTO files VALUES (?1, ?2, ?3)").map_err(sql_error)?
+		.execute(rusqlite::params![name, modified, json]).map_err(sql_error)?;
+	let mut insert = tx.prepare_cached("INSERT INTO texts VALUES (?1, ?2, ?3, ?4, ?5)").map_err(sql_error)?;
+	for poem in poems {
+		for version in poem.versions.values() {
+			insert.execute(rusqlite::params![name, poem.id(), version.title, version.author, version.text]).map_err(sql_error)?;
+		}
+	}
+	Ok(())
+}
+
+// Loads the library through the index: a file unchanged since it was indexed comes from
+// there, any other is read, parsed and indexed, and files gone from the library are dropped.
+// Encrypted files are always read, as storing them would leave their text in the clear.
+pub fn load(poems: &mut Vec<Poem>, errors: &mut Vec<LoadError>) -> io::Result<()> {
+	let mut conn = open()?;
+	let tx = conn.transaction().map_err(sql_error)?;
+	let mut indexed: HashMap<String, i64> = tx.prepare("SELECT name, modified FROM files").map_err(sql_error)?
+		.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).map_err(sql_error)?
+		.collect::<Result<_, _>>().map_err(sql_error)?;
+	let root = poems_dir();
+	let mut files = poem_files()?;
+	files.sort();
+	let (mut reused, mut read) = (0, 0);
+	for path in files {
+		let name = library_name(&root, &path);
+		let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+		let previous = indexed.remove(&name);
+		if let (Some(previous), Some(modified)) = (previous, modified) {
+			if previous == stamp(modified) {
+				if let Some(stored) = stored_poems(&tx, &name, Some(modified))? {
+					let start = poems.len();
+					poems.extend(stored);
+					drop_texts(&mut poems[start..]);
+					reused += 1;
+					continue;
+				}
+			}
+		}
+		read += 1;
+		let content = fs::read(&path).and_then(|bytes| plain_content(&name, bytes));
+		let (start, errors_before) = (poems.len(), errors.len());
+		push_library_file(LibraryFile { name: name.clone(), content, modified }, poems, errors)?;
+		if let Some(modified) = modified.filter(|_| !is_encrypted(&name)) {
+			let whole = errors.len() == errors_before;
+			store(&tx, &name, stamp(modified), whole.then(|| &poems[start..]))?;
+		}
+		drop_texts(&mut poems[start..]);
+	}
+	for name in indexed.keys() {
+		store(&tx, name, 0, None)?;
+	}
+	tx.commit().map_err(sql_error)?;
+	tracing::info!(reused, read, dropped = indexed.len(), "loaded library through the index");
+	Ok(())
+}
+
+// Ids of the poems with a version whose title, author or text has words starting with every
+// word of `query`
+pub fn search(query: &str) -> io::Result<HashSet<String>> {
+	// Each word quoted, so what FTS would read as syntax is looked for as it is
+	let terms: Vec<String> = query.split_whitespace().map(|word| format!("\"{}\"*", word.replace('"', "\"\""))).collect();
+	if terms.is_empty() {
+		return Ok(HashSet::new());
+	}
+	let conn = open()?;
+	let mut statement = conn.prepare("SELECT DISTINCT id FROM texts WHERE texts MATCH ?1").map_err(sql_error)?;
+	let ids = statement.query_map([terms.join(" ")], |row| row.get(0)).map_err(sql_error)?
+		.collect::<Result<_, _>>().map_err(sql_error)?;
+	Ok(ids)
+}
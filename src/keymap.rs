@@ -0,0 +1,367 @@
+use crate::app::{App, AppMode, Start};
+use crate::config::Config;
+use crate::history::History;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+
+// What a key asks for, whatever key it was: the reader loop maps each key to an action here
+// and hands it to `App::handle_action`, or carries it out itself when it rewrites the library
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+	Quit,
+	// Typing into the search, the rename and commit prompts and the metadata form
+	Type(char),
+	DeleteChar,
+	// A step through the list, menu or form on screen; in the viewer, a line of the poem
+	Down,
+	Up,
+	// Opens what is selected in a list, the menu or the search
+	Select,
+	// One level up: from a poem to its list, from a list to the menu
+	Back,
+	// Leaves a popup, a prompt or the word cursor
+	Close,
+	Menu,
+	Search,
+	CycleSort,
+	NextPoem,
+	PreviousPoem,
+	NextUnread,
+	// The columns of a vertical poem too wide for the screen
+	ScrollLeft,
+	ScrollRight,
+	ShowInfo,
+	// The n-th `see_also` link, from 0
+	FollowLink(usize),
+	ChooseVersion,
+	NextVersion,
+	PreviousVersion,
+	ToggleAcrostic,
+	CycleScriptView,
+	CycleTextVariant,
+	ToggleWordCursor,
+	// By lines and words in reading order
+	MoveWordCursor(isize, isize),
+	LookUpWord,
+	ToggleRecording,
+	#[cfg(feature = "tts")]
+	ToggleSpeech,
+	#[cfg(feature = "tts")]
+	PauseSpeech,
+	// Opens the poem file in the program the system has for it
+	OpenFile,
+	StartEditing,
+	StartMetadataEdit,
+	StartRename,
+	ToggleRenameSuggestion,
+	FinishRename,
+	StartDelete,
+	MarkForMerge,
+	ShowGitDiff,
+	StartCommit,
+	Commit,
+	ToggleAuthorSort,
+	AnalyseAuthor,
+	JumpToTitleGroup(char),
+	// Carried out by the reader loop, which loads the library again afterwards
+	CreateSamplePoem,
+	AddTranslation,
+	Delete,
+	Merge,
+	Restore,
+	SaveMetadata,
+	// A key for the built-in editor, which has keys of its own
+	Editor(KeyEvent),
+	// A command of the `keybind` setting, by its key
+	RunCommand(char),
+	// Stops the work running in the background
+	CancelTask,
+}
+
+// Prompts and forms take every character typed
+fn typed(key: KeyEvent) -> Option<char> {
+	match key.code {
+		KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => Some(c),
+		_ => None,
+	}
+}
+
+// The action of `key` in the state the app is in, None when it does nothing there
+pub fn action(app: &App, key: KeyEvent) -> Option<Action> {
+	let control = key.modifiers.contains(KeyModifiers::CONTROL);
+	// The library may change under a running task, so nothing else is done until it ends
+	if app.task.is_some() {
+		return (key.code == KeyCode::Esc).then_some(Action::CancelTask);
+	}
+	match app.mode {
+		// Nothing else works without poems
+		AppMode::Onboarding => return match key.code {
+			KeyCode::Char('q') => Some(Action::Quit),
+			KeyCode::Char('c') => Some(Action::CreateSamplePoem),
+			_ => None,
+		},
+		AppMode::Rename => return typed(key).map(Action::Type).or(match key.code {
+			KeyCode::Backspace => Some(Action::DeleteChar),
+			KeyCode::Tab => Some(Action::ToggleRenameSuggestion),
+			KeyCode::Enter => Some(Action::FinishRename),
+			KeyCode::Esc => Some(Action::Close),
+			_ => None,
+		}),
+		AppMode::Editing if app.editor.is_some() => return Some(Action::Editor(key)),
+		AppMode::GitCommit => return typed(key).map(Action::Type).or(match key.code {
+			KeyCode::Backspace => Some(Action::DeleteChar),
+			KeyCode::Enter => Some(Action::Commit),
+			KeyCode::Esc => Some(Action::Close),
+			_ => None,
+		}),
+		AppMode::EditMetadata => return match key.code {
+			KeyCode::Enter => Some(Action::SaveMetadata),
+			KeyCode::Esc => Some(Action::Close),
+			KeyCode::Down | KeyCode::Tab => Some(Action::Down),
+			KeyCode::Up | KeyCode::BackTab => Some(Action::Up),
+			KeyCode::Backspace => Some(Action::DeleteChar),
+			_ => typed(key).map(Action::Type),
+		},
+		AppMode::Search => return typed(key).map(Action::Type).or(match key.code {
+			KeyCode::Backspace => Some(Action::DeleteChar),
+			KeyCode::Esc => Some(Action::Close),
+			KeyCode::Enter => Some(Action::Select),
+			KeyCode::Up => Some(Action::Up),
+			KeyCode::Down => Some(Action::Down),
+			_ => None,
+		}),
+		_ => {}
+	}
+	let mode = &app.mode;
+	let viewing = *mode == AppMode::Viewing;
+	let word_cursor = viewing && app.word_cursor.is_some();
+	let several_versions = viewing && app.poems[app.current_poem].versions.len() > 1;
+	let action = match key.code {
+		KeyCode::Char('q') => Action::Quit,
+		KeyCode::Esc | KeyCode::Char('i') if *mode == AppMode::Metadata => Action::Close,
+		KeyCode::Char('i') if viewing => Action::ShowInfo,
+		KeyCode::Char(c @ '1'..='9') if *mode == AppMode::Metadata => Action::FollowLink(c as usize - '1' as usize),
+		KeyCode::Char('M') if viewing => Action::MarkForMerge,
+		KeyCode::Char('R') if viewing => Action::StartRename,
+		KeyCode::Char('D') if viewing => Action::StartDelete,
+		KeyCode::Char('T') if viewing => Action::AddTranslation,
+		KeyCode::Char('E') if viewing => Action::StartEditing,
+		KeyCode::Char('G') if viewing => Action::ShowGitDiff,
+		KeyCode::Char('c') if *mode == AppMode::GitDiff => Action::StartCommit,
+		KeyCode::Esc | KeyCode::Backspace | KeyCode::Char('G') if *mode == AppMode::GitDiff => Action::Close,
+		// Ctrl+m only comes apart from Enter in terminals with the kitty keyboard protocol
+		KeyCode::Char('m') if control && viewing => Action::StartMetadataEdit,
+		KeyCode::Char('I') if viewing => Action::StartMetadataEdit,
+		KeyCode::Char('y') if *mode == AppMode::ConfirmDelete => Action::Delete,
+		KeyCode::Char('y') if *mode == AppMode::ConfirmMerge => Action::Merge,
+		KeyCode::Esc | KeyCode::Backspace | KeyCode::Char('n') if matches!(mode, AppMode::ConfirmDelete | AppMode::ConfirmMerge) => Action::Close,
+		KeyCode::Esc if matches!(mode, AppMode::VersionSelect | AppMode::Definition) => Action::Close,
+		KeyCode::Esc | KeyCode::Backspace if *mode == AppMode::CommandOutput => Action::Close,
+		KeyCode::Esc if word_cursor => Action::Close,
+		KeyCode::Char('c') if viewing => Action::ToggleWordCursor,
+		KeyCode::Char('r') if viewing => Action::ToggleRecording,
+		#[cfg(feature = "tts")]
+		KeyCode::Char('p') if viewing => Action::ToggleSpeech,
+		#[cfg(feature = "tts")]
+		KeyCode::Char('P') if viewing => Action::PauseSpeech,
+		KeyCode::Char('d') if viewing => Action::LookUpWord,
+		// Words are counted in reading order, which in RTL text runs to the left
+		KeyCode::Right if word_cursor => Action::MoveWordCursor(0, if app.shows_rtl() { -1 } else { 1 }),
+		KeyCode::Left if word_cursor => Action::MoveWordCursor(0, if app.shows_rtl() { 1 } else { -1 }),
+		KeyCode::Down | KeyCode::Char('j') if word_cursor => Action::MoveWordCursor(1, 0),
+		KeyCode::Up | KeyCode::Char('k') if word_cursor => Action::MoveWordCursor(-1, 0),
+		KeyCode::Char('/') => Action::Search,
+		KeyCode::Backspace => Action::Back,
+		KeyCode::Char('n') if *mode == AppMode::AuthorList => Action::ToggleAuthorSort,
+		KeyCode::Char('w') if *mode == AppMode::AuthorList => Action::AnalyseAuthor,
+		KeyCode::Char('o') => Action::CycleSort,
+		KeyCode::Char('u') if viewing => Action::NextUnread,
+		KeyCode::Char('a') if viewing => Action::ToggleAcrostic,
+		KeyCode::Char('t') if viewing => Action::CycleScriptView,
+		KeyCode::Char('x') if viewing => Action::CycleTextVariant,
+		KeyCode::Char(c) if c.is_uppercase() && *mode == AppMode::TitleList => Action::JumpToTitleGroup(c),
+		KeyCode::Char('m') => Action::Menu,
+		KeyCode::Char('s') if viewing => Action::ChooseVersion,
+		KeyCode::Char('v') if several_versions => Action::NextVersion,
+		KeyCode::Char('V') if several_versions => Action::PreviousVersion,
+		KeyCode::Char('h') if viewing && app.shows_vertical() => Action::ScrollLeft,
+		KeyCode::Char('l') if viewing && app.shows_vertical() => Action::ScrollRight,
+		KeyCode::Right if viewing => Action::NextPoem,
+		KeyCode::Left if viewing => Action::PreviousPoem,
+		KeyCode::Down | KeyCode::Char('j') => Action::Down,
+		KeyCode::Up | KeyCode::Char('k') => Action::Up,
+		KeyCode::Char('e') if control && viewing => Action::OpenFile,
+		KeyCode::Enter if *mode == AppMode::Trash => Action::Restore,
+		KeyCode::Enter => Action::Select,
+		KeyCode::Char(c) if viewing && !control && app.commands.contains_key(&c) => Action::RunCommand(c),
+		_ => return None,
+	};
+	Some(action)
+}
+
+// The `keybind` keys that the viewer keeps for itself with some poem or other, so that their
+// commands never run there: what `action` makes of each of them on every demo poem
+pub fn taken_by_viewer(commands: &HashMap<char, String>) -> Vec<char> {
+	let mut app = App::new(crate::models::demo_poems(), &Config::default(), History::default());
+	app.save_history = false;
+	app.commands = commands.clone();
+	let ids: Vec<String> = app.poems.iter().map(|poem| poem.id().to_string()).collect();
+	let mut taken = Vec::new();
+	for id in ids {
+		if app.start_at(Start::Poem(id)).is_err() {
+			continue;
+		}
+		for &c in commands.keys() {
+			if action(&app, KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)) != Some(Action::RunCommand(c)) && !taken.contains(&c) {
+				taken.push(c);
+			}
+		}
+	}
+	taken.sort();
+	taken
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn app_at(id: &str) -> App {
+		let mut app = App::new(crate::models::demo_poems(), &Config::default(), History::default());
+		app.save_history = false;
+		app.start_at(Start::Poem(id.to_string())).unwrap();
+		app
+	}
+
+	fn key(code: KeyCode) -> KeyEvent {
+		KeyEvent::new(code, KeyModifiers::NONE)
+	}
+
+	fn ctrl(c: char) -> KeyEvent {
+		KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+	}
+
+	#[test]
+	fn prompts_take_every_character() {
+		let mut app = app_at("dickinson-hope");
+		for mode in [AppMode::Search, AppMode::Rename, AppMode::GitCommit, AppMode::EditMetadata] {
+			app.mode = mode;
+			assert_eq!(action(&app, key(KeyCode::Char('q'))), Some(Action::Type('q')));
+			assert_eq!(action(&app, key(KeyCode::Backspace)), Some(Action::DeleteChar));
+			assert_eq!(action(&app, ctrl('q')), None);
+		}
+	}
+
+	#[test]
+	fn enter_depends_on_the_mode() {
+		let mut app = app_at("dickinson-hope");
+		let enter = key(KeyCode::Enter);
+		// An Enter pressed by mistake while reading changes nothing
+		assert_eq!(action(&app, enter), Some(Action::Select));
+		assert_eq!(action(&app, ctrl('m')), Some(Action::StartMetadataEdit));
+		assert_eq!(action(&app, key(KeyCode::Char('I'))), Some(Action::StartMetadataEdit));
+		let expected = [
+			(AppMode::Menu, Action::Select),
+			(AppMode::Trash, Action::Restore),
+			(AppMode::Rename, Action::FinishRename),
+			(AppMode::GitCommit, Action::Commit),
+			(AppMode::EditMetadata, Action::SaveMetadata),
+		];
+		for (mode, expected) in expected {
+			app.mode = mode;
+			assert_eq!(action(&app, enter), Some(expected));
+		}
+	}
+
+	#[test]
+	fn the_word_cursor_takes_the_arrows() {
+		let mut app = app_at("dickinson-hope");
+		assert_eq!(action(&app, key(KeyCode::Right)), Some(Action::NextPoem));
+		assert_eq!(action(&app, key(KeyCode::Char('j'))), Some(Action::Down));
+		app.word_cursor = Some((0, 0));
+		assert_eq!(action(&app, key(KeyCode::Right)), Some(Action::MoveWordCursor(0, 1)));
+		assert_eq!(action(&app, key(KeyCode::Char('j'))), Some(Action::MoveWordCursor(1, 0)));
+		assert_eq!(action(&app, key(KeyCode::Esc)), Some(Action::Close));
+	}
+
+	#[test]
+	fn words_of_rtl_poems_run_to_the_left() {
+		let mut app = app_at("hafez-ghazal-1");
+		app.word_cursor = Some((0, 0));
+		assert_eq!(action(&app, key(KeyCode::Left)), Some(Action::MoveWordCursor(0, 1)));
+	}
+
+	#[test]
+	fn columns_scroll_only_in_vertical_poems() {
+		let app = app_at("dickinson-hope");
+		assert_eq!(action(&app, key(KeyCode::Char('l'))), None);
+		let app = app_at("li-bai-quiet-night");
+		assert_eq!(action(&app, key(KeyCode::Char('h'))), Some(Action::ScrollLeft));
+		assert_eq!(action(&app, key(KeyCode::Char('l'))), Some(Action::ScrollRight));
+	}
+
+	#[test]
+	fn versions_cycle_only_when_there_are_several() {
+		let app = app_at("dickinson-hope");
+		assert_eq!(action(&app, key(KeyCode::Char('v'))), None);
+		let app = app_at("goethe-wandrers-nachtlied");
+		assert_eq!(action(&app, key(KeyCode::Char('v'))), Some(Action::NextVersion));
+		assert_eq!(action(&app, key(KeyCode::Char('V'))), Some(Action::PreviousVersion));
+	}
+
+	#[test]
+	fn confirmations_take_yes_or_no() {
+		let mut app = app_at("dickinson-hope");
+		app.mode = AppMode::ConfirmDelete;
+		assert_eq!(action(&app, key(KeyCode::Char('y'))), Some(Action::Delete));
+		assert_eq!(action(&app, key(KeyCode::Char('n'))), Some(Action::Close));
+		app.mode = AppMode::ConfirmMerge;
+		assert_eq!(action(&app, key(KeyCode::Char('y'))), Some(Action::Merge));
+		assert_eq!(action(&app, key(KeyCode::Backspace)), Some(Action::Close));
+	}
+
+	#[test]
+	fn keys_the_viewer_takes_from_commands_are_found() {
+		// `v` only with several versions and `h` only in vertical poems
+		let commands = ['x', 'v', 'h', 'W', 'z'].into_iter().map(|c| (c, "true".to_string())).collect();
+		assert_eq!(taken_by_viewer(&commands), ['h', 'v', 'x']);
+	}
+
+	#[test]
+	fn commands_get_the_keys_the_viewer_leaves_free() {
+		let mut app = app_at("dickinson-hope");
+		app.commands.insert('x', "echo {title}".to_string());
+		app.commands.insert('X', "echo {title}".to_string());
+		assert_eq!(action(&app, key(KeyCode::Char('X'))), Some(Action::RunCommand('X')));
+		assert_eq!(action(&app, key(KeyCode::Char('x'))), Some(Action::CycleTextVariant));
+		app.mode = AppMode::TitleList;
+		assert_eq!(action(&app, key(KeyCode::Char('X'))), Some(Action::JumpToTitleGroup('X')));
+	}
+
+	#[test]
+	fn a_running_task_only_takes_cancelling() {
+		let mut app = app_at("dickinson-hope");
+		app.task = Some((crate::tasks::Task::spawn("Waiting", |_| Ok(())), crate::app::TaskEnd::ShowOutput));
+		assert_eq!(action(&app, key(KeyCode::Esc)), Some(Action::CancelTask));
+		assert_eq!(action(&app, key(KeyCode::Char('q'))), None);
+		assert_eq!(action(&app, key(KeyCode::Down)), None);
+	}
+
+	#[test]
+	fn only_the_onboarding_keys_work_without_poems() {
+		let mut app = app_at("dickinson-hope");
+		app.mode = AppMode::Onboarding;
+		assert_eq!(action(&app, key(KeyCode::Char('c'))), Some(Action::CreateSamplePoem));
+		assert_eq!(action(&app, key(KeyCode::Char('q'))), Some(Action::Quit));
+		assert_eq!(action(&app, key(KeyCode::Char('/'))), None);
+	}
+
+	#[test]
+	fn capitals_jump_in_the_title_list() {
+		let mut app = app_at("dickinson-hope");
+		app.mode = AppMode::TitleList;
+		assert_eq!(action(&app, key(KeyCode::Char('W'))), Some(Action::JumpToTitleGroup('W')));
+		// Elsewhere they are keys of their own, or none
+		app.mode = AppMode::Viewing;
+		assert_eq!(action(&app, key(KeyCode::Char('W'))), None);
+	}
+}
@@ -0,0 +1,244 @@
+use crate::formatter::has_comments;
+use crate::models::{check_writable, is_markdown, parse_file, poem_extension, poems_dir, read_library_file, read_versions, rewrite_poem, Poem, Version};
+use crate::utils::slug;
+use std::{collections::HashMap, fs, io, path::{Component, Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+
+// Files set aside instead of deleted, inside the library; hidden, so never loaded as poems
+pub const TRASH_DIR: &str = ".trash";
+
+// Only whole `.poem` files can be rewritten: a markdown poem has no versions to add to, and
+// rewriting one poem of a file holding several would have to touch the others
+pub fn check_rewritable(poem: &Poem) -> io::Result<()> {
+	if is_markdown(Path::new(&poem.filename)) {
+		return Err(io::Error::new(io::ErrorKind::Unsupported, format!("{} is a markdown file", poem.filename)));
+	}
+	if poem.document.is_some() {
+		return Err(io::Error::new(io::ErrorKind::Unsupported, format!("{} holds several poems", poem.filename)));
+	}
+	Ok(())
+}
+
+// Reads the poem's file again, so edits made since loading are kept, lets `change` alter its
+// versions and writes them back in the layout of `leaves fmt`. That layout has no place for
+// comments, so a file with any is left alone rather than losing them.
+fn rewrite<T>(poem: &Poem, change: impl FnOnce(&mut HashMap<String, Version>) -> io::Result<T>) -> io::Result<T> {
+	check_rewritable(poem)?;
+	let content = read_library_file(&poem.filename)?;
+	if has_comments(&content) {
+		return Err(io::Error::new(io::ErrorKind::Unsupported, format!("{} has comments, which rewriting it would drop", poem.filename)));
+	}
+	let mut versions = parse_file(Path::new(&poem.filename), &content).into_iter().next()
+		.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("{} holds no poem", poem.filename)))?
+		.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{} no longer parses: {}", poem.filename, e)))?;
+	let result = change(&mut versions)?;
+	rewrite_poem(&poems_dir().join(&poem.filename), &[versions])?;
+	Ok(result)
+}
+
+// The key each version of `from` gets in `into`: its own when free, else one after its
+// language (the canonical version of a poem is rarely canonical in another), numbered on clashes
+pub fn merge_keys(into: &Poem, from: &Poem) -> Vec<(String, String)> {
+	let mut taken: Vec<String> = into.versions.keys().cloned().collect();
+	let mut keys: Vec<&String> = from.versions.keys().collect();
+	keys.sort_by_key(|k| (k.as_str() != "canonical", k.as_str()));
+	keys.into_iter().map(|key| {
+		let base = match (key.as_str(), from.versions[key].language.as_deref()) {
+			("canonical", Some(language)) => language.to_string(),
+			("canonical", None) => from.id().to_string(),
+			_ => key.clone(),
+		};
+		let mut new_key = base.clone();
+		let mut n = 2;
+		while taken.contains(&new_key) {
+			new_key = format!("{}-{}", base, n);
+			n += 1;
+		}
+		taken.push(new_key.clone());
+		(key.clone(), new_key)
+	}).collect()
+}
+
+// Adds every version of `from` to the file of `into` under the keys of `merge_keys`, then
+// moves the file of `from` to the trash
+pub fn merge(into: &Poem, from: &Poem) -> io::Result<PathBuf> {
+	check_rewritable(from)?;
+	let mut added = read_versions(&from.filename, None)?;
+	rewrite(into, |versions| {
+		for (key, new_key) in merge_keys(into, from) {
+			if let Some(version) = added.remove(&key) {
+				versions.insert(new_key, version);
+			}
+		}
+		Ok(())
+	})?;
+	trash(&from.filename)
+}
+
+// Adds a copy of version `key` to the poem's file as a starting point for a translation:
+// the text to work over and what stays true of it (author, date, form, tags), with the title,
+// language and everything else that belongs to one version left out. Returns the new key.
+pub fn add_translation(poem: &Poem, key: &str) -> io::Result<String> {
+	rewrite(poem, |versions| {
+		let original = versions.get(key)
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} no longer has a version {}", poem.filename, key)))?;
+		let translation = Version {
+			author: original.author.clone(),
+			sort_name: original.sort_name.clone(),
+			author_names: original.author_names.clone(),
+			year: original.year,
+			date: original.date.clone(),
+			form: original.form.clone(),
+			tags: original.tags.clone(),
+			text: original.text.clone(),
+			..Default::default()
+		};
+		let mut new_key = "translation".to_string();
+		let mut n = 2;
+		while versions.contains_key(&new_key) {
+			new_key = format!("translation-{}", n);
+			n += 1;
+		}
+		versions.insert(new_key.clone(), translation);
+		Ok(new_key)
+	})
+}
+
+// New values for the fields the quick editor changes; `None` removes a field
+pub struct MetadataEdit {
+	pub title: Option<String>,
+	pub author: Option<String>,
+	pub language: Option<String>,
+	pub rtl: Option<bool>,
+	pub vertical: Option<bool>,
+	pub tags: Option<Vec<String>>,
+}
+
+pub fn edit_metadata(poem: &Poem, key: &str, edit: MetadataEdit) -> io::Result<()> {
+	rewrite(poem, |versions| {
+		let version = versions.get_mut(key)
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} no longer has a version {}", poem.filename, key)))?;
+		version.title = edit.title;
+		version.author = edit.author;
+		version.language = edit.language;
+		version.rtl = edit.rtl;
+		version.vertical = edit.vertical;
+		version.tags = edit.tags;
+		Ok(())
+	})
+}
+
+// Replaces the text of version `key`
+pub fn save_text(poem: &Poem, key: &str, text: String) -> io::Result<()> {
+	rewrite(poem, |versions| {
+		let version = versions.get_mut(key)
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} no longer has a version {}", poem.filename, key)))?;
+		version.text = text;
+		Ok(())
+	})
+}
+
+// Moves a poem file into the trash, keeping its folder; a file of the same name already
+// there is kept too, by adding the time the newcomer was trashed after its extension
+pub fn trash(filename: &str) -> io::Result<PathBuf> {
+	check_writable()?;
+	let root = poems_dir();
+	let mut target = root.join(TRASH_DIR).join(filename);
+	if target.exists() {
+		let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+		target = root.join(TRASH_DIR).join(format!("{}.{}", filename, seconds));
+	}
+	if let Some(parent) = target.parent() {
+		fs::create_dir_all(parent)?;
+	}
+	fs::rename(root.join(filename), &target)?;
+	Ok(target)
+}
+
+// Every file in the trash, by its name there, in path order
+pub fn trashed_files() -> io::Result<Vec<String>> {
+	let trash = poems_dir().join(TRASH_DIR);
+	let mut files = Vec::new();
+	let mut pending = vec![trash.clone()];
+	while let Some(dir) = pending.pop() {
+		let Ok(entries) = fs::read_dir(&dir) else { continue };
+		for entry in entries {
+			let path = entry?.path();
+			if path.is_dir() {
+				pending.push(path);
+			} else if let Ok(relative) = path.strip_prefix(&trash) {
+				files.push(relative.to_string_lossy().replace('\\', "/"));
+			}
+		}
+	}
+	files.sort();
+	Ok(files)
+}
+
+// Where a trashed file came from: its name, without the time added by `trash`
+pub fn original_name(trashed: &str) -> &str {
+	match trashed.rsplit_once('.') {
+		Some((name, seconds)) if !seconds.is_empty() && seconds.bytes().all(|b| b.is_ascii_digit()) => name,
+		_ => trashed,
+	}
+}
+
+// Moves a file out of the trash back to where it was; a file that took its place is left alone
+pub fn restore(trashed: &str) -> io::Result<String> {
+	check_writable()?;
+	let root = poems_dir();
+	let name = original_name(trashed);
+	let target = root.join(name);
+	if target.exists() {
+		return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} exists again; rename it first", name)));
+	}
+	if let Some(parent) = target.parent() {
+		fs::create_dir_all(parent)?;
+	}
+	fs::rename(root.join(TRASH_DIR).join(trashed), &target)?;
+	Ok(name.to_string())
+}
+
+// A file name made from the author and title of the canonical version, in the poem's folder
+pub fn suggested_filename(poem: &Poem) -> String {
+	let canonical = poem.canonical();
+	let author = canonical.and_then(|v| v.author.as_deref()).unwrap_or("unknown");
+	let title = canonical.and_then(|v| v.title.as_deref()).unwrap_or("untitled");
+	let stem: String = format!("{}-{}", slug(author), slug(title)).chars().take(80).collect();
+	let extension = poem_extension(&poem.filename);
+	let name = format!("{}.{}", stem.trim_end_matches('-'), extension);
+	match poem.folder() {
+		"" => name,
+		folder => format!("{}/{}", folder, name),
+	}
+}
+
+// Renames or moves a poem file inside the library, creating the folders it goes into, and
+// returns the name it ended up with. The extension is kept, or the file would stop being
+// read as a poem; an existing file is never replaced.
+pub fn rename(from: &str, to: &str) -> io::Result<String> {
+	check_writable()?;
+	let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+	let to = to.trim().trim_start_matches('/');
+	let path = Path::new(to);
+	if to.is_empty() || path.components().any(|c| !matches!(c, Component::Normal(_))) {
+		return Err(invalid(format!("\"{}\" is not a path inside the library", to)));
+	}
+	if path.starts_with(TRASH_DIR) {
+		return Err(invalid(format!("{}/ is for deleted poems", TRASH_DIR)));
+	}
+	let extension = format!(".{}", poem_extension(from));
+	let to = if to.ends_with(&extension) { to.to_string() } else { format!("{}{}", to, extension) };
+	if to == from {
+		return Ok(to);
+	}
+	let root = poems_dir();
+	let target = root.join(&to);
+	if target.exists() {
+		return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", to)));
+	}
+	if let Some(parent) = target.parent() {
+		fs::create_dir_all(parent)?;
+	}
+	fs::rename(root.join(from), &target)?;
+	Ok(to)
+}
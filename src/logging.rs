@@ -0,0 +1,34 @@
+use crate::paths;
+use std::{fs::{self, OpenOptions}, path::PathBuf, sync::Mutex};
+use tracing_subscriber::EnvFilter;
+
+// Environment variable holding a filter like `leaves=trace`, taking precedence over `--verbose`
+const FILTER_VAR: &str = "LEAVES_LOG";
+// A log that grows past this starts over instead
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+pub fn log_path() -> PathBuf {
+	paths::state_dir().join("leaves.log")
+}
+
+// Sends log events to `leaves.log` in the state directory: warnings such as skipped poem
+// files always, and with `verbose` also load timings and key presses. Logging never goes
+// to the terminal, which belongs to the reader; without a writable log file there is none.
+pub fn init(verbose: bool) {
+	let path = log_path();
+	if let Some(parent) = path.parent() {
+		let _ = fs::create_dir_all(parent);
+	}
+	let too_big = fs::metadata(&path).is_ok_and(|m| m.len() > MAX_LOG_SIZE);
+	let Ok(file) = OpenOptions::new().create(true).append(!too_big).write(true).truncate(too_big).open(&path) else {
+		return;
+	};
+	let default = if verbose { "leaves=debug" } else { "leaves=warn" };
+	let filter = EnvFilter::try_from_env(FILTER_VAR).unwrap_or_else(|_| EnvFilter::new(default));
+	let _ = tracing_subscriber::fmt()
+		.with_env_filter(filter)
+		.with_writer(Mutex::new(file))
+		.with_ansi(false)
+		.with_target(false)
+		.try_init();
+}
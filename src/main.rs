@@ -1,64 +1,531 @@
 mod models;
+mod config;
 mod app;
 mod ui;
-mod utils; 
+mod utils;
+mod history;
+mod stats;
+mod analysis;
+mod dictionary;
+mod cli;
+mod export;
+mod import;
+mod fetch;
+mod bundle;
+mod migrate;
+mod doctor;
+mod formatter;
+mod ignore;
+mod paths;
+mod sync;
+mod tasks;
+mod terminal;
+mod commands;
+mod facets;
+mod logging;
+mod wizard;
+mod library;
+mod collate;
+mod shaping;
+mod i18n;
+mod keymap;
+mod editor;
+mod backup;
+mod git;
+#[cfg(feature = "archives")]
+mod archive;
+#[cfg(feature = "encryption")]
+mod crypt;
+mod publish;
+mod feed;
+#[cfg(feature = "server")]
+mod server;
+#[cfg(feature = "sqlite")]
+mod index;
+#[cfg(feature = "search")]
+mod search;
+#[cfg(feature = "plugins")]
+mod plugins;
+mod audio;
+#[cfg(feature = "images")]
+mod images;
+#[cfg(feature = "tts")]
+mod speech;
 
 use crossterm::{
-	event::{self, Event, KeyCode, KeyModifiers},
-	terminal::{disable_raw_mode, enable_raw_mode, SetTitle, EnterAlternateScreen, LeaveAlternateScreen},
+	cursor,
+	event::{self, Event, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags},
+	terminal::{disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, SetTitle, EnterAlternateScreen, LeaveAlternateScreen},
 	execute,
 };
 use ratatui::{
 	Terminal,
-	widgets::{Block, Borders, Paragraph, List, ListItem, Scrollbar, ScrollbarOrientation, ScrollbarState, Clear},
-	layout::{Constraint, Direction, Layout, Rect},
+	widgets::{block::{Title, Position}, Block, Borders, Paragraph, List, ListItem, ListState, Scrollbar, ScrollbarOrientation, ScrollbarState, Clear, Table, TableState, Row, Cell, Wrap},
+	layout::{Alignment, Constraint, Direction, Layout, Rect},
 	style::{Style, Color},
 	text::{Line, Span},
 };
-use textwrap;
-use std::{io, path::PathBuf};
+use std::{fs, io, path::Path, sync::atomic::{AtomicBool, Ordering}, time::{Duration, Instant}};
 use app::App;
+use config::Config;
+use history::History;
 use models::load_poems;
-use rand::Rng;
-use crate::utils::get_language_name;
+use clap::Parser;
+use crate::utils::{language_label, script_language_label};
+use crate::i18n::tr;
+use crate::keymap::Action;
 use crate::ui::popup_area;
+use textwrap::core::display_width;
+
+// Errors before the reader takes over the terminal are printed plainly
+fn exit_with(e: io::Error) -> ! {
+	tracing::error!("{}", e);
+	eprintln!("leaves: {}", e);
+	std::process::exit(1);
+}
+
+// How often the reader wakes up between keys, e.g. to see whether a poem file read on its
+// own changed
+const TICK_RATE: Duration = Duration::from_millis(250);
+
+// The next thing for the reader to react to; a tick once TICK_RATE went by since the last
+fn next_message(last_tick: &mut Instant) -> io::Result<app::Message> {
+	loop {
+		if !event::poll(TICK_RATE.saturating_sub(last_tick.elapsed()))? {
+			*last_tick = Instant::now();
+			return Ok(app::Message::Tick);
+		}
+		match event::read()? {
+			Event::Key(key) => return Ok(app::Message::Key(key)),
+			Event::Resize(..) => return Ok(app::Message::Resize),
+			_ => {}
+		}
+	}
+}
+
+// True when the tick changed what is on screen
+fn on_tick(app: &mut App, preview: Option<&Path>, config: &Config, strict: bool) -> bool {
+	let mut changed = app.tick();
+	// The popup of a running task counts the seconds, so it is drawn on every tick
+	if let Some((task, _)) = &mut app.task {
+		task.poll();
+		if task.finished.is_some() {
+			finish_task(app, config, strict);
+		}
+		changed = true;
+	}
+	match preview {
+		Some(file) => reload_preview(app, file, config) || changed,
+		None => changed,
+	}
+}
+
+// Loads the library again after its files changed, keeping the reading history. When it
+// cannot be read, say while a sync is halfway or a network mount is away, the reader stays
+// as it was with the error in the status bar, and false is returned.
+fn reload_library(app: &mut App, config: &Config, strict: bool) -> bool {
+	let (poems, load_errors) = match load_poems(strict) {
+		Ok(loaded) => loaded,
+		Err(e) => {
+			tracing::warn!(error = %e, "could not reload the library");
+			app.status_message = Some(format!("Could not read the library again: {}", e));
+			return false;
+		},
+	};
+	let history = std::mem::take(&mut app.history);
+	*app = App::new(poems, config, history);
+	app.load_errors = load_errors;
+	app.trashed = library::trashed_files().unwrap_or_default();
+	app.git_status = git::status();
+	app.sync_conflicts = sync::conflicts();
+	app.syncable = syncable(config);
+	true
+}
+
+fn syncable(config: &Config) -> bool {
+	config.sync_backend.is_some() || sync::remote_library().is_some()
+}
+
+// Loads the library again once a task that may have changed it is done, and shows what the
+// task wrote last, or why it failed
+fn finish_task(app: &mut App, config: &Config, strict: bool) {
+	let Some((mut task, end)) = app.task.take() else { return };
+	if end == app::TaskEnd::ShowOutput {
+		return app.show_command_output(task);
+	}
+	if !reload_library(app, config, strict) {
+		return;
+	}
+	app.status_message = Some(match task.finished.take() {
+		Some(Ok(())) => task.lines.pop().unwrap_or(task.title),
+		Some(Err(e)) if e.kind() == io::ErrorKind::Interrupted => format!("{}: cancelled", task.title),
+		Some(Err(e)) => format!("{}: {}", task.title, e),
+		None => task.title,
+	});
+}
+
+// Moves the current poem's file to the trash and goes back to the menu
+fn delete_current(app: &mut App, config: &Config, strict: bool) {
+	let filename = app.poems[app.current_poem].filename.clone();
+	match library::trash(&filename) {
+		Ok(_) if reload_library(app, config, strict) => {
+			app.status_message = Some(format!("Moved {} to {}/", filename, library::TRASH_DIR));
+		},
+		Ok(_) => app.mode = app::AppMode::Viewing,
+		Err(e) => {
+			app.mode = app::AppMode::Viewing;
+			app.status_message = Some(format!("Could not delete: {}", e));
+		},
+	}
+}
+
+// Puts the selected file of the trash back, staying in the trash while anything is left there
+fn restore_selected(app: &mut App, config: &Config, strict: bool) {
+	let Some(trashed) = app.trash_list_state.selected().and_then(|i| app.trashed.get(i)).cloned() else {
+		return;
+	};
+	let selected = app.trash_list_state.selected();
+	match library::restore(&trashed) {
+		Ok(name) => {
+			if !reload_library(app, config, strict) {
+				return;
+			}
+			if !app.trashed.is_empty() {
+				app.trash_list_state.select(selected.map(|i| i.min(app.trashed.len() - 1)));
+				app.mode = app::AppMode::Trash;
+			}
+			app.status_message = Some(format!("Restored {}", name));
+		},
+		Err(e) => app.status_message = Some(format!("Could not restore: {}", e)),
+	}
+}
+
+// Adds a translation scaffold of the version being read and opens it
+fn add_translation(app: &mut App, config: &Config, strict: bool) {
+	if app.read_only {
+		app.status_message = Some("These poems are read-only".to_string());
+		return;
+	}
+	let poem = &app.poems[app.current_poem];
+	let (id, filename) = (poem.id().to_string(), poem.filename.clone());
+	match library::add_translation(poem, &app.current_version) {
+		Ok(key) => {
+			if !reload_library(app, config, strict) {
+				return;
+			}
+			if app.start_at(app::Start::Poem(id)).is_ok() {
+				app.current_version = key.clone();
+			}
+			app.status_message = Some(format!("Added version `{}` to {}; ctrl+e opens it to translate", key, filename));
+		},
+		Err(e) => app.status_message = Some(format!("Could not add a translation: {}", e)),
+	}
+}
+
+// Writes the built-in editor's text into the version being read
+fn save_edited(app: &mut App, config: &Config, strict: bool) {
+	let Some(editor) = &app.editor else {
+		return;
+	};
+	let poem = &app.poems[app.current_poem];
+	let (id, filename, version) = (poem.id().to_string(), poem.filename.clone(), app.current_version.clone());
+	match library::save_text(poem, &version, editor.text()) {
+		Ok(()) => {
+			if !reload_library(app, config, strict) {
+				return;
+			}
+			if app.start_at(app::Start::Poem(id)).is_ok() {
+				app.current_version = version;
+			}
+			app.status_message = Some(format!("Saved {}", filename));
+		},
+		// The editor stays open with the changes
+		Err(e) => app.status_message = Some(format!("Could not save: {}", e)),
+	}
+}
+
+// Writes the metadata editor's fields into the version being read
+fn save_metadata(app: &mut App, config: &Config, strict: bool) {
+	let poem = &app.poems[app.current_poem];
+	let (id, filename, version) = (poem.id().to_string(), poem.filename.clone(), app.current_version.clone());
+	match library::edit_metadata(poem, &version, app.metadata_form.to_edit()) {
+		Ok(()) => {
+			if !reload_library(app, config, strict) {
+				return;
+			}
+			if app.start_at(app::Start::Poem(id)).is_ok() {
+				app.current_version = version;
+			}
+			app.status_message = Some(format!("Saved {}", filename));
+		},
+		// The form stays open with what was typed
+		Err(e) => app.status_message = Some(format!("Could not save: {}", e)),
+	}
+}
+
+// Merges the poem marked with `M` into the current one, then reloads the library and reopens it
+fn merge_marked(app: &mut App, config: &Config, strict: bool) {
+	let Some(marked) = app.merge_mark.take() else {
+		return;
+	};
+	let (into, from) = (&app.poems[app.current_poem], &app.poems[marked]);
+	let id = into.id().to_string();
+	let message = match library::merge(into, from) {
+		Ok(trashed) => format!("Merged {} into {}; the old file is in {}", from.filename, into.filename, trashed.display()),
+		Err(e) => {
+			app.mode = app::AppMode::Viewing;
+			app.status_message = Some(format!("Could not merge: {}", e));
+			return;
+		}
+	};
+	if !reload_library(app, config, strict) {
+		app.mode = app::AppMode::Viewing;
+		return;
+	}
+	if app.start_at(app::Start::Poem(id)).is_err() {
+		app.mode = app::AppMode::Menu;
+	}
+	app.status_message = Some(message);
+}
+
+// Reads the previewed file again once it changed on disk, staying at the same poem and
+// version; true when that changed what is on screen
+fn reload_preview(app: &mut App, file: &Path, config: &Config) -> bool {
+	let modified = fs::metadata(file).and_then(|m| m.modified()).ok();
+	if modified.is_none() || modified == app.poems.first().and_then(|p| p.modified) {
+		return false;
+	}
+	match models::load_file(file) {
+		Ok((poems, load_errors)) => {
+			let current_poem = app.current_poem.min(poems.len() - 1);
+			let mut reloaded = App::new(poems, config, std::mem::take(&mut app.history));
+			reloaded.save_history = false;
+			reloaded.load_errors = load_errors;
+			reloaded.current_poem = current_poem;
+			reloaded.current_version = if reloaded.poems[current_poem].versions.contains_key(&app.current_version) {
+				app.current_version.clone()
+			} else {
+				reloaded.preferred_version(current_poem)
+			};
+			reloaded.scroll_position = app.scroll_position;
+			reloaded.mode = app.mode.clone();
+			*app = reloaded;
+			true
+		}
+		// Shown until the file parses again; the last good version stays on screen
+		Err(e) => {
+			let message = Some(e.to_string());
+			let changed = app.status_message != message;
+			app.status_message = message;
+			changed
+		}
+	}
+}
+
+// Whether the terminal was asked for the kitty keyboard protocol, to be turned off on the way out
+static KEYBOARD_ENHANCED: AtomicBool = AtomicBool::new(false);
+
+// Puts the terminal back the way the shell expects it; harmless when it already is
+fn restore_terminal() {
+	if KEYBOARD_ENHANCED.swap(false, Ordering::Relaxed) {
+		let _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+	}
+	let _ = disable_raw_mode();
+	let _ = execute!(io::stdout(), LeaveAlternateScreen, cursor::Show);
+}
+
+// Restores the terminal however the reader ends: returning, `?` on an error or a panic unwinding
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+	fn drop(&mut self) {
+		restore_terminal();
+	}
+}
 
 fn main() -> Result<(), io::Error> {
+	let cli = cli::Cli::parse();
+	logging::init(cli.verbose);
+	tracing::debug!(version = env!("CARGO_PKG_VERSION"), command = ?cli.command, "starting");
+	// The first start of the reader asks what would otherwise go in the config by hand
+	if cli.command.is_none() && !cli.demo && cli.file.is_none() && wizard::needed() {
+		wizard::run().unwrap_or_else(|e| exit_with(e));
+	}
+	let mut config = Config::load().unwrap_or_else(|e| exit_with(e));
+	models::set_library(&config);
+	let strict = cli.strict || config.strict;
+	if cli.read_only || config.read_only {
+		models::set_read_only();
+	}
+	// Subcommands run headless; without one the reader starts, `pick` may start it at a poem
+	let mut start = cli.start();
+	if let Some(command) = cli.command {
+		match cli::run(command, strict) {
+			Ok(Some(id)) => start = Some(app::Start::Poem(id)),
+			Ok(None) => return Ok(()),
+			// Output piped into `head` and the like may be cut short
+			Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+			Err(e) => exit_with(e),
+		}
+	}
+	// Neither the demo poems nor a file read on its own come from the library, so there is
+	// nothing to unload, and they leave the reading history alone
+	let keep_apart = cli.demo || cli.file.is_some();
+	if keep_apart {
+		config.low_memory = false;
+	}
+	let (poems, load_errors) = if cli.demo {
+		(models::demo_poems(), Vec::new())
+	} else if let Some(file) = &cli.file {
+		models::load_file(file).unwrap_or_else(|e| exit_with(e))
+	} else {
+		match load_poems(strict) {
+			// A library directory that does not exist yet is offered to be started on the onboarding screen
+			Err(e) if e.kind() == io::ErrorKind::NotFound && sync::remote_library().is_none() && !models::poems_dir().exists() => (Vec::new(), Vec::new()),
+			result => result.unwrap_or_else(|e| exit_with(e)),
+		}
+	};
+	let history = if keep_apart { History::default() } else { History::load().unwrap_or_else(|e| exit_with(e)) };
+	// The panic message would otherwise go to the alternate screen and vanish with it
+	let default_hook = std::panic::take_hook();
+	std::panic::set_hook(Box::new(move |info| {
+		restore_terminal();
+		tracing::error!("{}", info);
+		default_hook(info);
+	}));
+	// Before the app is made, as they may add facets to it
+	#[cfg(feature = "plugins")]
+	let mut plugins = plugins::Plugins::load();
+	let mut app = App::new(poems, &config, history);
+	app.load_errors = load_errors;
+	app.demo = cli.demo;
+	app.save_history = !keep_apart;
+	app.read_only = keep_apart || models::is_archive(&models::poems_dir()) || models::is_read_only();
+	if !app.read_only {
+		app.trashed = library::trashed_files().unwrap_or_default();
+		app.git_status = git::status();
+		app.sync_conflicts = sync::conflicts();
+	}
+	app.syncable = !keep_apart && syncable(&config);
+	if let Some(start) = start {
+		app.start_at(start).unwrap_or_else(|e| exit_with(e));
+	}
+	// Said once, as the reader starts, of `keybind` keys whose commands would never run
+	let taken = keymap::taken_by_viewer(&app.commands);
+	if !taken.is_empty() {
+		let keys: Vec<String> = taken.iter().map(char::to_string).collect();
+		tracing::warn!(keys = keys.join(""), "keybind keys the reader already uses are ignored");
+		app.status_message = Some(format!("keybind: {} already used by the reader; ignored", keys.join(", ")));
+	}
 	enable_raw_mode()?;
+	let _guard = TerminalGuard;
 	execute!(io::stdout(), EnterAlternateScreen)?;
+	// Keys such as ctrl+m then come apart from the ones terminals otherwise send for them (Enter)
+	if matches!(supports_keyboard_enhancement(), Ok(true)) {
+		execute!(io::stdout(), PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES))?;
+		KEYBOARD_ENHANCED.store(true, Ordering::Relaxed);
+	}
 	let mut stdout = io::stdout();
 	execute!(stdout, SetTitle("leaves"))?;
 	let backend = ratatui::backend::CrosstermBackend::new(stdout);
 	let mut terminal = Terminal::new(backend)?;
-	let poems = load_poems()?;
-	let mut app = App::new(poems);
+	let capabilities = terminal::detect(config.ascii_only);
+	tracing::info!(?capabilities, "terminal");
+	terminal::set(capabilities);
+	let theme = ui::Theme::named(config.theme.as_deref(), capabilities.basic_colors);
+	#[cfg(feature = "images")]
+	let mut images = images::Renderer::new(images::detect(config.image_protocol.as_deref()));
+	#[cfg(feature = "plugins")]
+	plugins.startup(&mut app);
+	let mut last_tick = Instant::now();
 	loop {
+		if matches!(app.mode, app::AppMode::Viewing | app::AppMode::VersionSelect | app::AppMode::Metadata | app::AppMode::ConfirmMerge | app::AppMode::Rename
+			| app::AppMode::ConfirmDelete | app::AppMode::EditMetadata | app::AppMode::GitDiff | app::AppMode::GitCommit | app::AppMode::CommandOutput) {
+			// A file renamed behind the reader's back or that no longer decrypts leaves the poem blank
+			if let Err(e) = app.ensure_current_loaded() {
+				app.status_message = Some(format!("Could not read {} again: {}", app.poems[app.current_poem].filename, e));
+			}
+		}
+		if app.mode == app::AppMode::Viewing {
+			app.mark_current_read()?;
+		}
+		#[cfg(feature = "plugins")]
+		plugins.poem_opened(&mut app);
+		app.stop_stale_recording();
+		#[cfg(feature = "tts")]
+		app.stop_stale_speech();
+		#[cfg(feature = "images")]
+		let image = images.protocol.and(app.current_image());
+		#[cfg(not(feature = "images"))]
+		let image: Option<String> = None;
+		let mut image_area = None;
+		let max_scroll = app.max_scroll;
 		terminal.draw(|f| {
 			let chunks = Layout::default().direction(Direction::Vertical).constraints([Constraint::Min(1), Constraint::Length(1)].as_ref()).split(f.size());
+			// The poem shares the screen with its illustration when there is one to draw
+			let poem_area = match &image {
+				Some(_) if matches!(app.mode, app::AppMode::Viewing | app::AppMode::VersionSelect | app::AppMode::Definition | app::AppMode::CommandOutput) => {
+					let (text_area, area) = ui::split_image_area(chunks[0]);
+					image_area = Some(area);
+					text_area
+				},
+				_ => chunks[0],
+			};
 			if let app::AppMode::Viewing = app.mode {
-				app.viewport_height = Some(chunks[0].height.saturating_sub(2));
+				app.viewport_height = Some(poem_area.height.saturating_sub(2));
 			}
+			let acrostic_word;
 			let status_bar = match app.mode {
 				app::AppMode::Viewing => {
 					let mut items = vec![
-						if app.filtered_poems.is_none() && app.previous_mode.is_none() {
+						if app.filtered_poems.is_none() && app.previous_mode.is_none() && app.link_trail.is_empty() {
 							("m/backspace", "menu")
 						} else {
 							("m", "main menu")
 						},
 						("←/→", "navigate poems")
 					];
-					let text = ui::render_poem_text(app.get_current_version());
-					let lines = text.lines().count();
-					let viewport_height = chunks[0].height as usize - 2;
-					if lines > viewport_height {
-						items.push(("↑/↓", "scroll"));
+					if app.max_scroll > 0 {
+						items.push(if app.shows_vertical() { ("h/l", "scroll columns") } else { ("↑/↓", "scroll") });
+					}
+					if app.shown_version().transliteration().is_some() {
+						items.push(("t", app.shown_script().next().label()));
 					}
-					if app.filtered_poems.is_some() {
+					if app.get_current_version().variants.as_ref().is_some_and(|variants| !variants.is_empty()) {
+						items.push(("x", app.next_text_variant().map_or("original text", String::as_str)));
+					}
+					if !app.link_trail.is_empty() {
+						items.push(("backspace", "back along link"));
+					} else if app.filtered_poems.is_some() {
 						items.push(("backspace", "back to list"));
 					}
 					if app.poems[app.current_poem].versions.len() > 1 {
-						items.push(("s", "switch version"));
+						items.push(("s/v/V", "switch version"));
+					}
+					items.push(("u", "next unread"));
+					items.push(("i", if app.see_also().is_empty() { "info" } else { "info & links" }));
+					if app.git_state(app.current_poem).is_some() {
+						items.push(("G", "uncommitted changes"));
+					}
+					if app.word_cursor.is_some() {
+						items = vec![("c", "exit word cursor"), ("←/→/↑/↓", "move"), ("d", "define")];
+					}
+					if app.recording.is_some() {
+						items.push(("r", "stop recording"));
+					} else if app.get_current_version().audio.is_some() {
+						items.push(("r", "play recording"));
+					}
+					#[cfg(feature = "tts")]
+					match &app.speech {
+						Some(speech) if speech.paused => items.extend([("p", "stop"), ("P", "resume")]),
+						Some(_) => items.extend([("p", "stop"), ("P", "pause")]),
+						None => items.push(("p", "read aloud")),
+					}
+					if app.acrostic {
+						acrostic_word = ui::acrostic(&ui::parse_markdown(&app.shown_version().text));
+						items.push(("a", &acrostic_word));
+					} else {
+						items.push(("a", "acrostic"));
 					}
 					// items.push(("ctrl+e", "edit"));
 					ui::render_status_bar(items)
@@ -73,510 +540,810 @@ fn main() -> Result<(), io::Error> {
 					("↑/↓", "select"),
 					("enter", "choose")
 				]),
-				app::AppMode::AuthorList | app::AppMode::LanguageList | app::AppMode::TitleList | app::AppMode::FilteredList => ui::render_status_bar(vec![
+				app::AppMode::AuthorList => ui::render_status_bar(vec![
 					("↑/↓", "select"),
 					("enter", "choose"),
+					("o", app.current_sort().label()),
+					("n", if app.sort_authors_by_surname { "sort by given name" } else { "sort by surname" }),
+					("w", "word frequency"),
+					("backspace", "back")
+				]),
+				app::AppMode::TitleList => ui::render_status_bar(vec![
+					("↑/↓", "select"),
+					("enter", "choose"),
+					("A-Z", "jump to letter"),
+					("o", app.current_sort().label()),
+					("backspace", "back")
+				]),
+				app::AppMode::Definition | app::AppMode::CommandOutput => ui::render_status_bar(vec![
+					("Esc", "close"),
+					("↑/↓", "scroll")
+				]),
+				app::AppMode::GitDiff => ui::render_status_bar(vec![
+					("↑/↓", "scroll"),
+					("c", "commit this file"),
+					("Esc", "close")
+				]),
+				app::AppMode::GitCommit => ui::render_status_bar(vec![
+					("enter", "commit"),
+					("Esc", "back to the diff")
+				]),
+				app::AppMode::Editing => match &app.editor {
+					Some(editor) if editor.quit_warned() => ui::render_status_bar(vec![
+						("q", "drop the changes"),
+						("ctrl+s", "save")
+					]),
+					Some(editor) if editor.mode == editor::EditorMode::Insert => ui::render_status_bar(vec![
+						("Esc", "normal mode"),
+						("ctrl+s", "save")
+					]),
+					_ => ui::render_status_bar(vec![
+						("i/a/o", "insert"),
+						("x", "delete character"),
+						("dd", "delete line"),
+						("u", "undo"),
+						("ctrl+s", "save"),
+						("q", "close")
+					]),
+				},
+				app::AppMode::EditMetadata => ui::render_status_bar(vec![
+					("↑/↓/tab", "field"),
+					if app.metadata_form.is_flag(app.metadata_form.selected) { ("space", "toggle") } else { ("type", "edit") },
+					("enter", "save"),
+					("Esc", "cancel")
+				]),
+				app::AppMode::Rename => ui::render_status_bar(vec![
+					("enter", "rename"),
+					("tab", "suggested/current name"),
+					("Esc", "cancel")
+				]),
+				app::AppMode::ConfirmDelete => ui::render_status_bar(vec![
+					("y", "delete"),
+					("n/Esc", "cancel")
+				]),
+				app::AppMode::Trash => ui::render_status_bar(vec![
+					("↑/↓", "select"),
+					("enter", "restore"),
+					("backspace", "back")
+				]),
+				app::AppMode::ConfirmMerge => ui::render_status_bar(vec![
+					("y", "merge"),
+					("n/Esc", "cancel")
+				]),
+				app::AppMode::Metadata => {
+					let mut items = vec![("Esc/i", "close")];
+					if app.see_also().iter().any(|(_, idx)| idx.is_some()) {
+						items.push(("1-9", "open a linked poem"));
+					}
+					ui::render_status_bar(items)
+				},
+				app::AppMode::WordFrequency => ui::render_status_bar(vec![
+					("↑/↓", "scroll"),
+					("backspace", "back to authors")
+				]),
+				app::AppMode::Statistics => ui::render_status_bar(vec![
+					("backspace", "back")
+				]),
+				app::AppMode::LoadErrors => ui::render_status_bar(vec![
+					("↑/↓", "scroll"),
+					("backspace", "back")
+				]),
+				app::AppMode::Onboarding => ui::render_status_bar(vec![
+					("c", "create the sample poem"),
+					("q", "quit")
+				]),
+				app::AppMode::FolderList => ui::render_status_bar(vec![
+					("↑/↓", "select"),
+					("enter", "open"),
+					("backspace", if app.folder.is_empty() { "back" } else { "parent folder" })
+				]),
+				app::AppMode::FacetList(_) => ui::render_status_bar(vec![
+					("↑/↓", "select"),
+					("enter", "choose"),
+					("backspace", "back")
+				]),
+				app::AppMode::LanguageList | app::AppMode::FilteredList => ui::render_status_bar(vec![
+					("↑/↓", "select"),
+					("enter", "choose"),
+					("o", app.current_sort().label()),
 					("backspace", "back")
 				]),
 				_ => ui::render_status_bar(vec![]),
 			};
 			if app.mode == app::AppMode::Search {
-				let items: Vec<ListItem> = app.search_results.iter().map(|&idx| {
+				let height = chunks[0].height.saturating_sub(2) as usize;
+				let selected = app.search_list_state.selected();
+				let window = ui::list_window(app.search_list_state.offset(), selected.unwrap_or(0), app.search_results.len(), height);
+				let items: Vec<ListItem> = app.search_results[window.clone()].iter().map(|&idx| {
 					let poem = &app.poems[idx];
 					if let Some(canonical) = poem.canonical() {
 						let author = canonical.author.as_deref().unwrap_or("Unknown");
 						let title = canonical.title.as_deref().unwrap_or("Untitled");
-						ListItem::new(format!("{} - {}", author, title))
+						ListItem::new(format!("{}{} - {}", ui::read_marker(app.is_read(idx)), author, title))
 					} else {
 						ListItem::new("Unknown poem".to_string())
 					}
 				}).collect();
 				let search_list = List::new(items)
-					.block(Block::default().title(Span::styled(format!("Search: {} ", app.search_query), Style::default().fg(Color::Yellow))).borders(Borders::ALL))
-					.style(Style::default().fg(Color::White))
-					.highlight_style(Style::default().fg(Color::Black).bg(Color::White));
-				f.render_stateful_widget(search_list, chunks[0], &mut app.search_list_state);
+					.block(Block::default().title(Span::styled(format!("Search: {} ", app.search_query), theme.heading)).borders(Borders::ALL).border_set(terminal::border()))
+					.style(theme.text)
+					.highlight_style(theme.highlight);
+				let mut list_state = ListState::default().with_selected(selected.map(|i| i - window.start));
+				f.render_stateful_widget(search_list, chunks[0], &mut list_state);
+				*app.search_list_state.offset_mut() = window.start;
+				ui::render_list_scrollbar(f, chunks[0], app.search_results.len(), selected.unwrap_or(0));
 			}
 			match app.mode {
-				app::AppMode::Viewing | app::AppMode::VersionSelect => {
-					let version = app.get_current_version();
-					let mut poem_text = String::new();
-					if let Some(epigraph) = &version.epigraph {
-						poem_text.push_str(epigraph);
-						poem_text.push('\n');
-					}
-					poem_text.push_str(&ui::render_poem_text(version));
-					let alignment = if version.rtl.unwrap_or(false) {
+				app::AppMode::Viewing | app::AppMode::VersionSelect | app::AppMode::Definition | app::AppMode::Metadata | app::AppMode::ConfirmMerge | app::AppMode::Rename
+					| app::AppMode::ConfirmDelete | app::AppMode::EditMetadata | app::AppMode::GitDiff | app::AppMode::GitCommit | app::AppMode::CommandOutput => {
+					let version = app.shown_version();
+					let version = version.as_ref();
+					// In the language of the version where the poem gives the author's name in it
+					let author = app.poems[app.current_poem].author_for(version);
+					let max_scroll;
+					let alignment = if app.shows_rtl() {
 						ratatui::layout::Alignment::Right
 					} else {
 						ratatui::layout::Alignment::Left
 					};
-					// Use the overall chunk height to compute an approximate viewport height
-					let viewport_height = chunks[0].height.saturating_sub(2) as usize;
-					let total_lines = poem_text.lines().count();
-					let max_scroll = total_lines.saturating_sub(viewport_height) as u16;
-					let scroll_offset = app.scroll_position.min(max_scroll);
-
-					// Check if we're in vertical + RTL mode for special title handling
-					let is_vertical_rtl = version.vertical.unwrap_or(false) && version.rtl.unwrap_or(false);
-					
-					if is_vertical_rtl {
-						// For vertical RTL: display title/author overlapping right border
-						// Adjust the poem block area to leave space for vertical title on right
-						let adjusted_area = Rect {
-							x: chunks[0].x,
-							y: chunks[0].y,
-							width: chunks[0].width.saturating_sub(2), // Make room for vertical title (2 chars for full-width)
-							height: chunks[0].height,
-						};
-						let poem_block = Block::default().borders(Borders::ALL);
-						let inner_area = poem_block.inner(adjusted_area);
+
+					// Shown bottom-left when the version has a recording attached
+					let audio_label = match (&version.audio, &app.recording) {
+						(Some(_), Some(_)) => Some(Title::from(Span::styled(format!(" {} playing ", terminal::symbol("▶", ">")), Style::default().fg(Color::Green)))),
+						(Some(_), None) => Some(Title::from(format!(" {} ", terminal::symbol("♪", "~")))),
+						(None, _) => None,
+					};
+
+					let notes: Vec<String> = version.language.as_deref().map(|code| language_label(code).to_string()).into_iter()
+						.chain(app.version_label())
+						.chain(app.shown_text_variant().cloned())
+						.chain(app.is_preferred_pick().then(|| "preferred".to_string()))
+						.collect();
+					let notes = (!notes.is_empty()).then(|| Span::styled(format!("[{}] ", notes.join(terminal::symbol(" · ", ", "))), Style::default().fg(Color::DarkGray)));
+
+					if app.shows_vertical() {
+						// Laid out as a printed page: the title in a column on the right, the
+						// poem's columns next to it, and the rest of the title along the top
+						let mut top = Line::from(vec![Span::raw(ui::title_notes(version)), Span::raw(" ")]);
+						top.spans.extend(notes);
+						let mut poem_block = Block::default().title(Title::from(top).alignment(Alignment::Right)).borders(Borders::ALL).border_set(terminal::border())
+							.title(Title::from(format!(" {} ", version.counts.summary())).position(Position::Bottom).alignment(Alignment::Left));
+						if let Some(label) = audio_label {
+							poem_block = poem_block.title(label.position(Position::Bottom).alignment(Alignment::Right));
+						}
+						let inner_area = poem_block.inner(poem_area);
+						let heading = ui::vertical_heading(version.title.as_deref().unwrap_or("Untitled"), author, inner_area.height as usize);
+						let heading_width = heading.first().map_or(0, |row| display_width(row)) as u16;
 						let content_chunks = Layout::default()
 							.direction(Direction::Horizontal)
-							.constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+							.constraints([Constraint::Min(1), Constraint::Length(2), Constraint::Length(heading_width)].as_ref())
 							.split(inner_area);
-						
-						let actual_viewport_height = content_chunks[0].height as usize;
-						let max_width = content_chunks[0].width as usize;
-						let options = textwrap::Options::new(max_width)
-							.subsequent_indent("  ");
-						let wrapped_text: String = poem_text.lines()
-							.map(|line| {
-								if line.trim().is_empty() {
-									String::new()
-								} else {
-									textwrap::fill(line, options.clone())
-								}
-							})
-							.collect::<Vec<_>>()
-							.join("\n");
-						
-						// Render poem content
-						let poem_para = Paragraph::new(wrapped_text)
-							.style(Style::default().fg(Color::White))
-							.alignment(alignment)
-							.scroll((scroll_offset, 0));
-						f.render_widget(poem_block.clone(), adjusted_area);
-						f.render_widget(poem_para, content_chunks[0]);
-						
-						// Render vertical title overlapping the right border
-						let author = version.author.as_deref().unwrap_or("Unknown");
-						let title = version.title.as_deref().unwrap_or("Untitled");
-						let vertical_title_text = ui::render_vertical_rtl_title(author, title);
-						let title_lines: Vec<&str> = vertical_title_text.lines().collect();
-						
-						// Position title to start at top in the space we made
-						let title_start_y = chunks[0].y + 1; // Start at top, just below the top border
-						let title_x = chunks[0].x + chunks[0].width - 3; // Position to interrupt the border
-						
-						for (i, line) in title_lines.iter().enumerate() {
-							if (title_start_y + i as u16) < (chunks[0].y + chunks[0].height - 1) {
-								let title_area = Rect {
-									x: title_x,
-									y: title_start_y + i as u16,
-									width: 2,
-									height: 1,
-								};
-								let char_para = Paragraph::new(*line)
-									.style(Style::default().fg(Color::Yellow));
-								f.render_widget(char_para, title_area);
-							}
-						}
-						
-						// Render scrollbar if needed - position it in the original area
-						if total_lines > actual_viewport_height {
-							let content_length = total_lines.saturating_sub(actual_viewport_height).saturating_add(1);
-							let mut scrollbar_state = ScrollbarState::new(content_length)
-								.position(app.scroll_position as usize)
-								.viewport_content_length(actual_viewport_height);
-							let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
-								.begin_symbol(Some("▲"))
-								.end_symbol(Some("▼"))
-								.thumb_symbol("▐")
-								.track_symbol(Some("│"));
-							f.render_stateful_widget(scrollbar, content_chunks[1], &mut scrollbar_state);
-						}
+						f.render_widget(poem_block, poem_area);
+						f.render_widget(Paragraph::new(heading.into_iter().map(Line::from).collect::<Vec<_>>()).style(theme.heading), content_chunks[2]);
+						// Its columns are as tall as the area; those that do not fit across are scrolled to
+						let poem = ui::VerticalPoem::new(version).style(theme.text).scroll(app.scroll_position as usize);
+						max_scroll = poem.overflow(content_chunks[0]);
+						f.render_widget(poem, content_chunks[0]);
 					} else {
-						// Normal mode: title on top as before
-						let title = Line::from(vec![
+						let mut title = Line::from(vec![
 							Span::raw(" "),
-							Span::styled(version.author.as_deref().unwrap_or("Unknown"), Style::default().fg(Color::Yellow)),
+							Span::styled(author.unwrap_or("Unknown"), theme.heading),
 							Span::raw(" - "),
-							Span::styled(version.title.as_deref().unwrap_or("Untitled"), Style::default().fg(Color::Yellow)),
+							Span::styled(version.title.as_deref().unwrap_or("Untitled"), theme.heading),
+							Span::raw(ui::title_notes(version)),
 							Span::raw(" ")
 						]);
-						let poem_block = Block::default().title(title).borders(Borders::ALL);
-						let inner_area = poem_block.inner(chunks[0]);
+						title.spans.extend(notes);
+						// RTL poems get the layout mirrored: title on the right, reading from
+						// the right, and the scrollbar and line counts on the left
+						let rtl = app.shows_rtl();
+						let (start, end) = if rtl { (Alignment::Right, Alignment::Left) } else { (Alignment::Left, Alignment::Right) };
+						let title = if rtl { ui::mirrored_line(title) } else { title };
+						let mut poem_block = Block::default().title(Title::from(title).alignment(start)).borders(Borders::ALL).border_set(terminal::border())
+							.title(Title::from(format!(" {} ", version.counts.summary())).position(Position::Bottom).alignment(end));
+						if let Some(label) = audio_label {
+							poem_block = poem_block.title(label.position(Position::Bottom).alignment(start));
+						}
+						let inner_area = poem_block.inner(poem_area);
 						let content_chunks = Layout::default()
 							.direction(Direction::Horizontal)
-							.constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+							.constraints(if rtl { [Constraint::Length(1), Constraint::Min(1)] } else { [Constraint::Min(1), Constraint::Length(1)] })
 							.split(inner_area);
-						let actual_viewport_height = content_chunks[0].height as usize;
-						let max_width = content_chunks[0].width as usize;
-						let options = textwrap::Options::new(max_width)
-							.subsequent_indent("  ");
-						let wrapped_text: String = poem_text.lines()
-							.map(|line| {
-								if line.trim().is_empty() {
-									String::new()
-								} else {
-									textwrap::fill(line, options.clone())
-								}
-							})
-							.collect::<Vec<_>>()
-							.join("\n");
-						let poem_para = Paragraph::new(wrapped_text)
-							.style(Style::default().fg(Color::White))
-							.alignment(alignment)
-							.scroll((scroll_offset, 0));
-						f.render_widget(poem_block.clone(), chunks[0]);
-						f.render_widget(poem_para, content_chunks[0]);
+						let (text_area, scrollbar_area) = if rtl { (content_chunks[1], content_chunks[0]) } else { (content_chunks[0], content_chunks[1]) };
+						let actual_viewport_height = text_area.height as usize;
+						let max_width = text_area.width as usize;
+						f.render_widget(poem_block.clone(), poem_area);
+						let scroll_position = app.scroll_position as usize;
+						let wrapped_text = app.wrapped_lines(max_width);
+						// Scrolled by the lines on screen, which wrapping made more of; only those
+						// are handed over to be drawn
+						let total_lines = wrapped_text.len();
+						max_scroll = total_lines.saturating_sub(actual_viewport_height);
+						let first = scroll_position.min(max_scroll);
+						let visible = wrapped_text[first..(first + actual_viewport_height).min(total_lines)].to_vec();
+						let poem_para = Paragraph::new(visible)
+							.style(theme.text)
+							.alignment(alignment);
+						f.render_widget(poem_para, text_area);
 						if total_lines > actual_viewport_height {
 							let content_length = total_lines.saturating_sub(actual_viewport_height).saturating_add(1);
 							let mut scrollbar_state = ScrollbarState::new(content_length)
 								.position(app.scroll_position as usize)
 								.viewport_content_length(actual_viewport_height);
-							let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
-								.begin_symbol(Some("▲"))
-								.end_symbol(Some("▼"))
-								.thumb_symbol("▐")
-								.track_symbol(Some("│"));
-							f.render_stateful_widget(scrollbar, content_chunks[1], &mut scrollbar_state);
+							let scrollbar = Scrollbar::new(if rtl { ScrollbarOrientation::VerticalLeft } else { ScrollbarOrientation::VerticalRight })
+								.begin_symbol(Some(terminal::symbol("▲", "^")))
+								.end_symbol(Some(terminal::symbol("▼", "v")))
+								.thumb_symbol(if rtl { terminal::symbol("▌", "#") } else { terminal::symbol("▐", "#") })
+								.track_symbol(Some(terminal::symbol("│", "|")));
+							f.render_stateful_widget(scrollbar, scrollbar_area, &mut scrollbar_state);
 						}
 					}
+					app.max_scroll = max_scroll as u16;
 				},
 				app::AppMode::Menu => {
-					let items = vec![
-						ListItem::new(format!("Browse by author ({})", app.author_counts.len())),
-						ListItem::new(format!("Browse by language ({})", app.language_counts.len())),
-						ListItem::new(format!("Browse by title ({})", app.poems.len())),
-						ListItem::new(format!("Search ({})", app.poems.len())),
-						ListItem::new("Random poem"),
-					];
+					let items: Vec<ListItem> = app.menu_entries().into_iter().map(|entry| match entry {
+						app::MenuEntry::Authors => ListItem::new(format!("{} ({})", tr("Browse by author"), app.author_counts.len())),
+						app::MenuEntry::Languages => ListItem::new(format!("{} ({})", tr("Browse by language"), app.language_counts.len())),
+						app::MenuEntry::Titles => ListItem::new(format!("{} ({})", tr("Browse by title"), app.poems.len())),
+						app::MenuEntry::Folders => ListItem::new(tr("Browse by folder")),
+						app::MenuEntry::Facet(facet) => ListItem::new(format!("{} ({})", tr(&app.facets[facet].menu_label()), app.facet_group_count(facet))),
+						app::MenuEntry::Search => ListItem::new(format!("{} ({})", tr("Search"), app.poems.len())),
+						app::MenuEntry::Random => ListItem::new(tr("Random poem")),
+						app::MenuEntry::Unread => ListItem::new(format!("{} ({})", tr("Unread poems"), app.unread_count())),
+						app::MenuEntry::Statistics => ListItem::new(tr("Statistics")),
+						app::MenuEntry::LoadErrors => ListItem::new(Span::styled(format!("{} ({})", tr("Load errors"), app.load_errors.len()), Style::default().fg(Color::Red))),
+						app::MenuEntry::Trash => ListItem::new(format!("{} ({})", tr("Trash"), app.trashed.len())),
+						app::MenuEntry::SyncConflicts => ListItem::new(Span::styled(format!("{} ({})", tr("Sync conflicts"), app.sync_conflicts.len()), Style::default().fg(Color::Red))),
+						app::MenuEntry::Sync => ListItem::new(tr("Sync library")),
+					}).collect();
 					let menu = List::new(items)
-						.block(Block::default().title(Span::styled("Menu", Style::default().fg(Color::Yellow))).borders(Borders::ALL))
-						.style(Style::default().fg(Color::White))
-						.highlight_style(Style::default().fg(Color::Black).bg(Color::White));
+						.block(Block::default().title(Span::styled(tr("Menu"), theme.heading)).borders(Borders::ALL).border_set(terminal::border()))
+						.style(theme.text)
+						.highlight_style(theme.highlight);
 					f.render_stateful_widget(menu, chunks[0], &mut app.menu_state);
 				},
 				app::AppMode::TitleList => {
 					let titles = app.get_sorted_titles();
-					let items: Vec<ListItem> = titles.iter().map(|(_, title)| ListItem::new(title.clone())).collect();
-					let title_list = List::new(items).block(Block::default().title(Span::styled("Titles", Style::default().fg(Color::Yellow))).borders(Borders::ALL)).style(Style::default().fg(Color::White)).highlight_style(Style::default().fg(Color::Black).bg(Color::White));
-					f.render_stateful_widget(title_list, chunks[0], &mut app.title_list_state);
+					// Split the inner width between the two columns, leaving one cell of spacing
+					let inner_width = chunks[0].width.saturating_sub(3) as usize;
+					let title_width = inner_width * 3 / 5;
+					let author_width = inner_width - title_width;
+					// Section headers are interleaved with the titles, so the selected
+					// title has to be mapped onto its row in the table.
+					let selected = app.title_list_state.selected().unwrap_or(0);
+					let selected_row = app.title_rows.iter().position(|row| *row == app::TitleRow::Title(selected)).unwrap_or(0);
+					let height = chunks[0].height.saturating_sub(2) as usize;
+					let window = ui::list_window(app.title_list_state.offset(), selected_row, app.title_rows.len(), height);
+					let rows: Vec<Row> = app.title_rows[window.clone()].iter().map(|row| match row {
+						app::TitleRow::Header(group) => Row::new(vec![Cell::from(Span::styled(group.clone(), theme.heading))]),
+						app::TitleRow::Title(i) => {
+							let (idx, title) = &titles[*i];
+							let author = app.poems[*idx].canonical().and_then(|v| v.author.as_deref()).unwrap_or("Unknown");
+							let badge = format!("{}{}", ui::version_badge(app.poems[*idx].versions.len()), app.list_suffix(*idx));
+							Row::new(vec![
+								Cell::from(Line::from(vec![
+									Span::raw(ui::truncate(&format!("{}{}", ui::read_marker(app.is_read(*idx)), title), title_width.saturating_sub(display_width(&badge)))),
+									Span::styled(badge, Style::default().fg(Color::DarkGray)),
+								])),
+								Cell::from(Span::styled(ui::truncate(author, author_width), Style::default().fg(Color::Gray))),
+							])
+						},
+					}).collect();
+					let widths = [Constraint::Length(title_width as u16), Constraint::Length(author_width as u16)];
+					let title_table = Table::new(rows)
+						.widths(&widths)
+						.column_spacing(1)
+						.block(Block::default().title(Span::styled(tr("Titles"), theme.heading)).borders(Borders::ALL).border_set(terminal::border()))
+						.style(theme.text)
+						.highlight_style(theme.highlight);
+					let mut table_state = TableState::default().with_selected(Some(selected_row - window.start));
+					f.render_stateful_widget(title_table, chunks[0], &mut table_state);
+					*app.title_list_state.offset_mut() = window.start;
+					ui::render_list_scrollbar(f, chunks[0], app.title_rows.len(), selected_row);
 				},
 				app::AppMode::AuthorList => {
 					let authors = app.get_sorted_authors();
-					let items: Vec<ListItem> = authors.iter().map(|author| ListItem::new(format!("{} ({})", author, app.author_counts[author]))).collect();
-					let author_list = List::new(items).block(Block::default().title(Span::styled("Authors", Style::default().fg(Color::Yellow))).borders(Borders::ALL)).style(Style::default().fg(Color::White)).highlight_style(Style::default().fg(Color::Black).bg(Color::White));
-					f.render_stateful_widget(author_list, chunks[0], &mut app.author_list_state);
+					let height = chunks[0].height.saturating_sub(2) as usize;
+					let selected = app.author_list_state.selected();
+					let window = ui::list_window(app.author_list_state.offset(), selected.unwrap_or(0), authors.len(), height);
+					let items: Vec<ListItem> = authors[window.clone()].iter().map(|author| ListItem::new(format!("{} ({})", author, app.author_counts[author]))).collect();
+					let author_list = List::new(items).block(Block::default().title(Span::styled(tr("Authors"), theme.heading)).borders(Borders::ALL).border_set(terminal::border())).style(theme.text).highlight_style(theme.highlight);
+					let mut list_state = ListState::default().with_selected(selected.map(|i| i - window.start));
+					let total = authors.len();
+					f.render_stateful_widget(author_list, chunks[0], &mut list_state);
+					*app.author_list_state.offset_mut() = window.start;
+					ui::render_list_scrollbar(f, chunks[0], total, selected.unwrap_or(0));
 				},
 				app::AppMode::LanguageList => {
 					let languages = app.get_sorted_languages();
-					let items: Vec<ListItem> = languages.iter()
+					let height = chunks[0].height.saturating_sub(2) as usize;
+					let selected = app.language_list_state.selected();
+					let window = ui::list_window(app.language_list_state.offset(), selected.unwrap_or(0), languages.len(), height);
+					let items: Vec<ListItem> = languages[window.clone()].iter()
 						.map(|lang| {
-							let display_name = get_language_name(lang).unwrap_or(lang);
-							ListItem::new(format!("{} ({})", display_name, app.language_counts[lang]))
+							ListItem::new(format!("{} ({})", script_language_label(lang), app.language_counts[lang]))
 						})
 						.collect();
-				
+
 					let language_list = List::new(items)
 						.block(Block::default()
-							.title(Span::styled("Languages", Style::default().fg(Color::Yellow)))
-							.borders(Borders::ALL))
-						.style(Style::default().fg(Color::White))
-						.highlight_style(Style::default().fg(Color::Black).bg(Color::White));
-				
-					f.render_stateful_widget(language_list, chunks[0], &mut app.language_list_state);
-				},				
+							.title(Span::styled(tr("Languages"), theme.heading))
+							.borders(Borders::ALL).border_set(terminal::border()))
+						.style(theme.text)
+						.highlight_style(theme.highlight);
+
+					let mut list_state = ListState::default().with_selected(selected.map(|i| i - window.start));
+					let total = languages.len();
+					f.render_stateful_widget(language_list, chunks[0], &mut list_state);
+					*app.language_list_state.offset_mut() = window.start;
+					ui::render_list_scrollbar(f, chunks[0], total, selected.unwrap_or(0));
+				},
+				app::AppMode::FacetList(facet) => {
+					let height = chunks[0].height.saturating_sub(2) as usize;
+					let selected = app.facet_list_state.selected();
+					let total = app.facet_groups.len();
+					let window = ui::list_window(app.facet_list_state.offset(), selected.unwrap_or(0), total, height);
+					let items: Vec<ListItem> = app.facet_groups[window.clone()].iter()
+						.map(|(group, poems)| ListItem::new(format!("{} ({})", group, poems.len())))
+						.collect();
+					let facet_list = List::new(items)
+						.block(Block::default().title(Span::styled(tr(&app.facets[facet].list_title()).to_string(), theme.heading)).borders(Borders::ALL).border_set(terminal::border()))
+						.style(theme.text)
+						.highlight_style(theme.highlight);
+					let mut list_state = ListState::default().with_selected(selected.map(|i| i - window.start));
+					f.render_stateful_widget(facet_list, chunks[0], &mut list_state);
+					*app.facet_list_state.offset_mut() = window.start;
+					ui::render_list_scrollbar(f, chunks[0], total, selected.unwrap_or(0));
+				},
 				app::AppMode::FilteredList => {
 					if let Some(indices) = &app.filtered_poems {
-						let items: Vec<ListItem> = indices.iter().map(|&idx| {
-							let poem = &app.poems[idx];
-							let display_text = match app.previous_mode {
-								Some(app::AppMode::AuthorList) => {
-									if let Some(canonical) = poem.canonical() {
-										canonical.title.as_deref().unwrap_or("Untitled").to_string()
-									} else {
-										"Untitled".to_string()
-									}
-								},
-								Some(app::AppMode::LanguageList) => {
-									if let Some(lang_index) = app.language_list_state.selected() {
-										let languages = app.get_sorted_languages();
-										if let Some(language) = languages.get(lang_index) {
-											let (version, _found) = app.get_version_in_language(idx, language);
-											let author = version.author.as_deref().unwrap_or("Unknown");
-											let title = version.title.as_deref().unwrap_or("Untitled");
-											format!("{} - {}", author, title)
-										} else {
-											if let Some(canonical) = poem.canonical() {
-												let author = canonical.author.as_deref().unwrap_or("Unknown");
-												let title = canonical.title.as_deref().unwrap_or("Untitled");
-												format!("{} - {}", author, title)
-											} else {
-												"Unknown - Untitled".to_string()
-											}
-										}
-									} else {
-										if let Some(canonical) = poem.canonical() {
-											let author = canonical.author.as_deref().unwrap_or("Unknown");
-											let title = canonical.title.as_deref().unwrap_or("Untitled");
-											format!("{} - {}", author, title)
-										} else {
-											"Unknown - Untitled".to_string()
-										}
-									}
-								},
-								_ => {
-									if let Some(canonical) = poem.canonical() {
-										let author = canonical.author.as_deref().unwrap_or("Unknown");
-										let title = canonical.title.as_deref().unwrap_or("Untitled");
-										format!("{} - {}", author, title)
-									} else {
-										"Unknown - Untitled".to_string()
-									}
+						let language = app.language_list_state.selected().and_then(|i| app.get_sorted_languages().get(i));
+						let height = chunks[0].height.saturating_sub(2) as usize;
+						let selected = app.filtered_list_state.selected();
+						let window = ui::list_window(app.filtered_list_state.offset(), selected.unwrap_or(0), indices.len(), height);
+						let items: Vec<ListItem> = indices[window.clone()].iter().map(|&idx| {
+							let version = match (&app.previous_mode, language) {
+								(Some(app::AppMode::LanguageList), Some(language)) => Some(app.get_version_in_language(idx, language).0),
+								(Some(app::AppMode::FacetList(_)), _) => app.facet_version(idx).and_then(|key| app.poems[idx].versions.get(key)),
+								_ => app.poems[idx].canonical(),
+							};
+							let display_text = match (&app.previous_mode, version) {
+								(Some(app::AppMode::AuthorList), Some(version)) => version.title.as_deref().unwrap_or("Untitled").to_string(),
+								(Some(app::AppMode::AuthorList), None) => "Untitled".to_string(),
+								(_, Some(version)) => {
+									let author = version.author.as_deref().unwrap_or("Unknown");
+									let title = version.title.as_deref().unwrap_or("Untitled");
+									format!("{} - {}", author, title)
 								},
+								(_, None) => "Unknown - Untitled".to_string(),
 							};
-							ListItem::new(display_text)
+							ListItem::new(Line::from(vec![
+								Span::raw(ui::read_marker(app.is_read(idx))),
+								Span::raw(display_text),
+								// Every entry of a language's list is in that language
+								Span::styled(match (&app.previous_mode, version.and_then(|v| v.language.as_deref())) {
+									(Some(app::AppMode::LanguageList), _) | (_, None) => String::new(),
+									(_, Some(code)) => format!("{}{}", terminal::symbol(" · ", " - "), language_label(code)),
+								}, Style::default().fg(Color::DarkGray)),
+								Span::styled(ui::version_badge(app.poems[idx].versions.len()), Style::default().fg(Color::DarkGray)),
+								Span::styled(app.list_suffix(idx), Style::default().fg(Color::DarkGray)),
+							]))
 						}).collect();
-						let filtered_list = List::new(items).block(Block::default().title(Span::styled(app.get_filtered_list_title(), Style::default().fg(Color::Yellow))).borders(Borders::ALL)).style(Style::default().fg(Color::White)).highlight_style(Style::default().fg(Color::Black).bg(Color::White));
-						f.render_stateful_widget(filtered_list, chunks[0], &mut app.filtered_list_state);
+						let total = indices.len();
+						let filtered_list = List::new(items).block(Block::default().title(Span::styled(app.get_filtered_list_title(), theme.heading)).borders(Borders::ALL).border_set(terminal::border())).style(theme.text).highlight_style(theme.highlight);
+						let mut list_state = ListState::default().with_selected(selected.map(|i| i - window.start));
+						f.render_stateful_widget(filtered_list, chunks[0], &mut list_state);
+						*app.filtered_list_state.offset_mut() = window.start;
+						ui::render_list_scrollbar(f, chunks[0], total, selected.unwrap_or(0));
 					}
 				}
+				app::AppMode::Statistics => {
+					let stats = stats::Stats::compute(&app.poems, &app.history);
+					let heading = theme.heading;
+					let mut lines = vec![
+						Line::from(Span::styled("Library", heading)),
+						Line::from(format!("  Poems:      {}", stats.poems)),
+						Line::from(format!("  Authors:    {}", stats.authors)),
+						Line::from(format!("  Languages:  {}", stats.languages)),
+						Line::from(format!("  Versions:   {}", stats.versions)),
+						Line::from(format!("  Lines:      {}", stats.lines)),
+						Line::from(format!("  Words:      {}", stats.words)),
+						Line::from(""),
+						Line::from(Span::styled("Reading", heading)),
+						Line::from(format!("  Poems read:      {} of {}", stats.poems_read, stats.poems)),
+						Line::from(format!("  Read this week:  {}", stats.read_this_week)),
+						Line::from(format!("  Current streak:  {} day(s)", stats.streak_days)),
+					];
+					if !stats.top_authors.is_empty() {
+						lines.push(Line::from(""));
+						lines.push(Line::from(Span::styled("Most-read authors", heading)));
+						for (author, reads) in &stats.top_authors {
+							lines.push(Line::from(format!("  {} ({})", author, reads)));
+						}
+					}
+					let statistics = Paragraph::new(lines)
+						.block(Block::default().title(Span::styled(tr("Statistics"), heading)).borders(Borders::ALL).border_set(terminal::border()))
+						.style(theme.text);
+					f.render_widget(statistics, chunks[0]);
+				},
+				app::AppMode::WordFrequency => {
+					let author = app.author_list_state.selected().and_then(|i| app.get_sorted_authors().get(i)).cloned().unwrap_or_default();
+					let height = chunks[0].height.saturating_sub(2) as usize;
+					let selected = app.word_frequency_state.selected();
+					let window = ui::list_window(app.word_frequency_state.offset(), selected.unwrap_or(0), app.word_frequencies.len(), height);
+					let max_count = app.word_frequencies.first().map_or(1, |(_, count)| *count);
+					let bar_width = (chunks[0].width as usize).saturating_sub(30);
+					let items: Vec<ListItem> = app.word_frequencies[window.clone()].iter().map(|(word, count)| {
+						let bar = terminal::symbol("▇", "#").repeat((count * bar_width / max_count).max(1));
+						ListItem::new(Line::from(vec![
+							Span::raw(format!("{:<16} {:>5} ", ui::truncate(word, 16), count)),
+							Span::styled(bar, Style::default().fg(Color::DarkGray)),
+						]))
+					}).collect();
+					let total = app.word_frequencies.len();
+					let list = List::new(items)
+						.block(Block::default().title(Span::styled(format!("Word frequency: {}", author), theme.heading)).borders(Borders::ALL).border_set(terminal::border()))
+						.style(theme.text)
+						.highlight_style(theme.highlight);
+					let mut list_state = ListState::default().with_selected(selected.map(|i| i - window.start));
+					f.render_stateful_widget(list, chunks[0], &mut list_state);
+					*app.word_frequency_state.offset_mut() = window.start;
+					ui::render_list_scrollbar(f, chunks[0], total, selected.unwrap_or(0));
+				},
+				app::AppMode::LoadErrors => {
+					let mut lines = Vec::new();
+					for error in &app.load_errors {
+						lines.push(Line::from(Span::styled(error.filename.as_str(), theme.heading)));
+						lines.push(Line::from(format!("  {}", error.reason)));
+						lines.push(Line::from(""));
+					}
+					lines.push(Line::from(Span::styled("`leaves doctor` checks the whole library from the command line", Style::default().fg(Color::DarkGray))));
+					let errors = Paragraph::new(lines)
+						.wrap(Wrap { trim: false })
+						.scroll((app.load_errors_scroll, 0))
+						.block(Block::default().title(Span::styled("Skipped poem files", theme.heading)).borders(Borders::ALL).border_set(terminal::border()))
+						.style(theme.text);
+					f.render_widget(errors, chunks[0]);
+				},
+				app::AppMode::FolderList => {
+					let height = chunks[0].height.saturating_sub(2) as usize;
+					let selected = app.folder_list_state.selected();
+					let window = ui::list_window(app.folder_list_state.offset(), selected.unwrap_or(0), app.folder_entries.len(), height);
+					let items: Vec<ListItem> = app.folder_entries[window.clone()].iter().map(|entry| match entry {
+						app::FolderEntry::Folder(name, count) => ListItem::new(Line::from(vec![
+							Span::styled(format!("{}/", name), Style::default().fg(Color::Cyan)),
+							Span::styled(format!(" ({})", count), Style::default().fg(Color::DarkGray)),
+						])),
+						app::FolderEntry::Poem(idx) => {
+							let version = app.poems[*idx].canonical();
+							let author = version.and_then(|v| v.author.as_deref()).unwrap_or("Unknown");
+							let title = version.and_then(|v| v.title.as_deref()).unwrap_or("Untitled");
+							ListItem::new(Line::from(vec![
+								Span::raw(ui::read_marker(app.is_read(*idx))),
+								Span::raw(format!("{} - {}", author, title)),
+								Span::styled(ui::version_badge(app.poems[*idx].versions.len()), Style::default().fg(Color::DarkGray)),
+								Span::styled(app.list_suffix(*idx), Style::default().fg(Color::DarkGray)),
+							]))
+						},
+					}).collect();
+					let total = app.folder_entries.len();
+					let title = if app.folder.is_empty() { "Folders".to_string() } else { format!("Folders: {}", app.folder) };
+					let folder_list = List::new(items)
+						.block(Block::default().title(Span::styled(title, theme.heading)).borders(Borders::ALL).border_set(terminal::border()))
+						.style(theme.text)
+						.highlight_style(theme.highlight);
+					let mut list_state = ListState::default().with_selected(selected.map(|i| i - window.start));
+					f.render_stateful_widget(folder_list, chunks[0], &mut list_state);
+					*app.folder_list_state.offset_mut() = window.start;
+					ui::render_list_scrollbar(f, chunks[0], total, selected.unwrap_or(0));
+				},
+				app::AppMode::Editing => {
+					if let Some(editor) = app.editor.as_mut() {
+						let block = Block::default()
+							.title(Span::styled(format!(" {} - {}{}{} ",
+								app.poems[app.current_poem].filename,
+								app.current_version,
+								if editor.modified { " [+]" } else { "" },
+								if editor.mode == editor::EditorMode::Insert { " -- INSERT --" } else { "" }), theme.heading))
+							.borders(Borders::ALL).border_set(terminal::border());
+						let inner = block.inner(chunks[0]);
+						editor.scroll_to_cursor(inner.height as usize);
+						let lines: Vec<Line> = editor.lines.iter().skip(editor.scroll).take(inner.height as usize).map(|line| Line::from(line.as_str())).collect();
+						f.render_widget(Paragraph::new(lines).style(theme.text).block(block), chunks[0]);
+						let before: String = editor.lines[editor.row].chars().take(editor.col).collect();
+						let x = inner.x + (display_width(&before) as u16).min(inner.width.saturating_sub(1));
+						f.set_cursor(x, inner.y + (editor.row - editor.scroll) as u16);
+					}
+				},
+				app::AppMode::Trash => {
+					let height = chunks[0].height.saturating_sub(2) as usize;
+					let selected = app.trash_list_state.selected();
+					let total = app.trashed.len();
+					let window = ui::list_window(app.trash_list_state.offset(), selected.unwrap_or(0), total, height);
+					let items: Vec<ListItem> = app.trashed[window.clone()].iter().map(|trashed| {
+						let name = library::original_name(trashed);
+						let mut spans = vec![Span::raw(name.to_string())];
+						if name != trashed {
+							spans.push(Span::styled(" (trashed again later)", Style::default().fg(Color::DarkGray)));
+						}
+						ListItem::new(Line::from(spans))
+					}).collect();
+					let trash_list = List::new(items)
+						.block(Block::default().title(Span::styled(format!("Trash: {}/{}", models::poems_dir().display(), library::TRASH_DIR), theme.heading)).borders(Borders::ALL).border_set(terminal::border()))
+						.style(theme.text)
+						.highlight_style(theme.highlight);
+					let mut list_state = ListState::default().with_selected(selected.map(|i| i - window.start));
+					f.render_stateful_widget(trash_list, chunks[0], &mut list_state);
+					*app.trash_list_state.offset_mut() = window.start;
+					ui::render_list_scrollbar(f, chunks[0], total, selected.unwrap_or(0));
+				},
+				app::AppMode::Onboarding => {
+					let heading = theme.heading;
+					let mut lines = vec![
+						Line::from(Span::styled("Welcome to leaves", heading)),
+						Line::from(""),
+						Line::from(format!("There are no poems in {} yet.", models::poems_dir().display())),
+						Line::from("Each poem is a YAML file ending in .poem, with a `canonical` version and any number of others (translations, other spellings):"),
+						Line::from(""),
+					];
+					let sample = models::poem_yaml(&[models::sample_poem()]).unwrap_or_default();
+					lines.extend(sample.lines().map(|line| Line::from(Span::styled(format!("  {}", line), Style::default().fg(Color::Gray)))));
+					lines.push(Line::from(""));
+					lines.push(Line::from(format!("Press c to save this one there, or set `library` in {} to read poems from another directory.", config::config_path().display())));
+					lines.push(Line::from("Run `leaves --demo` to try the reader on a few sample poems first."));
+					if !app.load_errors.is_empty() {
+						lines.push(Line::from(""));
+						lines.push(Line::from(Span::styled(format!("{} files in the library could not be read; `leaves doctor` tells why.", app.load_errors.len()), Style::default().fg(Color::Red))));
+					}
+					let onboarding = Paragraph::new(lines)
+						.wrap(Wrap { trim: false })
+						.block(Block::default().borders(Borders::ALL).border_set(terminal::border()))
+						.style(theme.text);
+					f.render_widget(onboarding, chunks[0]);
+				},
 				app::AppMode::Search => {} // No rendering here since search is handled separately
 			}
 
+			if let (app::AppMode::Definition, Some((word, definition))) = (&app.mode, &app.definition) {
+				let popup = popup_area(f.size(), 70, 60);
+				f.render_widget(Clear, popup);
+				let definition_para = Paragraph::new(definition.as_str())
+					.wrap(Wrap { trim: false })
+					.scroll((app.definition_scroll, 0))
+					.block(Block::default()
+						.title(Span::styled(format!(" {} ", word), theme.heading))
+						.borders(Borders::ALL)
+						.border_set(terminal::popup_border()));
+				f.render_widget(definition_para, popup);
+			}
+
+			if let (app::AppMode::CommandOutput, Some((command, output))) = (&app.mode, &app.command_output) {
+				let popup = popup_area(f.size(), 80, 70);
+				f.render_widget(Clear, popup);
+				let output = Paragraph::new(output.as_str())
+					.wrap(Wrap { trim: false })
+					.scroll((app.command_output_scroll, 0))
+					.style(theme.text)
+					.block(Block::default()
+						.title(Span::styled(format!(" {} ", command), theme.heading))
+						.borders(Borders::ALL)
+						.border_set(terminal::popup_border()));
+				f.render_widget(output, popup);
+			}
+
+			if app.mode == app::AppMode::Metadata {
+				let rows = app.metadata_rows();
+				let label_width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+				let lines: Vec<Line> = rows.into_iter().map(|(label, value)| Line::from(vec![
+					Span::styled(format!("{:>width$}  ", label, width = label_width), Style::default().fg(Color::DarkGray)),
+					Span::raw(value),
+				])).collect();
+				// Tall enough for every row plus the border, as far as the screen allows
+				let height_percent = ((lines.len() as u16 + 4) * 100 / f.size().height.max(1)).clamp(30, 90);
+				let popup = popup_area(f.size(), 70, height_percent);
+				f.render_widget(Clear, popup);
+				let metadata = Paragraph::new(lines)
+					.wrap(Wrap { trim: false })
+					.style(theme.text)
+					.block(Block::default()
+						.title(Span::styled(tr(" About this poem "), theme.heading))
+						.borders(Borders::ALL)
+						.border_set(terminal::popup_border()));
+				f.render_widget(metadata, popup);
+			}
+
+			if matches!(app.mode, app::AppMode::ConfirmMerge | app::AppMode::ConfirmDelete) {
+				let (title, preview) = if app.mode == app::AppMode::ConfirmMerge {
+					(" Merge poems? ", app.merge_preview())
+				} else {
+					(" Delete poem? ", app.delete_preview())
+				};
+				let lines: Vec<Line> = preview.into_iter().map(Line::from).collect();
+				let height_percent = ((lines.len() as u16 + 4) * 100 / f.size().height.max(1)).clamp(30, 90);
+				let popup = popup_area(f.size(), 70, height_percent);
+				f.render_widget(Clear, popup);
+				let preview = Paragraph::new(lines)
+					.wrap(Wrap { trim: false })
+					.style(theme.text)
+					.block(Block::default()
+						.title(Span::styled(title, theme.heading))
+						.borders(Borders::ALL)
+						.border_set(terminal::popup_border()));
+				f.render_widget(preview, popup);
+			}
+
+			if matches!(app.mode, app::AppMode::GitDiff | app::AppMode::GitCommit) {
+				let lines: Vec<Line> = app.git_diff.lines().map(|line| {
+					let style = if line.starts_with("+++") || line.starts_with("---") {
+						Style::default().fg(Color::DarkGray)
+					} else if line.starts_with('+') {
+						Style::default().fg(Color::Green)
+					} else if line.starts_with('-') {
+						Style::default().fg(Color::Red)
+					} else if line.starts_with("@@") {
+						Style::default().fg(Color::Cyan)
+					} else {
+						theme.text
+					};
+					Line::from(Span::styled(line.to_string(), style))
+				}).collect();
+				let popup = popup_area(f.size(), 80, 80);
+				f.render_widget(Clear, popup);
+				let diff = Paragraph::new(lines)
+					.scroll((app.git_diff_scroll, 0))
+					.block(Block::default()
+						.title(Span::styled(format!(" Changes to {} ", app.poems[app.current_poem].filename), theme.heading))
+						.borders(Borders::ALL)
+						.border_set(terminal::popup_border()));
+				f.render_widget(diff, popup);
+				if app.mode == app::AppMode::GitCommit {
+					let area = Rect { x: popup.x + 2, y: popup.y + popup.height.saturating_sub(4), width: popup.width.saturating_sub(4), height: 3 };
+					f.render_widget(Clear, area);
+					let input = Paragraph::new(format!("{}_", app.commit_input))
+						.style(theme.text)
+						.block(Block::default().title(" Commit message ").borders(Borders::ALL).border_set(terminal::border()));
+					f.render_widget(input, area);
+				}
+			}
+
+			if app.mode == app::AppMode::EditMetadata {
+				let form = &app.metadata_form;
+				let label_width = form.fields.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+				let lines: Vec<Line> = form.fields.iter().enumerate().map(|(i, (label, value))| {
+					let value = if i == form.selected && !form.is_flag(i) { format!("{}_", value) } else { value.clone() };
+					Line::from(vec![
+						Span::styled(format!("{:>width$}  ", label, width = label_width), Style::default().fg(Color::DarkGray)),
+						Span::styled(value, if i == form.selected { theme.highlight } else { theme.text }),
+					])
+				}).collect();
+				let height_percent = ((lines.len() as u16 + 4) * 100 / f.size().height.max(1)).clamp(30, 90);
+				let popup = popup_area(f.size(), 70, height_percent);
+				f.render_widget(Clear, popup);
+				let editor = Paragraph::new(lines)
+					.style(theme.text)
+					.block(Block::default()
+						.title(Span::styled(format!(" Edit version {} ", app.current_version), theme.heading))
+						.borders(Borders::ALL)
+						.border_set(terminal::popup_border()));
+				f.render_widget(editor, popup);
+			}
+
+			if app.mode == app::AppMode::Rename {
+				let lines = vec![
+					Line::from(Span::styled("Path inside the library; missing folders are created", Style::default().fg(Color::DarkGray))),
+					Line::from(""),
+					Line::from(format!("{}_", app.rename_input)),
+				];
+				let height_percent = ((lines.len() as u16 + 4) * 100 / f.size().height.max(1)).clamp(20, 90);
+				let popup = popup_area(f.size(), 70, height_percent);
+				f.render_widget(Clear, popup);
+				let rename = Paragraph::new(lines)
+					.wrap(Wrap { trim: false })
+					.style(theme.text)
+					.block(Block::default()
+						.title(Span::styled(format!(" Rename {} ", app.poems[app.current_poem].filename), theme.heading))
+						.borders(Borders::ALL)
+						.border_set(terminal::popup_border()));
+				f.render_widget(rename, popup);
+			}
+
 			if let app::AppMode::VersionSelect = app.mode {
 				let popup = popup_area(f.size(), 50, 40);
 				f.render_widget(Clear, popup);
 
-				let poem = &app.poems[app.current_poem];
-				let versions: Vec<String> = poem.versions.keys().cloned().collect();
-				let items: Vec<ListItem> = versions
-					.iter()
-					.map(|v| {
-						ListItem::new(v.to_string())
-					})
+				let items: Vec<ListItem> = app.version_keys(app.current_poem)
+					.into_iter()
+					.map(|key| ListItem::new(app.version_choice(app.current_poem, key)))
 					.collect();
 				let list = List::new(items)
 					.block(Block::default()
-						.title("Select Version")
+						.title(tr("Select Version"))
 						.borders(Borders::ALL)
-						.border_type(ratatui::widgets::BorderType::Double))
-					.highlight_style(Style::default().fg(Color::Black).bg(Color::White));
+						.border_set(terminal::popup_border()))
+					.highlight_style(theme.highlight);
 				f.render_stateful_widget(list, popup, &mut app.version_list_state);
 			}
 
-			f.render_widget(status_bar, chunks[1]);
+			// Above anything else, as no other key works until the task is done
+			if let Some((task, _)) = &app.task {
+				let state = if task.is_cancelled() { "Cancelling…".to_string() } else { format!("Running for {}s", task.started.elapsed().as_secs()) };
+				let popup = popup_area(f.size(), 60, 30);
+				let shown = popup.height.saturating_sub(4) as usize;
+				let mut lines = vec![Line::from(Span::styled(state, Style::default().fg(Color::DarkGray))), Line::from("")];
+				lines.extend(task.lines[task.lines.len().saturating_sub(shown)..].iter().map(|line| Line::from(line.as_str())));
+				f.render_widget(Clear, popup);
+				let progress = Paragraph::new(lines)
+					.wrap(Wrap { trim: false })
+					.style(theme.text)
+					.block(Block::default()
+						.title(Span::styled(format!(" {} ", task.title), theme.heading))
+						.borders(Borders::ALL)
+						.border_set(terminal::popup_border()));
+				f.render_widget(progress, popup);
+			}
+
+			match &app.status_message {
+				Some(message) => f.render_widget(Paragraph::new(message.as_str()).style(Style::default().fg(Color::Red)), chunks[1]),
+				None if app.task.is_some() => f.render_widget(ui::render_status_bar(vec![("Esc", "cancel")]), chunks[1]),
+				None => f.render_widget(status_bar, chunks[1]),
+			}
 		})?;
-		if let Event::Key(key) = event::read()? {
-			if app.mode == app::AppMode::Search {
-				match key.code {
-					KeyCode::Char(c) => {
-						if !key.modifiers.contains(KeyModifiers::CONTROL) {
-							app.search_query.push(c);
-							app.update_search_results();
-						}
-					},
-					KeyCode::Backspace => {
-						app.search_query.pop();
-						app.update_search_results();
-					},
-					KeyCode::Esc => {
-						app.mode = app::AppMode::Menu;
-					},
-					KeyCode::Enter => {
-						if let Some(index) = app.search_list_state.selected() {
-							if let Some(&poem_index) = app.search_results.get(index) {
-								app.current_poem = poem_index;
-								app.current_version = "canonical".to_string();
-								app.mode = app::AppMode::Viewing;
-							}
-						}
-					},
-					KeyCode::Up => {
-						if let Some(i) = app.search_list_state.selected() {
-							let new_i = if i == 0 { app.search_results.len().saturating_sub(1) } else { i - 1 };
-							app.search_list_state.select(Some(new_i));
-						}
-					},
-					KeyCode::Down => {
-						if let Some(i) = app.search_list_state.selected() {
-							let new_i = (i + 1) % app.search_results.len().max(1);
-							app.search_list_state.select(Some(new_i));
-						}
-					},
-					_ => {}
-				}
+		// The status bar is drawn before the poem finds out how far it can be scrolled
+		if app.mode == app::AppMode::Viewing && app.max_scroll != max_scroll {
+			continue;
+		}
+		#[cfg(feature = "images")]
+		match images.update(terminal.backend_mut(), image.as_deref().zip(image_area)) {
+			// An image went away: repaint everything so sixel pixels do not linger
+			Ok(true) => {
+				terminal.clear()?;
 				continue;
-			}
-			match key.code {
-				KeyCode::Char('q') => break,
-				KeyCode::Esc => {
-					if let app::AppMode::VersionSelect = app.mode {
-						app.mode = app::AppMode::Viewing;
-					}
-				}
-				KeyCode::Char('/') => {
-					app.mode = app::AppMode::Search;
-					app.search_query.clear();
-					app.search_results.clear();
-					app.update_search_results();
-					app.search_list_state.select(Some(0));
-				},
-				KeyCode::Backspace => {
-					match app.mode {
-						app::AppMode::Viewing => {
-							if app.filtered_poems.is_some() {
-								app.mode = app::AppMode::FilteredList;
-							} else {
-								app.mode = app::AppMode::Menu;
-							}
-						},
-						app::AppMode::FilteredList => {
-							app.mode = app.previous_mode.clone().unwrap_or(app::AppMode::Menu);
-						},
-						app::AppMode::AuthorList | app::AppMode::LanguageList | app::AppMode::TitleList => {
-							app.set_mode(app::AppMode::Menu)
-						},
-						_ => {}
-					}
-				},
-				KeyCode::Char('m') => {
-					app.mode = app::AppMode::Menu;
-				},
-				KeyCode::Char('s') => {
-					if let app::AppMode::Viewing = app.mode {
-						app.version_list_state.select(Some(0));
-						app.mode = app::AppMode::VersionSelect;
-					}
-				},
-				KeyCode::Right => match app.mode {
-					app::AppMode::Viewing => app.next_poem(),
-					_ => {}
-				},
-				KeyCode::Left => match app.mode {
-					app::AppMode::Viewing => app.previous_poem(),
-					_ => {}
-				},
-				KeyCode::Down | KeyCode::Char('j') => match app.mode {
-					app::AppMode::Viewing => {
-						let text = ui::render_poem_text(app.get_current_version());
-						let lines = text.lines().count();
-						if let Some(viewport_height) = app.viewport_height {
-							let max_scroll = lines.saturating_sub(viewport_height as usize) as u16;
-							app.scroll_down(1, max_scroll);
-						}
-					},
-					app::AppMode::AuthorList => app.next_author(),
-					app::AppMode::LanguageList => app.next_language(),
-					app::AppMode::TitleList => app.next_title(),
-					app::AppMode::FilteredList => app.next_filtered(),
-                    app::AppMode::Menu => {
-                        if let Some(i) = app.menu_state.selected() {
-                            let total_items = 5;
-                            let new_index = (i + 1) % total_items;
-                            app.menu_state.select(Some(new_index));
-                        }
-                    },
-					app::AppMode::Search => {},
-					app::AppMode::VersionSelect => {
-						let poem = &app.poems[app.current_poem];
-						let versions_len = poem.versions.len();
-						let i = match app.version_list_state.selected() {
-							Some(i) => (i + 1) % versions_len,
-							None => 0,
-						};
-						app.version_list_state.select(Some(i));
-					}
-				},
-				KeyCode::Up | KeyCode::Char('k') => match app.mode {
-					app::AppMode::Viewing => {
-						app.scroll_up(1);
-					},
-					app::AppMode::AuthorList => app.previous_author(),
-					app::AppMode::LanguageList => app.previous_language(),
-					app::AppMode::TitleList => app.previous_title(),
-					app::AppMode::FilteredList => app.previous_filtered(),
-                    app::AppMode::Menu => {
-                        if let Some(i) = app.menu_state.selected() {
-                            let total_items = 5;
-                            let new_index = if i == 0 { total_items - 1 } else { i - 1 };
-                            app.menu_state.select(Some(new_index));
-                        }
-                    },
-					app::AppMode::Search => {},
-					app::AppMode::VersionSelect => {
-						let poem = &app.poems[app.current_poem];
-						let versions_len = poem.versions.len();
-						let i = match app.version_list_state.selected() {
-							Some(i) => if i == 0 { versions_len - 1 } else { i - 1 },
-							None => 0,
-						};
-						app.version_list_state.select(Some(i));
-					}
+			},
+			Ok(false) => {},
+			Err(e) => app.status_message = Some(format!("Could not show image: {}", e)),
+		}
+		// Ticks come in while waiting for a key; only those that changed something are drawn
+		let key = loop {
+			match next_message(&mut last_tick)? {
+				app::Message::Key(key) => break Some(key),
+				app::Message::Resize => break None,
+				app::Message::Tick => if on_tick(&mut app, cli.file.as_deref(), &config, strict) {
+					break None;
 				},
-				KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-					match app.mode {
-						app::AppMode::Viewing => {
-							let home = std::env::var("HOME").expect("HOME not set");
-							let poem_path = PathBuf::from(home).join("literature").join("poetry").join(&app.poems[app.current_poem].filename);
-							#[cfg(target_os = "macos")]
-							std::process::Command::new("open").arg(&poem_path).spawn().expect("Failed to open file");
-							#[cfg(target_os = "linux")]
-							std::process::Command::new("xdg-open").arg(&poem_path).spawn().expect("Failed to open file");
-							#[cfg(target_os = "windows")]
-							std::process::Command::new("cmd").args(["/C", "start", poem_path.to_str().unwrap()]).spawn().expect("Failed to open file");
-						},
-						_ => {}
-					}
+			}
+		};
+		let Some(key) = key else { continue };
+		tracing::debug!(code = ?key.code, modifiers = ?key.modifiers, mode = ?app.mode, "key");
+		app.status_message = None;
+		match keymap::action(&app, key) {
+			Some(Action::Quit) => break,
+			Some(Action::CreateSamplePoem) => match app.create_sample_poem() {
+				Ok(path) => if reload_library(&mut app, &config, strict) {
+					app.status_message = Some(format!("Created {}", path.display()));
 				},
-					KeyCode::Enter => match app.mode {
-						app::AppMode::AuthorList => app.select_current_author(),
-						app::AppMode::LanguageList => app.select_current_language(),
-						app::AppMode::TitleList => app.select_current_title(),
-						app::AppMode::FilteredList => app.select_current_filtered(),
-						app::AppMode::VersionSelect => {
-							let poem = &app.poems[app.current_poem];
-							let versions: Vec<String> = poem.versions.keys().cloned().collect();
-							if let Some(i) = app.version_list_state.selected() {
-								if let Some(selected_version) = versions.get(i) {
-									app.current_version = selected_version.clone();
-									app.mode = app::AppMode::Viewing;
-								}
-							}
-						}
-					app::AppMode::Menu => {
-						match app.menu_state.selected() {
-							Some(0) => app.mode = app::AppMode::AuthorList,
-							Some(1) => app.mode = app::AppMode::LanguageList,
-							Some(2) => app.mode = app::AppMode::TitleList,
-							Some(3) => {
-								// New Search option
-								app.mode = app::AppMode::Search;
-								app.search_query.clear();
-								app.search_results.clear();
-								app.update_search_results();
-								app.search_list_state.select(Some(0));
-							},
-							Some(4) => {
-								let mut rng = rand::thread_rng();
-								app.current_poem = rng.gen_range(0..app.poems.len());
-								app.current_version = "canonical".to_string();
-								app.filtered_poems = None;
-								app.mode = app::AppMode::Viewing;
-							},
-							_ => {}
-						}
-					},
-					_ => {}
+				Err(e) => app.status_message = Some(format!("Could not create the sample poem: {}", e)),
+			},
+			Some(Action::AddTranslation) => add_translation(&mut app, &config, strict),
+			Some(Action::Delete) => delete_current(&mut app, &config, strict),
+			Some(Action::Merge) => merge_marked(&mut app, &config, strict),
+			Some(Action::Restore) => restore_selected(&mut app, &config, strict),
+			Some(Action::SaveMetadata) => save_metadata(&mut app, &config, strict),
+			Some(Action::Editor(key)) => match app.editor.as_mut().map(|editor| editor.handle_key(key)) {
+				Some(editor::EditorAction::Save) => save_edited(&mut app, &config, strict),
+				Some(editor::EditorAction::Quit) => {
+					app.editor = None;
+					app.mode = app::AppMode::Viewing;
 				},
-				_ => {}
-			}
+				Some(editor::EditorAction::None) | None => {},
+			},
+			Some(action) => app.handle_action(action)?,
+			// Keys the viewer has no use for may be bound by plugins
+			#[cfg(feature = "plugins")]
+			None if app.mode == app::AppMode::Viewing => if let event::KeyCode::Char(c) = key.code {
+				plugins.run_key(&mut app, c);
+			},
+			None => {}
 		}
 	}
-	disable_raw_mode()?;
-	execute!(io::stdout(), LeaveAlternateScreen)?;
 	Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,44 @@
+use crate::models::{is_encrypted, is_markdown, parse_documents, poem_files, read_poem_file, rewrite_poem, Schema};
+use std::io::{self, Write};
+
+// Rewrites `.poem` files that only parse with the legacy schema into the current one and
+// returns how many there were. With `dry_run` nothing is written.
+pub fn migrate(out: &mut impl Write, dry_run: bool) -> io::Result<usize> {
+	let mut migrated = 0;
+	let mut files = poem_files()?;
+	files.retain(|path| !is_markdown(path));
+	files.sort();
+	for path in &files {
+		let content = match read_poem_file(path) {
+			Ok(content) => content,
+			Err(e) if is_encrypted(&path.to_string_lossy()) => {
+				eprintln!("leaves: {} cannot be read, left alone: {}", path.display(), e);
+				continue;
+			},
+			Err(e) => return Err(e),
+		};
+		let parsed: Result<Vec<_>, _> = parse_documents(&content).into_iter().collect();
+		let poems = match parsed {
+			Ok(poems) => poems,
+			Err(e) => {
+				eprintln!("leaves: {} does not parse with either schema, left alone: {}", path.display(), e);
+				continue;
+			}
+		};
+		if poems.iter().all(|(_, schema)| matches!(schema, Schema::Current)) {
+			continue;
+		}
+		let mut keys: Vec<&str> = poems.iter().flat_map(|(versions, _)| versions.keys().map(|k| k.as_str())).collect();
+		keys.sort();
+		keys.dedup();
+		writeln!(out, "{}: legacy schema -> current (versions: {})", path.display(), keys.join(", "))?;
+		if !dry_run {
+			let poems: Vec<_> = poems.into_iter().map(|(versions, _)| versions).collect();
+			rewrite_poem(path, &poems)?;
+		}
+		migrated += 1;
+	}
+	let verb = if dry_run { "would be migrated" } else { "migrated" };
+	writeln!(out, "{} of {} files {}", migrated, files.len(), verb)?;
+	Ok(migrated)
+}
@@ -1,5 +1,10 @@
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, io, fs, path::PathBuf};
+use crate::backup;
+use crate::config::Config;
+use crate::ignore::Ignore;
+use crate::sync;
+use crate::utils::{count_text, home_dir, is_han, is_mostly_rtl, romanized, LanguageTag, TextCounts};
+use std::{borrow::Cow, collections::{BTreeMap, HashMap, HashSet, VecDeque}, fmt, io::{self, Write}, fs, path::{Path, PathBuf}, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex, OnceLock}, time::{Instant, SystemTime}};
 
 // Legacy format for backward compatibility
 #[derive(Debug, Serialize, Deserialize)]
@@ -15,17 +20,124 @@ pub struct Poem {
 	pub versions: HashMap<String, Version>,
 	#[serde(skip)]
 	pub filename: String,
+	// Position among the poems of a file holding several as YAML documents, counted from 1
+	#[serde(skip)]
+	pub document: Option<usize>,
+	#[serde(skip)]
+	id: String,
+	#[serde(skip)]
+	pub unloaded: bool,
+	#[serde(skip)]
+	pub modified: Option<SystemTime>,
+	#[serde(skip)]
+	pub line_count: usize,
+	#[serde(skip)]
+	pub word_count: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Version {
+	// Fields are in the order `leaves fmt` writes them, with the text last
 	pub title: Option<String>,
 	pub author: Option<String>,
+	// How the author should be filed, e.g. "Rilke, Rainer Maria"
+	pub sort_name: Option<String>,
+	// The author's name as written in other languages, by language code, e.g. `eng: Matsuo
+	// Bashō` for 松尾芭蕉; the names are listed as one author
+	pub author_names: Option<BTreeMap<String, String>>,
+	// Who made this version, for translations
+	pub translator: Option<String>,
+	// When it was written, as a year (negative before the common era) and/or as free text,
+	// e.g. "3 May 1850" or "c. 1200"; without `year` one is read from `date`
+	pub year: Option<i32>,
+	pub date: Option<String>,
+	// Poetic form, e.g. "sonnet", "haiku", "ghazal" or "free verse"
+	pub form: Option<String>,
 	pub language: Option<String>,
-	pub epigraph: Option<String>,
-	pub text: String,
 	pub rtl: Option<bool>,
 	pub vertical: Option<bool>,
+	// Recording of the poem, relative to the poetry directory
+	pub audio: Option<String>,
+	// Illustration or manuscript scan, relative to the poetry directory
+	pub image: Option<String>,
+	pub tags: Option<Vec<String>>,
+	// Terms the text may be shared under, e.g. "public domain" or "CC BY 4.0"
+	pub license: Option<String>,
+	// Where the text was taken from, e.g. a URL
+	pub source: Option<String>,
+	// Ids of related poems, e.g. the poem this one answers, parodies or translates
+	pub see_also: Option<Vec<String>>,
+	// Fields leaves does not use (e.g. `notes`), kept so rewriting a file never drops them
+	#[serde(flatten)]
+	pub other: BTreeMap<String, serde_yaml::Value>,
+	// Other readings of the text by name, e.g. `modernized` for a text in pre-reform spelling;
+	// the viewer switches between them and `text`
+	pub variants: Option<BTreeMap<String, String>>,
+	// The text in Latin letters, line for line, e.g. in pinyin or romaji
+	pub transliteration: Option<String>,
+	pub epigraph: Option<String>,
+	pub text: String,
+	#[serde(skip)]
+	pub counts: TextCounts,
+}
+
+impl Version {
+	pub fn year(&self) -> Option<i32> {
+		self.year.or_else(|| self.date.as_deref().and_then(year_in))
+	}
+
+	// The date as written, or else the year
+	pub fn date_label(&self) -> Option<String> {
+		self.date.clone().or_else(|| self.year.map(year_label))
+	}
+
+	// `vertical`, or when unset, whether the script of `language` is written vertically
+	pub fn is_vertical(&self) -> bool {
+		self.vertical.unwrap_or_else(|| {
+			self.language.as_deref().and_then(LanguageTag::parse).is_some_and(|tag| tag.is_vertical_script())
+		})
+	}
+
+	// `transliteration`, or else the text romanized when it is in another script. Kanji are
+	// left alone: romanizing them would give their Chinese readings.
+	pub fn transliteration(&self) -> Option<Cow<'_, str>> {
+		if let Some(transliteration) = &self.transliteration {
+			return Some(Cow::Borrowed(transliteration));
+		}
+		let japanese = self.language.as_deref().and_then(LanguageTag::parse).is_some_and(|tag| matches!(tag.language.as_str(), "ja" | "jpn"));
+		if japanese && self.text.chars().any(is_han) {
+			return None;
+		}
+		romanized(&self.text).map(Cow::Owned)
+	}
+
+	// `rtl`, or when unset, whether `language` is written right to left or most of the text is
+	pub fn is_rtl(&self) -> bool {
+		self.rtl.unwrap_or_else(|| {
+			self.language.as_deref().and_then(LanguageTag::parse).is_some_and(|tag| tag.is_rtl()) || is_mostly_rtl(&self.text)
+		})
+	}
+
+	pub fn is_public_domain(&self) -> bool {
+		self.license.as_deref().is_some_and(|license| {
+			PUBLIC_DOMAIN_LICENSES.contains(&license.trim().to_lowercase().as_str())
+		})
+	}
+}
+
+// Ways of writing a `license` that puts no restrictions on sharing
+const PUBLIC_DOMAIN_LICENSES: [&str; 5] = ["public domain", "pd", "cc0", "cc0 1.0", "cc0-1.0"];
+
+// The year in a free-form date: the first number of three or more digits, so a day of the
+// month before it is passed over, or else the first number; negated when marked "BC" or "BCE"
+fn year_in(date: &str) -> Option<i32> {
+	let numbers: Vec<&str> = date.split(|c: char| !c.is_ascii_digit()).filter(|n| !n.is_empty()).collect();
+	let year: i32 = numbers.iter().find(|n| n.len() >= 3).or(numbers.first())?.parse().ok()?;
+	Some(if date.contains("BC") { -year } else { year })
+}
+
+pub fn year_label(year: i32) -> String {
+	if year < 0 { format!("{} BC", -year) } else { year.to_string() }
 }
 
 impl Poem {
@@ -33,47 +145,796 @@ impl Poem {
 		self.versions.get("canonical")
 	}
 
+	// When the poem was written, from its canonical version
+	pub fn year(&self) -> Option<i32> {
+		self.canonical().and_then(Version::year)
+	}
+
+	// Only when every version is, since a translation has rights of its own
+	pub fn is_public_domain(&self) -> bool {
+		self.versions.values().all(Version::is_public_domain)
+	}
+
 	pub fn has_canonical(&self) -> bool {
 		self.versions.contains_key("canonical")
 	}
+
+	// Directory of the file inside the library, "" at the top
+	pub fn folder(&self) -> &str {
+		self.filename.rsplit_once('/').map_or("", |(folder, _)| folder)
+	}
+
+	// Case-insensitive match of the canonical title or author, under any of the author's
+	// names, against a lowercased query
+	pub fn matches(&self, query: &str) -> bool {
+		self.canonical().is_some_and(|canonical| {
+			canonical.title.as_ref().is_some_and(|t| t.to_lowercase().contains(query))
+				|| canonical.author.iter().chain(canonical.author_names.iter().flat_map(|names| names.values()))
+					.any(|a| a.to_lowercase().contains(query))
+		})
+	}
+
+	// The author's name for readers of `version`: one of its `author_names`, or else of the
+	// canonical version's, in the language of `version`, or else its `author` as written
+	pub fn author_for<'a>(&'a self, version: &'a Version) -> Option<&'a str> {
+		let language = version.language.as_deref().and_then(LanguageTag::parse);
+		let named = language.and_then(|language| {
+			[version, self.canonical().unwrap_or(version)].into_iter()
+				.filter_map(|v| v.author_names.as_ref())
+				.flat_map(|names| names.iter())
+				.find(|(code, _)| LanguageTag::parse(code).is_some_and(|tag| tag.same_language(&language)))
+				.map(|(_, name)| name.as_str())
+		});
+		named.or(version.author.as_deref()).or_else(|| self.canonical().and_then(|v| v.author.as_deref()))
+	}
+
+	// Stable identifier used on the command line, see `poem_id`
+	pub fn id(&self) -> &str {
+		&self.id
+	}
+
+	// After the file was renamed or moved inside the library
+	pub fn set_filename(&mut self, filename: &str) {
+		self.id = poem_id(filename, self.document);
+		self.filename = filename.to_string();
+	}
+
+	// Key the reading history is stored under
+	pub fn history_key(&self) -> Cow<'_, str> {
+		match self.document {
+			Some(document) => Cow::Owned(format!("{}#{}", self.filename, document)),
+			None => Cow::Borrowed(&self.filename),
+		}
+	}
+
+	// Drops the text of every version, keeping only the metadata
+	pub fn unload_text(&mut self) {
+		for version in self.versions.values_mut() {
+			version.text = String::new();
+			version.variants = None;
+			version.transliteration = None;
+			version.epigraph = None;
+		}
+		self.unloaded = true;
+	}
+
+	// Re-reads the poem file to restore text dropped by `unload_text`
+	pub fn reload_text(&mut self) -> io::Result<()> {
+		let mut versions = read_versions(&self.filename, self.document)?;
+		for (key, version) in self.versions.iter_mut() {
+			if let Some(fresh) = versions.remove(key) {
+				version.text = fresh.text;
+				version.variants = fresh.variants;
+				version.transliteration = fresh.transliteration;
+				version.epigraph = fresh.epigraph;
+			}
+		}
+		self.unloaded = false;
+		Ok(())
+	}
 }
 
-pub fn load_poems() -> io::Result<Vec<Poem>> {
-	let home = std::env::var("HOME").expect("HOME environment variable not set");
-	let poems_dir = PathBuf::from(home).join("literature").join("poetry");
-	let mut poems = Vec::new();
-	for entry in fs::read_dir(poems_dir)? {
-		let entry = entry?;
-		if entry.path().extension().and_then(|s| s.to_str()) == Some("poem") {
-			let content = fs::read_to_string(entry.path())?;
-
-			// Try to parse as new format first
-			if let Ok(poem) = serde_yaml::from_str::<Poem>(&content) {
-				let mut poem = poem;
-				if !poem.has_canonical() {
-					continue; // Skip poems without canonical version as required by schema
-				}
-				poem.filename = entry.path().file_name().unwrap_or_default().to_string_lossy().into();
-				poems.push(poem);
+// The library of this run, fixed by `set_library` once the config is loaded at startup
+struct Library {
+	dir: PathBuf,
+	// File extensions of the poem files read from it
+	extensions: Vec<String>,
+	// `low_memory` in the config: poems keep no text once loaded, and nothing read from an
+	// archive is kept
+	low_memory: bool,
+}
+
+static LIBRARY: OnceLock<Library> = OnceLock::new();
+
+pub fn set_library(config: &Config) {
+	let _ = LIBRARY.set(Library { dir: library_dir(config), extensions: config.extensions(), low_memory: config.low_memory });
+}
+
+// Whether `low_memory` is set for the library of this run
+pub fn low_memory() -> bool {
+	library().low_memory
+}
+
+fn library() -> &'static Library {
+	LIBRARY.get_or_init(|| {
+		let config = Config::default();
+		Library { dir: library_dir(&config), extensions: config.extensions(), low_memory: config.low_memory }
+	})
+}
+
+// Where poems are read from: `library` in the config (the local copy when it is a URL),
+// or ~/literature/poetry
+pub fn poems_dir() -> PathBuf {
+	library().dir.clone()
+}
+
+fn library_dir(config: &Config) -> PathBuf {
+	let home = home_dir();
+	match config.library.clone() {
+		Some(library) if library.to_str().is_some_and(sync::is_remote) => sync::local_copy(&library.to_string_lossy()),
+		Some(library) => match library.strip_prefix("~") {
+			Ok(rest) => home.join(rest),
+			Err(_) => library,
+		},
+		None => home.join("literature").join("poetry"),
+	}
+}
+
+// Set at startup by `--read-only` or `read_only` in the config
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+pub fn set_read_only() {
+	READ_ONLY.store(true, Ordering::Relaxed);
+}
+
+// Whether nothing may be written into the library
+pub fn is_read_only() -> bool {
+	READ_ONLY.load(Ordering::Relaxed)
+}
+
+// Checked before anything is written into the library
+pub fn check_writable() -> io::Result<()> {
+	if is_read_only() {
+		return Err(io::Error::new(io::ErrorKind::PermissionDenied, "the library is read-only (`--read-only` or `read_only` in the configuration)"));
+	}
+	Ok(())
+}
+
+// A library packed into a single `.zip`, `.tar.gz` or `.tgz` file, read without extracting it
+pub fn is_archive(path: &Path) -> bool {
+	let name = path.to_string_lossy();
+	path.is_file() && [".zip", ".tar.gz", ".tgz"].iter().any(|extension| name.ends_with(extension))
+}
+
+#[cfg(feature = "archives")]
+fn archive_entries(root: &Path, wanted: impl Fn(&str) -> bool) -> io::Result<Vec<(String, Vec<u8>)>> {
+	crate::archive::read_entries(root, wanted)
+}
+
+#[cfg(not(feature = "archives"))]
+fn archive_entries(root: &Path, _wanted: impl Fn(&str) -> bool) -> io::Result<Vec<(String, Vec<u8>)>> {
+	Err(io::Error::new(io::ErrorKind::Unsupported, format!("{} is an archive, which needs leaves built with the `archives` feature", root.display())))
+}
+
+type ArchivePoems = Arc<Vec<(String, Vec<u8>)>>;
+
+// The poem files of an archive library as stored, by when the archive was last changed, so
+// that it is decompressed again only after it was
+static ARCHIVE: Mutex<Option<(SystemTime, ArchivePoems)>> = Mutex::new(None);
+
+// The poem files of an archive library, those `wanted` among them at least. In low-memory
+// mode nothing is kept, and only the wanted ones are read each time.
+fn archive_poems(root: &Path, wanted: impl Fn(&str) -> bool) -> io::Result<ArchivePoems> {
+	let extensions = &library().extensions;
+	if low_memory() {
+		return Ok(Arc::new(archive_entries(root, |name| wanted_extension(name, extensions) && wanted(name))?));
+	}
+	let modified = fs::metadata(root)?.modified().ok();
+	let mut cached = ARCHIVE.lock().unwrap_or_else(|e| e.into_inner());
+	if let (Some(modified), Some((when, poems))) = (modified, cached.as_ref()) {
+		if modified == *when {
+			return Ok(poems.clone());
+		}
+	}
+	let poems = Arc::new(archive_entries(root, |name| wanted_extension(name, extensions))?);
+	*cached = modified.map(|modified| (modified, poems.clone()));
+	Ok(poems)
+}
+
+fn wanted_extension(name: &str, extensions: &[String]) -> bool {
+	let name = name.strip_suffix(ENCRYPTED_SUFFIX).unwrap_or(name);
+	Path::new(name).extension().and_then(|s| s.to_str()).is_some_and(|e| extensions.iter().any(|x| x == e))
+}
+
+// Added to any poem file name for its encrypted variant, e.g. `diary.poem.age`
+pub const ENCRYPTED_SUFFIX: &str = ".age";
+
+pub fn is_encrypted(name: &str) -> bool {
+	name.ends_with(ENCRYPTED_SUFFIX)
+}
+
+// The extension a poem file is read by, with the one of encryption: `poem`, `md`, `poem.age`
+pub fn poem_extension(name: &str) -> String {
+	let plain = name.strip_suffix(ENCRYPTED_SUFFIX).unwrap_or(name);
+	let extension = Path::new(plain).extension().and_then(|e| e.to_str()).unwrap_or("poem");
+	if is_encrypted(name) { format!("{}{}", extension, ENCRYPTED_SUFFIX) } else { extension.to_string() }
+}
+
+#[cfg(feature = "encryption")]
+fn decrypt(bytes: &[u8]) -> io::Result<Vec<u8>> {
+	crate::crypt::decrypt(bytes)
+}
+
+#[cfg(not(feature = "encryption"))]
+fn decrypt(_bytes: &[u8]) -> io::Result<Vec<u8>> {
+	Err(io::Error::new(io::ErrorKind::Unsupported, "encrypted, which needs leaves built with the `encryption` feature"))
+}
+
+#[cfg(feature = "encryption")]
+fn encrypt(previous: &[u8], plain: &[u8]) -> io::Result<Vec<u8>> {
+	crate::crypt::encrypt(previous, plain)
+}
+
+#[cfg(not(feature = "encryption"))]
+fn encrypt(_previous: &[u8], _plain: &[u8]) -> io::Result<Vec<u8>> {
+	Err(io::Error::new(io::ErrorKind::Unsupported, "encrypted poems need leaves built with the `encryption` feature"))
+}
+
+// Encrypted poem files by name, as read and as decrypted this run. Deriving the key from a
+// passphrase takes about a second, which reloading the library after every change would
+// otherwise spend again on each of them; a file that changed since is decrypted anew.
+type Decrypted = BTreeMap<String, (Vec<u8>, Vec<u8>)>;
+
+static DECRYPTED: Mutex<Decrypted> = Mutex::new(BTreeMap::new());
+
+// The content of a poem file as stored, or decrypted when it is an encrypted one. Low-memory
+// mode keeps nothing decrypted.
+pub fn plain_content(name: &str, bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+	if !is_encrypted(name) {
+		return Ok(bytes);
+	}
+	if low_memory() {
+		return decrypt(&bytes);
+	}
+	let mut decrypted = DECRYPTED.lock().unwrap_or_else(|e| e.into_inner());
+	if let Some((encrypted, plain)) = decrypted.get(name) {
+		if *encrypted == bytes {
+			return Ok(plain.clone());
+		}
+	}
+	let plain = decrypt(&bytes)?;
+	decrypted.insert(name.to_string(), (bytes, plain.clone()));
+	Ok(plain)
+}
+
+// Reads a poem file of the library directory, decrypting it when needed
+pub fn read_poem_file(path: &Path) -> io::Result<String> {
+	let name = path.to_string_lossy();
+	let bytes = plain_content(&name, fs::read(path)?)?;
+	String::from_utf8(bytes).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("{} is not valid UTF-8", name)))
+}
+
+// A poem file as read from the library, whether a directory or an archive
+pub struct LibraryFile {
+	// Path inside the library, with `/` between folders
+	pub name: String,
+	pub content: io::Result<Vec<u8>>,
+	pub modified: Option<SystemTime>,
+}
+
+// Hands every poem file in the library to `visit`, in path order
+pub fn read_library(mut visit: impl FnMut(LibraryFile) -> io::Result<()>) -> io::Result<()> {
+	let root = poems_dir();
+	if let (false, Some(url)) = (root.exists(), sync::remote_library()) {
+		return Err(io::Error::new(io::ErrorKind::NotFound, format!("the library at {} has not been downloaded yet; run `leaves sync`", url)));
+	}
+	if is_archive(&root) {
+		let modified = fs::metadata(&root)?.modified().ok();
+		for (name, content) in archive_poems(&root, |_| true)?.iter() {
+			let content = plain_content(name, content.clone());
+			visit(LibraryFile { name: name.clone(), content, modified })?;
+		}
+		return Ok(());
+	}
+	let mut files = poem_files()?;
+	files.sort();
+	for path in files {
+		let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+		let name = library_name(&root, &path);
+		let content = fs::read(&path).and_then(|bytes| plain_content(&name, bytes));
+		visit(LibraryFile { name, content, modified })?;
+	}
+	Ok(())
+}
+
+// The names of the poem files in the library, without reading them
+pub fn library_names() -> io::Result<Vec<String>> {
+	let root = poems_dir();
+	if is_archive(&root) {
+		return Ok(archive_poems(&root, |_| true)?.iter().map(|(name, _)| name.clone()).collect());
+	}
+	Ok(poem_files()?.iter().map(|path| library_name(&root, path)).collect())
+}
+
+// One poem file of the library, by its name inside it
+pub fn read_library_file(name: &str) -> io::Result<String> {
+	let root = poems_dir();
+	let bytes = if is_archive(&root) {
+		archive_poems(&root, |entry| entry == name)?.iter().find(|(entry, _)| entry == name).map(|(_, content)| content.clone())
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} has no {}", root.display(), name)))?
+	} else {
+		fs::read(root.join(name))?
+	};
+	let bytes = plain_content(name, bytes)?;
+	String::from_utf8(bytes).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("{} is not valid UTF-8", name)))
+}
+
+// The file name without extension, plus `.N` for the Nth poem of a file holding several
+pub fn poem_id(filename: &str, document: Option<usize>) -> String {
+	let filename = filename.strip_suffix(ENCRYPTED_SUFFIX).unwrap_or(filename);
+	let stem = Path::new(filename).file_stem().and_then(|s| s.to_str()).unwrap_or(filename);
+	match document {
+		Some(document) => format!("{}.{}", stem, document),
+		None => stem.to_string(),
+	}
+}
+
+// Reads the versions of a single poem in the library
+pub fn read_versions(filename: &str, document: Option<usize>) -> io::Result<HashMap<String, Version>> {
+	let content = read_library_file(filename)?;
+	let parsed = parse_file(Path::new(filename), &content).into_iter().nth(document.unwrap_or(1) - 1)
+		.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} no longer holds poem {}", filename, document.unwrap_or(1))))?;
+	parsed.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{} no longer parses: {}", filename, e)))
+}
+
+pub fn is_markdown(path: &Path) -> bool {
+	let name = path.to_string_lossy();
+	Path::new(name.strip_suffix(ENCRYPTED_SUFFIX).unwrap_or(&name)).extension().and_then(|s| s.to_str()) == Some("md")
+}
+
+// The poems in a file: one, or one per YAML document in `.poem` files holding several
+pub fn parse_file(path: &Path, content: &str) -> Vec<Result<HashMap<String, Version>, ParseError>> {
+	if is_markdown(path) {
+		return vec![parse_markdown(content).ok_or_else(|| ParseError {
+			message: "no poem text, or frontmatter that is not a YAML mapping".to_string(),
+			location: None,
+			hint: None,
+		})];
+	}
+	parse_documents(content).into_iter().map(|parsed| parsed.map(|(versions, _)| versions)).collect()
+}
+
+// Splits a `.poem` file at `---` lines into its YAML documents, each with the line it starts
+// after. Poem text sits in indented blocks, so a `---` at the start of a line always separates.
+fn split_documents(content: &str) -> Vec<(usize, &str)> {
+	let mut documents = Vec::new();
+	let (mut start, mut start_line, mut offset) = (0, 0, 0);
+	for (i, line) in content.split_inclusive('\n').enumerate() {
+		if line.trim_end() == "---" {
+			documents.push((start_line, &content[start..offset]));
+			start = offset + line.len();
+			start_line = i + 1;
+		}
+		offset += line.len();
+	}
+	documents.push((start_line, &content[start..]));
+	// Drop what comes before a leading `---`, and documents holding only comments
+	documents.retain(|(_, document)| document.lines().any(|l| !l.trim().is_empty() && !l.trim_start().starts_with('#')));
+	documents
+}
+
+// Parses every document of a `.poem` file; a file with a single one parses as a whole
+pub fn parse_documents(content: &str) -> Vec<Parsed> {
+	let documents = split_documents(content);
+	if documents.len() <= 1 {
+		return vec![parse_with_schema(content)];
+	}
+	documents.into_iter().enumerate().map(|(i, (line, document))| {
+		parse_with_schema(document).map_err(|e| ParseError {
+			// serde's own positions in the message count from the start of the document
+			message: format!("poem {} (from line {}): {}", i + 1, line + 1, e.message),
+			location: e.location.map(|(l, c)| (l + line, c)),
+			hint: e.hint,
+		})
+	}).collect()
+}
+
+// Line/word counts are taken at load time so they survive low-memory unloading
+pub fn with_counts(mut versions: HashMap<String, Version>) -> HashMap<String, Version> {
+	for version in versions.values_mut() {
+		version.counts = count_text(&version.text);
+	}
+	versions
+}
+
+// Which schema a `.poem` file was read with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Schema {
+	Current,
+	Legacy,
+}
+
+// Why a poem file could not be read
+#[derive(Debug)]
+pub struct ParseError {
+	pub message: String,
+	// Line and column, both counted from 1
+	pub location: Option<(usize, usize)>,
+	pub hint: Option<&'static str>,
+}
+
+impl fmt::Display for ParseError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.message)?;
+		if let Some(hint) = self.hint {
+			write!(f, " ({})", hint)?;
+		}
+		Ok(())
+	}
+}
+
+const CANONICAL_HINT: &str = "did you forget the `canonical:` top-level key?";
+
+// A likely cause for common mistakes, from the file and serde_yaml's message
+fn hint(content: &str, message: &str) -> Option<&'static str> {
+	let top_level_keys = content.lines()
+		.filter(|l| !l.starts_with([' ', '\t', '#', '-']))
+		.filter_map(|l| l.split_once(':').map(|(key, _)| key.trim()));
+	if top_level_keys.clone().any(|key| matches!(key, "title" | "author" | "language" | "epigraph" | "text")) {
+		Some(CANONICAL_HINT)
+	} else if content.lines().any(|l| l.trim_start_matches(' ').starts_with('\t')) {
+		Some("YAML has to be indented with spaces, not tabs")
+	} else if message.contains("missing field `text`") {
+		Some("every version needs a `text:` field")
+	} else if message.contains("mapping values are not allowed") {
+		Some("a line contains `: `; keep the poem inside a `text: |` block or quote the value")
+	} else if top_level_keys.clone().next().is_none() {
+		Some("a poem file is a mapping of versions, starting with `canonical:`")
+	} else {
+		None
+	}
+}
+
+// The versions of a poem and the schema they were read with
+pub type Parsed = Result<(HashMap<String, Version>, Schema), ParseError>;
+
+// Parses either the current or the legacy format into a version map
+pub fn parse_with_schema(content: &str) -> Parsed {
+	// Try to parse as new format first
+	let current = match serde_yaml::from_str::<Poem>(content) {
+		// Poems need a canonical version as required by the schema
+		Ok(poem) if !poem.has_canonical() => {
+			let message = "no `canonical` version".to_string();
+			let hint = hint(content, &message).or(Some("one of the versions has to be called `canonical`"));
+			return Err(ParseError { message, location: None, hint });
+		}
+		Ok(poem) => return Ok((with_counts(poem.versions), Schema::Current)),
+		Err(e) => e,
+	};
+	// Fall back to legacy format
+	let legacy = match serde_yaml::from_str::<LegacyPoem>(content) {
+		Ok(legacy_poem) => {
+			let mut versions = HashMap::new();
+			versions.insert("canonical".to_string(), legacy_poem.canonical);
+
+			// Add other versions from the legacy format
+			for (key, version) in legacy_poem.other_versions {
+				versions.insert(key, version);
 			}
-			// Fall back to legacy format
-			else if let Ok(legacy_poem) = serde_yaml::from_str::<LegacyPoem>(&content) {
-				let mut versions = HashMap::new();
-				versions.insert("canonical".to_string(), legacy_poem.canonical);
-
-				// Add other versions from the legacy format
-				for (key, version) in legacy_poem.other_versions {
-					versions.insert(key, version);
+			return Ok((with_counts(versions), Schema::Legacy));
+		}
+		Err(e) => e,
+	};
+	// The parse that got further into the file says more about what is wrong
+	let location = |e: &serde_yaml::Error| e.location().map(|l| (l.line(), l.column()));
+	let error = if location(&legacy) > location(&current) { legacy } else { current };
+	let message = error.to_string();
+	Err(ParseError { hint: hint(content, &message), location: location(&error), message })
+}
+
+// Markdown poem: optional YAML frontmatter holds the metadata of a single canonical
+// version and the body is its text. A leading `# heading` stands in for a missing title.
+pub fn parse_markdown(content: &str) -> Option<HashMap<String, Version>> {
+	let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+	let (frontmatter, body) = match content.strip_prefix("---").and_then(|rest| rest.split_once("\n---")) {
+		Some((frontmatter, rest)) => (frontmatter, rest.split_once('\n').map_or("", |(_, body)| body)),
+		None => ("", content),
+	};
+	let mut fields = match serde_yaml::from_str::<serde_yaml::Value>(frontmatter).ok()? {
+		serde_yaml::Value::Mapping(fields) => fields,
+		serde_yaml::Value::Null => serde_yaml::Mapping::new(),
+		_ => return None,
+	};
+	// Trailing backslashes and double spaces are markdown hard breaks; every line is one here
+	let mut lines: Vec<&str> = body.lines().map(|l| l.trim_end().trim_end_matches('\\').trim_end()).collect();
+	let trim_start = |lines: &mut Vec<&str>| {
+		let blank = lines.iter().take_while(|l| l.is_empty()).count();
+		lines.drain(..blank);
+	};
+	trim_start(&mut lines);
+	if !fields.contains_key("title") {
+		if let Some(heading) = lines.first().and_then(|l| l.strip_prefix("# ")) {
+			fields.insert("title".into(), heading.trim().into());
+			lines.remove(0);
+			trim_start(&mut lines);
+		}
+	}
+	while lines.last().is_some_and(|l| l.is_empty()) {
+		lines.pop();
+	}
+	if lines.is_empty() {
+		return None;
+	}
+	fields.insert("text".into(), format!("{}\n", lines.join("\n")).into());
+	let version: Version = serde_yaml::from_value(serde_yaml::Value::Mapping(fields)).ok()?;
+	Some(with_counts(HashMap::from([("canonical".to_string(), version)])))
+}
+
+// Versions as they are written to a `.poem` file: canonical first, unset fields left out
+pub fn poem_document(versions: &HashMap<String, Version>) -> io::Result<serde_yaml::Mapping> {
+	let mut keys: Vec<&String> = versions.keys().collect();
+	keys.sort_by_key(|k| (k.as_str() != "canonical", k.as_str()));
+	let mut document = serde_yaml::Mapping::new();
+	for key in keys {
+		let mut value = serde_yaml::to_value(&versions[key]).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+		if let serde_yaml::Value::Mapping(fields) = &mut value {
+			fields.retain(|_, v| !v.is_null());
+		}
+		document.insert(key.as_str().into(), value);
+	}
+	Ok(document)
+}
+
+// The poems of a file as YAML, separated by `---` when there are several
+pub fn poem_yaml(poems: &[HashMap<String, Version>]) -> io::Result<String> {
+	let mut yaml = String::new();
+	for (i, versions) in poems.iter().enumerate() {
+		if i > 0 {
+			yaml.push_str("---\n");
+		}
+		yaml.push_str(&serde_yaml::to_string(&poem_document(versions)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?);
+	}
+	Ok(yaml)
+}
+
+// Replaces a `.poem` file in place, after backing it up; written next to it first so a
+// failure never leaves half a poem. An encrypted file is encrypted again the same way.
+pub fn rewrite_poem(path: &Path, poems: &[HashMap<String, Version>]) -> io::Result<()> {
+	check_writable()?;
+	backup::backup(path)?;
+	let mut content = poem_yaml(poems)?.into_bytes();
+	if is_encrypted(&path.to_string_lossy()) {
+		content = encrypt(&fs::read(path)?, &content)?;
+	}
+	let temporary = path.with_extension("poem.tmp");
+	fs::write(&temporary, content)?;
+	fs::rename(&temporary, path)
+}
+
+// Writes a new `.poem` file into the library. Existing files are never overwritten.
+pub fn write_poem(filename: &str, versions: &HashMap<String, Version>) -> io::Result<PathBuf> {
+	check_writable()?;
+	let yaml = poem_yaml(std::slice::from_ref(versions))?;
+	let path = poems_dir().join(filename);
+	let mut file = fs::OpenOptions::new().write(true).create_new(true).open(&path)?;
+	file.write_all(yaml.as_bytes())?;
+	Ok(path)
+}
+
+// A poem to start an empty library with
+pub fn sample_poem() -> HashMap<String, Version> {
+	let version = Version {
+		title: Some("Hope is the thing with feathers".to_string()),
+		author: Some("Emily Dickinson".to_string()),
+		language: Some("eng".to_string()),
+		text: "Hope is the thing with feathers\nThat perches in the soul,\nAnd sings the tune without the words,\nAnd never stops at all,\n".to_string(),
+		..Default::default()
+	};
+	HashMap::from([("canonical".to_string(), version)])
+}
+
+// The poem of the given day (counted from the unix epoch). The pick only depends on the
+// day and the set of poem ids, so every run and every machine agrees on it.
+pub fn daily_poem(poems: &[Poem], day: u64) -> Option<&Poem> {
+	let mut ids: Vec<(&str, usize)> = poems.iter().enumerate().map(|(i, p)| (p.id(), i)).collect();
+	ids.sort();
+	// splitmix64, so consecutive days land far apart
+	let mut x = day.wrapping_add(0x9e3779b97f4a7c15);
+	x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+	x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+	x ^= x >> 31;
+	ids.get((x % ids.len().max(1) as u64) as usize).map(|&(_, i)| &poems[i])
+}
+
+// Path of a file relative to the library directory, with `/` between folders
+pub fn library_name(root: &Path, path: &Path) -> String {
+	let relative = path.strip_prefix(root).unwrap_or(path);
+	relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+// Every poem file in the library and its subdirectories, markdown ones included.
+// Hidden directories such as `.git` are skipped. Symlinked directories are followed, so a
+// library can gather several repositories; a directory or file reached by more than one
+// path is only taken once, under the shortest path (then the first in sort order).
+pub fn poem_files() -> io::Result<Vec<PathBuf>> {
+	let extensions = &library().extensions;
+	let dir = poems_dir();
+	if is_archive(&dir) {
+		return Err(io::Error::new(io::ErrorKind::Unsupported, format!("the library is the archive {}, which is read-only", dir.display())));
+	}
+	let ignore = Ignore::load(&dir);
+	let mut files = Vec::new();
+	let mut visited = HashSet::from([dir.canonicalize()?]);
+	let mut seen_files = HashSet::new();
+	let mut pending = VecDeque::from([dir.clone()]);
+	while let Some(current) = pending.pop_front() {
+		let mut entries = fs::read_dir(&current)?.map(|entry| entry.map(|e| e.path())).collect::<io::Result<Vec<_>>>()?;
+		entries.sort();
+		for path in entries {
+			let relative = path.strip_prefix(&dir).unwrap_or(&path);
+			// Broken symlinks have nothing to read
+			let Ok(canonical) = path.canonicalize() else { continue };
+			if canonical.is_dir() {
+				let hidden = path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+				if !hidden && !ignore.ignores(relative, true) && visited.insert(canonical) {
+					pending.push_back(path);
 				}
+				continue;
+			}
+			let wanted = wanted_extension(&path.to_string_lossy(), extensions);
+			if wanted && canonical.is_file() && !ignore.ignores(relative, false) && seen_files.insert(canonical) {
+				files.push(path);
+			}
+		}
+	}
+	Ok(files)
+}
 
-				let poem = Poem {
-					versions,
-					filename: entry.path().file_name().unwrap_or_default().to_string_lossy().into(),
-				};
+// A poem file left out of the library
+#[derive(Debug)]
+pub struct LoadError {
+	pub filename: String,
+	pub reason: String,
+}
+
+// A poem of a file read from the library or elsewhere; `document` as in `Poem`
+pub fn poem_from(filename: &str, document: Option<usize>, versions: HashMap<String, Version>, modified: Option<SystemTime>) -> Poem {
+	let counts = versions.get("canonical").map(|v| v.counts).unwrap_or_default();
+	Poem {
+		versions,
+		id: poem_id(filename, document),
+		filename: filename.to_string(),
+		document,
+		unloaded: false,
+		modified,
+		line_count: counts.lines,
+		word_count: counts.words + counts.cjk_chars,
+	}
+}
 
-				poems.push(poem);
+// Adds the poems of one file to `poems`, and what could not be parsed to `errors`
+fn push_poems(filename: &str, content: &str, modified: Option<SystemTime>, poems: &mut Vec<Poem>, errors: &mut Vec<LoadError>) {
+	let parsed = parse_file(Path::new(filename), content);
+	let several = parsed.len() > 1;
+	for (i, result) in parsed.into_iter().enumerate() {
+		match result {
+			Ok(versions) => poems.push(poem_from(filename, several.then_some(i + 1), versions, modified)),
+			Err(e) => {
+				tracing::warn!(file = filename, reason = %e, "skipped malformed poem");
+				errors.push(LoadError { filename: filename.to_string(), reason: e.to_string() });
 			}
 		}
 	}
-	Ok(poems)
+}
+
+// Adds the poems of a file of the library as `read_library` gives it to `poems`, and the
+// file to `errors` when it cannot be read
+pub fn push_library_file(file: LibraryFile, poems: &mut Vec<Poem>, errors: &mut Vec<LoadError>) -> io::Result<()> {
+	let filename = file.name;
+	let content = match file.content {
+		Ok(content) => content,
+		// Skipped like a malformed file: without the key or passphrase it cannot be read
+		Err(e) if is_encrypted(&filename) => {
+			tracing::warn!(file = filename, error = %e, "skipped encrypted poem file");
+			errors.push(LoadError { filename, reason: e.to_string() });
+			return Ok(());
+		},
+		Err(e) => return Err(e),
+	};
+	let Ok(content) = String::from_utf8(content) else {
+		tracing::warn!(file = filename, "skipped poem file that is not valid UTF-8");
+		errors.push(LoadError { filename, reason: "not valid UTF-8".to_string() });
+		return Ok(());
+	};
+	push_poems(&filename, &content, file.modified, poems, errors);
+	Ok(())
+}
+
+// In low-memory mode poems keep no text from loading on, so that the whole library is never
+// in memory at once; the text of the poem being read is read back for it
+pub fn drop_texts(poems: &mut [Poem]) {
+	if low_memory() {
+		for poem in poems {
+			poem.unload_text();
+		}
+	}
+}
+
+fn push_library_poems(file: LibraryFile, poems: &mut Vec<Poem>, errors: &mut Vec<LoadError>) -> io::Result<()> {
+	let start = poems.len();
+	push_library_file(file, poems, errors)?;
+	drop_texts(&mut poems[start..]);
+	Ok(())
+}
+
+// Through the index when it is turned on, which reads only the files changed since
+#[cfg(feature = "sqlite")]
+fn read_poems(poems: &mut Vec<Poem>, errors: &mut Vec<LoadError>) -> io::Result<()> {
+	if crate::index::in_use()? {
+		return crate::index::load(poems, errors);
+	}
+	read_library(|file| push_library_poems(file, poems, errors))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn read_poems(poems: &mut Vec<Poem>, errors: &mut Vec<LoadError>) -> io::Result<()> {
+	if Config::load()?.index {
+		tracing::warn!("`index` is set, which needs leaves built with the `sqlite` feature");
+	}
+	read_library(|file| push_library_poems(file, poems, errors))
+}
+
+// Every poem that parses, plus the files that were skipped and why. With `strict`
+// any skipped file is an error instead, listing all of them.
+pub fn load_poems(strict: bool) -> io::Result<(Vec<Poem>, Vec<LoadError>)> {
+	let started = Instant::now();
+	let mut poems = Vec::new();
+	let mut errors = Vec::new();
+	read_poems(&mut poems, &mut errors)?;
+	tracing::info!(library = %poems_dir().display(), poems = poems.len(), skipped = errors.len(), elapsed = ?started.elapsed(), "loaded library");
+	if strict && !errors.is_empty() {
+		let mut message = format!("{} malformed poem files:", errors.len());
+		for error in &errors {
+			message.push_str(&format!("\n  {}: {}", error.filename, error.reason));
+		}
+		return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+	}
+	Ok((poems, errors))
+}
+
+// The poems of a single file outside the library scan, for `leaves path/to/file.poem`.
+// Their filename is the absolute path; a file without any readable poem is an error.
+pub fn load_file(path: &Path) -> io::Result<(Vec<Poem>, Vec<LoadError>)> {
+	let path = std::path::absolute(path)?;
+	let name = path.to_string_lossy();
+	let content = read_poem_file(&path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", name, e)))?;
+	let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+	let mut poems = Vec::new();
+	let mut errors = Vec::new();
+	push_poems(&name, &content, modified, &mut poems, &mut errors);
+	if poems.is_empty() {
+		let reasons: Vec<String> = errors.iter().map(|e| e.reason.clone()).collect();
+		return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", name, reasons.join("; "))));
+	}
+	Ok((poems, errors))
+}
+
+// The poems in `demo/`, built into the binary for `leaves --demo`
+const DEMO_POEMS: [(&str, &str); 6] = [
+	("basho-old-pond.poem", include_str!("../demo/basho-old-pond.poem")),
+	("dickinson-hope.poem", include_str!("../demo/dickinson-hope.poem")),
+	("goethe-wandrers-nachtlied.poem", include_str!("../demo/goethe-wandrers-nachtlied.poem")),
+	("hafez-ghazal-1.poem", include_str!("../demo/hafez-ghazal-1.poem")),
+	("li-bai-quiet-night.poem", include_str!("../demo/li-bai-quiet-night.poem")),
+	("pushkin-i-loved-you.poem", include_str!("../demo/pushkin-i-loved-you.poem")),
+];
+
+pub fn demo_poems() -> Vec<Poem> {
+	let mut poems = Vec::new();
+	let mut errors = Vec::new();
+	for (filename, content) in DEMO_POEMS {
+		push_poems(filename, content, None, &mut poems, &mut errors);
+	}
+	debug_assert!(errors.is_empty(), "malformed demo poem: {:?}", errors);
+	poems
 }
@@ -0,0 +1,44 @@
+use crate::utils::home_dir;
+use std::path::PathBuf;
+
+// XDG says relative paths in these variables are to be ignored
+fn env_dir(name: &str) -> Option<PathBuf> {
+	std::env::var_os(name).map(PathBuf::from).filter(|path| path.is_absolute())
+}
+
+// The `leaves` directory under an XDG base directory when its variable is set, otherwise
+// under the platform's usual place: `windows` names the variable holding it on Windows,
+// `macos` and `unix` are paths inside the home directory.
+fn app_dir(xdg: &str, windows: &str, macos: &[&str], unix: &[&str]) -> PathBuf {
+	let base = env_dir(xdg)
+		.or_else(|| if cfg!(windows) { env_dir(windows) } else { None })
+		.unwrap_or_else(|| {
+			let parts = if cfg!(target_os = "macos") { macos } else { unix };
+			parts.iter().fold(home_dir(), |path, part| path.join(part))
+		});
+	base.join("leaves")
+}
+
+// Settings: `~/.config/leaves` (also on macOS, like most command-line tools), `%APPDATA%\leaves`
+pub fn config_dir() -> PathBuf {
+	app_dir("XDG_CONFIG_HOME", "APPDATA", &[".config"], &[".config"])
+}
+
+// What the app keeps between sessions, such as the reading history:
+// `~/.local/state/leaves`, `~/Library/Application Support/leaves`, `%LOCALAPPDATA%\leaves`
+pub fn state_dir() -> PathBuf {
+	app_dir("XDG_STATE_HOME", "LOCALAPPDATA", &["Library", "Application Support"], &[".local", "state"])
+}
+
+// Anything that can be downloaded or computed again:
+// `~/.cache/leaves`, `~/Library/Caches/leaves`, `%LOCALAPPDATA%\leaves`
+pub fn cache_dir() -> PathBuf {
+	app_dir("XDG_CACHE_HOME", "LOCALAPPDATA", &["Library", "Caches"], &[".cache"])
+}
+
+// `path`, unless only the file at its location from before the base directories were
+// followed exists (`~/.config` or `~/.local/state` on every platform)
+pub fn or_legacy(path: PathBuf, legacy: &[&str]) -> PathBuf {
+	let legacy = legacy.iter().fold(home_dir(), |path, part| path.join(part));
+	if !path.exists() && legacy.exists() { legacy } else { path }
+}
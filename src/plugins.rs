@@ -0,0 +1,283 @@
+use crate::app::{App, AppMode, Start};
+use crate::facets::{self, Facet};
+use crate::backup;
+use crate::models::{check_writable, poems_dir, Poem, Version};
+use crate::paths;
+use rhai::{Array, CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use std::{cell::RefCell, fs, path::{Component, Path, PathBuf}, process::Command, rc::Rc};
+
+// Scripts in Rhai (https://rhai.rs) from `plugins` in the config directory, with the `plugins`
+// feature. A script may define
+//
+//   fn on_startup(library)  run once the reader has loaded the library
+//   fn on_open_poem(poem)   run whenever the viewer shows another poem or version
+//
+// and bind its own functions to keys of the viewer with `bind("X", "name")` at its top level;
+// they are called with the poem being read, and the library too when they take a second
+// parameter. Keys the viewer already has are left to it. `facet("syllable form", "name")`
+// adds a way of browsing the library to the menu, grouping poems by what the function returns
+// for each version: text, a number, an array of either, or nothing.
+//
+// A poem is a map of `id`, `file`, `version`, `title`, `author`, `language`, `year`, `form`,
+// `tags` and `text`, the library an array of them (of the canonical version of each poem,
+// with no text in low-memory mode).
+// Besides Rhai's own functions, scripts can call `message(text)` for the status bar,
+// `open(id)`, `read_file(path)`, `write_file(path, text)`, with paths inside the library,
+// and `run(program, [args])`, which returns what the program printed.
+
+// Scripts stuck in a loop are stopped rather than freezing the reader
+const MAX_OPERATIONS: u64 = 100_000_000;
+
+// What the functions a script called asked of the reader
+#[derive(Default)]
+struct Requests {
+	message: Option<String>,
+	open: Option<String>,
+	// Keys bound by the script being loaded, to its functions
+	bindings: Vec<(char, String)>,
+	// Facets it added, by their name and function
+	facets: Vec<(String, String)>,
+}
+
+struct Script {
+	name: String,
+	ast: Rc<AST>,
+}
+
+// A facet of a script, whose function is called on every version
+struct ScriptFacet {
+	engine: Rc<Engine>,
+	ast: Rc<AST>,
+	name: String,
+	function: String,
+}
+
+impl Facet for ScriptFacet {
+	fn name(&self) -> String {
+		self.name.clone()
+	}
+	// Scripts are handed the text along with the rest of the poem
+	fn needs_text(&self) -> bool {
+		true
+	}
+	fn values(&self, poem: &Poem, key: &str, version: &Version) -> Vec<(i32, String)> {
+		let options = CallFnOptions::new().eval_ast(false);
+		let result = self.engine.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast, &self.function, (poem_map(poem, key, version),));
+		let value = |value: Dynamic| match value.as_int() {
+			Ok(n) => Some((n as i32, n.to_string())),
+			Err(_) => value.into_string().ok().map(|text| (0, text)).filter(|(_, text)| !text.is_empty()),
+		};
+		match result {
+			Ok(values) if values.is_array() => values.into_array().unwrap_or_default().into_iter().filter_map(value).collect(),
+			Ok(other) => value(other).into_iter().collect(),
+			Err(e) => {
+				tracing::debug!(facet = self.name, "{}", e);
+				Vec::new()
+			},
+		}
+	}
+}
+
+pub struct Plugins {
+	engine: Rc<Engine>,
+	scripts: Vec<Script>,
+	// Keys of the viewer with the script and the function each runs
+	bindings: Vec<(char, usize, String)>,
+	requests: Rc<RefCell<Requests>>,
+	// Scripts that did not load, to be told once the reader is up
+	errors: Vec<String>,
+	// The poem and version on_open_poem last ran for
+	opened: Option<(usize, String)>,
+}
+
+fn script_error(e: impl std::fmt::Display) -> Box<EvalAltResult> {
+	e.to_string().into()
+}
+
+// Scripts only reach files inside the library
+fn library_path(path: &str) -> Result<PathBuf, Box<EvalAltResult>> {
+	if path.is_empty() || Path::new(path).components().any(|c| !matches!(c, Component::Normal(_))) {
+		return Err(script_error(format!("\"{}\" is not a path inside the library", path)));
+	}
+	Ok(poems_dir().join(path))
+}
+
+fn engine(requests: &Rc<RefCell<Requests>>) -> Engine {
+	let mut engine = Engine::new();
+	engine.set_max_operations(MAX_OPERATIONS);
+	// The terminal belongs to the reader
+	engine.on_print(|text| tracing::info!(text, "plugin"));
+	engine.on_debug(|text, _, position| tracing::debug!(text, ?position, "plugin"));
+	let shared = requests.clone();
+	engine.register_fn("message", move |text: &str| shared.borrow_mut().message = Some(text.to_string()));
+	let shared = requests.clone();
+	engine.register_fn("open", move |id: &str| shared.borrow_mut().open = Some(id.to_string()));
+	let shared = requests.clone();
+	engine.register_fn("bind", move |key: &str, name: &str| -> Result<(), Box<EvalAltResult>> {
+		let mut chars = key.chars();
+		match (chars.next(), chars.next()) {
+			(Some(c), None) => {
+				shared.borrow_mut().bindings.push((c, name.to_string()));
+				Ok(())
+			},
+			_ => Err(script_error(format!("bind takes a single character, not \"{}\"", key))),
+		}
+	});
+	let shared = requests.clone();
+	engine.register_fn("facet", move |name: &str, function: &str| shared.borrow_mut().facets.push((name.to_string(), function.to_string())));
+	engine.register_fn("read_file", |path: &str| fs::read_to_string(library_path(path)?).map_err(script_error));
+	// Like every other change to the library, kept from a read-only one and backed up first
+	engine.register_fn("write_file", |path: &str, text: &str| -> Result<(), Box<EvalAltResult>> {
+		let path = library_path(path)?;
+		check_writable().map_err(script_error)?;
+		backup::backup(&path).map_err(script_error)?;
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent).map_err(script_error)?;
+		}
+		fs::write(path, text).map_err(script_error)
+	});
+	engine.register_fn("run", |program: &str, args: Array| -> Result<String, Box<EvalAltResult>> {
+		let args: Vec<String> = args.into_iter().map(|arg| arg.to_string()).collect();
+		let output = Command::new(program).args(&args).current_dir(poems_dir()).output()
+			.map_err(|e| script_error(format!("could not run {}: {}", program, e)))?;
+		if !output.status.success() {
+			return Err(script_error(format!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim())));
+		}
+		Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+	});
+	engine
+}
+
+fn poem_map(poem: &Poem, key: &str, version: &Version) -> Map {
+	let text = |value: Option<&str>| value.map_or(Dynamic::UNIT, |value| value.into());
+	let mut map = Map::new();
+	map.insert("id".into(), poem.id().into());
+	map.insert("file".into(), poems_dir().join(&poem.filename).to_string_lossy().into_owned().into());
+	map.insert("version".into(), key.into());
+	map.insert("title".into(), text(version.title.as_deref()));
+	map.insert("author".into(), text(poem.author_for(version)));
+	map.insert("language".into(), text(version.language.as_deref()));
+	map.insert("year".into(), version.year().map_or(Dynamic::UNIT, |year| Dynamic::from(year as i64)));
+	map.insert("form".into(), text(version.form.as_deref()));
+	let tags: Array = version.tags.iter().flatten().map(|tag| tag.as_str().into()).collect();
+	map.insert("tags".into(), tags.into());
+	map.insert("text".into(), version.text.as_str().into());
+	map
+}
+
+fn library(app: &App) -> Array {
+	app.poems.iter().filter_map(|poem| {
+		let (key, version) = poem.versions.get_key_value("canonical").or_else(|| poem.versions.iter().min_by_key(|(key, _)| *key))?;
+		Some(poem_map(poem, key, version).into())
+	}).collect()
+}
+
+fn current_poem(app: &App) -> Option<Dynamic> {
+	let poem = app.poems.get(app.current_poem)?;
+	let version = poem.versions.get(&app.current_version)?;
+	Some(poem_map(poem, &app.current_version, version).into())
+}
+
+impl Plugins {
+	// Compiles every script and runs its top level, which binds its keys and adds its facets,
+	// for apps made after
+	pub fn load() -> Plugins {
+		let requests = Rc::new(RefCell::new(Requests::default()));
+		let mut plugins = Plugins { engine: Rc::new(engine(&requests)), scripts: Vec::new(), bindings: Vec::new(), requests, errors: Vec::new(), opened: None };
+		let mut files: Vec<PathBuf> = fs::read_dir(paths::config_dir().join("plugins")).into_iter().flatten()
+			.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+			.filter(|path| path.extension().is_some_and(|extension| extension == "rhai"))
+			.collect();
+		files.sort();
+		for path in files {
+			let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+			let loaded = plugins.engine.compile_file(path.clone()).and_then(|ast| plugins.engine.run_ast(&ast).map(|_| ast));
+			let Requests { bindings, facets, .. } = std::mem::take(&mut *plugins.requests.borrow_mut());
+			match loaded {
+				Ok(ast) => {
+					let ast = Rc::new(ast);
+					let script = plugins.scripts.len();
+					plugins.bindings.extend(bindings.into_iter().map(|(key, function)| (key, script, function)));
+					for (name, function) in facets {
+						facets::register(Rc::new(ScriptFacet { engine: plugins.engine.clone(), ast: ast.clone(), name, function }));
+					}
+					plugins.scripts.push(Script { name, ast });
+				},
+				Err(e) => {
+					tracing::warn!(plugin = name, "{}", e);
+					plugins.errors.push(format!("Plugin {}: {}", name, e));
+				},
+			}
+		}
+		tracing::info!(scripts = plugins.scripts.len(), keys = plugins.bindings.len(), "loaded plugins");
+		plugins
+	}
+
+	// Tells of scripts that did not load and runs on_startup
+	pub fn startup(&mut self, app: &mut App) {
+		if let Some(error) = self.errors.first() {
+			app.status_message = Some(error.clone());
+		}
+		for script in 0..self.scripts.len() {
+			self.call(app, script, "on_startup", |app, _| vec![library(app).into()]);
+		}
+	}
+
+	// Runs on_open_poem when the viewer shows another poem or version than last time, or the
+	// same one again after going back to the menu
+	pub fn poem_opened(&mut self, app: &mut App) {
+		if app.mode == AppMode::Menu {
+			self.opened = None;
+		}
+		if app.mode != AppMode::Viewing || app.poems.is_empty() {
+			return;
+		}
+		let shown = (app.current_poem, app.current_version.clone());
+		if self.opened.as_ref() == Some(&shown) {
+			return;
+		}
+		self.opened = Some(shown);
+		for script in 0..self.scripts.len() {
+			self.call(app, script, "on_open_poem", |app, _| current_poem(app).into_iter().collect());
+		}
+	}
+
+	// Runs the function bound to `key`; false when no script bound it
+	pub fn run_key(&mut self, app: &mut App, key: char) -> bool {
+		let Some((_, script, function)) = self.bindings.iter().find(|(bound, ..)| *bound == key).cloned() else { return false };
+		self.call(app, script, &function, |app, params| {
+			let mut args: Vec<Dynamic> = current_poem(app).into_iter().collect();
+			if params > 1 {
+				args.push(library(app).into());
+			}
+			args
+		});
+		true
+	}
+
+	// Calls `function` of a script if it has it, with as many of `args` as it takes, then
+	// carries out what it asked for. The library is only made into a map when it is taken.
+	fn call(&mut self, app: &mut App, script: usize, function: &str, args: impl Fn(&App, usize) -> Vec<Dynamic>) {
+		let Script { name, ast } = &self.scripts[script];
+		let Some(params) = ast.iter_functions().find(|f| f.name == function).map(|f| f.params.len()) else { return };
+		let mut args = args(app, params);
+		args.truncate(params);
+		let options = CallFnOptions::new().eval_ast(false);
+		let result = self.engine.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), ast, function, args);
+		let requests = std::mem::take(&mut *self.requests.borrow_mut());
+		if let Err(e) = result {
+			tracing::warn!(plugin = name, function, "{}", e);
+			app.status_message = Some(format!("Plugin {}: {}", name, e));
+			return;
+		}
+		if let Some(id) = requests.open {
+			if let Err(e) = app.start_at(Start::Poem(id)) {
+				app.status_message = Some(format!("Plugin {}: {}", name, e));
+				return;
+			}
+		}
+		if let Some(message) = requests.message {
+			app.status_message = Some(message);
+		}
+	}
+}
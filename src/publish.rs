@@ -0,0 +1,159 @@
+use crate::export::{convert, escape_html, stanzas, HTML};
+use crate::models::{Poem, Version};
+use crate::utils::{get_language_name, slug};
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+const STYLE: &str = "body { max-width: 42rem; margin: 2rem auto; padding: 0 1rem; font-family: Georgia, serif; line-height: 1.5; }
+a { color: inherit; }
+nav { margin-bottom: 2rem; font-size: 0.9rem; }
+.epigraph { text-align: end; font-style: italic; font-size: 0.9rem; }
+.vertical { writing-mode: vertical-lr; max-height: 80vh; }
+.vertical[dir=\"rtl\"] { writing-mode: vertical-rl; }
+.switcher a { margin-right: 1rem; }
+.version { display: none; }
+.version.canonical, .version:target { display: block; }
+.versions:has(.version:target) .version.canonical:not(:target) { display: none; }
+";
+
+fn escaped(text: &str) -> String {
+	let mut out = String::new();
+	for c in text.chars() {
+		escape_html(c, &mut out);
+	}
+	out
+}
+
+// `root` leads back to the top of the site from the page's directory
+fn page(title: &str, root: &str, body: &str) -> String {
+	format!(
+		"<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n<link rel=\"stylesheet\" href=\"{}style.css\">\n</head>\n<body>\n<nav><a href=\"{}index.html\">Library</a></nav>\n{}</body>\n</html>\n",
+		escaped(title), root, root, body
+	)
+}
+
+fn title_of(version: &Version) -> &str {
+	version.title.as_deref().unwrap_or("Untitled")
+}
+
+fn author_of(version: &Version) -> &str {
+	version.author.as_deref().unwrap_or("Unknown")
+}
+
+fn language_label(code: &str) -> String {
+	get_language_name(code).map(|name| format!("{} ({})", name, code)).unwrap_or_else(|| code.to_string())
+}
+
+fn render_version(key: &str, version: &Version) -> String {
+	let mut classes = vec!["version"];
+	if key == "canonical" {
+		classes.push("canonical");
+	}
+	if version.is_vertical() {
+		classes.push("vertical");
+	}
+	let mut html = format!("<section id=\"v-{}\" class=\"{}\"", escaped(key), classes.join(" "));
+	if let Some(language) = &version.language {
+		html.push_str(&format!(" lang=\"{}\"", escaped(language)));
+	}
+	if version.is_rtl() {
+		html.push_str(" dir=\"rtl\"");
+	}
+	html.push_str(&format!(">\n<h1>{}</h1>\n<p><i>{}</i></p>\n", escaped(title_of(version)), escaped(author_of(version))));
+	if let Some(epigraph) = &version.epigraph {
+		html.push_str(&format!("<p class=\"epigraph\">{}</p>\n", convert(epigraph.trim_end(), &HTML).replace('\n', "<br>\n")));
+	}
+	let text = convert(&version.text, &HTML);
+	for stanza in stanzas(&text) {
+		html.push_str(&format!("<p>{}</p>\n", stanza.join("<br>\n")));
+	}
+	html.push_str("</section>\n");
+	html
+}
+
+fn render_poem(poem: &Poem) -> String {
+	let mut keys: Vec<&String> = poem.versions.keys().collect();
+	keys.sort_by_key(|k| (k.as_str() != "canonical", k.as_str()));
+	let mut body = String::new();
+	if keys.len() > 1 {
+		body.push_str("<p class=\"switcher\">");
+		for key in &keys {
+			let version = &poem.versions[*key];
+			let label = version.language.as_deref().map(language_label).unwrap_or_else(|| key.to_string());
+			body.push_str(&format!("<a href=\"#v-{}\">{}</a>", escaped(key), escaped(&label)));
+		}
+		body.push_str("</p>\n");
+	}
+	body.push_str("<div class=\"versions\">\n");
+	for key in keys {
+		body.push_str(&render_version(key, &poem.versions[key]));
+	}
+	body.push_str("</div>\n");
+	body
+}
+
+// A list of links to poem pages, sorted by author and title
+fn poem_list(poems: &[&Poem], root: &str) -> String {
+	let mut entries: Vec<(&str, &str, &str)> = poems.iter()
+		.filter_map(|p| p.canonical().map(|v| (author_of(v), title_of(v), p.id())))
+		.collect();
+	entries.sort();
+	let mut html = String::from("<ul>\n");
+	for (author, title, id) in entries {
+		html.push_str(&format!("<li><a href=\"{}poems/{}.html\">{}</a> — {}</li>\n", root, escaped(id), escaped(title), escaped(author)));
+	}
+	html.push_str("</ul>\n");
+	html
+}
+
+// Writes an index page per group and returns the links for the front page
+fn write_groups(out: &Path, dir: &str, heading: &str, groups: &BTreeMap<String, Vec<&Poem>>, label: impl Fn(&str) -> String) -> io::Result<String> {
+	if groups.is_empty() {
+		return Ok(String::new());
+	}
+	fs::create_dir_all(out.join(dir))?;
+	let mut links = format!("<h2>{}</h2>\n<ul>\n", heading);
+	for (name, poems) in groups {
+		let file = format!("{}.html", slug(name));
+		let title = label(name);
+		let body = format!("<h1>{}</h1>\n{}", escaped(&title), poem_list(poems, "../"));
+		fs::write(out.join(dir).join(&file), page(&title, "../", &body))?;
+		links.push_str(&format!("<li><a href=\"{}/{}\">{}</a> ({})</li>\n", dir, file, escaped(&title), poems.len()));
+	}
+	links.push_str("</ul>\n");
+	Ok(links)
+}
+
+// Renders the library as a static site: a front page, index pages per author,
+// language and tag, and one page per poem with all of its versions.
+pub fn publish(poems: &[Poem], out: &Path) -> io::Result<usize> {
+	fs::create_dir_all(out.join("poems"))?;
+	fs::write(out.join("style.css"), STYLE)?;
+
+	let mut authors: BTreeMap<String, Vec<&Poem>> = BTreeMap::new();
+	let mut languages: BTreeMap<String, Vec<&Poem>> = BTreeMap::new();
+	let mut tags: BTreeMap<String, Vec<&Poem>> = BTreeMap::new();
+	for poem in poems {
+		let Some(canonical) = poem.canonical() else { continue };
+		authors.entry(author_of(canonical).to_string()).or_default().push(poem);
+		let mut poem_languages: Vec<&str> = poem.versions.values().filter_map(|v| v.language.as_deref()).collect();
+		poem_languages.sort();
+		poem_languages.dedup();
+		for language in poem_languages {
+			languages.entry(language.to_string()).or_default().push(poem);
+		}
+		for tag in canonical.tags.iter().flatten() {
+			tags.entry(tag.clone()).or_default().push(poem);
+		}
+		fs::write(out.join("poems").join(format!("{}.html", poem.id())), page(title_of(canonical), "../", &render_poem(poem)))?;
+	}
+
+	let mut index = String::from("<h1>Library</h1>\n");
+	index.push_str(&write_groups(out, "authors", "Authors", &authors, |name| name.to_string())?);
+	index.push_str(&write_groups(out, "languages", "Languages", &languages, language_label)?);
+	index.push_str(&write_groups(out, "tags", "Tags", &tags, |name| name.to_string())?);
+	let all: Vec<&Poem> = poems.iter().collect();
+	index.push_str("<h2>All poems</h2>\n");
+	index.push_str(&poem_list(&all, ""));
+	fs::write(out.join("index.html"), page("Library", "", &index))?;
+	Ok(poems.len())
+}
@@ -0,0 +1,256 @@
+use crate::models::{is_encrypted, low_memory, poems_dir, read_versions, Poem};
+use crate::paths;
+use crate::utils::{is_cjk, LanguageTag};
+use std::{collections::{hash_map::DefaultHasher, HashSet}, fs, hash::{Hash, Hasher}, io, time::UNIX_EPOCH};
+use tantivy::collector::TopDocs;
+use tantivy::query::{BooleanQuery, BoostQuery, Occur, Query, QueryParser};
+use tantivy::schema::{Field, IndexRecordOption, Schema, TantivyDocument, TextFieldIndexing, TextOptions, Value, STORED, STRING};
+use tantivy::tokenizer::{Language, Stemmer, TextAnalyzer, Token, TokenStream, Tokenizer};
+use tantivy::{Index, IndexReader};
+
+// Ranked full-text search over every version of every poem, with the `search` feature:
+// `"phrases in quotes"`, `-excluded` words, `title:` and `author:` fields, and words a letter
+// off still found, below exact ones
+
+// Raised whenever the layout of the index changes, which has it built again
+const SCHEMA: u64 = 2;
+
+// Languages whose versions are also indexed by word stem, so "hoped" finds "hoping", by their
+// ISO 639-1 code, which names the field holding them
+const STEMMED: [(&str, Language); 18] = [
+	("ar", Language::Arabic), ("da", Language::Danish), ("de", Language::German), ("el", Language::Greek),
+	("en", Language::English), ("es", Language::Spanish), ("fi", Language::Finnish), ("fr", Language::French),
+	("hu", Language::Hungarian), ("it", Language::Italian), ("nl", Language::Dutch), ("no", Language::Norwegian),
+	("pt", Language::Portuguese), ("ro", Language::Romanian), ("ru", Language::Russian), ("sv", Language::Swedish),
+	("ta", Language::Tamil), ("tr", Language::Turkish),
+];
+
+fn search_error(e: impl std::fmt::Display) -> io::Error {
+	io::Error::other(format!("search index: {}", e))
+}
+
+// Words in lower case. Han, kana and hangul are written without spaces, so their runs are cut
+// into overlapping pairs of characters instead, and a query of two or more is found anywhere.
+#[derive(Clone, Default)]
+struct PoemTokenizer;
+
+struct PoemTokens {
+	tokens: std::vec::IntoIter<Token>,
+	current: Token,
+}
+
+impl TokenStream for PoemTokens {
+	fn advance(&mut self) -> bool {
+		match self.tokens.next() {
+			Some(token) => {
+				self.current = token;
+				true
+			}
+			None => false,
+		}
+	}
+	fn token(&self) -> &Token {
+		&self.current
+	}
+	fn token_mut(&mut self) -> &mut Token {
+		&mut self.current
+	}
+}
+
+impl Tokenizer for PoemTokenizer {
+	type TokenStream<'a> = PoemTokens;
+	fn token_stream<'a>(&'a mut self, text: &'a str) -> PoemTokens {
+		PoemTokens { tokens: tokens(text).into_iter(), current: Token::default() }
+	}
+}
+
+fn tokens(text: &str) -> Vec<Token> {
+	let chars: Vec<(usize, char)> = text.char_indices().collect();
+	let offset = |i: usize| chars.get(i).map_or(text.len(), |&(offset, _)| offset);
+	let mut tokens = Vec::new();
+	let mut push = |from: usize, to: usize| {
+		let position = tokens.len();
+		tokens.push(Token { offset_from: from, offset_to: to, position, text: text[from..to].to_lowercase(), position_length: 1 });
+	};
+	let mut i = 0;
+	while i < chars.len() {
+		let c = chars[i].1;
+		if !c.is_alphanumeric() {
+			i += 1;
+			continue;
+		}
+		let cjk = is_cjk(c);
+		let end = (i..chars.len()).find(|&j| !chars[j].1.is_alphanumeric() || is_cjk(chars[j].1) != cjk).unwrap_or(chars.len());
+		if cjk && end - i > 1 {
+			for j in i..end - 1 {
+				push(offset(j), offset(j + 2));
+			}
+		} else {
+			push(offset(i), offset(end));
+		}
+		i = end;
+	}
+	tokens
+}
+
+// The ISO 639-1 code of a language code as written in a poem, e.g. `en` for `eng-GB`
+fn two_letter_code(code: &str) -> Option<String> {
+	let tag = LanguageTag::parse(code)?;
+	if tag.language.len() == 2 {
+		return Some(tag.language);
+	}
+	isolang::Language::from_639_3(&tag.language)?.to_639_1().map(str::to_string)
+}
+
+pub struct SearchIndex {
+	index: Index,
+	reader: IndexReader,
+	id: Field,
+	title: Field,
+	author: Field,
+	text: Field,
+	stemmed: Vec<Field>,
+}
+
+impl SearchIndex {
+	// The index of `poems`: with `persist`, the one kept in the cache directory, built again
+	// when any of their files changed since; otherwise one built in memory, for poems read from
+	// elsewhere than the library
+	pub fn open(poems: &[Poem], persist: bool) -> io::Result<SearchIndex> {
+		let indexing = |tokenizer: &str| TextOptions::default()
+			.set_indexing_options(TextFieldIndexing::default().set_tokenizer(tokenizer).set_index_option(IndexRecordOption::WithFreqsAndPositions));
+		let mut builder = Schema::builder();
+		let id = builder.add_text_field("id", STRING | STORED);
+		let title = builder.add_text_field("title", indexing("poem"));
+		let author = builder.add_text_field("author", indexing("poem"));
+		let text = builder.add_text_field("text", indexing("poem"));
+		let stemmed = STEMMED.iter().map(|(code, _)| builder.add_text_field(&format!("text_{}", code), indexing(&format!("poem_{}", code)))).collect();
+		let schema = builder.build();
+
+		// The fingerprint of what a new index holds is written once it is all there
+		let mut unstamped = None;
+		let index = if persist {
+			let dir = paths::cache_dir().join("search");
+			let stamp = dir.join("fingerprint");
+			let fingerprint = fingerprint(poems).to_string();
+			let current = fs::read_to_string(&stamp).is_ok_and(|stamped| stamped == fingerprint);
+			match Index::open_in_dir(&dir) {
+				Ok(index) if current => index,
+				_ => {
+					if dir.exists() {
+						fs::remove_dir_all(&dir)?;
+					}
+					fs::create_dir_all(&dir)?;
+					unstamped = Some((stamp, fingerprint));
+					Index::create_in_dir(&dir, schema).map_err(search_error)?
+				}
+			}
+		} else {
+			Index::create_in_ram(schema)
+		};
+		let fresh = !persist || unstamped.is_some();
+		index.tokenizers().register("poem", TextAnalyzer::from(PoemTokenizer));
+		for (code, language) in STEMMED {
+			index.tokenizers().register(&format!("poem_{}", code), TextAnalyzer::builder(PoemTokenizer).filter(Stemmer::new(language)).build());
+		}
+		let reader = index.reader().map_err(search_error)?;
+		let search_index = SearchIndex { index, reader, id, title, author, text, stemmed };
+		if fresh {
+			search_index.build(poems, persist)?;
+		}
+		if let Some((stamp, fingerprint)) = unstamped {
+			fs::write(stamp, fingerprint)?;
+		}
+		Ok(search_index)
+	}
+
+	// One document per version, under the id of its poem. Encrypted poems are left out of an
+	// index kept on disk, which would hold their text in the clear; they are still found by
+	// title and author.
+	fn build(&self, poems: &[Poem], persist: bool) -> io::Result<()> {
+		let started = std::time::Instant::now();
+		// In low-memory mode a single indexing thread with the least tantivy takes
+		let writer = if low_memory() { self.index.writer_with_num_threads(1, 15_000_000) } else { self.index.writer(50_000_000) };
+		let mut writer = writer.map_err(search_error)?;
+		for poem in poems {
+			if persist && is_encrypted(&poem.filename) {
+				continue;
+			}
+			// Texts dropped in low-memory mode are read back just for the index
+			let reloaded = if poem.unloaded { Some(read_versions(&poem.filename, poem.document)?) } else { None };
+			for version in reloaded.as_ref().unwrap_or(&poem.versions).values() {
+				let mut document = TantivyDocument::default();
+				document.add_text(self.id, poem.id());
+				if let Some(title) = &version.title {
+					document.add_text(self.title, title);
+				}
+				let names = version.author_names.iter().flat_map(|names| names.values().map(String::as_str));
+				for name in poem.author_for(version).into_iter().chain(version.sort_name.as_deref()).chain(names) {
+					document.add_text(self.author, name);
+				}
+				let texts = [version.epigraph.as_deref(), Some(version.text.as_str()), version.transliteration.as_deref()].into_iter().flatten()
+					.chain(version.variants.iter().flat_map(|variants| variants.values().map(String::as_str)));
+				let stemmed = version.language.as_deref().and_then(two_letter_code)
+					.and_then(|code| STEMMED.iter().position(|(stemmed, _)| *stemmed == code))
+					.map(|i| self.stemmed[i]);
+				for text in texts {
+					document.add_text(self.text, text);
+					if let Some(field) = stemmed {
+						document.add_text(field, text);
+					}
+				}
+				writer.add_document(document).map_err(search_error)?;
+			}
+		}
+		writer.commit().map_err(search_error)?;
+		self.reader.reload().map_err(search_error)?;
+		tracing::info!(poems = poems.len(), elapsed = ?started.elapsed(), "built search index");
+		Ok(())
+	}
+
+	// Ids of the poems matching `query`, best first, at most `limit` of them. Every word has to
+	// be found; parts of the query that do not parse, such as an unclosed quote, are left out.
+	pub fn search(&self, query: &str, limit: usize) -> io::Result<Vec<String>> {
+		let fields: Vec<Field> = [self.title, self.author, self.text].into_iter().chain(self.stemmed.iter().copied()).collect();
+		let mut exact = QueryParser::for_index(&self.index, fields);
+		exact.set_conjunction_by_default();
+		let mut fuzzy = exact.clone();
+		for field in [self.title, self.author, self.text] {
+			fuzzy.set_field_fuzzy(field, false, 1, true);
+		}
+		let (exact, _) = exact.parse_query_lenient(query);
+		let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![(Occur::Should, Box::new(BoostQuery::new(exact, 2.0)))];
+		// A pair of Han characters a character off is any pair sharing one with it
+		if !query.chars().any(is_cjk) {
+			clauses.push((Occur::Should, fuzzy.parse_query_lenient(query).0));
+		}
+		let query = BooleanQuery::new(clauses);
+		let searcher = self.reader.searcher();
+		// A poem is found once for each of its versions that matches
+		let versions = (searcher.num_docs() as usize).clamp(1, limit.saturating_mul(4).max(1));
+		let mut seen = HashSet::new();
+		let mut ids = Vec::new();
+		for (_, address) in searcher.search(&query, &TopDocs::with_limit(versions)).map_err(search_error)? {
+			let document: TantivyDocument = searcher.doc(address).map_err(search_error)?;
+			if let Some(id) = document.get_first(self.id).and_then(|value| value.as_str()) {
+				if ids.len() < limit && seen.insert(id.to_string()) {
+					ids.push(id.to_string());
+				}
+			}
+		}
+		Ok(ids)
+	}
+}
+
+// Changes with the library and with any file of it, so an index built before is known stale
+fn fingerprint(poems: &[Poem]) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	SCHEMA.hash(&mut hasher);
+	poems_dir().hash(&mut hasher);
+	for poem in poems {
+		poem.filename.hash(&mut hasher);
+		poem.document.hash(&mut hasher);
+		poem.modified.and_then(|m| m.duration_since(UNIX_EPOCH).ok()).hash(&mut hasher);
+	}
+	hasher.finish()
+}
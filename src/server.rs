@@ -0,0 +1,89 @@
+use crate::cli::PoemJson;
+use crate::models::Poem;
+use rand::seq::SliceRandom;
+use serde::Serialize;
+use std::io;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+// Decodes `%XX` escapes and `+` in a query string value
+fn percent_decode(value: &str) -> String {
+	let bytes = value.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		let escape = bytes.get(i + 1..i + 3)
+			.and_then(|hex| std::str::from_utf8(hex).ok())
+			.and_then(|hex| u8::from_str_radix(hex, 16).ok());
+		match (bytes[i], escape) {
+			(b'%', Some(byte)) => {
+				decoded.push(byte);
+				i += 2;
+			}
+			(b'+', _) => decoded.push(b' '),
+			(byte, _) => decoded.push(byte),
+		}
+		i += 1;
+	}
+	String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn query_param(query: &str, name: &str) -> Option<String> {
+	query.split('&')
+		.filter_map(|pair| pair.split_once('='))
+		.find(|(key, _)| *key == name)
+		.map(|(_, value)| percent_decode(value))
+}
+
+fn json_response(status: u16, value: &impl Serialize) -> Response<io::Cursor<Vec<u8>>> {
+	let body = serde_json::to_vec_pretty(value).unwrap_or_default();
+	let content_type = Header::from_bytes("Content-Type", "application/json; charset=utf-8").expect("valid header");
+	let cors = Header::from_bytes("Access-Control-Allow-Origin", "*").expect("valid header");
+	Response::from_data(body).with_status_code(status).with_header(content_type).with_header(cors)
+}
+
+fn error(status: u16, message: &str) -> Response<io::Cursor<Vec<u8>>> {
+	json_response(status, &serde_json::json!({ "error": message }))
+}
+
+fn listing<'a>(poems: &[&'a Poem]) -> Vec<PoemJson<'a>> {
+	poems.iter().map(|p| PoemJson::new(p, false, None)).collect()
+}
+
+fn respond(poems: &[Poem], request: &Request) -> Response<io::Cursor<Vec<u8>>> {
+	if *request.method() != Method::Get {
+		return error(405, "only GET is supported");
+	}
+	let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+	let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+	match segments.as_slice() {
+		["poems"] => json_response(200, &listing(&poems.iter().collect::<Vec<_>>())),
+		["poems", id] => {
+			let id = percent_decode(id);
+			match poems.iter().find(|p| p.id() == id) {
+				Some(poem) => json_response(200, &PoemJson::new(poem, true, None)),
+				None => error(404, "no such poem"),
+			}
+		}
+		["search"] => {
+			let query = query_param(query, "q").unwrap_or_default().to_lowercase();
+			let matches: Vec<&Poem> = poems.iter().filter(|p| !query.is_empty() && p.matches(&query)).collect();
+			json_response(200, &listing(&matches))
+		}
+		["random"] => match poems.choose(&mut rand::thread_rng()) {
+			Some(poem) => json_response(200, &PoemJson::new(poem, true, None)),
+			None => error(404, "the library is empty"),
+		},
+		_ => error(404, "unknown endpoint"),
+	}
+}
+
+// Serves the library read-only over HTTP until the process is stopped
+pub fn serve(poems: &[Poem], host: &str, port: u16) -> io::Result<()> {
+	let server = Server::http((host, port)).map_err(|e| io::Error::new(io::ErrorKind::AddrInUse, e.to_string()))?;
+	println!("Serving {} poems on http://{}:{}", poems.len(), host, port);
+	for request in server.incoming_requests() {
+		let response = respond(poems, &request);
+		let _ = request.respond(response);
+	}
+	Ok(())
+}
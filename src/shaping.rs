@@ -0,0 +1,155 @@
+use crate::config::Config;
+use std::{collections::HashMap, sync::OnceLock};
+use unicode_joining_type::{get_joining_type, JoiningType};
+
+// Presentation forms of Arabic and Persian letters: isolated, final, initial, medial; letters
+// that only join the one before them have no initial or medial form
+const FORMS: [(char, [char; 4]); 43] = [
+	('\u{0621}', ['\u{FE80}', '\u{FE80}', '\u{FE80}', '\u{FE80}']), // hamza
+	('\u{0622}', ['\u{FE81}', '\u{FE82}', '\u{FE81}', '\u{FE82}']), // alef with madda
+	('\u{0623}', ['\u{FE83}', '\u{FE84}', '\u{FE83}', '\u{FE84}']), // alef with hamza above
+	('\u{0624}', ['\u{FE85}', '\u{FE86}', '\u{FE85}', '\u{FE86}']), // waw with hamza
+	('\u{0625}', ['\u{FE87}', '\u{FE88}', '\u{FE87}', '\u{FE88}']), // alef with hamza below
+	('\u{0626}', ['\u{FE89}', '\u{FE8A}', '\u{FE8B}', '\u{FE8C}']), // yeh with hamza
+	('\u{0627}', ['\u{FE8D}', '\u{FE8E}', '\u{FE8D}', '\u{FE8E}']), // alef
+	('\u{0628}', ['\u{FE8F}', '\u{FE90}', '\u{FE91}', '\u{FE92}']), // beh
+	('\u{0629}', ['\u{FE93}', '\u{FE94}', '\u{FE93}', '\u{FE94}']), // teh marbuta
+	('\u{062A}', ['\u{FE95}', '\u{FE96}', '\u{FE97}', '\u{FE98}']), // teh
+	('\u{062B}', ['\u{FE99}', '\u{FE9A}', '\u{FE9B}', '\u{FE9C}']), // theh
+	('\u{062C}', ['\u{FE9D}', '\u{FE9E}', '\u{FE9F}', '\u{FEA0}']), // jeem
+	('\u{062D}', ['\u{FEA1}', '\u{FEA2}', '\u{FEA3}', '\u{FEA4}']), // hah
+	('\u{062E}', ['\u{FEA5}', '\u{FEA6}', '\u{FEA7}', '\u{FEA8}']), // khah
+	('\u{062F}', ['\u{FEA9}', '\u{FEAA}', '\u{FEA9}', '\u{FEAA}']), // dal
+	('\u{0630}', ['\u{FEAB}', '\u{FEAC}', '\u{FEAB}', '\u{FEAC}']), // thal
+	('\u{0631}', ['\u{FEAD}', '\u{FEAE}', '\u{FEAD}', '\u{FEAE}']), // reh
+	('\u{0632}', ['\u{FEAF}', '\u{FEB0}', '\u{FEAF}', '\u{FEB0}']), // zain
+	('\u{0633}', ['\u{FEB1}', '\u{FEB2}', '\u{FEB3}', '\u{FEB4}']), // seen
+	('\u{0634}', ['\u{FEB5}', '\u{FEB6}', '\u{FEB7}', '\u{FEB8}']), // sheen
+	('\u{0635}', ['\u{FEB9}', '\u{FEBA}', '\u{FEBB}', '\u{FEBC}']), // sad
+	('\u{0636}', ['\u{FEBD}', '\u{FEBE}', '\u{FEBF}', '\u{FEC0}']), // dad
+	('\u{0637}', ['\u{FEC1}', '\u{FEC2}', '\u{FEC3}', '\u{FEC4}']), // tah
+	('\u{0638}', ['\u{FEC5}', '\u{FEC6}', '\u{FEC7}', '\u{FEC8}']), // zah
+	('\u{0639}', ['\u{FEC9}', '\u{FECA}', '\u{FECB}', '\u{FECC}']), // ain
+	('\u{063A}', ['\u{FECD}', '\u{FECE}', '\u{FECF}', '\u{FED0}']), // ghain
+	('\u{0641}', ['\u{FED1}', '\u{FED2}', '\u{FED3}', '\u{FED4}']), // feh
+	('\u{0642}', ['\u{FED5}', '\u{FED6}', '\u{FED7}', '\u{FED8}']), // qaf
+	('\u{0643}', ['\u{FED9}', '\u{FEDA}', '\u{FEDB}', '\u{FEDC}']), // kaf
+	('\u{0644}', ['\u{FEDD}', '\u{FEDE}', '\u{FEDF}', '\u{FEE0}']), // lam
+	('\u{0645}', ['\u{FEE1}', '\u{FEE2}', '\u{FEE3}', '\u{FEE4}']), // meem
+	('\u{0646}', ['\u{FEE5}', '\u{FEE6}', '\u{FEE7}', '\u{FEE8}']), // noon
+	('\u{0647}', ['\u{FEE9}', '\u{FEEA}', '\u{FEEB}', '\u{FEEC}']), // heh
+	('\u{0648}', ['\u{FEED}', '\u{FEEE}', '\u{FEED}', '\u{FEEE}']), // waw
+	('\u{0649}', ['\u{FEEF}', '\u{FEF0}', '\u{FBE8}', '\u{FBE9}']), // alef maksura
+	('\u{064A}', ['\u{FEF1}', '\u{FEF2}', '\u{FEF3}', '\u{FEF4}']), // yeh
+	('\u{067E}', ['\u{FB56}', '\u{FB57}', '\u{FB58}', '\u{FB59}']), // peh
+	('\u{0686}', ['\u{FB7A}', '\u{FB7B}', '\u{FB7C}', '\u{FB7D}']), // tcheh
+	('\u{0698}', ['\u{FB8A}', '\u{FB8B}', '\u{FB8A}', '\u{FB8B}']), // jeh
+	('\u{06A9}', ['\u{FB8E}', '\u{FB8F}', '\u{FB90}', '\u{FB91}']), // keheh
+	('\u{06AF}', ['\u{FB92}', '\u{FB93}', '\u{FB94}', '\u{FB95}']), // gaf
+	('\u{06CC}', ['\u{FBFC}', '\u{FBFD}', '\u{FBFE}', '\u{FBFF}']), // farsi yeh
+	('\u{06C0}', ['\u{FBA4}', '\u{FBA5}', '\u{FBA4}', '\u{FBA5}']), // heh with yeh above
+];
+
+// Lam followed by an alef is written as one ligature: isolated, final
+const LAM_ALEF: [(char, [char; 2]); 4] = [
+	('\u{0622}', ['\u{FEF5}', '\u{FEF6}']),
+	('\u{0623}', ['\u{FEF7}', '\u{FEF8}']),
+	('\u{0625}', ['\u{FEF9}', '\u{FEFA}']),
+	('\u{0627}', ['\u{FEFB}', '\u{FEFC}']),
+];
+
+const LAM: char = '\u{0644}';
+
+// `arabic_shaping` from the config, read once
+pub fn enabled() -> bool {
+	static ENABLED: OnceLock<bool> = OnceLock::new();
+	*ENABLED.get_or_init(|| Config::load().is_ok_and(|config| config.arabic_shaping))
+}
+
+fn joins_before(kind: JoiningType) -> bool {
+	matches!(kind, JoiningType::DualJoining | JoiningType::RightJoining | JoiningType::JoinCausing)
+}
+
+fn joins_after(kind: JoiningType) -> bool {
+	matches!(kind, JoiningType::DualJoining | JoiningType::LeftJoining | JoiningType::JoinCausing)
+}
+
+// The letters of a line (in logical order) to draw as presentation forms, for terminals
+// that show every Arabic letter on its own: the form each takes by its neighbours, keyed
+// by byte offset. An alef joined into a lam-alef ligature maps to None, to be left out.
+pub fn presentation_forms(line: &str) -> HashMap<usize, Option<char>> {
+	// Marks between letters do not break a join
+	let letters: Vec<(usize, char, JoiningType)> = line.char_indices()
+		.map(|(at, c)| (at, c, get_joining_type(c)))
+		.filter(|(_, _, kind)| *kind != JoiningType::Transparent)
+		.collect();
+	let mut forms = HashMap::new();
+	let mut i = 0;
+	while i < letters.len() {
+		let (at, c, kind) = letters[i];
+		let before = i.checked_sub(1).is_some_and(|j| joins_after(letters[j].2)) && joins_before(kind);
+		let next = letters.get(i + 1);
+		if c == LAM {
+			if let Some(&(alef_at, alef, _)) = next {
+				if let Some((_, ligature)) = LAM_ALEF.iter().find(|(a, _)| *a == alef) {
+					forms.insert(at, Some(ligature[before as usize]));
+					forms.insert(alef_at, None);
+					i += 2;
+					continue;
+				}
+			}
+		}
+		let after = next.is_some_and(|&(_, _, next_kind)| joins_before(next_kind)) && joins_after(kind);
+		if let Some((_, shapes)) = FORMS.iter().find(|(letter, _)| *letter == c) {
+			let form = match (before, after) {
+				(false, false) => 0,
+				(true, false) => 1,
+				(false, true) => 2,
+				(true, true) => 3,
+			};
+			forms.insert(at, Some(shapes[form]));
+		}
+		i += 1;
+	}
+	forms
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// The forms of a line by position, for lines of two-byte letters
+	fn forms(line: &str) -> Vec<Option<Option<char>>> {
+		let forms = presentation_forms(line);
+		(0..line.len()).step_by(2).map(|at| forms.get(&at).copied()).collect()
+	}
+
+	#[test]
+	fn letters_take_their_form_from_their_neighbours() {
+		// beh alone, then beh beh beh: initial, medial, final
+		assert_eq!(forms("ب"), [Some(Some('\u{FE8F}'))]);
+		assert_eq!(forms("ببب"), [Some(Some('\u{FE91}')), Some(Some('\u{FE92}')), Some(Some('\u{FE90}'))]);
+	}
+
+	#[test]
+	fn lam_and_alef_make_a_ligature() {
+		assert_eq!(forms("لا"), [Some(Some('\u{FEFB}')), Some(None)]);
+		// After beh, which joins it, the ligature takes its final form
+		assert_eq!(forms("بلا"), [Some(Some('\u{FE91}')), Some(Some('\u{FEFC}')), Some(None)]);
+		// Dal does not join the letter after it
+		assert_eq!(forms("دلا"), [Some(Some('\u{FEA9}')), Some(Some('\u{FEFB}')), Some(None)]);
+	}
+
+	#[test]
+	fn marks_do_not_break_a_join() {
+		// beh, fatha, beh
+		assert_eq!(forms("بَب"), [Some(Some('\u{FE91}')), None, Some(Some('\u{FE90}'))]);
+	}
+
+	#[test]
+	fn letters_joining_one_side_break_the_join() {
+		// beh, dal, beh: dal joins the beh before it but not the one after
+		assert_eq!(forms("بدب"), [Some(Some('\u{FE91}')), Some(Some('\u{FEAA}')), Some(Some('\u{FE8F}'))]);
+		assert_eq!(presentation_forms("ب ب").values().collect::<Vec<_>>(), [&Some('\u{FE8F}'); 2]);
+	}
+}
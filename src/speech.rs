@@ -0,0 +1,86 @@
+use std::{io::{self, Write}, process::{Child, Command, Stdio}};
+
+#[cfg(target_os = "macos")]
+const DEFAULT_COMMAND: &str = "say -v {voice}";
+#[cfg(not(target_os = "macos"))]
+const DEFAULT_COMMAND: &str = "espeak-ng -v {voice}";
+
+// Maps a version's language code to a voice name understood by the TTS engine
+fn voice(language: Option<&str>) -> &'static str {
+	let code = language.map(|l| l.split('-').next().unwrap_or(l));
+	if cfg!(target_os = "macos") {
+		match code {
+			Some("deu") | Some("de") => "Anna",
+			Some("fra") | Some("fr") => "Thomas",
+			Some("rus") | Some("ru") => "Milena",
+			Some("bul") | Some("bg") => "Daria",
+			Some("jpn") | Some("ojp") | Some("ja") => "Kyoko",
+			Some("lzh") | Some("zho") | Some("zh") => "Tingting",
+			Some("yue") => "Sinji",
+			_ => "Samantha",
+		}
+	} else {
+		match code {
+			Some("deu") | Some("de") => "de",
+			Some("fra") | Some("fr") => "fr",
+			Some("rus") | Some("ru") => "ru",
+			Some("bul") | Some("bg") => "bg",
+			Some("fas") | Some("fa") => "fa",
+			Some("jpn") | Some("ojp") | Some("ja") => "ja",
+			Some("lzh") | Some("zho") | Some("zh") => "cmn",
+			Some("yue") => "yue",
+			Some("mn") => "mn",
+			_ => "en",
+		}
+	}
+}
+
+// A running TTS process reading one version of a poem
+pub struct Speech {
+	child: Child,
+	pub poem: usize,
+	pub version: String,
+	pub paused: bool,
+}
+
+impl Speech {
+	// Starts `command` (or the platform default) with `{voice}` filled in and the text on stdin
+	pub fn start(text: &str, language: Option<&str>, command: Option<&str>, poem: usize, version: &str) -> io::Result<Speech> {
+		let template = command.unwrap_or(DEFAULT_COMMAND);
+		let mut parts = template.split_whitespace().map(|part| part.replace("{voice}", voice(language)));
+		let program = parts.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty tts_command"))?;
+		let mut child = Command::new(program)
+			.args(parts)
+			.stdin(Stdio::piped())
+			.stdout(Stdio::null())
+			.stderr(Stdio::null())
+			.spawn()?;
+		if let Some(mut stdin) = child.stdin.take() {
+			stdin.write_all(text.as_bytes())?;
+		}
+		Ok(Speech { child, poem, version: version.to_string(), paused: false })
+	}
+
+	pub fn is_running(&mut self) -> bool {
+		matches!(self.child.try_wait(), Ok(None))
+	}
+
+	// Suspends or resumes the process with SIGSTOP/SIGCONT
+	pub fn toggle_pause(&mut self) {
+		let signal = if self.paused { "-CONT" } else { "-STOP" };
+		let paused = Command::new("kill")
+			.args([signal, &self.child.id().to_string()])
+			.status()
+			.is_ok_and(|status| status.success());
+		if paused {
+			self.paused = !self.paused;
+		}
+	}
+}
+
+impl Drop for Speech {
+	fn drop(&mut self) {
+		let _ = self.child.kill();
+		let _ = self.child.wait();
+	}
+}
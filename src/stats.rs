@@ -0,0 +1,70 @@
+use crate::history::{now, History, DAY};
+use crate::models::Poem;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+// Summary of the library and of the reading history
+#[derive(Debug, Default, Serialize)]
+pub struct Stats {
+	pub poems: usize,
+	pub authors: usize,
+	pub languages: usize,
+	pub versions: usize,
+	pub lines: usize,
+	pub words: usize,
+	pub poems_read: usize,
+	pub read_this_week: usize,
+	pub streak_days: u64,
+	pub top_authors: Vec<(String, usize)>,
+}
+
+impl Stats {
+	pub fn compute(poems: &[Poem], history: &History) -> Stats {
+		let authors: HashSet<&str> = poems.iter().filter_map(|p| p.canonical()).filter_map(|v| v.author.as_deref()).collect();
+		let languages: HashSet<&str> = poems.iter().flat_map(|p| p.versions.values()).filter_map(|v| v.language.as_deref()).collect();
+
+		let now = now();
+		let week_ago = now.saturating_sub(7 * DAY);
+		let mut read_days = HashSet::new();
+		let mut read_this_week = 0;
+		let mut author_reads: HashMap<String, usize> = HashMap::new();
+		for poem in poems {
+			let Some(opens) = history.read.get(poem.history_key().as_ref()) else { continue };
+			if opens.iter().any(|&t| t >= week_ago) {
+				read_this_week += 1;
+			}
+			read_days.extend(opens.iter().map(|t| t / DAY));
+			if let Some(author) = poem.canonical().and_then(|v| v.author.as_ref()) {
+				*author_reads.entry(author.clone()).or_insert(0) += opens.len();
+			}
+		}
+
+		// The streak counts back from today, or from yesterday if nothing was read yet today
+		let mut day = now / DAY;
+		if !read_days.contains(&day) {
+			day = day.saturating_sub(1);
+		}
+		let mut streak_days = 0;
+		while read_days.contains(&day) {
+			streak_days += 1;
+			day -= 1;
+		}
+
+		let mut top_authors: Vec<(String, usize)> = author_reads.into_iter().collect();
+		top_authors.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+		top_authors.truncate(5);
+
+		Stats {
+			poems: poems.len(),
+			authors: authors.len(),
+			languages: languages.len(),
+			versions: poems.iter().map(|p| p.versions.len()).sum(),
+			lines: poems.iter().map(|p| p.line_count).sum(),
+			words: poems.iter().map(|p| p.word_count).sum(),
+			poems_read: poems.iter().filter(|p| history.is_read(&p.history_key())).count(),
+			read_this_week,
+			streak_days,
+			top_authors,
+		}
+	}
+}
@@ -0,0 +1,281 @@
+use crate::backup;
+use crate::config::Config;
+use crate::git;
+use crate::library::{self, TRASH_DIR};
+use crate::models::{check_writable, poems_dir};
+use crate::paths;
+use crate::tasks;
+use crate::utils::slug;
+use std::{collections::BTreeSet, fs, io::{self, Write}, path::{Path, PathBuf}, process::Command};
+
+const ARCHIVE_EXTENSIONS: [&str; 3] = [".zip", ".tar.gz", ".tgz"];
+
+// Added to the name of a file changed on both sides for the other side's version. Not a poem
+// extension, so the copy is never loaded, and never synced itself.
+pub const CONFLICT_EXTENSION: &str = "conflict";
+
+pub fn is_remote(library: &str) -> bool {
+	library.starts_with("https://") || library.starts_with("http://")
+}
+
+// The `library` setting when it is a URL
+pub fn remote_library() -> Option<String> {
+	let library = Config::load().ok()?.library?;
+	let library = library.to_str()?;
+	is_remote(library).then(|| library.to_string())
+}
+
+fn archive_extension(url: &str) -> Option<&'static str> {
+	let path = url.split(['?', '#']).next().unwrap_or(url);
+	ARCHIVE_EXTENSIONS.into_iter().find(|extension| path.ends_with(extension))
+}
+
+// Where the local copy of a remote library lives: the archive as downloaded, or a git checkout
+pub fn local_copy(url: &str) -> PathBuf {
+	let dir = paths::cache_dir().join("libraries");
+	let name = slug(url.split_once("://").map_or(url, |(_, rest)| rest));
+	match archive_extension(url) {
+		Some(extension) => dir.join(format!("{}{}", name.trim_end_matches(&extension.replace('.', "-")), extension)),
+		None => dir.join(name),
+	}
+}
+
+// What the program printed is kept for its error, so that it never lands on the reader's screen
+fn run(program: &str, command: &mut Command) -> io::Result<()> {
+	let output = tasks::output(command).map_err(|e| io::Error::new(e.kind(), format!("could not run {}: {}", program, e)))?;
+	if !output.status.success() {
+		let stderr = String::from_utf8_lossy(&output.stderr);
+		return Err(io::Error::other(match stderr.trim() {
+			"" => format!("{} failed ({})", program, output.status),
+			stderr => format!("{} failed: {}", program, stderr),
+		}));
+	}
+	Ok(())
+}
+
+// Downloads the library at `url` into its local copy, or brings the copy up to date.
+// Archives are fetched whole with curl; anything else is taken for a git repository.
+pub fn sync(out: &mut impl Write, url: &str) -> io::Result<()> {
+	let copy = local_copy(url);
+	if let Some(parent) = copy.parent() {
+		fs::create_dir_all(parent)?;
+	}
+	if archive_extension(url).is_some() {
+		// Downloaded next to the copy first, so a failed download leaves the old one readable
+		let partial = copy.with_file_name(format!("{}.part", copy.file_name().unwrap_or_default().to_string_lossy()));
+		if let Err(e) = run("curl", Command::new("curl").args(["-fsSL", "-o"]).arg(&partial).arg(url)) {
+			let _ = fs::remove_file(&partial);
+			return Err(e);
+		}
+		fs::rename(&partial, &copy)?;
+		writeln!(out, "Downloaded {} to {}", url, copy.display())
+	} else if copy.join(".git").is_dir() {
+		run("git", Command::new("git").arg("-C").arg(&copy).args(["pull", "--ff-only", "--quiet"]))?;
+		writeln!(out, "Updated {}", copy.display())
+	} else {
+		run("git", Command::new("git").args(["clone", "--quiet", url]).arg(&copy))?;
+		writeln!(out, "Cloned {} into {}", url, copy.display())
+	}
+}
+
+// Keeps the library directory the same as its other copies with `backend`: "git" commits local
+// changes, pulls and pushes; "rsync" and "rclone" copy changes both ways with `remote`. A file
+// changed on both sides keeps the library's version and gets the other one beside it.
+pub fn sync_library(out: &mut impl Write, backend: &str, remote: Option<&str>) -> io::Result<()> {
+	check_writable()?;
+	match (backend, remote) {
+		("git", _) => sync_git(out)?,
+		("rsync" | "rclone", Some(remote)) => sync_copies(out, backend, remote)?,
+		("rsync" | "rclone", None) => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("`sync_remote` is needed to sync with {}", backend))),
+		_ => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unknown sync backend \"{}\" (git, rsync or rclone)", backend))),
+	}
+	// Including those left from earlier syncs
+	let conflicts = conflicts();
+	if conflicts.is_empty() {
+		return writeln!(out, "The library is in sync");
+	}
+	for name in &conflicts {
+		writeln!(out, "Conflict: {} changed on both sides; the other version is in {}.{}", name, name, CONFLICT_EXTENSION)?;
+	}
+	writeln!(out, "Keep what you want of each in the poem file and delete the .{} file", CONFLICT_EXTENSION)
+}
+
+// What `leaves sync` does: brings the local copy of a remote library up to date, or syncs the
+// library with `sync_backend`
+pub fn sync_configured(out: &mut impl Write) -> io::Result<()> {
+	if let Some(url) = remote_library() {
+		return sync(out, &url);
+	}
+	let config = Config::load()?;
+	match config.sync_backend {
+		Some(backend) => sync_library(out, &backend, config.sync_remote.as_deref()),
+		None => Err(io::Error::new(io::ErrorKind::InvalidInput, "set `sync_backend` in the configuration, or `library` to a URL, to have something to sync")),
+	}
+}
+
+fn conflict_path(path: &Path) -> PathBuf {
+	let mut name = path.as_os_str().to_owned();
+	name.push(format!(".{}", CONFLICT_EXTENSION));
+	PathBuf::from(name)
+}
+
+// Files under `dir` by their path inside it, leaving out hidden ones (the trash, `.git`)
+fn files(dir: &Path) -> io::Result<BTreeSet<String>> {
+	let mut files = BTreeSet::new();
+	let mut pending = vec![dir.to_path_buf()];
+	while let Some(current) = pending.pop() {
+		let Ok(entries) = fs::read_dir(&current) else { continue };
+		for entry in entries {
+			let path = entry?.path();
+			if path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')) {
+				continue;
+			}
+			if path.is_dir() {
+				pending.push(path);
+			} else if let Ok(relative) = path.strip_prefix(dir) {
+				files.insert(relative.to_string_lossy().replace('\\', "/"));
+			}
+		}
+	}
+	Ok(files)
+}
+
+// Poem files with the other side's version beside them, waiting to be reconciled
+pub fn conflicts() -> Vec<String> {
+	let suffix = format!(".{}", CONFLICT_EXTENSION);
+	files(&poems_dir()).unwrap_or_default().into_iter()
+		.filter_map(|name| name.strip_suffix(&suffix).map(str::to_string))
+		.collect()
+}
+
+fn sync_git(out: &mut impl Write) -> io::Result<()> {
+	if git::status().is_none() {
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, "the library is not in a git repository"));
+	}
+	let exclude = format!(":(exclude)*.{}", CONFLICT_EXTENSION);
+	git::git(&["add", "--all", "--", ".", &exclude])?;
+	if git::git(&["diff", "--cached", "--quiet", "--", "."]).is_err() {
+		git::git(&["commit", "--quiet", "-m", "Sync poems", "--", "."])?;
+		writeln!(out, "Committed the changes to the library")?;
+	}
+	if let Err(e) = git::git(&["pull", "--quiet", "--no-rebase", "--no-edit"]) {
+		let unmerged = git::git(&["diff", "--name-only", "--relative", "--diff-filter=U"])?;
+		if unmerged.trim().is_empty() {
+			return Err(e);
+		}
+		// Both sides changed these: the library's version is kept and the other written beside
+		// it; a file deleted on one side takes the other side's version
+		for name in unmerged.lines() {
+			match git::git(&["show", &format!(":3:./{}", name)]) {
+				Ok(theirs) if git::git(&["checkout", "--ours", "--", name]).is_ok() => {
+					fs::write(conflict_path(&poems_dir().join(name)), theirs)?;
+				},
+				Ok(_) => {
+					git::git(&["checkout", "--theirs", "--", name])?;
+				},
+				Err(_) => {
+					git::git(&["checkout", "--ours", "--", name])?;
+				},
+			}
+			git::git(&["add", "--", name])?;
+		}
+		git::git(&["commit", "--quiet", "--no-edit"])?;
+	}
+	git::git(&["push", "--quiet"])?;
+	writeln!(out, "Pulled and pushed the library")
+}
+
+// Copies the other side into `into`, which stays between runs so only changes are transferred
+fn fetch(backend: &str, remote: &str, into: &Path) -> io::Result<()> {
+	fs::create_dir_all(into)?;
+	let remote = format!("{}/", remote.trim_end_matches('/'));
+	match backend {
+		"rsync" => run("rsync", Command::new("rsync").args(["-a", "--delete", "--exclude=.*", &format!("--exclude=*.{}", CONFLICT_EXTENSION)]).arg(remote).arg(into)),
+		_ => run("rclone", Command::new("rclone").args(["sync", "--exclude", ".*", "--exclude", ".*/**", "--exclude", &format!("*.{}", CONFLICT_EXTENSION)]).arg(remote).arg(into)),
+	}
+}
+
+// Sends the library to the other side, except the files in conflict, whose other version stays
+fn push(backend: &str, remote: &str, conflicts: &[String]) -> io::Result<()> {
+	let local = format!("{}/", poems_dir().display());
+	let remote = format!("{}/", remote.trim_end_matches('/'));
+	let mut command = match backend {
+		"rsync" => {
+			let mut command = Command::new("rsync");
+			command.args(["-a", "--delete", "--exclude=.*", &format!("--exclude=*.{}", CONFLICT_EXTENSION)]);
+			command.args(conflicts.iter().map(|name| format!("--exclude=/{}", name)));
+			command
+		},
+		_ => {
+			let mut command = Command::new("rclone");
+			command.args(["sync", "--exclude", ".*", "--exclude", ".*/**", "--exclude", &format!("*.{}", CONFLICT_EXTENSION)]);
+			command.args(conflicts.iter().flat_map(|name| ["--exclude".to_string(), format!("/{}", name)]));
+			command
+		},
+	};
+	run(backend, command.arg(local).arg(remote))
+}
+
+// rsync and rclone only copy, so which side changed a file is told by comparing both with the
+// other side as it was at the end of the last sync, kept in the state directory
+fn sync_copies(out: &mut impl Write, backend: &str, remote: &str) -> io::Result<()> {
+	let state = paths::state_dir().join("sync").join(slug(remote));
+	let (base, incoming) = (state.join("base"), state.join("incoming"));
+	fetch(backend, remote, &incoming)?;
+	let local = poems_dir();
+	let mut names = files(&base)?;
+	names.extend(files(&incoming)?);
+	names.extend(files(&local)?);
+	let mut conflicts = Vec::new();
+	for name in names {
+		let (old, theirs, ours) = (fs::read(base.join(&name)).ok(), fs::read(incoming.join(&name)).ok(), fs::read(local.join(&name)).ok());
+		let path = local.join(&name);
+		let conflict = conflict_path(&path);
+		// Not reconciled since an earlier sync: the other version is only brought up to date
+		if conflict.exists() {
+			if let Some(theirs) = theirs {
+				fs::write(&conflict, theirs)?;
+			}
+			conflicts.push(name);
+			continue;
+		}
+		// The same on both sides, or changed only here and sent over below
+		if theirs == ours || theirs == old {
+			continue;
+		}
+		match theirs {
+			Some(theirs) if ours.is_some() && ours != old => {
+				fs::write(&conflict, theirs)?;
+				conflicts.push(name);
+			},
+			Some(theirs) => {
+				backup::backup(&path)?;
+				if let Some(parent) = path.parent() {
+					fs::create_dir_all(parent)?;
+				}
+				fs::write(&path, theirs)?;
+				writeln!(out, "Updated {}", name)?;
+			},
+			None if ours == old => {
+				library::trash(&name)?;
+				writeln!(out, "Moved {} to {}/, as it was deleted on the other side", name, TRASH_DIR)?;
+			},
+			// Deleted there but changed here: the change wins and is sent back
+			None => {},
+		}
+	}
+	push(backend, remote, &conflicts)?;
+	// What the other side holds now is the base of the next sync
+	fetch(backend, remote, &incoming)?;
+	if base.exists() {
+		fs::remove_dir_all(&base)?;
+	}
+	for name in files(&incoming)? {
+		let target = base.join(&name);
+		if let Some(parent) = target.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		fs::copy(incoming.join(&name), target)?;
+	}
+	writeln!(out, "Copied the library both ways with {}", backend)
+}
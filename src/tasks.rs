@@ -0,0 +1,150 @@
+use std::{cell::RefCell, io::{self, Read, Write}, process::{Command, Output, Stdio}, sync::{atomic::{AtomicBool, Ordering}, mpsc::{self, Receiver, Sender, TryRecvError}, Arc}, thread, time::{Duration, Instant}};
+
+// Work that takes a while, such as syncing the library, run on a thread of its own so the
+// reader keeps drawing and taking keys. What the work writes and how it ended come back over
+// a channel, taken in on every tick; cancelling kills the program it is waiting for.
+
+// How often a program run for a task is checked on, and whether the task was cancelled
+const POLL_RATE: Duration = Duration::from_millis(50);
+
+enum TaskEvent {
+	// A line the work wrote
+	Progress(String),
+	Done(io::Result<()>),
+}
+
+pub struct Task {
+	pub title: String,
+	// What the work wrote so far, a line each
+	pub lines: Vec<String>,
+	pub started: Instant,
+	// Set once the work ended, until the reader loop takes it
+	pub finished: Option<io::Result<()>>,
+	events: Receiver<TaskEvent>,
+	cancelled: Arc<AtomicBool>,
+}
+
+thread_local! {
+	// The cancellation flag of the task running on this thread, if any
+	static CANCELLED: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+fn cancelled_error() -> io::Error {
+	io::Error::new(io::ErrorKind::Interrupted, "cancelled")
+}
+
+fn in_task() -> bool {
+	CANCELLED.with(|flag| flag.borrow().is_some())
+}
+
+// Whether the task running on this thread was cancelled; never outside a task
+pub fn cancelled() -> bool {
+	CANCELLED.with(|flag| flag.borrow().as_ref().is_some_and(|flag| flag.load(Ordering::Relaxed)))
+}
+
+// Where a task writes: each line is sent to the reader as it is finished, and writing fails
+// once the task was cancelled, which stops work that writes as it goes
+pub struct TaskOutput {
+	events: Sender<TaskEvent>,
+	partial: String,
+}
+
+impl Write for TaskOutput {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		if cancelled() {
+			return Err(cancelled_error());
+		}
+		self.partial.push_str(&String::from_utf8_lossy(buf));
+		while let Some(end) = self.partial.find('\n') {
+			let line: String = self.partial.drain(..=end).collect();
+			let _ = self.events.send(TaskEvent::Progress(line.trim_end().to_string()));
+		}
+		Ok(buf.len())
+	}
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+impl Task {
+	// Starts `work` on a thread of its own
+	pub fn spawn(title: &str, work: impl FnOnce(&mut TaskOutput) -> io::Result<()> + Send + 'static) -> Task {
+		let (sender, events) = mpsc::channel();
+		let flag = Arc::new(AtomicBool::new(false));
+		let task_flag = flag.clone();
+		thread::spawn(move || {
+			CANCELLED.with(|cancelled| *cancelled.borrow_mut() = Some(task_flag));
+			let mut out = TaskOutput { events: sender.clone(), partial: String::new() };
+			let mut result = work(&mut out);
+			if !out.partial.is_empty() {
+				let _ = sender.send(TaskEvent::Progress(out.partial));
+			}
+			if result.is_ok() && cancelled() {
+				result = Err(cancelled_error());
+			}
+			let _ = sender.send(TaskEvent::Done(result));
+		});
+		tracing::info!(title, "started task");
+		Task { title: title.to_string(), lines: Vec::new(), started: Instant::now(), finished: None, events, cancelled: flag }
+	}
+
+	pub fn cancel(&self) {
+		self.cancelled.store(true, Ordering::Relaxed);
+	}
+
+	pub fn is_cancelled(&self) -> bool {
+		self.cancelled.load(Ordering::Relaxed)
+	}
+
+	// Takes in what the work sent since the last call; true when anything came
+	pub fn poll(&mut self) -> bool {
+		let mut changed = false;
+		while self.finished.is_none() {
+			match self.events.try_recv() {
+				Ok(TaskEvent::Progress(line)) => self.lines.push(line),
+				Ok(TaskEvent::Done(result)) => {
+					tracing::info!(title = self.title, elapsed = ?self.started.elapsed(), ok = result.is_ok(), "finished task");
+					self.finished = Some(result);
+				},
+				Err(TryRecvError::Empty) => break,
+				// The thread went away without a word, i.e. it panicked
+				Err(TryRecvError::Disconnected) => self.finished = Some(Err(io::Error::other("the task stopped unexpectedly"))),
+			}
+			changed = true;
+		}
+		changed
+	}
+}
+
+// Runs `command` to the end and returns what it printed, like `Command::output`, but inside a
+// task kills it as soon as the task is cancelled. Nothing is read from the terminal, which the
+// reader has.
+pub fn output(command: &mut Command) -> io::Result<Output> {
+	// Quick calls such as `git status` are not held up by the polling
+	if !in_task() {
+		return command.output();
+	}
+	let mut child = command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+	// Both are read on threads of their own, so a program writing a lot never blocks on them
+	let read = |pipe: Option<Box<dyn Read + Send>>| thread::spawn(move || {
+		let mut bytes = Vec::new();
+		if let Some(mut pipe) = pipe {
+			let _ = pipe.read_to_end(&mut bytes);
+		}
+		bytes
+	});
+	let stdout = read(child.stdout.take().map(|pipe| Box::new(pipe) as Box<dyn Read + Send>));
+	let stderr = read(child.stderr.take().map(|pipe| Box::new(pipe) as Box<dyn Read + Send>));
+	let status = loop {
+		if let Some(status) = child.try_wait()? {
+			break status;
+		}
+		if cancelled() {
+			let _ = child.kill();
+			let _ = child.wait();
+			return Err(cancelled_error());
+		}
+		thread::sleep(POLL_RATE);
+	};
+	Ok(Output { status, stdout: stdout.join().unwrap_or_default(), stderr: stderr.join().unwrap_or_default() })
+}
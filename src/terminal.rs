@@ -0,0 +1,92 @@
+use ratatui::symbols::border;
+use std::{env, sync::atomic::{AtomicBool, Ordering}};
+
+// What the terminal the reader runs in can show, guessed from the environment. Terminals such
+// as the Linux console have the 16 basic colours at most and fonts without box-drawing
+// characters, so the reader keeps to those colours there and draws its borders and symbols in ASCII.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capabilities {
+	// Only the basic colours, without 256 colours or bright backgrounds
+	pub basic_colors: bool,
+	// Box-drawing characters, arrows and other symbols beyond ASCII
+	pub unicode: bool,
+}
+
+// Terminals known to have neither, by `TERM`
+const BASIC_TERMINALS: [&str; 7] = ["linux", "vt100", "vt102", "vt220", "ansi", "cons25", "dumb"];
+
+// Uses the `ascii_only` setting if given for symbols, otherwise guesses from the environment
+pub fn detect(ascii_only: Option<bool>) -> Capabilities {
+	let term = env::var("TERM").unwrap_or_default();
+	let colorterm = env::var("COLORTERM").unwrap_or_default();
+	let basic = BASIC_TERMINALS.contains(&term.as_str());
+	let basic_colors = basic && !matches!(colorterm.as_str(), "truecolor" | "24bit");
+	let unicode = match ascii_only {
+		Some(ascii_only) => !ascii_only,
+		None => !basic && utf8_locale(),
+	};
+	Capabilities { basic_colors, unicode }
+}
+
+// Whether the locale has text in UTF-8; taken to when none is set, as on Windows
+fn utf8_locale() -> bool {
+	let locale = ["LC_ALL", "LC_CTYPE", "LANG"].into_iter().filter_map(|name| env::var(name).ok()).find(|value| !value.is_empty());
+	locale.is_none_or(|locale| {
+		let locale = locale.to_lowercase();
+		locale.contains("utf-8") || locale.contains("utf8")
+	})
+}
+
+// Set at startup from `detect`; until then, as in tests and headless commands, symbols are drawn in Unicode
+static ASCII: AtomicBool = AtomicBool::new(false);
+
+pub fn set(capabilities: Capabilities) {
+	ASCII.store(!capabilities.unicode, Ordering::Relaxed);
+}
+
+fn ascii() -> bool {
+	ASCII.load(Ordering::Relaxed)
+}
+
+const ASCII_BORDER: border::Set = border::Set {
+	top_left: "+",
+	top_right: "+",
+	bottom_left: "+",
+	bottom_right: "+",
+	vertical_left: "|",
+	vertical_right: "|",
+	horizontal_top: "-",
+	horizontal_bottom: "-",
+};
+
+// Popups are set apart from the panes under them by a double border
+const ASCII_POPUP_BORDER: border::Set = border::Set { horizontal_top: "=", horizontal_bottom: "=", ..ASCII_BORDER };
+
+// Borders of panes and lists
+pub fn border() -> border::Set {
+	if ascii() { ASCII_BORDER } else { border::PLAIN }
+}
+
+pub fn popup_border() -> border::Set {
+	if ascii() { ASCII_POPUP_BORDER } else { border::DOUBLE }
+}
+
+// A symbol of the reader's own, such as a scrollbar arrow or a badge, or its stand-in in ASCII.
+// Poem text is never passed through here, and keeps its dashes and dots on any terminal.
+pub fn symbol(unicode: &'static str, ascii_stand_in: &'static str) -> &'static str {
+	if ascii() { ascii_stand_in } else { unicode }
+}
+
+// Keys as named in the status bar, with the arrow keys spelled out where arrows cannot be shown
+pub fn keys(keys: &str) -> String {
+	if !ascii() {
+		return keys.to_string();
+	}
+	keys.split('/').map(|key| match key {
+		"↑" => "Up",
+		"↓" => "Down",
+		"←" => "Left",
+		"→" => "Right",
+		key => key,
+	}).collect::<Vec<_>>().join("/")
+}
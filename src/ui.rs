@@ -1,14 +1,50 @@
-use crossterm::terminal;
 use crate::models::Version;
-use unicode_bidi::BidiInfo;
+use unicode_bidi::{BidiInfo, Level};
+use crate::i18n::tr;
+use crate::shaping;
+use crate::terminal;
+use std::{collections::HashMap, ops::Range};
+use textwrap::core::display_width;
 use ratatui::{
-    layout::{Alignment, Constraint, Layout, Rect},
-    widgets::{Paragraph},
+    buffer::Buffer,
+    layout::{Alignment, Constraint, Layout, Margin, Rect},
+    widgets::{Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, StatefulWidget, Widget},
     text::{Line, Span},
     style::{Style, Color},
     prelude::*,
 };
 
+// Names accepted by `theme` in the config
+pub const THEMES: [&str; 2] = ["dark", "light"];
+
+// Colours of the reader's lists and text, picked with `theme` in the config
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+	pub text: Style,
+	pub highlight: Style,
+	// Titles of panes, group headers and the poem heading
+	pub heading: Style,
+}
+
+impl Theme {
+	// Anything but "light" gets the dark theme. Basic terminals such as the Linux console have
+	// no bright backgrounds, so the dark theme highlights in light grey there.
+	pub fn named(name: Option<&str>, basic_colors: bool) -> Theme {
+		match name {
+			Some("light") => Theme {
+				text: Style::default().fg(Color::Black),
+				highlight: Style::default().fg(Color::White).bg(Color::Black),
+				heading: Style::default().fg(Color::Blue),
+			},
+			_ => Theme {
+				text: Style::default().fg(Color::White),
+				highlight: Style::default().fg(Color::Black).bg(if basic_colors { Color::Gray } else { Color::White }),
+				heading: Style::default().fg(Color::Yellow),
+			},
+		}
+	}
+}
+
 pub fn parse_markdown(text: &str) -> String {
 	let mut result = String::new();
 	let mut in_bold = false;
@@ -41,75 +77,68 @@ pub fn parse_markdown(text: &str) -> String {
 	result
 }
 
+// The text of a version before it is wrapped: markdown turned into its markers. Horizontal
+// text, RTL or not, is left in logical order: it is reordered for display only once it has
+// been wrapped, by `wrap_poem_lines`. Vertical poems are laid out by `VerticalPoem` from
+// their text as written.
 pub fn render_poem_text(version: &Version) -> String {
-	// Case 1: No vertical or RTL formatting enabled.
-	// Simply parse the markdown and return the result.
-	if !version.vertical.unwrap_or(false) && !version.rtl.unwrap_or(false) {
-		return parse_markdown(&version.text);
+	if version.is_vertical() {
+		return version.text.clone();
 	}
+	parse_markdown(&version.text)
+}
 
-	// Case 2: RTL formatting only (vertical is false).
-	// Parse the markdown, then reverse each line for proper RTL display.
-	if !version.vertical.unwrap_or(false) && version.rtl.unwrap_or(false) {
-		let text = parse_markdown(&version.text);
-		return process_rtl_text(&text);
+// Same as the viewer for a given height: vertical poems come laid out in columns
+pub fn render_poem_text_for_height(version: &Version, viewport_height: usize) -> String {
+	if !version.is_vertical() {
+		return parse_markdown(&version.text);
 	}
+	vertical_columns(&version.text, viewport_height, version.is_rtl()).join("\n")
+}
 
-	// Case 3: Vertical formatting is enabled.
-	// First, get the terminal size; default to 80x24 if unavailable.
-	let (_cols, rows) = terminal::size().unwrap_or((80, 24));
-	// Reserve a few rows (e.g., for UI elements) and set the viewport height.
-	let viewport_height = rows.saturating_sub(3) as usize;
+// Every character of vertical text takes two cells, as CJK ones do, so the columns stay straight
+const CELL_WIDTH: usize = 2;
 
+fn vertical_cell(c: char) -> String {
+	let mut cell = c.to_string();
+	let width = display_width(&cell);
+	cell.push_str(&" ".repeat(CELL_WIDTH.saturating_sub(width)));
+	cell
+}
+
+// Lays the lines of `text` out as columns of at most `height` characters, read top to
+// bottom and, for RTL text, from the right; returns the rows of the result
+pub fn vertical_columns(text: &str, height: usize, rtl: bool) -> Vec<String> {
+	let columns = vertical_cells(text, height, rtl);
+	let rows = columns.first().map_or(0, Vec::len);
+	(0..rows).map(|row| columns.iter().map(|column| column[row].as_str()).collect()).collect()
+}
+
+// The columns of `vertical_columns` from the left, each a cell per row
+fn vertical_cells(text: &str, height: usize, rtl: bool) -> Vec<Vec<String>> {
+	let viewport_height = height.max(1);
 	// Split the original text into individual lines.
-	let lines: Vec<&str> = version.text.lines().collect();
+	let lines: Vec<&str> = text.lines().collect();
 	// Determine the maximum number of characters in any line (after trimming).
 	let max_line_length = lines.iter().map(|l| l.trim().chars().count()).max().unwrap_or(0);
+	// Shorter columns are padded with a full-width space ('　') to ensure equal length.
+	let column = |chars: &[char], length: usize| -> Vec<String> {
+		chars.iter().copied().chain(std::iter::repeat('　')).take(length).map(vertical_cell).collect()
+	};
 
-	// If the longest line fits within the viewport height,
-	// render without wrapping by building a character matrix.
+	// If the longest line fits within the viewport height, render without wrapping,
+	// each line a column, the first one on the right.
 	if max_line_length <= viewport_height {
-		let width = max_line_length;
-		// Create a matrix of characters where each row represents a line.
-		// Shorter lines are padded with a full-width space ('　') to ensure equal length.
-		let matrix: Vec<Vec<char>> = lines
-			.iter()
-			.map(|line| {
-				let mut v: Vec<char> = line.trim().chars().collect();
-				while v.len() < width {
-					v.push('　');
-				}
-				v
-			})
-			.collect();
-		let height = matrix.len();
-		// Render the poem vertically by reading the matrix column-wise in reverse row order.
-		return (0..width)
-			.map(|x| (0..height).rev().map(|y| matrix[y][x]).collect::<String>())
-			.collect::<Vec<String>>()
-			.join("\n");
+		lines.iter().rev().map(|line| column(&line.trim().chars().collect::<Vec<char>>(), max_line_length)).collect()
 	} else {
 		// Otherwise, one or more lines are too long and need wrapping.
 		// Process each original line individually, splitting it into segments that fit the viewport height.
-		let mut groups: Vec<Vec<Vec<char>>> = Vec::new();
+		let mut groups: Vec<Vec<Vec<String>>> = Vec::new();
 		for line in lines {
-			// Trim the line and convert it into a vector of characters.
 			let chars: Vec<char> = line.trim().chars().collect();
-			let mut segments: Vec<Vec<char>> = Vec::new();
-			let mut start = 0;
-			// Split the line into segments of at most viewport_height characters.
-			while start < chars.len() {
-				let end = (start + viewport_height).min(chars.len());
-				let mut seg: Vec<char> = chars[start..end].to_vec();
-				// If the segment is shorter than viewport_height, pad it with a full-width space.
-				while seg.len() < viewport_height {
-					seg.push('　');
-				}
-				segments.push(seg);
-				start += viewport_height;
-			}
+			let mut segments: Vec<Vec<String>> = chars.chunks(viewport_height).map(|chunk| column(chunk, viewport_height)).collect();
 			// For RTL text, reverse the order of segments to preserve the correct reading order.
-			if version.rtl.unwrap_or(false) {
+			if rtl {
 				segments.reverse();
 			}
 			groups.push(segments);
@@ -117,40 +146,250 @@ pub fn render_poem_text(version: &Version) -> String {
 
 		// Combine all segments from every line into a single vector of columns.
 		// For RTL texts, reverse the overall order of the groups.
-		let mut all_columns: Vec<Vec<char>> = Vec::new();
-		if version.rtl.unwrap_or(false) {
+		if rtl {
 			groups.reverse();
-			for group in groups {
-				for seg in group {
-					all_columns.push(seg);
+		}
+		groups.into_iter().flatten().collect()
+	}
+}
+
+// Whether the first columns of `vertical_columns` are on the right: always, unless lines are
+// wrapped into several columns each without the poem being RTL
+fn vertical_reads_from_right(text: &str, height: usize, rtl: bool) -> bool {
+	rtl || text.lines().map(|l| l.trim().chars().count()).max().unwrap_or(0) <= height.max(1)
+}
+
+// A vertical poem, laid out afresh for the height of the area it is drawn in so that resizing
+// the terminal or the layout reflows its columns; an epigraph goes above it, horizontally.
+// Columns that do not fit across are scrolled to, from the side reading starts on, with a
+// scrollbar along the bottom.
+pub struct VerticalPoem<'a> {
+	text: &'a str,
+	epigraph: Option<&'a str>,
+	rtl: bool,
+	style: Style,
+	scroll: usize,
+}
+
+// Where the parts of a vertical poem go in an area, and its columns for that height
+struct VerticalLayout {
+	epigraph: Vec<Line<'static>>,
+	epigraph_area: Rect,
+	columns_area: Rect,
+	// Each column top to bottom, in screen order from the left
+	columns: Vec<Vec<String>>,
+	// How many columns do not fit
+	overflow: usize,
+	from_right: bool,
+}
+
+impl<'a> VerticalPoem<'a> {
+	pub fn new(version: &'a Version) -> VerticalPoem<'a> {
+		VerticalPoem { text: &version.text, epigraph: version.epigraph.as_deref(), rtl: version.is_rtl(), style: Style::default(), scroll: 0 }
+	}
+
+	pub fn style(mut self, style: Style) -> VerticalPoem<'a> {
+		self.style = style;
+		self
+	}
+
+	// Columns scrolled past, counted from the first one read
+	pub fn scroll(mut self, columns: usize) -> VerticalPoem<'a> {
+		self.scroll = columns;
+		self
+	}
+
+	// How many columns are left out when drawn in `area`, the most it can be scrolled by
+	pub fn overflow(&self, area: Rect) -> usize {
+		self.layout(area).overflow
+	}
+
+	fn layout(&self, area: Rect) -> VerticalLayout {
+		let mut epigraph = Vec::new();
+		let mut columns_area = area;
+		if let Some(text) = self.epigraph {
+			epigraph = wrap_poem_lines(text, area.width.max(1) as usize, 0, false, self.rtl, None);
+			let height = (epigraph.len() as u16 + 1).min(area.height);
+			columns_area = Rect { y: area.y + height, height: area.height - height, ..area };
+		}
+		let epigraph_area = Rect { height: columns_area.y - area.y, ..area };
+		let fitting = columns_area.width as usize / CELL_WIDTH;
+		let mut columns = vertical_cells(self.text, columns_area.height as usize, self.rtl);
+		if columns.len() > fitting && columns_area.height > 1 {
+			// Make room for the scrollbar
+			columns_area.height -= 1;
+			columns = vertical_cells(self.text, columns_area.height as usize, self.rtl);
+		}
+		let overflow = columns.len().saturating_sub(fitting);
+		let from_right = vertical_reads_from_right(self.text, columns_area.height as usize, self.rtl);
+		VerticalLayout { epigraph, epigraph_area, columns_area, columns, overflow, from_right }
+	}
+}
+
+impl Widget for VerticalPoem<'_> {
+	fn render(self, area: Rect, buf: &mut Buffer) {
+		let alignment = if self.rtl { Alignment::Right } else { Alignment::Left };
+		let layout = self.layout(area);
+		Paragraph::new(layout.epigraph).style(self.style).alignment(alignment).render(layout.epigraph_area, buf);
+		let scroll = self.scroll.min(layout.overflow);
+		let count = layout.columns.len();
+		let shown = count - layout.overflow;
+		// Scrolling reveals columns further along in reading order, so leftwards when that starts on the right
+		let visible = if layout.from_right { count - shown - scroll..count - scroll } else { scroll..scroll + shown };
+		let columns = &layout.columns[visible];
+		let height = columns.first().map_or(0, Vec::len);
+		let rows: Vec<Line> = (0..height).map(|y| Line::from(columns.iter().map(|column| column[y].as_str()).collect::<String>())).collect();
+		// Against the side reading starts on, where the title is when that is the right
+		let columns_alignment = if layout.from_right { Alignment::Right } else { Alignment::Left };
+		Paragraph::new(rows).style(self.style).alignment(columns_alignment).render(layout.columns_area, buf);
+		if layout.overflow > 0 {
+			// The thumb is as wide as the share of columns shown and reaches the end at the last one
+			let mut state = ScrollbarState::new(layout.overflow)
+				.position(if layout.from_right { layout.overflow - scroll } else { scroll })
+				.viewport_content_length((layout.overflow * shown / count).max(1));
+			let scrollbar_area = Rect { y: layout.columns_area.y + layout.columns_area.height, height: 1, ..layout.columns_area };
+			Scrollbar::new(ScrollbarOrientation::HorizontalBottom)
+				.begin_symbol(Some(terminal::symbol("◀", "<")))
+				.end_symbol(Some(terminal::symbol("▶", ">")))
+				.thumb_symbol(terminal::symbol("▄", "#"))
+				.track_symbol(Some(terminal::symbol("─", "-")))
+				.render(scrollbar_area, buf, &mut state);
+		}
+	}
+}
+
+// Works out which rows of a long list are on screen, keeping the selection
+// visible, so that only those rows have to be built each frame.
+pub fn list_window(offset: usize, selected: usize, len: usize, height: usize) -> Range<usize> {
+	let selected = selected.min(len.saturating_sub(1));
+	let mut start = offset.min(len.saturating_sub(height));
+	if selected < start {
+		start = selected;
+	} else if height > 0 && selected >= start + height {
+		start = selected + 1 - height;
+	}
+	start..(start + height).min(len)
+}
+
+// Draws a scrollbar over the right border of a bordered list when it has more
+// entries than fit, using the same symbols as the poem viewer.
+pub fn render_list_scrollbar(f: &mut Frame, area: Rect, total: usize, position: usize) {
+	let viewport_height = area.height.saturating_sub(2) as usize;
+	if total <= viewport_height {
+		return;
+	}
+	let mut scrollbar_state = ScrollbarState::new(total)
+		.position(position)
+		.viewport_content_length(viewport_height);
+	let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+		.begin_symbol(Some(terminal::symbol("▲", "^")))
+		.end_symbol(Some(terminal::symbol("▼", "v")))
+		.thumb_symbol(terminal::symbol("▐", "#"))
+		.track_symbol(Some(terminal::symbol("│", "|")));
+	f.render_stateful_widget(scrollbar, area.inner(&Margin { vertical: 1, horizontal: 0 }), &mut scrollbar_state);
+}
+
+// Wraps each poem line to `width` with a hanging indent. With `initials` set, the
+// first letter of every line after the first `skip` lines (the epigraph) is
+// highlighted; in RTL text that letter sits at the right end of the line.
+// `selected_word` is a (line, word) position highlighted by the word cursor.
+// Lines come in logical order and each wrapped piece is reordered for display on
+// its own, so a long RTL line breaks between words and reads on from the right, and
+// words of the other direction inside a line keep their own order.
+pub fn wrap_poem_lines(poem_text: &str, width: usize, skip: usize, initials: bool, rtl: bool, selected_word: Option<(usize, usize)>) -> Vec<Line<'static>> {
+	let options = textwrap::Options::new(width).subsequent_indent("  ");
+	let initial_style = Style::default().fg(Color::Black).bg(Color::Yellow);
+	let cursor_style = Style::default().fg(Color::Black).bg(Color::Cyan);
+	let mut lines = Vec::new();
+	for (i, line) in poem_text.lines().enumerate() {
+		if line.trim().is_empty() {
+			lines.push(Line::from(""));
+			continue;
+		}
+		let pieces = textwrap::wrap(line, options.clone());
+		// Words never straddle two pieces, so the cursor word is found by counting words per piece
+		let mut words_before = 0;
+		for (j, logical) in pieces.into_iter().enumerate() {
+			// Highlights are found in reading order, then moved to where the reordering put them
+			let (piece, shown_at) = visual_order(&logical, rtl);
+			let shown = |start: usize, end: usize| {
+				let mut chars = logical[start..end].char_indices().map(|(at, c)| (shown_at[start + at], c.len_utf8()));
+				let first = chars.next().unwrap_or((0, 0));
+				chars.fold((first.0, first.0 + first.1), |(from, to), (at, len)| (from.min(at), to.max(at + len)))
+			};
+			let words: Vec<(usize, &str)> = word_spans(&logical);
+			let mut highlights: Vec<(usize, usize, Style)> = Vec::new();
+			if let Some((line_idx, word_idx)) = selected_word {
+				if line_idx == i && word_idx >= words_before && word_idx < words_before + words.len() {
+					let (at, word) = words[word_idx - words_before];
+					let (start, end) = shown(at, at + word.len());
+					highlights.push((start, end, cursor_style));
 				}
 			}
-		} else {
-			for group in groups {
-				for seg in group {
-					all_columns.push(seg);
+			words_before += words.len();
+			if initials && i >= skip && j == 0 {
+				if let Some((at, c)) = logical.char_indices().find(|(_, c)| c.is_alphanumeric()) {
+					let (start, end) = shown(at, at + c.len_utf8());
+					if !highlights.iter().any(|(from, to, _)| start >= *from && start < *to) {
+						highlights.push((start, end, initial_style));
+					}
 				}
 			}
+			highlights.sort_by_key(|h| h.0);
+			let mut spans = Vec::new();
+			let mut position = 0;
+			for (start, end, style) in highlights {
+				spans.push(Span::raw(piece[position..start].to_string()));
+				spans.push(Span::styled(piece[start..end].to_string(), style));
+				position = end;
+			}
+			spans.push(Span::raw(piece[position..].to_string()));
+			lines.push(Line::from(spans));
 		}
+	}
+	lines
+}
 
-		// Build the final output by reading the characters row by row across all columns.
-		let mut output_lines: Vec<String> = Vec::with_capacity(viewport_height);
-		for row in 0..viewport_height {
-			let mut line = String::new();
-			for col in &all_columns {
-				line.push(col[row]);
-			}
-			output_lines.push(line);
+// Byte offsets and text of the whitespace-separated words in `line`
+pub fn word_spans(line: &str) -> Vec<(usize, &str)> {
+	let mut spans = Vec::new();
+	let mut start = None;
+	for (i, c) in line.char_indices() {
+		match (c.is_whitespace(), start) {
+			(true, Some(s)) => {
+				spans.push((s, &line[s..i]));
+				start = None;
+			},
+			(false, None) => start = Some(i),
+			_ => {},
 		}
-		return output_lines.join("\n");
 	}
+	if let Some(s) = start {
+		spans.push((s, &line[s..]));
+	}
+	spans
+}
+
+// Shown after the title in the viewer, e.g. " (1850, tr. by Longfellow)"
+pub fn title_notes(version: &Version) -> String {
+	let notes: Vec<String> = version.date_label().into_iter()
+		.chain(version.translator.as_ref().map(|translator| format!("tr. by {}", translator)))
+		.collect();
+	if notes.is_empty() { String::new() } else { format!(" ({})", notes.join(", ")) }
+}
+
+// The word spelled by the first letter of every non-empty line
+pub fn acrostic(text: &str) -> String {
+	text.lines()
+		.filter_map(|line| line.chars().find(|c| c.is_alphanumeric()))
+		.collect()
 }
 
 pub fn render_status_bar(items: Vec<(&str, &str)>) -> Paragraph<'static> {
 	let spans: Vec<Span<'static>> = items.into_iter().flat_map(|(key, desc)| vec![
-		Span::styled(key.to_string(), Style::default().fg(Color::Yellow)),
+		Span::styled(terminal::keys(key), Style::default().fg(Color::Yellow)),
 		Span::raw(": ".to_string()),
-		Span::raw(desc.to_string()),
+		Span::raw(tr(desc).to_string()),
 		Span::raw(" | ".to_string()),
 	]).collect();
 	let mut spans = spans;
@@ -160,26 +399,160 @@ pub fn render_status_bar(items: Vec<(&str, &str)>) -> Paragraph<'static> {
 	Paragraph::new(Line::from(spans)).alignment(Alignment::Left)
 }
 
-fn process_rtl_text(text: &str) -> String {
-	text
-		.lines()
-		.map(|line| {
-			let bidi_info = BidiInfo::new(line, None);
-			let para = bidi_info.paragraphs.first().unwrap();
-			bidi_info.reorder_line(para, para.range.clone()).into_owned()
-		})
-		.collect::<Vec<_>>()
-		.join("\n")
+// Splits the viewer into text and illustration: beside the poem on wide terminals, above it otherwise
+pub fn split_image_area(area: Rect) -> (Rect, Rect) {
+	if area.width >= 100 {
+		let chunks = Layout::default()
+			.direction(Direction::Horizontal)
+			.constraints([Constraint::Min(40), Constraint::Percentage(40)])
+			.split(area);
+		(chunks[0], chunks[1])
+	} else {
+		let chunks = Layout::default()
+			.direction(Direction::Vertical)
+			.constraints([Constraint::Length((area.height / 3).min(12)), Constraint::Min(1)])
+			.split(area);
+		(chunks[1], chunks[0])
+	}
+}
+
+// Prefix that marks poems not opened yet in lists
+pub fn read_marker(read: bool) -> &'static str {
+	if read { "  " } else { terminal::symbol("• ", "* ") }
+}
+
+// Badge appended to list entries of poems that have more than one version
+pub fn version_badge(count: usize) -> String {
+	if count > 1 {
+		format!(" {}{} versions{}", terminal::symbol("⟨", "<"), count, terminal::symbol("⟩", ">"))
+	} else {
+		String::new()
+	}
+}
+
+// The lines of `text` each followed by the line of `transliteration` at the same place, dimmed
+// and left to right; the first `skip` lines of `text` (the epigraph) have none
+pub fn interlinear_lines(text: &str, transliteration: &str, skip: usize, width: usize, rtl: bool) -> Vec<Line<'static>> {
+	let dim = Style::default().fg(Color::DarkGray);
+	let mut transliterated = transliteration.lines();
+	let mut lines = Vec::new();
+	for (i, line) in text.lines().enumerate() {
+		lines.extend(wrap_poem_lines(line, width, 0, false, rtl, None));
+		if i < skip {
+			continue;
+		}
+		match transliterated.next() {
+			Some(spelled) if !spelled.trim().is_empty() => {
+				lines.extend(wrap_poem_lines(spelled, width, 0, false, false, None).into_iter().map(|mut line| {
+					line.patch_style(dim);
+					line
+				}));
+			},
+			_ => {},
+		}
+	}
+	lines
+}
+
+// Shortens `text` to at most `width` cells on screen, marking the cut with an ellipsis
+pub fn truncate(text: &str, width: usize) -> String {
+	if display_width(text) <= width {
+		return text.to_string();
+	}
+	if width == 0 {
+		return String::new();
+	}
+	let mut truncated = String::new();
+	let mut used = 0;
+	for c in text.chars() {
+		let c_width = display_width(c.encode_utf8(&mut [0; 4]));
+		if used + c_width > width - 1 {
+			break;
+		}
+		used += c_width;
+		truncated.push(c);
+	}
+	truncated.push_str(terminal::symbol("…", "."));
+	truncated
+}
+
+// A line of a right-to-left interface, such as a title of author, title and notes: its
+// spans go from the right, each laid out as RTL text
+pub fn mirrored_line(line: Line) -> Line<'static> {
+	let spans = line.spans.into_iter().rev()
+		.map(|span| Span::styled(visual_order(&span.content, true).0, span.style))
+		.collect::<Vec<_>>();
+	Line::from(spans)
 }
 
-pub fn render_vertical_rtl_title(author: &str, title: &str) -> String {
-	// Create vertical text that reads from top to bottom for RTL mode
-	// Format: "Author - Title" but displayed vertically
-	let full_text = format!("{}|{}", author, title);
+// Brackets and quotes face the other way inside right-to-left runs
+const MIRRORED: [(char, char); 8] = [('(', ')'), (')', '('), ('[', ']'), (']', '['), ('{', '}'), ('}', '{'), ('«', '»'), ('»', '«')];
 
-	// Convert to vertical text (each character on its own line)
-	// Don't skip any characters, including spaces
-	full_text.chars().map(|c| c.to_string()).collect::<Vec<String>>().join("\n")
+// A line as it is shown, left to right on screen: each run of either direction, by its
+// bidi level in a line of the poem's direction, is laid out in its own order. Also
+// returns where each character went, by byte offset in the line.
+fn visual_order(line: &str, rtl: bool) -> (String, Vec<usize>) {
+	let identity = || (line.to_string(), (0..line.len()).collect());
+	let bidi_info = BidiInfo::new(line, Some(if rtl { Level::rtl() } else { Level::ltr() }));
+	// An empty line, such as one between stanzas, has no paragraph to reorder
+	let Some(para) = bidi_info.paragraphs.first() else {
+		return identity();
+	};
+	if !rtl && !bidi_info.has_rtl() {
+		return identity();
+	}
+	let (levels, runs) = bidi_info.visual_runs(para, para.range.clone());
+	let forms = if shaping::enabled() { shaping::presentation_forms(line) } else { HashMap::new() };
+	let mut shown = String::with_capacity(line.len());
+	let mut shown_at = vec![0; line.len()];
+	for run in runs {
+		let chars: Vec<(usize, char)> = line[run.clone()].char_indices().collect();
+		if levels[run.start].is_rtl() {
+			for &(at, c) in chars.iter().rev() {
+				shown_at[run.start + at] = shown.len();
+				match forms.get(&(run.start + at)) {
+					Some(Some(form)) => shown.push(*form),
+					// The alef of a lam-alef ligature
+					Some(None) => {},
+					None => shown.push(MIRRORED.iter().find(|(from, _)| *from == c).map_or(c, |&(_, to)| to)),
+				}
+			}
+		} else {
+			for &(at, c) in &chars {
+				shown_at[run.start + at] = shown.len();
+				shown.push(c);
+			}
+		}
+	}
+	(shown, shown_at)
+}
+
+// The title of a vertical poem as rows of columns to put on its right, as on a printed page:
+// the title from the top, and left of it the author ending at the bottom. Either one too
+// long for `height` runs on into further columns leftwards, all then from the top.
+pub fn vertical_heading(title: &str, author: Option<&str>, height: usize) -> Vec<String> {
+	let height = height.max(1);
+	let cells = |text: &str| -> Vec<String> {
+		text.trim().chars().map(|c| vertical_cell(if c.is_whitespace() { '　' } else { c })).collect()
+	};
+	let title = cells(title);
+	let author = author.map(cells).unwrap_or_default();
+	let blank = || "　".to_string();
+	// Columns from the right
+	let mut columns: Vec<Vec<String>> = Vec::new();
+	if title.len() <= height && author.len() <= height {
+		columns.push(title.into_iter().chain(std::iter::repeat_with(blank)).take(height).collect());
+		if !author.is_empty() {
+			columns.push(std::iter::repeat_with(blank).take(height - author.len()).chain(author).collect());
+		}
+	} else {
+		for cells in [title, author] {
+			for chunk in cells.chunks(height) {
+				columns.push(chunk.iter().cloned().chain(std::iter::repeat_with(blank)).take(height).collect());
+			}
+		}
+	}
+	(0..height).map(|y| columns.iter().rev().map(|column| column[y].as_str()).collect()).collect()
 }
 
 pub fn popup_area(area: Rect, width_percent: u16, height_percent: u16) -> Rect {
@@ -1,21 +1,326 @@
-use std::collections::HashMap;
+use crate::config::Config;
+use crate::terminal;
+use any_ascii::any_ascii_char;
+use isolang::Language;
+use unicode_bidi::{bidi_class, BidiClass};
+use std::{collections::{HashMap, HashSet}, fmt, path::PathBuf, rc::Rc, sync::OnceLock};
 
+// The user's home directory: `HOME`, or `USERPROFILE` on Windows where HOME is usually unset
+pub fn home_dir() -> PathBuf {
+	std::env::var_os("HOME")
+		.filter(|home| !home.is_empty())
+		.or_else(|| std::env::var_os("USERPROFILE"))
+		.map(PathBuf::from)
+		.expect("neither HOME nor USERPROFILE is set")
+}
+
+// A BCP 47 language tag such as `zho-Hant`, `sr-Cyrl-RS` or `pt-BR` in its parts, written
+// in the usual case (`sr-Cyrl-RS`); variants and extensions after the region are dropped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LanguageTag {
+	pub language: String,
+	pub script: Option<String>,
+	pub region: Option<String>,
+}
+
+impl LanguageTag {
+	// None unless the tag starts with a language of two or three letters
+	pub fn parse(tag: &str) -> Option<LanguageTag> {
+		let mut parts = tag.split(['-', '_']).peekable();
+		let language = parts.next().filter(|p| (2..=3).contains(&p.len()) && p.chars().all(|c| c.is_ascii_alphabetic()))?;
+		let script = parts.next_if(|p| p.len() == 4 && p.chars().all(|c| c.is_ascii_alphabetic())).map(|p| {
+			format!("{}{}", p[..1].to_ascii_uppercase(), p[1..].to_ascii_lowercase())
+		});
+		let region = parts.next_if(|p| {
+			(p.len() == 2 && p.chars().all(|c| c.is_ascii_alphabetic())) || (p.len() == 3 && p.chars().all(|c| c.is_ascii_digit()))
+		}).map(str::to_ascii_uppercase);
+		Some(LanguageTag { language: language.to_ascii_lowercase(), script, region })
+	}
+
+	// The same tag with the script left out, so `sr-Cyrl` and `sr-Latn` fall together
+	pub fn without_script(&self) -> LanguageTag {
+		LanguageTag { script: None, ..self.clone() }
+	}
+
+	// The same language however it is coded, `ja` or `jpn`, in the same script when both
+	// tags give one
+	pub fn same_language(&self, other: &LanguageTag) -> bool {
+		let code = |tag: &LanguageTag| match tag.language.len() {
+			2 => Language::from_639_1(&tag.language),
+			_ => Language::from_639_3(&tag.language),
+		}.map_or(tag.language.clone(), |language| language.to_639_3().to_string());
+		code(self) == code(other) && (self.script.is_none() || other.script.is_none() || self.script == other.script)
+	}
+
+	// Scripts whose lines run top to bottom, in columns from the left
+	pub fn is_vertical_script(&self) -> bool {
+		matches!(self.script.as_deref(), Some("Mong" | "Phag"))
+	}
+
+	// Written right to left: in the script given, or else in the one the language is usually
+	// written in, so `az-Arab` is and `ug-Cyrl` is not
+	pub fn is_rtl(&self) -> bool {
+		match &self.script {
+			Some(script) => RTL_SCRIPTS.contains(&script.as_str()),
+			None => RTL_LANGUAGES.contains(&self.language.as_str()),
+		}
+	}
+}
+
+impl fmt::Display for LanguageTag {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.language)?;
+		for part in [&self.script, &self.region].into_iter().flatten() {
+			write!(f, "-{}", part)?;
+		}
+		Ok(())
+	}
+}
+
+const RTL_SCRIPTS: [&str; 10] = ["Arab", "Aran", "Hebr", "Syrc", "Thaa", "Nkoo", "Adlm", "Samr", "Mand", "Rohg"];
+
+// ISO 639-1 and 639-3 codes of languages written in Arabic, Hebrew, Syriac or Thaana script
+const RTL_LANGUAGES: [&str; 30] = [
+	"ar", "ara", "arb", "arz", "apc", "ary", // Arabic
+	"fa", "fas", "pes", "prs", // Persian, Dari
+	"he", "heb", "yi", "yid", "lad", // Hebrew, Yiddish, Ladino
+	"ur", "urd", "ps", "pus", "sd", "snd", // Urdu, Pashto, Sindhi
+	"ug", "uig", "ckb", "ota", // Uyghur, Sorani, Ottoman Turkish
+	"syr", "syc", "arc", // Syriac, Aramaic
+	"dv", "div", // Dhivehi
+];
+
+// Names kept over the registry's: forms of languages it has no autonym for or names
+// differently, and script variants
+const LANGUAGE_NAMES: [(&str, &str); 14] = [
+	("bul", "Български"),    // Bulgarian
+	("deu", "Deutsch"),      // German
+	("eng", "English"),      // English
+	("fas", "فارسی"),        // Persian
+	("fra", "Français"),     // French
+	("jpn", "日本語"),        // Japanese
+	("ojp", "上代日本語"), // Classical Japanese
+	("lzh", "文言"),          // Literary Chinese
+	("rus", "Русский"),      // Russian
+	("zho-Hans", "简体中文"), // Written Mandarin in Simplified Chinese
+	("zho-Hant", "繁體中文"), // Written Mandarin in Traditional Chinese (e.g., Taiwanese poetry)
+	("yue-Hant", "粵語"),      // Written Cantonese (Traditional)
+	("mn", "Монгол"),        // Mongolian (Default Traditional script)
+	("mn-Latn", "Mongolian (Latin)"), // Mongolian in Latin script
+];
+
+// `language_names` from the config, read once; they live as long as the program
+fn configured_language_names() -> &'static HashMap<String, &'static str> {
+	static NAMES: OnceLock<HashMap<String, &'static str>> = OnceLock::new();
+	NAMES.get_or_init(|| {
+		let names = Config::load().map(|config| config.language_names).unwrap_or_default();
+		names.into_iter().map(|(code, name)| (code, &*Box::leak(name.into_boxed_str()))).collect()
+	})
+}
+
+fn named(code: &str) -> Option<&'static str> {
+	configured_language_names().get(code).copied()
+		.or_else(|| LANGUAGE_NAMES.iter().find(|(known, _)| *known == code).map(|&(_, name)| name))
+}
+
+// The name of a language in itself where known, else in English, for an ISO 639-1 or 639-3
+// code; `language_names` in the config come first. A tag such as `srp-Cyrl-RS` is named as
+// written, else by its language and script, else by its language.
 pub fn get_language_name(code: &str) -> Option<&'static str> {
-	let lang_map: HashMap<&str, &str> = [
-		("bul", "Български"),    // Bulgarian
-		("eng", "English"),      // English
-		("fas", "فارسی"),        // Persian
-		("fra", "Français"),     // French
-		("jpn", "日本語"),        // Japanese
-		("ojp", "上代日本語"), // Classical Japanese
-		("lzh", "文言"),          // Literary Chinese
-		("rus", "Русский"),      // Russian
-		("zho-Hans", "简体中文"), // Written Mandarin in Simplified Chinese
-		("zho-Hant", "繁體中文"), // Written Mandarin in Traditional Chinese (e.g., Taiwanese poetry)
-		("yue-Hant", "粵語"),      // Written Cantonese (Traditional)
-		("mn", "Монгол"),        // Mongolian (Default Traditional script)
-		("mn-Latn", "Mongolian (Latin)"), // Mongolian in Latin script
-	].iter().cloned().collect();
-
-	lang_map.get(code).copied()
+	if let Some(name) = named(code) {
+		return Some(name);
+	}
+	let tag = LanguageTag::parse(code)?;
+	let without_region = LanguageTag { region: None, ..tag.clone() };
+	if let Some(name) = [tag.to_string(), without_region.to_string(), tag.language.clone()].iter().find_map(|code| named(code)) {
+		return Some(name);
+	}
+	let language = match tag.language.len() {
+		2 => Language::from_639_1(&tag.language),
+		_ => Language::from_639_3(&tag.language),
+	}?;
+	Some(language.to_autonym().unwrap_or(language.to_name()))
+}
+
+// The name of a language, or its code when it has none
+pub fn language_label(code: &str) -> &str {
+	get_language_name(code).unwrap_or(code)
+}
+
+// The name of a language with the script of the tag after it when the name leaves it out,
+// to tell `sr-Cyrl` from `sr-Latn`: `српски · Latn`
+pub fn script_language_label(code: &str) -> String {
+	let label = language_label(code);
+	if let Some(tag) = LanguageTag::parse(code) {
+		if let Some(script) = &tag.script {
+			if label == language_label(&tag.without_script().to_string()) {
+				return format!("{}{}{}", label, terminal::symbol(" · ", " - "), script);
+			}
+		}
+	}
+	label.to_string()
+}
+
+// Builds a key that files an author under their surname. An explicit
+// `sort_name` wins; otherwise the last word of the name is moved to the front.
+pub fn surname_sort_key(name: &str, sort_name: Option<&str>) -> String {
+	if let Some(sort_name) = sort_name {
+		return sort_name.to_lowercase();
+	}
+	let mut words: Vec<&str> = name.split_whitespace().collect();
+	match words.pop() {
+		Some(surname) if !words.is_empty() => format!("{}, {}", surname, words.join(" ")).to_lowercase(),
+		_ => name.to_lowercase(),
+	}
+}
+
+// One shared copy of each name handed to it. Authors and languages recur across thousands of
+// poems, and the counts and sorted lists built from them hold the copy instead of a clone.
+#[derive(Default)]
+pub struct Interner(HashSet<Rc<str>>);
+
+impl Interner {
+	pub fn intern(&mut self, name: &str) -> Rc<str> {
+		if let Some(interned) = self.0.get(name) {
+			return interned.clone();
+		}
+		let interned: Rc<str> = Rc::from(name);
+		self.0.insert(interned.clone());
+		interned
+	}
+}
+
+// Section a title is filed under in the grouped title list: its first Latin
+// letter, or a shared section per script for everything else.
+pub fn title_group(title: &str) -> String {
+	let Some(c) = title.chars().find(|c| c.is_alphanumeric()) else {
+		return "#".to_string();
+	};
+	match c as u32 {
+		_ if c.is_ascii_digit() => "#".to_string(),
+		0x0041..=0x024F => c.to_uppercase().collect(),
+		0x0370..=0x03FF => "Greek".to_string(),
+		0x0400..=0x052F => "Cyrillic".to_string(),
+		0x0590..=0x05FF => "Hebrew".to_string(),
+		0x0600..=0x06FF | 0x0750..=0x077F | 0xFB50..=0xFEFF => "Arabic".to_string(),
+		0x1100..=0x11FF | 0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF => "CJK".to_string(),
+		_ => "Other".to_string(),
+	}
+}
+
+// Han, kana and hangul are written without spaces, so each character counts as a word
+pub fn is_cjk(c: char) -> bool {
+	matches!(c as u32, 0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF)
+}
+
+pub fn is_han(c: char) -> bool {
+	matches!(c as u32, 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF)
+}
+
+fn is_latin(c: char) -> bool {
+	matches!(c, 'A'..='Z' | 'a'..='z' | '\u{C0}'..='\u{24F}' | '\u{1E00}'..='\u{1EFF}')
+}
+
+// The text in Latin letters, line for line, when most of its letters are in another script.
+// It is spelled out character by character, so it is a reading aid rather than a proper
+// transliteration: Han comes out as toneless pinyin, a syllable per character.
+pub fn romanized(text: &str) -> Option<String> {
+	let (latin, other) = text.chars().filter(|c| c.is_alphabetic())
+		.fold((0, 0), |(latin, other), c| if is_latin(c) { (latin + 1, other) } else { (latin, other + 1) });
+	if other <= latin {
+		return None;
+	}
+	let romanize = |line: &str| {
+		let mut romanized = String::with_capacity(line.len());
+		let mut after_han = false;
+		for c in line.chars() {
+			let han = is_han(c);
+			if (han && !romanized.is_empty() && !romanized.ends_with(' ')) || (after_han && c.is_alphanumeric()) {
+				romanized.push(' ');
+			}
+			romanized.push_str(&if han { any_ascii_char(c).to_lowercase() } else { any_ascii_char(c).to_string() });
+			after_han = han;
+		}
+		romanized
+	};
+	Some(text.lines().map(romanize).collect::<Vec<_>>().join("\n"))
+}
+
+// Whether most letters of a text are of a right-to-left script
+pub fn is_mostly_rtl(text: &str) -> bool {
+	let (mut rtl, mut ltr) = (0, 0);
+	for c in text.chars() {
+		match bidi_class(c) {
+			BidiClass::R | BidiClass::AL => rtl += 1,
+			BidiClass::L => ltr += 1,
+			_ => {},
+		}
+	}
+	rtl > ltr
+}
+
+// File-name friendly form of an author, title, language or tag
+pub fn slug(text: &str) -> String {
+	let mut slug = String::new();
+	for c in text.chars().flat_map(char::to_lowercase) {
+		if c.is_alphanumeric() {
+			slug.push(c);
+		} else if !slug.is_empty() && !slug.ends_with('-') {
+			slug.push('-');
+		}
+	}
+	let slug = slug.trim_end_matches('-');
+	if slug.is_empty() { "_".to_string() } else { slug.to_string() }
+}
+
+// Calendar date of a day counted from the unix epoch (proleptic Gregorian, UTC)
+pub fn date_from_days(days: u64) -> (i64, u32, u32) {
+	let z = days as i64 + 719468;
+	let era = z.div_euclid(146097);
+	let doe = z.rem_euclid(146097);
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+	let year = yoe + era * 400 + (month <= 2) as i64;
+	(year, month, day)
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextCounts {
+	pub lines: usize,
+	pub words: usize,
+	pub cjk_chars: usize,
+}
+
+impl TextCounts {
+	// Poetry is read slower than prose: ~130 words or ~250 CJK characters a minute
+	pub fn reading_seconds(&self) -> usize {
+		self.words * 60 / 130 + self.cjk_chars * 60 / 250
+	}
+
+	pub fn reading_time_label(&self) -> String {
+		match self.reading_seconds() {
+			0..=59 => "<1 min".to_string(),
+			secs => format!("~{} min", (secs + 30) / 60),
+		}
+	}
+
+	pub fn summary(&self) -> String {
+		let words = self.words + self.cjk_chars;
+		format!("{1} lines{0}{2} words{0}{3}", terminal::symbol(" · ", ", "), self.lines, words, self.reading_time_label())
+	}
+}
+
+pub fn count_text(text: &str) -> TextCounts {
+	let mut counts = TextCounts { lines: text.lines().filter(|l| !l.trim().is_empty()).count(), ..Default::default() };
+	for word in text.split_whitespace() {
+		let cjk = word.chars().filter(|c| is_cjk(*c)).count();
+		counts.cjk_chars += cjk;
+		if cjk < word.chars().filter(|c| c.is_alphanumeric()).count() {
+			counts.words += 1;
+		}
+	}
+	counts
 }
@@ -0,0 +1,60 @@
+use crate::config::config_path;
+use crate::ui::THEMES;
+use crate::utils::get_language_name;
+use serde_yaml::{Mapping, Value};
+use std::{fs, io::{self, IsTerminal}};
+
+const DEFAULT_LIBRARY: &str = "~/literature/poetry";
+
+// Whether to ask the first-run questions: there is no config yet and someone is there to answer
+pub fn needed() -> bool {
+	!config_path().exists() && io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+// Asks `question`, offering `default`; an empty answer or the end of input takes the default
+fn ask(question: &str, default: &str) -> io::Result<String> {
+	eprint!("{} [{}]: ", question, default);
+	let mut answer = String::new();
+	io::stdin().read_line(&mut answer)?;
+	let answer = answer.trim();
+	Ok(if answer.is_empty() { default } else { answer }.to_string())
+}
+
+// Asks for the library directory, theme and reading languages and writes them to a new config file
+pub fn run() -> io::Result<()> {
+	eprintln!("Welcome to leaves! A few questions before the first start; Enter takes the suggestion.");
+	eprintln!();
+	let library = ask("Directory of your poems (or the https:// URL of a shared library)", DEFAULT_LIBRARY)?;
+	let theme = loop {
+		let theme = ask(&format!("Theme, {}", THEMES.join(" or ")), THEMES[0])?;
+		if THEMES.contains(&theme.as_str()) {
+			break theme;
+		}
+		eprintln!("One of {}, please", THEMES.join(", "));
+	};
+	let languages = ask("Languages you prefer to read in, as codes separated by commas (e.g. eng, deu); poems open at a version in one of them", "none")?;
+	let languages: Vec<String> = match languages.as_str() {
+		"none" => Vec::new(),
+		languages => languages.split([',', ' ']).map(str::trim).filter(|code| !code.is_empty()).map(str::to_string).collect(),
+	};
+	for code in languages.iter().filter(|code| get_language_name(code).is_none()) {
+		eprintln!("Note: \"{}\" is not a language code leaves knows; it is kept as written", code);
+	}
+
+	let mut config = Mapping::new();
+	config.insert("library".into(), library.into());
+	config.insert("theme".into(), theme.into());
+	if !languages.is_empty() {
+		config.insert("languages".into(), languages.into());
+	}
+	let content = serde_yaml::to_string(&Value::Mapping(config))
+		.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+	let path = config_path();
+	if let Some(parent) = path.parent() {
+		fs::create_dir_all(parent)?;
+	}
+	fs::write(&path, content)?;
+	eprintln!();
+	eprintln!("Saved to {}; edit it any time, or delete it to be asked again.", path.display());
+	Ok(())
+}